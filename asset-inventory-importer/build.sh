@@ -0,0 +1,8 @@
+#!/usr/bin/env bash
+docker run --rm -it -v "$(pwd)":/home/rust/src -t 096f585a5019 cargo build --release &&
+cp "./target/x86_64-unknown-linux-musl/release/asset-inventory-importer" "./bootstrap" &&
+zip -r "./asset-inventory-importer.zip" "./bootstrap" &&
+cp "./asset-inventory-importer.zip" "../grapl-cdk/"
+rm "./asset-inventory-importer.zip"
+rm "./bootstrap"
+date
@@ -0,0 +1,68 @@
+use graph_descriptions::graph_description::*;
+use graph_descriptions::graph_description::host::HostId;
+
+use crate::inventory::InventoryRecord;
+
+/// The host ids an inventory record maps to its asset id.
+///
+/// Ip and mac address mappings only hold until the address is mapped again, ie: by a
+/// later import, so an address that has moved to another asset is not misattributed.
+pub fn host_ids(record: &InventoryRecord) -> Vec<HostId> {
+    let mut host_ids = vec![HostId::Hostname(record.hostname.clone())];
+
+    if let Some(ref fqdn) = record.fqdn {
+        if *fqdn != record.hostname {
+            host_ids.push(HostId::Hostname(fqdn.clone()));
+        }
+    }
+
+    host_ids.extend(record.ip_addresses.iter().cloned().map(HostId::Ip));
+    host_ids.extend(record.mac_addresses.iter().cloned().map(HostId::Mac));
+
+    host_ids
+}
+
+/// The Asset described by an inventory record.
+///
+/// The inventory is not a sighting of the asset, so its seen timestamps are left unset.
+pub fn asset_node(record: &InventoryRecord) -> Asset {
+    AssetBuilder::default()
+        .asset_id(record.asset_id().to_owned())
+        .hostname(record.hostname.clone())
+        .mac_address(record.mac_addresses.first().cloned())
+        .fqdn(record.fqdn.clone())
+        .os(record.os.clone())
+        .serial_number(record.serial_number.clone())
+        .owner(record.owner.clone())
+        .instance_id(record.instance_id.clone())
+        .ip_addresses(record.ip_addresses.clone())
+        .mac_addresses(record.mac_addresses.clone())
+        .build()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::parse_inventory;
+
+    #[test]
+    fn hostnames_and_addresses_are_mapped() {
+        let records = parse_inventory(
+            "hostname,fqdn,os,ip\nweb-1,web-1.corp.example.com,Ubuntu 18.04,10.0.0.5\n"
+        ).expect("parse_inventory");
+
+        assert_eq!(
+            host_ids(&records[0]),
+            vec![
+                HostId::Hostname("web-1".to_owned()),
+                HostId::Hostname("web-1.corp.example.com".to_owned()),
+                HostId::Ip("10.0.0.5".to_owned()),
+            ]
+        );
+
+        let asset = asset_node(&records[0]);
+        assert_eq!(asset.asset_id, Some("web-1".to_owned()));
+        assert_eq!(asset.os, Some("Ubuntu 18.04".to_owned()));
+    }
+}
@@ -0,0 +1,270 @@
+use failure::Error;
+use serde_json::{Map, Value};
+
+/// An asset, as exported by an inventory system (a CMDB, MDM, or cloud account listing)
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InventoryRecord {
+    /// The asset id the inventory knows the asset by, when it exports one
+    pub asset_id: Option<String>,
+    pub hostname: String,
+    pub fqdn: Option<String>,
+    pub os: Option<String>,
+    pub serial_number: Option<String>,
+    pub owner: Option<String>,
+    pub instance_id: Option<String>,
+    pub ip_addresses: Vec<String>,
+    pub mac_addresses: Vec<String>,
+    /// The raw record, used to identify it
+    pub line: String,
+}
+
+impl InventoryRecord {
+    /// Assets without an inventory asset id are known by their hostname, as they would be
+    /// if they had been seen in a log first
+    pub fn asset_id(&self) -> &str {
+        self.asset_id.as_ref().unwrap_or(&self.hostname)
+    }
+}
+
+// Inventory exports name their columns differently, so headers and keys are matched
+// loosely, ie: "Serial Number", "serial-number" and "serial" are all the serial_number
+fn field_name(header: &str) -> Option<&'static str> {
+    let header = header.trim().to_lowercase().replace([' ', '-'], "_");
+
+    let field = match header.as_str() {
+        "asset_id" | "assetid" | "asset" => "asset_id",
+        "hostname" | "host" | "host_name" | "name" | "computer_name" => "hostname",
+        "fqdn" | "dns_name" | "domain_name" => "fqdn",
+        "os" | "operating_system" | "os_name" | "platform" => "os",
+        "serial_number" | "serial" | "serialnumber" => "serial_number",
+        "owner" | "assigned_to" | "user" => "owner",
+        "instance_id" | "cloud_instance_id" | "instanceid" => "instance_id",
+        "ip_addresses" | "ip_address" | "ips" | "ip" => "ip_addresses",
+        "mac_addresses" | "mac_address" | "macs" | "mac" => "mac_addresses",
+        _ => return None,
+    };
+
+    Some(field)
+}
+
+// Lists are exported as a single cell, separated by ';', ',' or spaces
+fn split_list(value: &str) -> Vec<String> {
+    value.split(|c: char| c == ';' || c == ',' || c.is_whitespace())
+        .filter(|value| !value.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// MAC addresses are stored lowercase and ':' separated, as they are most commonly logged
+fn normalize_mac(mac: &str) -> String {
+    mac.to_lowercase().replace('-', ":")
+}
+
+fn record_from_fields(
+    fields: impl IntoIterator<Item=(&'static str, Vec<String>)>,
+    line: String,
+) -> Option<InventoryRecord> {
+    let mut record = InventoryRecord {
+        line,
+        ..Default::default()
+    };
+
+    for (field, mut values) in fields {
+        values.retain(|value| !value.is_empty());
+
+        match field {
+            "ip_addresses" => record.ip_addresses.extend(values),
+            "mac_addresses" => {
+                record.mac_addresses.extend(values.iter().map(|mac| normalize_mac(mac)))
+            }
+            _ => {
+                let value = match values.into_iter().next() {
+                    Some(value) => value,
+                    None => continue,
+                };
+
+                match field {
+                    "asset_id" => record.asset_id = Some(value),
+                    "hostname" => record.hostname = value,
+                    "fqdn" => record.fqdn = Some(value),
+                    "os" => record.os = Some(value),
+                    "serial_number" => record.serial_number = Some(value),
+                    "owner" => record.owner = Some(value),
+                    "instance_id" => record.instance_id = Some(value),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    // An asset exported only by its fqdn is known by its short hostname
+    if record.hostname.is_empty() {
+        record.hostname = record.fqdn.as_ref()?.split('.').next()?.to_owned();
+    }
+
+    Some(record)
+}
+
+/// Parses an inventory export, as CSV with a header row, a json array of objects,
+/// or one json object per line.
+/// Records without a hostname or fqdn can not be mapped to an asset, and are skipped.
+pub fn parse_inventory(payload: &str) -> Result<Vec<InventoryRecord>, Error> {
+    let payload = payload.trim_start_matches('\u{feff}').trim();
+
+    if payload.starts_with('[') {
+        let objects: Vec<Map<String, Value>> = serde_json::from_str(payload)
+            .map_err(|e| format_err!("Invalid inventory json: {}", e))?;
+
+        return Ok(objects.into_iter().filter_map(from_json).collect());
+    }
+
+    if payload.starts_with('{') {
+        let mut records = Vec::new();
+
+        for line in payload.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let object: Map<String, Value> = serde_json::from_str(line)
+                .map_err(|e| format_err!("Invalid inventory json {}: {}", line, e))?;
+
+            records.extend(from_json(object));
+        }
+
+        return Ok(records);
+    }
+
+    from_csv(payload)
+}
+
+fn from_json(object: Map<String, Value>) -> Option<InventoryRecord> {
+    let line = Value::Object(object.clone()).to_string();
+
+    let fields = object.into_iter()
+        .filter_map(|(key, value)| {
+            let field = field_name(&key)?;
+
+            let values = match value {
+                Value::String(value) if field.ends_with("_addresses") => split_list(&value),
+                Value::String(value) => vec![value.trim().to_owned()],
+                Value::Number(value) => vec![value.to_string()],
+                Value::Array(values) => values.into_iter()
+                    .filter_map(|value| value.as_str().map(|value| value.trim().to_owned()))
+                    .collect(),
+                _ => return None,
+            };
+
+            Some((field, values))
+        });
+
+    record_from_fields(fields, line)
+}
+
+fn from_csv(payload: &str) -> Result<Vec<InventoryRecord>, Error> {
+    let mut lines = payload.lines().filter(|line| !line.trim().is_empty());
+
+    let header = match lines.next() {
+        Some(header) => split_csv_line(header),
+        None => return Ok(vec![]),
+    };
+
+    let columns: Vec<_> = header.iter().map(|column| field_name(column)).collect();
+
+    if !columns.contains(&Some("hostname")) && !columns.contains(&Some("fqdn")) {
+        bail!("Inventory header has no hostname or fqdn column: {:?}", header);
+    }
+
+    let records = lines
+        .filter_map(|line| {
+            let fields = columns.iter()
+                .zip(split_csv_line(line))
+                .filter_map(|(field, value)| {
+                    let field = (*field)?;
+
+                    if field.ends_with("_addresses") {
+                        Some((field, split_list(&value)))
+                    } else {
+                        Some((field, vec![value.trim().to_owned()]))
+                    }
+                });
+
+            record_from_fields(fields, line.to_owned())
+        })
+        .collect();
+
+    Ok(records)
+}
+
+// Splits a CSV line on commas outside of quotes. Quoted values may contain escaped quotes,
+// as "", but not newlines.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => values.push(std::mem::take(&mut value)),
+            c => value.push(c),
+        }
+    }
+
+    values.push(value);
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_inventory_is_parsed() {
+        let payload = concat!(
+            "Hostname,FQDN,Operating System,Serial Number,Owner,IP Addresses,MAC Address,Instance ID\n",
+            "web-1,web-1.corp.example.com,Ubuntu 18.04,,\"Doe, Jane\",\"10.0.0.5, 10.0.1.5\",00-1A-2B-3C-4D-5E,i-0abcd1234\n",
+            ",db-1.corp.example.com,,SN123,,10.0.0.9,,\n",
+            ",,,SN456,,,,\n",
+        );
+
+        let records = parse_inventory(payload).expect("parse_inventory");
+
+        assert_eq!(records.len(), 2);
+
+        assert_eq!(records[0].hostname, "web-1");
+        assert_eq!(records[0].asset_id(), "web-1");
+        assert_eq!(records[0].owner, Some("Doe, Jane".to_owned()));
+        assert_eq!(records[0].serial_number, None);
+        assert_eq!(records[0].ip_addresses, vec!["10.0.0.5", "10.0.1.5"]);
+        assert_eq!(records[0].mac_addresses, vec!["00:1a:2b:3c:4d:5e"]);
+        assert_eq!(records[0].instance_id, Some("i-0abcd1234".to_owned()));
+
+        assert_eq!(records[1].hostname, "db-1");
+        assert_eq!(records[1].fqdn, Some("db-1.corp.example.com".to_owned()));
+    }
+
+    #[test]
+    fn json_inventory_is_parsed() {
+        let payload = r#"[
+            {"asset_id": "a-1234", "hostname": "laptop-7", "os": "macOS", "ip_addresses": ["192.168.1.20"], "macs": "aa:bb:cc:dd:ee:ff"},
+            {"serial": "SN789"}
+        ]"#;
+
+        let records = parse_inventory(payload).expect("parse_inventory");
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].asset_id(), "a-1234");
+        assert_eq!(records[0].hostname, "laptop-7");
+        assert_eq!(records[0].ip_addresses, vec!["192.168.1.20"]);
+        assert_eq!(records[0].mac_addresses, vec!["aa:bb:cc:dd:ee:ff"]);
+
+        let lines = concat!(
+            r#"{"hostname": "web-2", "owner": "ops"}"#, "\n",
+            r#"{"hostname": "web-3"}"#, "\n",
+        );
+
+        assert_eq!(parse_inventory(lines).expect("parse_inventory").len(), 2);
+    }
+}
@@ -0,0 +1,113 @@
+extern crate aws_lambda_events;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate node_identifier;
+extern crate rusoto_core;
+extern crate rusoto_dynamodb;
+extern crate serde_json;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use node_identifier::assetdb::AssetIdDb;
+use node_identifier::mapping_conflicts::{conflict_sink_from_env, ConflictPolicy};
+use rusoto_core::Region;
+use rusoto_dynamodb::{DynamoDb, DynamoDbClient};
+use subgraph_generator_runtime::{SniffingDecoder, SubgraphGenerator};
+
+use crate::generator::{asset_node, host_ids};
+use crate::inventory::{parse_inventory, InventoryRecord};
+
+mod generator;
+mod inventory;
+
+#[derive(Clone)]
+struct AssetInventoryImporter<D>
+    where D: DynamoDb + Clone + Send + Sync + 'static
+{
+    asset_id_db: AssetIdDb<D>,
+}
+
+impl<D> AssetInventoryImporter<D>
+    where D: DynamoDb + Clone + Send + Sync + 'static
+{
+    pub fn new(asset_id_db: AssetIdDb<D>) -> Self {
+        Self { asset_id_db }
+    }
+}
+
+#[async_trait]
+impl<D> SubgraphGenerator for AssetInventoryImporter<D>
+    where D: DynamoDb + Clone + Send + Sync + 'static
+{
+    type Event = InventoryRecord;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<InventoryRecord>, failure::Error> {
+        // The payload is a CSV or json inventory export
+        parse_inventory(&String::from_utf8_lossy(payload))
+    }
+
+    fn identity(&self, record: &InventoryRecord) -> String {
+        record.line.clone()
+    }
+
+    async fn generate_subgraph(&mut self, record: InventoryRecord) -> Result<Option<Graph>, failure::Error> {
+        // Mappings are created as of the import, and hosts are attributed to them
+        // before and after it, so that assets are known before they are first seen
+        let import_timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(n) => n.as_millis() as u64,
+            Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+        };
+
+        for host_id in host_ids(&record) {
+            self.asset_id_db.create_mapping(
+                &host_id,
+                record.asset_id().to_owned(),
+                import_timestamp,
+            ).await
+                .map_err(|e| format_err!("Failed to create asset id mapping for {:?}: {}", host_id, e))?;
+        }
+
+        let mut graph = Graph::new(import_timestamp);
+        graph.add_node(asset_node(&record));
+
+        Ok(Some(graph))
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    let region = {
+        let region_str = std::env::var("AWS_REGION").expect("AWS_REGION");
+        Region::from_str(&region_str).expect("Region error")
+    };
+
+    let conflict_policy = ConflictPolicy::from_env().expect("ASSET_MAPPING_CONFLICT_POLICY");
+
+    let importer = AssetInventoryImporter::new(
+        AssetIdDb::new(DynamoDbClient::new(region.clone()))
+            .with_conflict_policy(conflict_policy)
+            .with_conflict_sink(conflict_sink_from_env(region)),
+    );
+
+    subgraph_generator_runtime::run(event, ctx, "ASSET_INVENTORY", importer, SniffingDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
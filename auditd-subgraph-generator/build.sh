@@ -0,0 +1,8 @@
+#!/usr/bin/env bash
+docker run --rm -it -v "$(pwd)":/home/rust/src -t 096f585a5019 cargo build --release &&
+cp "./target/x86_64-unknown-linux-musl/release/auditd-subgraph-generator" "./bootstrap" &&
+zip -r "./auditd-subgraph-generator.zip" "./bootstrap" &&
+cp "./auditd-subgraph-generator.zip" "../grapl-cdk/"
+rm "./auditd-subgraph-generator.zip"
+rm "./bootstrap"
+date
@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use failure::Error;
+use graph_descriptions::container::{Container, ContainerBuilder};
+use graph_descriptions::graph_description::*;
+use graph_descriptions::file::FileState;
+use graph_descriptions::node::NodeT;
+use graph_descriptions::process::ProcessState;
+
+use crate::parser::{AuditEvent, AuditRecord, decode_hex};
+
+const AUDIT_ARCH_X86_64: &str = "c000003e";
+const AUDIT_ARCH_AARCH64: &str = "c00000b7";
+
+// auid is (uint32)-1 for processes that were never associated with a login
+const UNSET_AUID: u64 = 4294967295;
+
+// contid is (uint64)-1 for processes that were never assigned to a container
+const UNSET_CONTID: &str = "18446744073709551615";
+
+const O_ACCMODE: u64 = 0o3;
+const O_WRONLY: u64 = 0o1;
+const O_RDWR: u64 = 0o2;
+
+const AF_INET: u16 = 2;
+const AF_INET6: u16 = 10;
+
+// EINPROGRESS, returned by non blocking connects that will complete later
+const EINPROGRESS: i64 = -115;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Syscall {
+    Execve,
+    // The index of the syscall argument holding the open flags
+    Open { flags_arg: usize },
+    Creat,
+    Unlink,
+    Connect,
+    Accept,
+}
+
+fn lookup_syscall(arch: &str, number: u64) -> Option<Syscall> {
+    let syscall = match (arch, number) {
+        (AUDIT_ARCH_X86_64, 59) | (AUDIT_ARCH_X86_64, 322) => Syscall::Execve,
+        (AUDIT_ARCH_X86_64, 2) => Syscall::Open { flags_arg: 1 },
+        (AUDIT_ARCH_X86_64, 257) => Syscall::Open { flags_arg: 2 },
+        (AUDIT_ARCH_X86_64, 85) => Syscall::Creat,
+        (AUDIT_ARCH_X86_64, 87) | (AUDIT_ARCH_X86_64, 263) => Syscall::Unlink,
+        (AUDIT_ARCH_X86_64, 42) => Syscall::Connect,
+        (AUDIT_ARCH_X86_64, 43) | (AUDIT_ARCH_X86_64, 288) => Syscall::Accept,
+
+        (AUDIT_ARCH_AARCH64, 221) | (AUDIT_ARCH_AARCH64, 281) => Syscall::Execve,
+        (AUDIT_ARCH_AARCH64, 56) => Syscall::Open { flags_arg: 2 },
+        (AUDIT_ARCH_AARCH64, 35) => Syscall::Unlink,
+        (AUDIT_ARCH_AARCH64, 203) => Syscall::Connect,
+        (AUDIT_ARCH_AARCH64, 202) | (AUDIT_ARCH_AARCH64, 242) => Syscall::Accept,
+        _ => return None,
+    };
+
+    Some(syscall)
+}
+
+/// Generates a subgraph for a correlated audit event.
+/// Events that are not syscalls, or are syscalls we do not model, produce no graph.
+pub fn generate_subgraph(event: &AuditEvent) -> Result<Option<Graph>, Error> {
+    let syscall_record = match event.record("SYSCALL") {
+        Some(syscall_record) => syscall_record,
+        None => return Ok(None),
+    };
+
+    let arch = syscall_record.field("arch").unwrap_or_default();
+    let syscall = match syscall_record.u64_field("syscall").and_then(|n| lookup_syscall(arch, n)) {
+        Some(syscall) => syscall,
+        None => return Ok(None),
+    };
+
+    let exit: i64 = syscall_record.field("exit")
+        .and_then(|exit| exit.parse().ok())
+        .unwrap_or_default();
+    let succeeded = syscall_record.field("success") == Some("yes");
+
+    let in_progress_connect = syscall == Syscall::Connect && exit == EINPROGRESS;
+    if !succeeded && !in_progress_connect {
+        return Ok(None);
+    }
+
+    let hostname = match event.node {
+        Some(ref node) => node.clone(),
+        None => bail!(
+            "Audit event {} has no node= field, configure auditd with name_format",
+            event.serial
+        ),
+    };
+
+    let context = EventContext::new(hostname, event, syscall_record)?;
+
+    let graph = match syscall {
+        Syscall::Execve => handle_execve(&context, event),
+        Syscall::Open { flags_arg } => {
+            let flags = syscall_arg(syscall_record, flags_arg).unwrap_or_default();
+            handle_open(&context, event, flags)
+        }
+        Syscall::Creat => handle_open(&context, event, O_WRONLY),
+        Syscall::Unlink => handle_unlink(&context, event),
+        Syscall::Connect => handle_connection(&context, event, "connected_to"),
+        Syscall::Accept => handle_connection(&context, event, "accepted_from"),
+    };
+
+    Ok(graph)
+}
+
+// The fields of the SYSCALL record shared by every subgraph
+struct EventContext {
+    hostname: String,
+    timestamp: u64,
+    pid: u64,
+    ppid: u64,
+    uid: Option<u64>,
+    auid: Option<u64>,
+    comm: String,
+    exe: Option<String>,
+    // Set by the container orchestrator, and logged in a CONTAINER_ID record
+    container_id: Option<String>,
+}
+
+impl EventContext {
+    fn new(hostname: String, event: &AuditEvent, syscall_record: &AuditRecord) -> Result<Self, Error> {
+        let pid = match syscall_record.u64_field("pid") {
+            Some(pid) => pid,
+            None => bail!("SYSCALL record is missing pid"),
+        };
+
+        let container_id = event.record("CONTAINER_ID")
+            .and_then(|record| record.field("contid"))
+            .filter(|contid| *contid != UNSET_CONTID && *contid != "-1")
+            .map(String::from);
+
+        Ok(Self {
+            hostname,
+            timestamp: event.timestamp,
+            pid,
+            ppid: syscall_record.u64_field("ppid").unwrap_or_default(),
+            uid: syscall_record.u64_field("uid"),
+            auid: syscall_record.u64_field("auid").filter(|auid| *auid != UNSET_AUID),
+            comm: syscall_record.field("comm").unwrap_or_default().to_owned(),
+            exe: syscall_record.field("exe")
+                .filter(|exe| *exe != "(null)")
+                .map(String::from),
+            container_id,
+        })
+    }
+
+    fn asset(&self) -> Asset {
+        AssetBuilder::default()
+            .asset_id(self.hostname.clone())
+            .hostname(self.hostname.clone())
+            .build()
+            .unwrap()
+    }
+
+    fn existing_process(&self) -> Process {
+        ProcessBuilder::default()
+            .hostname(self.hostname.clone())
+            .state(ProcessState::Existing)
+            .process_id(self.pid)
+            .process_name(self.comm.clone())
+            .last_seen_timestamp(self.timestamp)
+            .build()
+            .unwrap()
+    }
+
+    // Users have no dedicated node type, so they are described dynamically,
+    // identified by their uid on the asset
+    fn user(&self, uid: u64) -> DynamicNode {
+        let mut user = DynamicNode {
+            properties: HashMap::with_capacity(1),
+            node_key: uuid::Uuid::new_v4().to_string(),
+            node_type: "User".to_owned(),
+            seen_at: self.timestamp,
+            asset_id: None,
+            hostname: Some(self.hostname.clone()),
+            host_ip: None,
+            host_mac: None,
+            id_strategy: vec![
+                Static {
+                    primary_key_properties: vec!["uid".to_owned()],
+                    primary_key_requires_asset_id: true,
+                }.into()
+            ],
+        };
+
+        user.set_property("uid", uid);
+
+        user
+    }
+
+    fn container(&self, container_id: &str) -> Container {
+        ContainerBuilder::default()
+            .container_id(container_id)
+            .hostname(self.hostname.clone())
+            .seen_at(self.timestamp)
+            .build()
+            .unwrap()
+    }
+
+    // Adds the process, its asset, the users it runs as, and its container
+    fn add_process(&self, graph: &mut Graph, asset: &Asset, process: Process) {
+        graph.add_edge(
+            "asset_processes",
+            asset.clone_node_key(),
+            process.clone_node_key(),
+        );
+
+        if let Some(uid) = self.uid {
+            let user = self.user(uid);
+            graph.add_edge("process_user", process.clone_node_key(), user.clone_node_key());
+            graph.add_node(user);
+        }
+
+        if let Some(auid) = self.auid.filter(|auid| Some(*auid) != self.uid) {
+            let login_user = self.user(auid);
+            graph.add_edge("process_login_user", process.clone_node_key(), login_user.clone_node_key());
+            graph.add_node(login_user);
+        }
+
+        if let Some(ref container_id) = self.container_id {
+            let container = self.container(container_id);
+            graph.add_edge("runs_in", process.clone_node_key(), container.clone_node_key());
+            graph.add_node(container);
+        }
+
+        graph.add_node(process);
+    }
+}
+
+fn handle_execve(context: &EventContext, event: &AuditEvent) -> Option<Graph> {
+    let mut graph = Graph::new(context.timestamp);
+
+    let asset = context.asset();
+
+    let parent = ProcessBuilder::default()
+        .hostname(context.hostname.clone())
+        .state(ProcessState::Existing)
+        .process_id(context.ppid)
+        .last_seen_timestamp(context.timestamp)
+        .build()
+        .unwrap();
+
+    // execve replaces the image of an existing pid, which we model as a new process
+    let command_line = event.record("EXECVE")
+        .map(command_line)
+        .unwrap_or_default();
+
+    let child = ProcessBuilder::default()
+        .hostname(context.hostname.clone())
+        .process_name(context.comm.clone())
+        .process_command_line(command_line)
+        .state(ProcessState::Created)
+        .process_id(context.pid)
+        .created_timestamp(context.timestamp)
+        .build()
+        .unwrap();
+
+    if let Some(ref exe) = context.exe {
+        let child_exe = FileBuilder::default()
+            .hostname(context.hostname.clone())
+            .state(FileState::Existing)
+            .last_seen_timestamp(context.timestamp)
+            .file_path(exe.clone())
+            .build()
+            .unwrap();
+
+        graph.add_edge("bin_file", child.clone_node_key(), child_exe.clone_node_key());
+        graph.add_node(child_exe);
+    }
+
+    graph.add_edge(
+        "asset_processes",
+        asset.clone_node_key(),
+        parent.clone_node_key(),
+    );
+
+    graph.add_edge("children", parent.clone_node_key(), child.clone_node_key());
+
+    graph.add_node(parent);
+    context.add_process(&mut graph, &asset, child);
+    graph.add_node(asset);
+
+    Some(graph)
+}
+
+fn handle_open(context: &EventContext, event: &AuditEvent, flags: u64) -> Option<Graph> {
+    let path_record = target_path(event, &["CREATE", "NORMAL", "UNKNOWN"])?;
+    let path = resolve_path(event, path_record)?;
+
+    let created = path_record.field("nametype") == Some("CREATE");
+    let writes = matches!(flags & O_ACCMODE, O_WRONLY | O_RDWR);
+
+    let mut graph = Graph::new(context.timestamp);
+
+    let asset = context.asset();
+    let process = context.existing_process();
+
+    let file = if created {
+        FileBuilder::default()
+            .hostname(context.hostname.clone())
+            .state(FileState::Created)
+            .created_timestamp(context.timestamp)
+            .file_path(path)
+            .build()
+            .unwrap()
+    } else {
+        FileBuilder::default()
+            .hostname(context.hostname.clone())
+            .state(FileState::Existing)
+            .last_seen_timestamp(context.timestamp)
+            .file_path(path)
+            .build()
+            .unwrap()
+    };
+
+    if created {
+        graph.add_edge("created_files", process.clone_node_key(), file.clone_node_key());
+    }
+
+    if writes {
+        graph.add_edge("wrote_files", process.clone_node_key(), file.clone_node_key());
+    } else {
+        graph.add_edge("read_files", process.clone_node_key(), file.clone_node_key());
+    }
+
+    graph.add_node(file);
+    context.add_process(&mut graph, &asset, process);
+    graph.add_node(asset);
+
+    Some(graph)
+}
+
+fn handle_unlink(context: &EventContext, event: &AuditEvent) -> Option<Graph> {
+    let path_record = target_path(event, &["DELETE"])?;
+    let path = resolve_path(event, path_record)?;
+
+    let mut graph = Graph::new(context.timestamp);
+
+    let asset = context.asset();
+    let deleter = context.existing_process();
+
+    let file = FileBuilder::default()
+        .hostname(context.hostname.clone())
+        .state(FileState::Deleted)
+        .deleted_timestamp(context.timestamp)
+        .file_path(path)
+        .build()
+        .unwrap();
+
+    graph.add_edge("deleted", deleter.clone_node_key(), file.clone_node_key());
+
+    graph.add_node(file);
+    context.add_process(&mut graph, &asset, deleter);
+    graph.add_node(asset);
+
+    Some(graph)
+}
+
+// The SOCKADDR record of a connect or accept only holds the remote address,
+// auditd never records the local side of the socket. Without both ends we can
+// not identify a NetworkConnection, so we link the process to the remote port.
+fn handle_connection(context: &EventContext, event: &AuditEvent, edge_name: &str) -> Option<Graph> {
+    let saddr = event.record("SOCKADDR")?.field("saddr")?;
+    let (remote_ip, remote_port) = decode_sockaddr(saddr)?;
+
+    let mut graph = Graph::new(context.timestamp);
+
+    let asset = context.asset();
+    let process = context.existing_process();
+
+    let ip_address = IpAddressBuilder::default()
+        .ip_address(remote_ip.clone())
+        .last_seen_timestamp(context.timestamp)
+        .build()
+        .unwrap();
+
+    // auditd does not tell us the socket type, so we assume the common case
+    let ip_port = IpPortBuilder::default()
+        .ip_address(remote_ip)
+        .port(remote_port)
+        .protocol("tcp".to_owned())
+        .build()
+        .unwrap();
+
+    graph.add_edge(edge_name, process.clone_node_key(), ip_port.clone_node_key());
+    graph.add_edge("port_ip", ip_port.clone_node_key(), ip_address.clone_node_key());
+
+    graph.add_node(ip_address);
+    graph.add_node(ip_port);
+    context.add_process(&mut graph, &asset, process);
+    graph.add_node(asset);
+
+    Some(graph)
+}
+
+fn syscall_arg(syscall_record: &AuditRecord, index: usize) -> Option<u64> {
+    let arg = syscall_record.field(&format!("a{}", index))?;
+    u64::from_str_radix(arg, 16).ok()
+}
+
+// The PATH records of a syscall include the parent directories it touched,
+// we want the item that was actually opened, created or deleted
+fn target_path<'a>(event: &'a AuditEvent, nametypes: &[&str]) -> Option<&'a AuditRecord> {
+    event.records("PATH")
+        .find(|record| {
+            record.field("nametype")
+                .map(|nametype| nametypes.contains(&nametype))
+                .unwrap_or_default()
+        })
+}
+
+// PATH names are relative to the cwd of the process unless they are absolute
+fn resolve_path(event: &AuditEvent, path_record: &AuditRecord) -> Option<String> {
+    let name = path_record.field("name").filter(|name| *name != "(null)")?;
+
+    if name.starts_with('/') {
+        return Some(name.to_owned());
+    }
+
+    match event.record("CWD").and_then(|cwd| cwd.field("cwd")) {
+        Some(cwd) => Some(format!("{}/{}", cwd.trim_end_matches('/'), name)),
+        None => Some(name.to_owned()),
+    }
+}
+
+fn command_line(execve_record: &AuditRecord) -> String {
+    let argc = execve_record.u64_field("argc").unwrap_or_default();
+
+    let mut args = Vec::with_capacity(argc as usize);
+
+    for i in 0..argc {
+        if let Some(arg) = execve_record.field(&format!("a{}", i)) {
+            args.push(arg.to_owned());
+            continue;
+        }
+
+        // Long arguments are split into a1[0], a1[1], ...
+        let mut arg = String::new();
+        let mut chunk = 0;
+        while let Some(part) = execve_record.field(&format!("a{}[{}]", i, chunk)) {
+            arg.push_str(part);
+            chunk += 1;
+        }
+
+        args.push(arg);
+    }
+
+    args.join(" ")
+}
+
+// saddr is the hex encoded sockaddr struct, with the family in host byte order
+// and the port and address in network byte order
+fn decode_sockaddr(saddr: &str) -> Option<(String, u32)> {
+    let bytes = decode_hex(saddr)?;
+
+    if bytes.len() < 4 {
+        return None;
+    }
+
+    let family = u16::from_le_bytes([bytes[0], bytes[1]]);
+    let port = u16::from_be_bytes([bytes[2], bytes[3]]) as u32;
+
+    match family {
+        AF_INET if bytes.len() >= 8 => {
+            let ip = Ipv4Addr::new(bytes[4], bytes[5], bytes[6], bytes[7]);
+            Some((ip.to_string(), port))
+        }
+        AF_INET6 if bytes.len() >= 24 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&bytes[8..24]);
+            Some((Ipv6Addr::from(octets).to_string(), port))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{group_records, parse_record};
+
+    fn parse_event(lines: &[&str]) -> AuditEvent {
+        let records = lines.iter().map(|line| parse_record(line).expect("parse_record"));
+        group_records(records).remove(0)
+    }
+
+    #[test]
+    fn execve_generates_parent_and_child() {
+        let event = parse_event(&[
+            r#"node=web-1 type=SYSCALL msg=audit(1364481363.243:24287): arch=c000003e syscall=59 success=yes exit=0 items=2 ppid=2686 pid=3538 auid=1000 uid=0 comm="cat" exe="/bin/cat""#,
+            r#"node=web-1 type=EXECVE msg=audit(1364481363.243:24287): argc=2 a0="cat" a1="/etc/ssh/sshd_config""#,
+            r#"node=web-1 type=CWD msg=audit(1364481363.243:24287): cwd="/home/user""#,
+            r#"node=web-1 type=PATH msg=audit(1364481363.243:24287): item=0 name="/bin/cat" nametype=NORMAL"#,
+        ]);
+
+        let graph = generate_subgraph(&event)
+            .expect("generate_subgraph")
+            .expect("execve produces a graph");
+
+        // asset, parent, child, bin_file, uid user and auid user
+        assert_eq!(graph.nodes.len(), 6);
+
+        let child = graph.nodes.values()
+            .filter_map(|node| node.as_process())
+            .find(|process| process.process_id == 3538)
+            .expect("child process");
+        assert_eq!(child.process_command_line, "cat /etc/ssh/sshd_config");
+    }
+
+    #[test]
+    fn container_id_record_adds_container() {
+        let event = parse_event(&[
+            r#"node=web-1 type=SYSCALL msg=audit(1.000:9): arch=c000003e syscall=59 success=yes exit=0 items=1 ppid=1 pid=42 uid=0 comm="nginx" exe="/usr/sbin/nginx""#,
+            r#"node=web-1 type=CONTAINER_ID msg=audit(1.000:9): contid=123459"#,
+        ]);
+
+        let graph = generate_subgraph(&event)
+            .expect("generate_subgraph")
+            .expect("execve produces a graph");
+
+        let container = graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .find(|node| node.node_type == "Container")
+            .expect("container");
+
+        assert_eq!(
+            container.properties.get("container_id").map(|id| id.to_string()),
+            Some("123459".to_owned())
+        );
+    }
+
+    #[test]
+    fn relative_open_is_resolved_against_cwd() {
+        let event = parse_event(&[
+            r#"node=web-1 type=SYSCALL msg=audit(1.000:7): arch=c000003e syscall=257 success=yes exit=3 a0=ffffff9c a1=7ffd a2=241 items=2 ppid=1 pid=42 uid=0 comm="sh" exe="/bin/sh""#,
+            r#"node=web-1 type=CWD msg=audit(1.000:7): cwd="/tmp""#,
+            r#"node=web-1 type=PATH msg=audit(1.000:7): item=0 name="/tmp/" nametype=PARENT"#,
+            r#"node=web-1 type=PATH msg=audit(1.000:7): item=1 name="out.txt" nametype=CREATE"#,
+        ]);
+
+        let graph = generate_subgraph(&event)
+            .expect("generate_subgraph")
+            .expect("openat produces a graph");
+
+        let file = graph.nodes.values()
+            .filter_map(|node| node.as_file())
+            .next()
+            .expect("file");
+        assert_eq!(file.file_path, "/tmp/out.txt");
+        assert_eq!(file.state, u32::from(FileState::Created));
+    }
+
+    #[test]
+    fn ipv4_sockaddr_is_decoded() {
+        assert_eq!(
+            decode_sockaddr("0200005008080808000000000000000000"),
+            Some(("8.8.8.8".to_owned(), 80))
+        );
+        assert_eq!(decode_sockaddr("01002F746D70"), None);
+    }
+}
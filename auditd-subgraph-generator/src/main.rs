@@ -0,0 +1,69 @@
+extern crate aws_lambda_events;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+extern crate uuid;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::generator::generate_subgraph;
+use crate::parser::{AuditEvent, group_records, parse_record};
+
+mod generator;
+mod parser;
+
+#[derive(Clone)]
+struct AuditdSubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for AuditdSubgraphGenerator {
+    type Event = AuditEvent;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<AuditEvent>, failure::Error> {
+        // Each line of the payload is one audit record, and an event may span several lines
+        let records: Vec<_> = payload.split(|i| *i == b'\n')
+            .map(String::from_utf8_lossy)
+            .filter(|record| !record.trim().is_empty())
+            .filter_map(|record| match parse_record(&record) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    warn!("Failed to parse audit record: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok(group_records(records))
+    }
+
+    fn identity(&self, event: &AuditEvent) -> String {
+        event.identity()
+    }
+
+    async fn generate_subgraph(&mut self, event: AuditEvent) -> Result<Option<Graph>, failure::Error> {
+        generate_subgraph(&event)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "AUDITD", AuditdSubgraphGenerator, ZstdDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
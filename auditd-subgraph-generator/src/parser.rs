@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use failure::Error;
+
+// Fields that auditd hex encodes when they contain spaces, quotes or control characters
+const ENCODED_FIELDS: &[&str] = &["name", "cwd", "exe", "comm", "proctitle", "path"];
+
+// Enriched logs (log_format = ENRICHED) append translated fields after a group separator
+const ENRICHED_SEPARATOR: char = '\x1d';
+
+#[derive(Clone, Debug)]
+pub struct AuditRecord {
+    pub record_type: String,
+    pub node: Option<String>,
+    // Milliseconds since the epoch
+    pub timestamp: u64,
+    pub serial: u64,
+    pub fields: HashMap<String, String>,
+}
+
+impl AuditRecord {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+
+    pub fn u64_field(&self, name: &str) -> Option<u64> {
+        self.field(name).and_then(|value| value.parse().ok())
+    }
+}
+
+/// All of the records emitted by the kernel for a single audit event.
+/// Records are grouped by node, timestamp and serial number.
+#[derive(Clone, Debug)]
+pub struct AuditEvent {
+    pub node: Option<String>,
+    pub timestamp: u64,
+    pub serial: u64,
+    pub records: Vec<AuditRecord>,
+}
+
+impl AuditEvent {
+    pub fn record(&self, record_type: &str) -> Option<&AuditRecord> {
+        self.records.iter().find(|record| record.record_type == record_type)
+    }
+
+    pub fn records<'a>(&'a self, record_type: &'a str) -> impl Iterator<Item=&'a AuditRecord> + 'a {
+        self.records.iter().filter(move |record| record.record_type == record_type)
+    }
+
+    /// A stable identity for the event, used as the cache key
+    pub fn identity(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.node.as_deref().unwrap_or_default(),
+            self.timestamp,
+            self.serial,
+        )
+    }
+}
+
+pub fn parse_record(line: &str) -> Result<AuditRecord, Error> {
+    let line = match line.find(ENRICHED_SEPARATOR) {
+        Some(index) => &line[..index],
+        None => line,
+    };
+
+    let mut record_type = None;
+    let mut node = None;
+    let mut header = None;
+    let mut raw_fields = Vec::new();
+
+    for token in tokenize(line) {
+        let (key, value) = match token.find('=') {
+            Some(index) => (&token[..index], &token[index + 1..]),
+            None => continue,
+        };
+
+        match key {
+            "type" if record_type.is_none() => record_type = Some(value.to_owned()),
+            "node" if node.is_none() => node = Some(value.to_owned()),
+            "msg" if value.starts_with("audit(") && header.is_none() => {
+                header = Some(parse_header(value)?)
+            }
+            _ => raw_fields.push((key, value)),
+        }
+    }
+
+    let record_type = match record_type {
+        Some(record_type) => record_type,
+        None => bail!("Audit record is missing its type: {}", line),
+    };
+
+    let (timestamp, serial) = match header {
+        Some(header) => header,
+        None => bail!("Audit record is missing its msg=audit(...) header: {}", line),
+    };
+
+    let fields = raw_fields
+        .into_iter()
+        .map(|(key, value)| (key.to_owned(), decode_value(&record_type, key, value)))
+        .collect();
+
+    Ok(AuditRecord { record_type, node, timestamp, serial, fields })
+}
+
+/// Groups records into events, preserving the order in which each event was first seen.
+/// Records from the same event are usually adjacent, but auditd makes no guarantee.
+pub fn group_records(records: impl IntoIterator<Item=AuditRecord>) -> Vec<AuditEvent> {
+    let mut events: Vec<AuditEvent> = Vec::new();
+    let mut index: HashMap<(Option<String>, u64, u64), usize> = HashMap::new();
+
+    for record in records {
+        // EOE only marks the end of a multi record event
+        if record.record_type == "EOE" {
+            continue;
+        }
+
+        let key = (record.node.clone(), record.timestamp, record.serial);
+
+        match index.get(&key) {
+            Some(i) => events[*i].records.push(record),
+            None => {
+                index.insert(key, events.len());
+                events.push(AuditEvent {
+                    node: record.node.clone(),
+                    timestamp: record.timestamp,
+                    serial: record.serial,
+                    records: vec![record],
+                });
+            }
+        }
+    }
+
+    events
+}
+
+// Parses 'audit(1364481363.243:24287):' into (milliseconds, serial)
+fn parse_header(header: &str) -> Result<(u64, u64), Error> {
+    let inner = header
+        .trim_start_matches("audit(")
+        .trim_end_matches(':')
+        .trim_end_matches(')');
+
+    let (timestamp, serial) = match inner.find(':') {
+        Some(index) => (&inner[..index], &inner[index + 1..]),
+        None => bail!("Invalid audit header: {}", header),
+    };
+
+    let (secs, millis) = match timestamp.find('.') {
+        Some(index) => (&timestamp[..index], &timestamp[index + 1..]),
+        None => (timestamp, "0"),
+    };
+
+    let secs: u64 = secs.parse()
+        .map_err(|e| format_err!("Invalid audit timestamp {}: {}", header, e))?;
+    let millis: u64 = millis.parse()
+        .map_err(|e| format_err!("Invalid audit timestamp {}: {}", header, e))?;
+    let serial: u64 = serial.parse()
+        .map_err(|e| format_err!("Invalid audit serial {}: {}", header, e))?;
+
+    Ok((secs * 1000 + millis, serial))
+}
+
+// Splits on whitespace, keeping quoted values together
+fn tokenize(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => (),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                start.get_or_insert(i);
+            }
+            (None, c) if c.is_whitespace() => {
+                if let Some(s) = start.take() {
+                    tokens.push(&line[s..i]);
+                }
+            }
+            (None, _) => {
+                start.get_or_insert(i);
+            }
+        }
+    }
+
+    if let Some(s) = start {
+        tokens.push(&line[s..]);
+    }
+
+    tokens
+}
+
+fn decode_value(record_type: &str, key: &str, value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        return value[1..value.len() - 1].to_owned();
+    }
+
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].to_owned();
+    }
+
+    if is_encoded_field(record_type, key) {
+        if let Some(decoded) = decode_hex(value) {
+            // proctitle separates arguments with nul bytes
+            return String::from_utf8_lossy(&decoded).replace('\0', " ");
+        }
+    }
+
+    value.to_owned()
+}
+
+fn is_encoded_field(record_type: &str, key: &str) -> bool {
+    if ENCODED_FIELDS.contains(&key) {
+        return true;
+    }
+
+    // SYSCALL records also have a0..a3, but those are raw hex syscall arguments
+    if record_type != "EXECVE" {
+        return false;
+    }
+
+    // EXECVE arguments: a0, a1, ... and a1[0], a1[1], ... for long arguments
+    let key = key.trim_start_matches('a');
+    let key = match key.find('[') {
+        Some(index) if key.ends_with(']') => &key[..index],
+        _ => key,
+    };
+
+    !key.is_empty() && key.chars().all(|c| c.is_ascii_digit())
+}
+
+pub fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if value.is_empty() || value.len() % 2 == 1 {
+        return None;
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_syscall_record() {
+        let line = concat!(
+            r#"node=web-1 type=SYSCALL msg=audit(1364481363.243:24287): arch=c000003e syscall=59 "#,
+            r#"success=yes exit=0 a0=1e2f2f0 items=2 ppid=2686 pid=3538 auid=1000 uid=0 "#,
+            r#"comm="cat" exe="/bin/cat" key="sshd_config""#,
+            "\x1dARCH=x86_64 SYSCALL=execve"
+        );
+
+        let record = parse_record(line).expect("parse_record");
+
+        assert_eq!(record.record_type, "SYSCALL");
+        assert_eq!(record.node.as_deref(), Some("web-1"));
+        assert_eq!(record.timestamp, 1364481363243);
+        assert_eq!(record.serial, 24287);
+        assert_eq!(record.field("exe"), Some("/bin/cat"));
+        assert_eq!(record.u64_field("pid"), Some(3538));
+        assert_eq!(record.field("SYSCALL"), None);
+    }
+
+    #[test]
+    fn hex_encoded_fields_are_decoded() {
+        let line = r#"type=EXECVE msg=audit(1364481363.243:24287): argc=2 a0="cat" a1=2F746D702F6120622E747874"#;
+
+        let record = parse_record(line).expect("parse_record");
+
+        assert_eq!(record.field("a0"), Some("cat"));
+        assert_eq!(record.field("a1"), Some("/tmp/a b.txt"));
+    }
+
+    #[test]
+    fn records_are_grouped_by_serial() {
+        let lines = [
+            r#"type=SYSCALL msg=audit(1.000:1): syscall=59 pid=10"#,
+            r#"type=SYSCALL msg=audit(1.000:2): syscall=59 pid=20"#,
+            r#"type=EXECVE msg=audit(1.000:1): argc=1 a0="ls""#,
+            r#"type=EOE msg=audit(1.000:1): "#,
+            r#"type=EXECVE msg=audit(1.000:2): argc=1 a0="ps""#,
+        ];
+
+        let records = lines.iter().map(|line| parse_record(line).expect("parse_record"));
+        let events = group_records(records);
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].serial, 1);
+        assert_eq!(events[0].records.len(), 2);
+        assert_eq!(events[0].record("EXECVE").and_then(|r| r.field("a0")), Some("ls"));
+        assert_eq!(events[1].record("EXECVE").and_then(|r| r.field("a0")), Some("ps"));
+    }
+}
@@ -0,0 +1,239 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use failure::Error;
+use regex::Regex;
+
+lazy_static! {
+    // <timestamp> <hostname> sshd[<pid>]: <message>
+    static ref SSHD_LINE: Regex = Regex::new(
+        r"^(?P<timestamp>[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}|\d{4}-\d{2}-\d{2}T\S+) (?P<hostname>\S+) sshd\[(?P<pid>\d+)\]: (?P<message>.*)$"
+    ).expect("SSHD_LINE");
+
+    static ref ACCEPTED: Regex = Regex::new(
+        r"^Accepted (?P<method>\S+) for (?P<user>\S+) from (?P<ip>\S+) port (?P<port>\d+)"
+    ).expect("ACCEPTED");
+
+    static ref FAILED: Regex = Regex::new(
+        r"^Failed (?P<method>\S+) for (?P<invalid>invalid user )?(?P<user>\S+) from (?P<ip>\S+) port (?P<port>\d+)"
+    ).expect("FAILED");
+
+    static ref INVALID_USER: Regex = Regex::new(
+        r"^Invalid user (?P<user>\S*) from (?P<ip>\S+) port (?P<port>\d+)"
+    ).expect("INVALID_USER");
+
+    static ref SESSION_OPENED: Regex = Regex::new(
+        r"^pam_unix\(sshd:session\): session opened for user (?P<user>\S+)"
+    ).expect("SESSION_OPENED");
+
+    static ref SESSION_CLOSED: Regex = Regex::new(
+        r"^pam_unix\(sshd:session\): session closed for user (?P<user>\S+)"
+    ).expect("SESSION_CLOSED");
+
+    // Disconnects are logged by both sides, before and after authentication
+    static ref DISCONNECTED: Regex = Regex::new(
+        r"^(?:Disconnected from|Connection closed by|Received disconnect from) (?:(?:authenticating |invalid )?user (?P<user>\S+) )?(?P<ip>[0-9a-fA-F.:]+) port (?P<port>\d+)"
+    ).expect("DISCONNECTED");
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SshEvent {
+    Accepted {
+        method: String,
+        user: String,
+        ip: String,
+        port: u16,
+    },
+    Failed {
+        method: Option<String>,
+        user: String,
+        // The user does not exist on the host
+        invalid_user: bool,
+        ip: String,
+        port: u16,
+    },
+    Opened {
+        user: String,
+    },
+    Closed {
+        user: Option<String>,
+        ip: Option<String>,
+        port: Option<u16>,
+    },
+}
+
+impl SshEvent {
+    fn from_message(message: &str) -> Option<SshEvent> {
+        if let Some(captures) = ACCEPTED.captures(message) {
+            return Some(SshEvent::Accepted {
+                method: captures["method"].to_owned(),
+                user: captures["user"].to_owned(),
+                ip: captures["ip"].to_owned(),
+                port: captures["port"].parse().ok()?,
+            });
+        }
+
+        if let Some(captures) = FAILED.captures(message) {
+            return Some(SshEvent::Failed {
+                method: Some(captures["method"].to_owned()),
+                user: captures["user"].to_owned(),
+                invalid_user: captures.name("invalid").is_some(),
+                ip: captures["ip"].to_owned(),
+                port: captures["port"].parse().ok()?,
+            });
+        }
+
+        if let Some(captures) = INVALID_USER.captures(message) {
+            return Some(SshEvent::Failed {
+                method: None,
+                user: captures["user"].to_owned(),
+                invalid_user: true,
+                ip: captures["ip"].to_owned(),
+                port: captures["port"].parse().ok()?,
+            });
+        }
+
+        if let Some(captures) = SESSION_OPENED.captures(message) {
+            return Some(SshEvent::Opened { user: captures["user"].to_owned() });
+        }
+
+        if let Some(captures) = SESSION_CLOSED.captures(message) {
+            return Some(SshEvent::Closed {
+                user: Some(captures["user"].to_owned()),
+                ip: None,
+                port: None,
+            });
+        }
+
+        if let Some(captures) = DISCONNECTED.captures(message) {
+            return Some(SshEvent::Closed {
+                user: captures.name("user").map(|user| user.as_str().to_owned()),
+                ip: Some(captures["ip"].to_owned()),
+                port: captures["port"].parse().ok(),
+            });
+        }
+
+        None
+    }
+}
+
+/// An sshd line from auth.log or secure
+#[derive(Clone, Debug)]
+pub struct AuthLogLine {
+    pub timestamp: u64,
+    pub hostname: String,
+    /// Every line about a connection is logged by the sshd process that is handling it
+    pub pid: u64,
+    pub event: SshEvent,
+    /// The raw line, used to identify it
+    pub line: String,
+}
+
+/// Parses the sshd lines we model from a syslog formatted payload, skipping the rest.
+///
+/// Traditional syslog timestamps have no year or timezone. They are taken to be UTC,
+/// in the year before `now` that puts them in the past
+pub fn parse_auth_log(payload: &str, now: DateTime<Utc>) -> Result<Vec<AuthLogLine>, Error> {
+    let mut lines = Vec::new();
+
+    for line in payload.lines() {
+        let captures = match SSHD_LINE.captures(line) {
+            Some(captures) => captures,
+            None => continue,
+        };
+
+        let event = match SshEvent::from_message(&captures["message"]) {
+            Some(event) => event,
+            None => continue,
+        };
+
+        lines.push(AuthLogLine {
+            timestamp: parse_timestamp(&captures["timestamp"], now)?,
+            hostname: captures["hostname"].to_owned(),
+            pid: captures["pid"].parse()?,
+            event,
+            line: line.to_owned(),
+        });
+    }
+
+    Ok(lines)
+}
+
+fn parse_timestamp(timestamp: &str, now: DateTime<Utc>) -> Result<u64, Error> {
+    let ts = match DateTime::parse_from_rfc3339(timestamp) {
+        Ok(ts) => ts.timestamp_millis(),
+        Err(_) => {
+            let mut year = now.year();
+
+            // Allow for clock skew between the host and us before rolling back a year
+            let ts = loop {
+                let ts = NaiveDateTime::parse_from_str(
+                    &format!("{} {}", year, timestamp),
+                    "%Y %b %e %H:%M:%S",
+                ).map_err(|e| format_err!("Invalid timestamp {}: {}", timestamp, e))?;
+
+                let ts = Utc.from_utc_datetime(&ts);
+                if ts <= now + Duration::days(1) || year < now.year() {
+                    break ts;
+                }
+                year -= 1;
+            };
+
+            ts.timestamp_millis()
+        }
+    };
+
+    if ts < 0 {
+        bail!("Timestamp is negative")
+    }
+
+    Ok(ts as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sshd_lines_are_parsed() {
+        let payload = concat!(
+            "Feb 11 18:03:12 web-01 sshd[24090]: Failed password for invalid user admin from 198.51.100.23 port 40122 ssh2\n",
+            "Feb 11 18:03:14 web-01 sudo: pam_unix(sudo:session): session opened for user root by alice(uid=1000)\n",
+            "2020-02-11T18:04:51.123456+00:00 web-01 sshd[24121]: Accepted publickey for alice from 203.0.113.12 port 51514 ssh2: RSA SHA256:3i1cbJ+dDTZnm/u6kFQ4kA\n",
+            "Feb 11 18:10:02 web-01 sshd[24121]: Disconnected from user alice 203.0.113.12 port 51514\n",
+        );
+
+        let now = "2020-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let lines = parse_auth_log(payload, now).expect("parse_auth_log");
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].timestamp, 1581444192000);
+        assert_eq!(lines[0].pid, 24090);
+        assert_eq!(
+            lines[0].event,
+            SshEvent::Failed {
+                method: Some("password".to_owned()),
+                user: "admin".to_owned(),
+                invalid_user: true,
+                ip: "198.51.100.23".to_owned(),
+                port: 40122,
+            }
+        );
+        assert_eq!(lines[1].timestamp, 1581444291123);
+        assert_eq!(
+            lines[2].event,
+            SshEvent::Closed {
+                user: Some("alice".to_owned()),
+                ip: Some("203.0.113.12".to_owned()),
+                port: Some(51514),
+            }
+        );
+    }
+
+    #[test]
+    fn timestamps_from_last_year() {
+        let now = "2020-01-01T00:05:00Z".parse::<DateTime<Utc>>().unwrap();
+        let ts = parse_timestamp("Dec 31 23:59:58", now).expect("parse_timestamp");
+
+        assert_eq!(ts, 1577836798000);
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::node::NodeT;
+
+use crate::auth_log::{AuthLogLine, SshEvent};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SessionState {
+    Created,
+    Existing,
+    Terminated,
+}
+
+struct LineContext<'a> {
+    line: &'a AuthLogLine,
+}
+
+impl<'a> LineContext<'a> {
+    fn asset(&self) -> Asset {
+        AssetBuilder::default()
+            .asset_id(self.line.hostname.clone())
+            .hostname(self.line.hostname.clone())
+            .build()
+            .unwrap()
+    }
+
+    // Only the user name is logged, so users are identified by it on the asset
+    fn user(&self, user_name: &str) -> DynamicNode {
+        let mut user = DynamicNode {
+            properties: HashMap::with_capacity(1),
+            node_key: uuid::Uuid::new_v4().to_string(),
+            node_type: "User".to_owned(),
+            seen_at: self.line.timestamp,
+            asset_id: None,
+            hostname: Some(self.line.hostname.clone()),
+            host_ip: None,
+            host_mac: None,
+            id_strategy: vec![
+                Static {
+                    primary_key_properties: vec!["user_name".to_owned()],
+                    primary_key_requires_asset_id: true,
+                }.into()
+            ],
+        };
+
+        user.set_property("user_name", user_name.to_owned());
+
+        user
+    }
+
+    // Every line about a connection comes from the sshd process handling it, so its pid
+    // identifies the session until the pid is reused, which the session timelines account for
+    fn session(&self, state: SessionState) -> DynamicNode {
+        let timestamp = self.line.timestamp;
+        let (created_time, last_seen_time, terminated_time) = match state {
+            SessionState::Created => (timestamp, 0, 0),
+            SessionState::Existing => (0, timestamp, 0),
+            SessionState::Terminated => (0, 0, timestamp),
+        };
+
+        let mut session = DynamicNode {
+            properties: HashMap::with_capacity(6),
+            node_key: uuid::Uuid::new_v4().to_string(),
+            node_type: "LoginSession".to_owned(),
+            seen_at: timestamp,
+            asset_id: None,
+            hostname: Some(self.line.hostname.clone()),
+            host_ip: None,
+            host_mac: None,
+            id_strategy: vec![
+                Session {
+                    primary_key_properties: vec!["sshd_pid".to_owned()],
+                    primary_key_requires_asset_id: true,
+                    created_time,
+                    last_seen_time,
+                    terminated_time,
+                }.into()
+            ],
+        };
+
+        session.set_property("sshd_pid", self.line.pid);
+
+        session
+    }
+
+    fn source_ip(&self, ip: &str) -> Result<IpAddress, Error> {
+        IpAddressBuilder::default()
+            .ip_address(ip.to_owned())
+            .last_seen_timestamp(self.line.timestamp)
+            .build()
+            .map_err(|e| format_err!("source_ip: {}", e))
+    }
+}
+
+fn set_source(session: &mut DynamicNode, ip: &str, port: u16) {
+    session.set_property("src_ip", ip.to_owned());
+    session.set_property("src_port", port as u64);
+}
+
+pub fn generate_subgraph(line: &AuthLogLine) -> Result<Graph, Error> {
+    let context = LineContext { line };
+
+    let mut graph = Graph::new(line.timestamp);
+    let asset = context.asset();
+
+    let (session, user_edge, ip) = match line.event {
+        SshEvent::Accepted { ref method, ref user, ref ip, port } => {
+            let mut session = context.session(SessionState::Created);
+            session.set_property("auth_method", method.clone());
+            session.set_property("user_name", user.clone());
+            session.set_property("authenticated", 1u64);
+            set_source(&mut session, ip, port);
+
+            (session, Some(("logon_sessions", user)), Some(ip))
+        }
+        SshEvent::Failed { ref method, ref user, invalid_user, ref ip, port } => {
+            // We do not know when the connection was made, only that it was live
+            let mut session = context.session(SessionState::Existing);
+            if let Some(method) = method {
+                session.set_property("auth_method", method.clone());
+            }
+            session.set_property("user_name", user.clone());
+            set_source(&mut session, ip, port);
+
+            // Users that do not exist on the asset only live on as the attempted name
+            let user_edge = if invalid_user { None } else { Some(("failed_logons", user)) };
+
+            (session, user_edge, Some(ip))
+        }
+        SshEvent::Opened { ref user } => {
+            (context.session(SessionState::Existing), Some(("logon_sessions", user)), None)
+        }
+        SshEvent::Closed { ref ip, .. } => {
+            (context.session(SessionState::Terminated), None, ip.as_ref())
+        }
+    };
+
+    graph.add_edge("logged_on_to", session.clone_node_key(), asset.clone_node_key());
+
+    if let Some((edge_name, user_name)) = user_edge {
+        let user = context.user(user_name);
+        graph.add_edge(edge_name, user.clone_node_key(), session.clone_node_key());
+        graph.add_node(user);
+    }
+
+    if let Some(ip) = ip {
+        let source_ip = context.source_ip(ip)?;
+        graph.add_edge("logon_from", session.clone_node_key(), source_ip.clone_node_key());
+        graph.add_node(source_ip);
+    }
+
+    graph.add_node(session);
+    graph.add_node(asset);
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use graph_descriptions::graph_description::id_strategy::Strategy;
+
+    use super::*;
+    use crate::auth_log::parse_auth_log;
+
+    fn generate(line: &str) -> Graph {
+        let now = "2020-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let lines = parse_auth_log(line, now).expect("parse_auth_log");
+
+        generate_subgraph(&lines[0]).expect("generate_subgraph")
+    }
+
+    fn session_strategy(graph: &Graph) -> Session {
+        let session = graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .find(|node| node.node_type == "LoginSession")
+            .expect("LoginSession");
+
+        match session.id_strategy[0].strategy {
+            Some(Strategy::Session(ref strategy)) => strategy.clone(),
+            _ => panic!("LoginSession is not identified by session"),
+        }
+    }
+
+    fn edge_names(graph: &Graph) -> Vec<String> {
+        let mut edge_names: Vec<_> = graph.edges.values()
+            .flat_map(|edges| edges.edges.iter())
+            .map(|edge| edge.edge_name.clone())
+            .collect();
+        edge_names.sort();
+        edge_names
+    }
+
+    #[test]
+    fn accepted_login_creates_session() {
+        let graph = generate(
+            "Feb 11 18:04:51 web-01 sshd[24121]: Accepted publickey for alice from 203.0.113.12 port 51514 ssh2"
+        );
+
+        assert_eq!(session_strategy(&graph).created_time, 1581444291000);
+        assert_eq!(edge_names(&graph), vec!["logged_on_to", "logon_from", "logon_sessions"]);
+    }
+
+    #[test]
+    fn failed_login_links_existing_user() {
+        let graph = generate(
+            "Feb 11 18:03:12 web-01 sshd[24090]: Failed password for root from 198.51.100.23 port 40122 ssh2"
+        );
+
+        assert_eq!(session_strategy(&graph).last_seen_time, 1581444192000);
+        assert_eq!(edge_names(&graph), vec!["failed_logons", "logged_on_to", "logon_from"]);
+    }
+
+    #[test]
+    fn failed_login_for_invalid_user_has_no_user() {
+        let graph = generate(
+            "Feb 11 18:03:12 web-01 sshd[24090]: Failed password for invalid user admin from 198.51.100.23 port 40122 ssh2"
+        );
+
+        assert_eq!(edge_names(&graph), vec!["logged_on_to", "logon_from"]);
+    }
+
+    #[test]
+    fn closed_session_terminates() {
+        let graph = generate(
+            "Feb 11 18:10:02 web-01 sshd[24121]: pam_unix(sshd:session): session closed for user alice"
+        );
+
+        assert_eq!(session_strategy(&graph).terminated_time, 1581444602000);
+        assert_eq!(edge_names(&graph), vec!["logged_on_to"]);
+    }
+}
@@ -0,0 +1,62 @@
+extern crate aws_lambda_events;
+extern crate chrono;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+#[macro_use]
+extern crate lazy_static;
+extern crate log;
+extern crate regex;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+extern crate uuid;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use chrono::Utc;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::auth_log::{parse_auth_log, AuthLogLine};
+use crate::generator::generate_subgraph;
+
+mod auth_log;
+mod generator;
+
+#[derive(Clone)]
+struct AuthLogSubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for AuthLogSubgraphGenerator {
+    type Event = AuthLogLine;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<AuthLogLine>, failure::Error> {
+        // The payload is syslog formatted lines from auth.log or secure
+        parse_auth_log(&String::from_utf8_lossy(payload), Utc::now())
+    }
+
+    fn identity(&self, event: &AuthLogLine) -> String {
+        event.line.clone()
+    }
+
+    async fn generate_subgraph(&mut self, event: AuthLogLine) -> Result<Option<Graph>, failure::Error> {
+        generate_subgraph(&event).map(Some)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "AUTH_LOG", AuthLogSubgraphGenerator, ZstdDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use chrono::prelude::*;
+use failure::Error;
+use serde_json::Value;
+
+/// A CloudTrail log file, as delivered to S3
+#[derive(Clone, Debug, Deserialize)]
+pub struct CloudTrailLog {
+    #[serde(rename = "Records")]
+    pub records: Vec<CloudTrailEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloudTrailEvent {
+    #[serde(rename = "eventID")]
+    pub event_id: String,
+    pub event_time: String,
+    pub event_source: String,
+    pub event_name: String,
+    pub aws_region: String,
+    #[serde(rename = "sourceIPAddress")]
+    pub source_ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub user_identity: UserIdentity,
+    pub error_code: Option<String>,
+    pub recipient_account_id: Option<String>,
+    #[serde(default)]
+    pub request_parameters: Option<Value>,
+    #[serde(default)]
+    pub response_elements: Option<Value>,
+    #[serde(default)]
+    pub resources: Vec<Resource>,
+}
+
+impl CloudTrailEvent {
+    pub fn timestamp(&self) -> Result<u64, Error> {
+        let event_time = DateTime::parse_from_rfc3339(&self.event_time)
+            .map_err(|e| format_err!("Invalid eventTime {}: {}", self.event_time, e))?;
+
+        let ts = event_time.timestamp_millis();
+        if ts < 0 {
+            bail!("Timestamp is negative")
+        }
+
+        Ok(ts as u64)
+    }
+
+    /// The account the call was made against, which owns the resources it touched
+    pub fn account_id(&self) -> Option<&str> {
+        self.recipient_account_id.as_ref()
+            .or(self.user_identity.account_id.as_ref())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserIdentity {
+    #[serde(rename = "type")]
+    pub identity_type: Option<String>,
+    pub principal_id: Option<String>,
+    pub arn: Option<String>,
+    pub account_id: Option<String>,
+    pub user_name: Option<String>,
+    pub session_context: Option<SessionContext>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionContext {
+    pub session_issuer: Option<SessionIssuer>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionIssuer {
+    #[serde(rename = "type")]
+    pub issuer_type: Option<String>,
+    pub principal_id: Option<String>,
+    pub arn: Option<String>,
+    pub account_id: Option<String>,
+    pub user_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Resource {
+    #[serde(rename = "ARN")]
+    pub arn: Option<String>,
+    #[serde(rename = "type")]
+    pub resource_type: Option<String>,
+}
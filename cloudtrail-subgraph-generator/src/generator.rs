@@ -0,0 +1,278 @@
+use std::collections::BTreeSet;
+
+use failure::Error;
+use grapl_graph_descriptions::graph_description::*;
+use serde_json::Value;
+
+use crate::cloudtrail::{CloudTrailEvent, SessionIssuer, UserIdentity};
+use crate::nodes::*;
+
+const S3_EVENT_SOURCE: &str = "s3.amazonaws.com";
+const EC2_INSTANCE_RESOURCE: &str = "AWS::EC2::Instance";
+const S3_BUCKET_RESOURCE: &str = "AWS::S3::Bucket";
+
+pub fn generate_subgraph(event: &CloudTrailEvent) -> Result<Graph, Error> {
+    let timestamp = event.timestamp()?;
+
+    let mut graph = Graph::new(timestamp);
+
+    let mut api_call = AwsApiCallNode::new(AwsApiCallNode::static_strategy(), timestamp);
+    api_call
+        .with_event_id(event.event_id.clone())
+        .with_event_name(event.event_name.clone())
+        .with_event_source(event.event_source.clone())
+        .with_aws_region(event.aws_region.clone())
+        .with_event_time(timestamp);
+
+    if let Some(ref user_agent) = event.user_agent {
+        api_call.with_user_agent(user_agent.clone());
+    }
+
+    if let Some(ref error_code) = event.error_code {
+        api_call.with_error_code(error_code.clone());
+    }
+
+    if let Some(principal) = principal(&event.user_identity, timestamp) {
+        graph.add_edge("made_call", principal.clone_node_key(), api_call.clone_node_key());
+
+        let issuer = event.user_identity.session_context.as_ref()
+            .and_then(|context| context.session_issuer.as_ref())
+            .and_then(|issuer| session_issuer(issuer, timestamp));
+
+        // An assumed role session was issued by the role it assumed
+        if let Some(issuer) = issuer {
+            graph.add_edge("session_issuer", principal.clone_node_key(), issuer.clone_node_key());
+            graph.add_node(issuer);
+        }
+
+        graph.add_node(principal);
+    }
+
+    if let Some(ref source_ip_address) = event.source_ip_address {
+        let mut source_ip = AwsSourceIpNode::new(AwsSourceIpNode::static_strategy(), timestamp);
+        source_ip.with_source_ip_address(source_ip_address.clone());
+
+        graph.add_edge("call_source", api_call.clone_node_key(), source_ip.clone_node_key());
+        graph.add_node(source_ip);
+    }
+
+    let account_id = event.account_id().unwrap_or_default();
+
+    for instance_id in instance_ids(event) {
+        let mut instance = AwsEc2InstanceNode::new(AwsEc2InstanceNode::static_strategy(), timestamp);
+        instance
+            .with_arn(format!(
+                "arn:aws:ec2:{}:{}:instance/{}",
+                event.aws_region, account_id, instance_id
+            ))
+            .with_instance_id(instance_id)
+            .with_aws_region(event.aws_region.clone())
+            .with_account_id(account_id.to_owned());
+
+        graph.add_edge("target_instance", api_call.clone_node_key(), instance.clone_node_key());
+        graph.add_node(instance);
+    }
+
+    for bucket_name in bucket_names(event) {
+        let mut bucket = AwsS3BucketNode::new(AwsS3BucketNode::static_strategy(), timestamp);
+        bucket
+            .with_arn(format!("arn:aws:s3:::{}", bucket_name))
+            .with_bucket_name(bucket_name);
+
+        graph.add_edge("target_bucket", api_call.clone_node_key(), bucket.clone_node_key());
+        graph.add_node(bucket);
+    }
+
+    graph.add_node(api_call);
+
+    Ok(graph)
+}
+
+// Calls made by AWS services on an account's behalf have no principal arn
+fn principal(user_identity: &UserIdentity, timestamp: u64) -> Option<AwsIamPrincipalNode> {
+    let principal = principal_node(
+        user_identity.arn.as_ref()?,
+        &user_identity.identity_type,
+        &user_identity.principal_id,
+        &user_identity.account_id,
+        &user_identity.user_name,
+        timestamp,
+    );
+
+    Some(principal)
+}
+
+fn session_issuer(issuer: &SessionIssuer, timestamp: u64) -> Option<AwsIamPrincipalNode> {
+    let issuer = principal_node(
+        issuer.arn.as_ref()?,
+        &issuer.issuer_type,
+        &issuer.principal_id,
+        &issuer.account_id,
+        &issuer.user_name,
+        timestamp,
+    );
+
+    Some(issuer)
+}
+
+fn principal_node(
+    arn: &str,
+    principal_type: &Option<String>,
+    principal_id: &Option<String>,
+    account_id: &Option<String>,
+    user_name: &Option<String>,
+    timestamp: u64,
+) -> AwsIamPrincipalNode {
+    let mut principal = AwsIamPrincipalNode::new(AwsIamPrincipalNode::static_strategy(), timestamp);
+    principal.with_arn(arn.to_owned());
+
+    if let Some(principal_type) = principal_type {
+        principal.with_principal_type(principal_type.clone());
+    }
+    if let Some(principal_id) = principal_id {
+        principal.with_principal_id(principal_id.clone());
+    }
+    if let Some(account_id) = account_id {
+        principal.with_account_id(account_id.clone());
+    }
+    if let Some(user_name) = user_name {
+        principal.with_user_name(user_name.clone());
+    }
+
+    principal
+}
+
+// EC2 calls name their instances in an instancesSet, or in a single instanceId
+fn instance_ids(event: &CloudTrailEvent) -> BTreeSet<String> {
+    let mut instance_ids = BTreeSet::new();
+
+    for elements in event.request_parameters.iter().chain(event.response_elements.iter()) {
+        if let Some(Value::Array(items)) = elements.pointer("/instancesSet/items") {
+            instance_ids.extend(
+                items.iter()
+                    .filter_map(|item| item.get("instanceId"))
+                    .filter_map(Value::as_str)
+                    .map(String::from)
+            );
+        }
+
+        if let Some(Value::String(instance_id)) = elements.get("instanceId") {
+            instance_ids.insert(instance_id.clone());
+        }
+    }
+
+    for resource in &event.resources {
+        if resource.resource_type.as_deref() != Some(EC2_INSTANCE_RESOURCE) {
+            continue;
+        }
+
+        let instance_id = resource.arn.as_ref()
+            .and_then(|arn| arn.find("instance/").map(|i| &arn[i + "instance/".len()..]));
+
+        if let Some(instance_id) = instance_id {
+            instance_ids.insert(instance_id.to_owned());
+        }
+    }
+
+    instance_ids
+}
+
+fn bucket_names(event: &CloudTrailEvent) -> BTreeSet<String> {
+    let mut bucket_names = BTreeSet::new();
+
+    if event.event_source == S3_EVENT_SOURCE {
+        let bucket_name = event.request_parameters.as_ref()
+            .and_then(|params| params.get("bucketName"))
+            .and_then(Value::as_str);
+
+        if let Some(bucket_name) = bucket_name {
+            bucket_names.insert(bucket_name.to_owned());
+        }
+    }
+
+    for resource in &event.resources {
+        if resource.resource_type.as_deref() != Some(S3_BUCKET_RESOURCE) {
+            continue;
+        }
+
+        let bucket_name = resource.arn.as_ref()
+            .map(|arn| arn.trim_start_matches("arn:aws:s3:::"));
+
+        if let Some(bucket_name) = bucket_name {
+            bucket_names.insert(bucket_name.to_owned());
+        }
+    }
+
+    bucket_names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cloudtrail::CloudTrailLog;
+
+    fn recorded_events() -> Vec<CloudTrailEvent> {
+        let log: CloudTrailLog = serde_json::from_str(include_str!("../test_data/cloudtrail.json"))
+            .expect("recorded CloudTrail log");
+        log.records
+    }
+
+    fn recorded_event(event_name: &str) -> CloudTrailEvent {
+        recorded_events()
+            .into_iter()
+            .find(|event| event.event_name == event_name)
+            .expect(event_name)
+    }
+
+    fn node_types(graph: &Graph) -> Vec<String> {
+        let mut node_types: Vec<_> = graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .map(|node| node.node_type.clone())
+            .collect();
+        node_types.sort();
+        node_types
+    }
+
+    #[test]
+    fn every_recorded_event_generates() {
+        for event in recorded_events() {
+            generate_subgraph(&event).expect(&event.event_name);
+        }
+    }
+
+    #[test]
+    fn run_instances_targets_every_instance() {
+        let graph = generate_subgraph(&recorded_event("RunInstances")).expect("RunInstances");
+
+        assert_eq!(
+            node_types(&graph),
+            vec!["AwsApiCall", "AwsEc2Instance", "AwsEc2Instance", "AwsIamPrincipal", "AwsSourceIp"]
+        );
+    }
+
+    #[test]
+    fn assumed_role_links_its_issuer() {
+        let graph = generate_subgraph(&recorded_event("GetObject")).expect("GetObject");
+
+        assert_eq!(
+            node_types(&graph),
+            vec!["AwsApiCall", "AwsIamPrincipal", "AwsIamPrincipal", "AwsS3Bucket", "AwsSourceIp"]
+        );
+
+        let bucket = graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .find(|node| node.node_type == "AwsS3Bucket")
+            .expect("bucket");
+        assert_eq!(
+            bucket.properties.get("arn").map(|arn| arn.to_string()),
+            Some("arn:aws:s3:::examplebucket".to_owned())
+        );
+    }
+
+    #[test]
+    fn service_calls_have_no_principal() {
+        let graph = generate_subgraph(&recorded_event("Decrypt")).expect("Decrypt");
+
+        assert_eq!(node_types(&graph), vec!["AwsApiCall", "AwsSourceIp"]);
+    }
+}
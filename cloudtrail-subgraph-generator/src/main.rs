@@ -0,0 +1,64 @@
+extern crate aws_lambda_events;
+extern crate chrono;
+extern crate derive_dynamic_node;
+#[macro_use]
+extern crate failure;
+extern crate grapl_graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+extern crate uuid;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use grapl_graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SniffingDecoder, SubgraphGenerator};
+
+use crate::cloudtrail::{CloudTrailEvent, CloudTrailLog};
+use crate::generator::generate_subgraph;
+
+mod cloudtrail;
+mod generator;
+mod nodes;
+
+#[derive(Clone)]
+struct CloudTrailSubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for CloudTrailSubgraphGenerator {
+    type Event = CloudTrailEvent;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<CloudTrailEvent>, failure::Error> {
+        let log: CloudTrailLog = serde_json::from_slice(payload)?;
+        Ok(log.records)
+    }
+
+    fn identity(&self, event: &CloudTrailEvent) -> String {
+        event.event_id.clone()
+    }
+
+    async fn generate_subgraph(&mut self, event: CloudTrailEvent) -> Result<Option<Graph>, failure::Error> {
+        generate_subgraph(&event).map(Some)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "CLOUDTRAIL", CloudTrailSubgraphGenerator, SniffingDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
@@ -0,0 +1,83 @@
+// These structs only describe the nodes, the derived *Node types are what we build
+#![allow(dead_code)]
+
+use derive_dynamic_node::{DynamicNode, GraplStaticId};
+use grapl_graph_descriptions::graph_description::*;
+
+/// An IAM user, role, assumed role session or root account
+#[derive(DynamicNode, GraplStaticId)]
+pub struct AwsIamPrincipal {
+    #[grapl(static_id)]
+    arn: String,
+    principal_type: String,
+    principal_id: String,
+    account_id: String,
+    user_name: String,
+}
+
+impl IAwsIamPrincipalNode for AwsIamPrincipalNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+/// A single call to an AWS API, identified by its CloudTrail event id
+#[derive(DynamicNode, GraplStaticId)]
+pub struct AwsApiCall {
+    #[grapl(static_id)]
+    event_id: String,
+    event_name: String,
+    event_source: String,
+    aws_region: String,
+    event_time: u64,
+    user_agent: String,
+    error_code: String,
+}
+
+impl IAwsApiCallNode for AwsApiCallNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+#[derive(DynamicNode, GraplStaticId)]
+pub struct AwsEc2Instance {
+    #[grapl(static_id)]
+    arn: String,
+    instance_id: String,
+    aws_region: String,
+    account_id: String,
+}
+
+impl IAwsEc2InstanceNode for AwsEc2InstanceNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+#[derive(DynamicNode, GraplStaticId)]
+pub struct AwsS3Bucket {
+    #[grapl(static_id)]
+    arn: String,
+    bucket_name: String,
+}
+
+impl IAwsS3BucketNode for AwsS3BucketNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+/// The address a call was made from. AWS services calling on a principal's
+/// behalf are recorded by their service name instead, ie: ec2.amazonaws.com
+#[derive(DynamicNode, GraplStaticId)]
+pub struct AwsSourceIp {
+    #[grapl(static_id)]
+    source_ip_address: String,
+}
+
+impl IAwsSourceIpNode for AwsSourceIpNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
@@ -68,6 +68,7 @@ pub fn derive_dynamic_node(input: TokenStream) -> TokenStream {
                 let dynamic_node = DynamicNode {
                     asset_id: None,
                     host_ip: None,
+                    host_mac: None,
                     hostname: None,
                     node_type: #struct_name_string .to_owned(),
                     id_strategy: vec![strategy],
@@ -180,7 +181,7 @@ pub fn derive_grapl_session(input: TokenStream) -> TokenStream {
         })
         .fold(quote!(), |mut acc, f| {
             let f = f.to_string();
-            acc.extend(quote!(#f .to_string()));
+            acc.extend(quote!(#f .to_string(),));
             acc
         });
 
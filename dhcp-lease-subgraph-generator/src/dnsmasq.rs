@@ -0,0 +1,84 @@
+use failure::Error;
+
+use crate::leases::{Lease, normalize_mac};
+
+/// Parses a dnsmasq leases file, one lease per line, ie:
+///
+/// ```text
+/// 1546548307 00:11:22:33:44:55 192.168.1.100 laptop-7 01:00:11:22:33:44:55
+/// ```
+///
+/// Each lease is written with its expiry, in seconds, or 0 for an infinite lease.
+/// Infinite leases are assumed to have held since the epoch, as their start is unknown.
+/// DHCPv6 leases, which have an IAID in place of the mac address, are kept without it.
+pub fn parse_dnsmasq_leases(payload: &str, lease_time: u64) -> Result<Vec<Lease>, Error> {
+    let mut leases = Vec::new();
+
+    for line in payload.lines() {
+        let line = line.trim();
+
+        // The server's DUID is written before any DHCPv6 leases
+        if line.is_empty() || line.starts_with("duid ") {
+            continue;
+        }
+
+        let fields: Vec<_> = line.split_whitespace().collect();
+
+        if fields.len() < 4 {
+            bail!("Invalid dnsmasq lease: {}", line);
+        }
+
+        let (expiry, mac, ip, hostname) = (fields[0], fields[1], fields[2], fields[3]);
+
+        let expiry = expiry.parse::<u64>()
+            .map_err(|e| format_err!("Invalid dnsmasq lease expiry {}: {}", line, e))?
+            * 1000;
+
+        let (start, end) = match expiry {
+            0 => (0, None),
+            expiry => (expiry.saturating_sub(lease_time), Some(expiry)),
+        };
+
+        leases.push(Lease {
+            ip: ip.to_owned(),
+            mac: normalize_mac(mac),
+            // Clients that sent no hostname are written as '*'
+            hostname: Some(hostname.to_owned()).filter(|hostname| hostname != "*"),
+            start,
+            end,
+            line: line.to_owned(),
+        });
+    }
+
+    Ok(leases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leases_start_a_lease_time_before_expiry() {
+        let payload = concat!(
+            "1546548307 00:11:22:33:44:55 192.168.1.100 laptop-7 01:00:11:22:33:44:55\n",
+            "0 66:77:88:99:aa:bb 192.168.1.2 * *\n",
+            "duid 00:01:00:01:23:45:67:89:00:11:22:33:44:55\n",
+            "1546548307 1144201 fd00::100 laptop-7 00:01:00:01:23:45:67:89:00:11:22:33:44:55\n",
+        );
+
+        let leases = parse_dnsmasq_leases(payload, 3600 * 1000).expect("parse_dnsmasq_leases");
+
+        assert_eq!(leases.len(), 3);
+
+        assert_eq!(leases[0].start, 1546544707000);
+        assert_eq!(leases[0].end, Some(1546548307000));
+        assert_eq!(leases[0].hostname, Some("laptop-7".to_owned()));
+
+        assert_eq!(leases[1].start, 0);
+        assert_eq!(leases[1].end, None);
+        assert_eq!(leases[1].hostname, None);
+
+        assert_eq!(leases[2].ip, "fd00::100");
+        assert_eq!(leases[2].mac, None);
+    }
+}
@@ -0,0 +1,18 @@
+use graph_descriptions::graph_description::*;
+
+use crate::leases::Lease;
+
+/// The Asset that held a lease, with the hostname and mac address of the client.
+///
+/// The leased address is not stored on the Asset, as it is only held for the lease,
+/// and is mapped to the asset in the asset id mappings instead.
+pub fn asset_node(lease: &Lease, asset_id: &str) -> Asset {
+    AssetBuilder::default()
+        .asset_id(asset_id.to_owned())
+        .hostname(lease.hostname.clone())
+        .mac_address(lease.mac.clone())
+        .mac_addresses(lease.mac.iter().cloned().collect::<Vec<_>>())
+        .last_seen_timestamp(lease.start)
+        .build()
+        .unwrap()
+}
@@ -0,0 +1,194 @@
+use failure::Error;
+
+use crate::leases::{Lease, normalize_mac, utc_millis};
+
+/// ISC dhcpd leases files are a list of lease declarations, ie:
+///
+/// ```text
+/// lease 192.168.1.100 {
+///   starts 4 2019/01/03 18:45:07;
+///   ends 4 2019/01/03 20:45:07;
+///   binding state active;
+///   hardware ethernet 00:11:22:33:44:55;
+///   client-hostname "laptop-7";
+/// }
+/// ```
+pub fn is_isc_leases(payload: &str) -> bool {
+    payload.lines()
+        .map(str::trim)
+        .any(|line| line.starts_with("lease ") && line.ends_with('{'))
+}
+
+/// Parses the IPv4 leases in an ISC dhcpd leases file.
+///
+/// Leases that are free, or abandoned, no longer describe a client, and are skipped.
+pub fn parse_isc_leases(payload: &str) -> Result<Vec<Lease>, Error> {
+    let mut leases = Vec::new();
+    let mut declaration: Option<Declaration> = None;
+
+    for line in payload.lines() {
+        // Comments may follow a statement, ie: ends epoch 1546548307; # Thu Jan 03 20:45:07 2019
+        let statement = line.split('#').next().unwrap_or_default().trim();
+        if statement.is_empty() {
+            continue;
+        }
+
+        if statement.starts_with("lease ") && statement.ends_with('{') {
+            let ip = statement["lease ".len()..statement.len() - 1].trim();
+            declaration = Some(Declaration::new(ip));
+            continue;
+        }
+
+        let current = match declaration.as_mut() {
+            Some(current) => current,
+            // Statements outside of a lease, ie: authoring-byte-order or server-duid
+            None => continue,
+        };
+
+        current.text.push_str(statement);
+        current.text.push('\n');
+
+        if statement == "}" {
+            if let Some(ended) = declaration.take() {
+                leases.extend(ended.into_lease()?);
+            }
+            continue;
+        }
+
+        current.statement(statement.trim_end_matches(';'))?;
+    }
+
+    Ok(leases)
+}
+
+#[derive(Default)]
+struct Declaration {
+    ip: String,
+    starts: Option<u64>,
+    ends: Option<u64>,
+    binding_state: Option<String>,
+    mac: Option<String>,
+    hostname: Option<String>,
+    text: String,
+}
+
+impl Declaration {
+    fn new(ip: &str) -> Self {
+        Self {
+            ip: ip.to_owned(),
+            text: format!("lease {} {{\n", ip),
+            ..Default::default()
+        }
+    }
+
+    fn statement(&mut self, statement: &str) -> Result<(), Error> {
+        let mut words = statement.splitn(2, ' ');
+        let keyword = words.next().unwrap_or_default();
+        let rest = words.next().unwrap_or_default().trim();
+
+        match keyword {
+            "starts" => self.starts = lease_time(rest)?,
+            "ends" => self.ends = lease_time(rest)?,
+            "binding" => self.binding_state = after("state ", rest).map(String::from),
+            "hardware" => self.mac = after("ethernet ", rest).and_then(normalize_mac),
+            "client-hostname" => {
+                self.hostname = Some(rest.trim_matches('"').to_owned())
+                    .filter(|hostname| !hostname.is_empty())
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn into_lease(self) -> Result<Option<Lease>, Error> {
+        match self.binding_state.as_deref() {
+            Some("free") | Some("backup") | Some("abandoned") => return Ok(None),
+            _ => (),
+        }
+
+        let start = match self.starts {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Lease {
+            ip: self.ip,
+            mac: self.mac,
+            hostname: self.hostname,
+            start,
+            end: self.ends,
+            line: self.text,
+        }))
+    }
+}
+
+// Lease times are written in UTC as "<weekday> <yyyy/mm/dd> <hh:mm:ss>", or with
+// db-time-format local, as "epoch <seconds>". Infinite leases end "never".
+fn lease_time(time: &str) -> Result<Option<u64>, Error> {
+    if time == "never" {
+        return Ok(None);
+    }
+
+    if let Some(seconds) = after("epoch ", time) {
+        return Ok(Some(seconds.trim().parse::<u64>()? * 1000));
+    }
+
+    let time = time.split_once(' ').map(|(_, time)| time).unwrap_or_default();
+    utc_millis(time, "%Y/%m/%d %H:%M:%S").map(Some)
+}
+
+fn after<'a>(prefix: &str, statement: &'a str) -> Option<&'a str> {
+    statement.strip_prefix(prefix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_and_released_leases_are_parsed() {
+        let payload = r#"
+# The format of this file is documented in the dhcpd.leases(5) manual page.
+authoring-byte-order little-endian;
+
+lease 192.168.1.100 {
+  starts 4 2019/01/03 18:45:07;
+  ends 4 2019/01/03 20:45:07;
+  cltt 4 2019/01/03 18:45:07;
+  binding state active;
+  next binding state free;
+  rewind binding state free;
+  hardware ethernet 00:11:22:33:44:55;
+  uid "\001\000\021\"3DU";
+  client-hostname "laptop-7";
+}
+lease 192.168.1.101 {
+  starts epoch 1546541107; # Thu Jan 03 18:45:07 2019
+  ends epoch 1546541200; # Thu Jan 03 18:46:40 2019
+  binding state released;
+  hardware ethernet 66:77:88:99:AA:BB;
+}
+lease 192.168.1.102 {
+  starts 4 2019/01/03 18:45:07;
+  ends 4 2019/01/03 20:45:07;
+  binding state free;
+  hardware ethernet 00:11:22:33:44:66;
+}
+"#;
+
+        let leases = parse_isc_leases(payload).expect("parse_isc_leases");
+
+        assert_eq!(leases.len(), 2);
+
+        assert_eq!(leases[0].ip, "192.168.1.100");
+        assert_eq!(leases[0].mac, Some("00:11:22:33:44:55".to_owned()));
+        assert_eq!(leases[0].hostname, Some("laptop-7".to_owned()));
+        assert_eq!(leases[0].start, 1546541107000);
+        assert_eq!(leases[0].end, Some(1546548307000));
+
+        assert_eq!(leases[1].mac, Some("66:77:88:99:aa:bb".to_owned()));
+        assert_eq!(leases[1].hostname, None);
+        assert_eq!(leases[1].end, Some(1546541200000));
+    }
+}
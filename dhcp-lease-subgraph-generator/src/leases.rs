@@ -0,0 +1,89 @@
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::dnsmasq::parse_dnsmasq_leases;
+use crate::isc::{is_isc_leases, parse_isc_leases};
+use crate::windows::{is_windows_audit_log, parse_windows_audit_log};
+
+/// An address leased to a client, from an ISC dhcpd leases file, a dnsmasq leases file,
+/// or a Windows DHCP Server audit log
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lease {
+    pub ip: String,
+    /// The client's mac address, lowercase and ':' separated
+    pub mac: Option<String>,
+    /// The hostname the client sent with its request
+    pub hostname: Option<String>,
+    /// When the lease started, in milliseconds
+    pub start: u64,
+    /// When the lease expired, or was released. Leases without an end, ie: infinite
+    /// leases, hold until the address is leased again.
+    pub end: Option<u64>,
+    /// The raw lease, used to identify it
+    pub line: String,
+}
+
+impl Lease {
+    /// A released lease ends where it starts, and unmaps the address from its client
+    pub fn is_release(&self) -> bool {
+        self.end == Some(self.start)
+    }
+}
+
+/// Parses a DHCP leases file, or audit log, detecting its format from its contents.
+///
+/// dnsmasq only records when its leases expire, so they are assumed to have started
+/// `dnsmasq_lease_time` milliseconds earlier.
+pub fn parse_leases(payload: &str, dnsmasq_lease_time: u64) -> Result<Vec<Lease>, Error> {
+    if is_isc_leases(payload) {
+        parse_isc_leases(payload)
+    } else if is_windows_audit_log(payload) {
+        parse_windows_audit_log(payload)
+    } else {
+        parse_dnsmasq_leases(payload, dnsmasq_lease_time)
+    }
+}
+
+/// Normalizes a mac address to lowercase and ':' separated, ie: 00-1A-2B-3C-4D-5E
+/// and 001A2B3C4D5E are both 00:1a:2b:3c:4d:5e
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let digits: String = mac.chars()
+        .filter(|c| *c != ':' && *c != '-' && *c != '.')
+        .collect();
+
+    if digits.len() != 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let octets: Vec<_> = digits.as_bytes()
+        .chunks(2)
+        .map(|octet| String::from_utf8_lossy(octet).to_lowercase())
+        .collect();
+
+    Some(octets.join(":"))
+}
+
+pub fn utc_millis(time: &str, format: &str) -> Result<u64, Error> {
+    let ts = NaiveDateTime::parse_from_str(time, format)
+        .map_err(|e| format_err!("Invalid timestamp {}: {}", time, e))?;
+    let ts = Utc.from_utc_datetime(&ts).timestamp_millis();
+
+    if ts < 0 {
+        bail!("Timestamp is negative")
+    }
+
+    Ok(ts as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mac_addresses_are_normalized() {
+        assert_eq!(normalize_mac("00-1A-2B-3C-4D-5E"), Some("00:1a:2b:3c:4d:5e".to_owned()));
+        assert_eq!(normalize_mac("001a2b3c4d5e"), Some("00:1a:2b:3c:4d:5e".to_owned()));
+        assert_eq!(normalize_mac("0"), None);
+        assert_eq!(normalize_mac("00:01:00:01:23:45:67:89"), None);
+    }
+}
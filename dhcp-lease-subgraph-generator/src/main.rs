@@ -0,0 +1,124 @@
+extern crate aws_lambda_events;
+extern crate chrono;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate node_identifier;
+extern crate rusoto_core;
+extern crate rusoto_dynamodb;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use node_identifier::assetdb::AssetIdDb;
+use node_identifier::mapping_conflicts::{conflict_sink_from_env, ConflictPolicy};
+use rusoto_core::Region;
+use rusoto_dynamodb::{DynamoDb, DynamoDbClient};
+use subgraph_generator_runtime::{SniffingDecoder, SubgraphGenerator};
+
+use crate::generator::asset_node;
+use crate::leases::{parse_leases, Lease};
+use crate::mapper::{map_lease, resolve_lease_asset};
+
+mod dnsmasq;
+mod generator;
+mod isc;
+mod leases;
+mod mapper;
+mod windows;
+
+#[derive(Clone)]
+struct DhcpLeaseSubgraphGenerator<D>
+    where D: DynamoDb + Clone + Send + Sync + 'static
+{
+    asset_id_db: AssetIdDb<D>,
+    dnsmasq_lease_time: u64,
+}
+
+impl<D> DhcpLeaseSubgraphGenerator<D>
+    where D: DynamoDb + Clone + Send + Sync + 'static
+{
+    pub fn new(asset_id_db: AssetIdDb<D>, dnsmasq_lease_time: u64) -> Self {
+        Self { asset_id_db, dnsmasq_lease_time }
+    }
+}
+
+#[async_trait]
+impl<D> SubgraphGenerator for DhcpLeaseSubgraphGenerator<D>
+    where D: DynamoDb + Clone + Send + Sync + 'static
+{
+    type Event = Lease;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<Lease>, failure::Error> {
+        // The payload is an ISC dhcpd or dnsmasq leases file, or a Windows DHCP audit log
+        parse_leases(&String::from_utf8_lossy(payload), self.dnsmasq_lease_time)
+    }
+
+    fn identity(&self, lease: &Lease) -> String {
+        lease.line.clone()
+    }
+
+    async fn generate_subgraph(&mut self, lease: Lease) -> Result<Option<Graph>, failure::Error> {
+        let asset = match resolve_lease_asset(&self.asset_id_db, &lease).await {
+            Ok(Some(asset)) => asset,
+            // Retrying will not make the client known, so the lease is dropped
+            Ok(None) => {
+                warn!("Could not attribute lease of {} to an asset", lease.ip);
+                return Ok(None);
+            }
+            Err(e) => bail!("Failed to resolve asset for lease of {}: {}", lease.ip, e),
+        };
+
+        map_lease(&self.asset_id_db, &lease, &asset).await
+            .map_err(|e| format_err!("Failed to map lease of {} to {}: {}", lease.ip, asset.asset_id, e))?;
+
+        let mut graph = Graph::new(0);
+
+        if !lease.is_release() {
+            graph.add_node(asset_node(&lease, &asset.asset_id));
+        }
+
+        Ok(Some(graph))
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    let region = {
+        let region_str = std::env::var("AWS_REGION").expect("AWS_REGION");
+        Region::from_str(&region_str).expect("Region error")
+    };
+
+    let conflict_policy = ConflictPolicy::from_env().expect("ASSET_MAPPING_CONFLICT_POLICY");
+
+    // dnsmasq's default lease time is an hour
+    let dnsmasq_lease_time = std::env::var("DNSMASQ_LEASE_TIME")
+        .map(|lease_time| lease_time.parse::<u64>().expect("DNSMASQ_LEASE_TIME"))
+        .unwrap_or(3600);
+
+    let generator = DhcpLeaseSubgraphGenerator::new(
+        AssetIdDb::new(DynamoDbClient::new(region.clone()))
+            .with_conflict_policy(conflict_policy)
+            .with_conflict_sink(conflict_sink_from_env(region)),
+        dnsmasq_lease_time * 1000,
+    );
+
+    subgraph_generator_runtime::run(event, ctx, "DHCP_LEASE", generator, SniffingDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use failure::Error;
+use graph_descriptions::graph_description::host::HostId;
+use node_identifier::assetdb::AssetIdDb;
+use node_identifier::storage::AssetMappingStore;
+
+use crate::leases::Lease;
+
+/// The asset a lease was held by
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaseAsset {
+    pub asset_id: String,
+    /// The client's host ids that were not mapped to the asset yet
+    pub unmapped: Vec<HostId>,
+}
+
+/// Resolves the asset that held a lease, by its mac address, or its hostname.
+///
+/// A client that has not been seen yet is known by its hostname, as it would be if it
+/// had been seen first. Leases from clients with neither are not attributed.
+pub async fn resolve_lease_asset(
+    asset_id_db: &AssetIdDb<impl AssetMappingStore>,
+    lease: &Lease,
+) -> Result<Option<LeaseAsset>, Error> {
+    let mut unmapped = vec![];
+
+    // A release is made by whoever the address was last leased to
+    if lease.is_release() {
+        let ip = HostId::Ip(lease.ip.clone());
+
+        if let Some(asset_id) = asset_id_db.find_last_mapping_before(&ip, lease.start).await? {
+            return Ok(Some(LeaseAsset { asset_id, unmapped }));
+        }
+    }
+
+    if let Some(ref mac) = lease.mac {
+        let mac = HostId::Mac(mac.clone());
+
+        match asset_id_db.resolve_asset_id(&mac, lease.start).await? {
+            Some(asset_id) => return Ok(Some(LeaseAsset { asset_id, unmapped })),
+            None => unmapped.push(mac),
+        }
+    }
+
+    if let Some(ref hostname) = lease.hostname {
+        let host_id = HostId::Hostname(hostname.clone());
+
+        if let Some(asset_id) = asset_id_db.resolve_asset_id(&host_id, lease.start).await? {
+            return Ok(Some(LeaseAsset { asset_id, unmapped }));
+        }
+
+        unmapped.push(host_id);
+
+        return Ok(Some(LeaseAsset { asset_id: hostname.clone(), unmapped }));
+    }
+
+    Ok(None)
+}
+
+/// Maps the leased address to the asset for the duration of the lease, and maps any of
+/// the client's host ids the asset was not known by
+pub async fn map_lease(
+    asset_id_db: &AssetIdDb<impl AssetMappingStore>,
+    lease: &Lease,
+    asset: &LeaseAsset,
+) -> Result<(), Error> {
+    for host_id in asset.unmapped.iter() {
+        asset_id_db.create_mapping(host_id, asset.asset_id.clone(), lease.start).await?;
+    }
+
+    let ip = HostId::Ip(lease.ip.clone());
+
+    match lease.end {
+        Some(end) => {
+            asset_id_db.create_ranged_mapping(&ip, asset.asset_id.clone(), lease.start, end).await
+        }
+        None => {
+            asset_id_db.create_mapping(&ip, asset.asset_id.clone(), lease.start).await?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use failure::Error;
+
+use crate::leases::{Lease, normalize_mac, utc_millis};
+
+const HEADER: &str = "ID,Date,Time,Description,IP Address,Host Name,MAC Address";
+
+/// Windows DHCP Server audit logs, ie: DhcpSrvLog-Thu.log, are CSV with a preamble
+/// describing the event ids
+pub fn is_windows_audit_log(payload: &str) -> bool {
+    payload.lines().any(|line| line.trim_start().starts_with(HEADER))
+}
+
+/// Parses the lease events in a Windows DHCP Server audit log, ie:
+///
+/// ```text
+/// 10,01/03/19,18:45:07,Assign,192.168.1.100,laptop-7.corp.example.com,001122334455,,1234567,0,,,,,,,,,0
+/// ```
+///
+/// Assigned, and renewed, leases have no end in the log, so they hold until the address
+/// is leased again. Released, deleted, and expired leases end where they start.
+///
+/// The log is written in the server's local time, which is expected to be UTC.
+pub fn parse_windows_audit_log(payload: &str) -> Result<Vec<Lease>, Error> {
+    let mut leases = Vec::new();
+
+    let lines = payload.lines()
+        .skip_while(|line| !line.trim_start().starts_with(HEADER))
+        .skip(1);
+
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<_> = line.split(',').map(str::trim).collect();
+
+        if fields.len() < 7 {
+            bail!("Invalid DHCP audit log event: {}", line);
+        }
+
+        let (id, date, time) = (fields[0], fields[1], fields[2]);
+        let (ip, hostname, mac) = (fields[4], fields[5], fields[6]);
+
+        let released = match id {
+            // A new lease, or a renewal
+            "10" | "11" => false,
+            // A release, a deletion, or an expiry
+            "12" | "16" | "17" | "18" => true,
+            _ => continue,
+        };
+
+        if ip.is_empty() {
+            continue;
+        }
+
+        let timestamp = utc_millis(&format!("{} {}", date, time), "%m/%d/%y %H:%M:%S")?;
+
+        leases.push(Lease {
+            ip: ip.to_owned(),
+            mac: normalize_mac(mac),
+            hostname: Some(hostname.to_owned()).filter(|hostname| !hostname.is_empty()),
+            start: timestamp,
+            end: Some(timestamp).filter(|_| released),
+            line: line.to_owned(),
+        });
+    }
+
+    Ok(leases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments_and_releases_are_parsed() {
+        let payload = concat!(
+            "\t\tMicrosoft DHCP Service Activity Log\n",
+            "\n",
+            "Event ID  Meaning\n",
+            "00\tThe log was started.\n",
+            "10\tA new IP address was leased to a client.\n",
+            "\n",
+            "ID,Date,Time,Description,IP Address,Host Name,MAC Address,User Name, TransactionID, QResult,Probationtime, CorrelationID,Dhcid,VendorClass(Hex),VendorClass(ASCII),UserClass(Hex),UserClass(ASCII),RelayAgentInformation,DnsRegError.\n",
+            "00,01/03/19,00:00:00,Started,,,,,0,6,,,,,,,,,0\n",
+            "10,01/03/19,18:45:07,Assign,192.168.1.100,laptop-7.corp.example.com,001122334455,,1234567,0,,,,,,,,,0\n",
+            "12,01/03/19,19:00:00,Release,192.168.1.100,laptop-7.corp.example.com,001122334455,,7654321,0,,,,,,,,,0\n",
+        );
+
+        assert!(is_windows_audit_log(payload));
+
+        let leases = parse_windows_audit_log(payload).expect("parse_windows_audit_log");
+
+        assert_eq!(leases.len(), 2);
+
+        assert_eq!(leases[0].start, 1546541107000);
+        assert_eq!(leases[0].end, None);
+        assert_eq!(leases[0].mac, Some("00:11:22:33:44:55".to_owned()));
+        assert_eq!(leases[0].hostname, Some("laptop-7.corp.example.com".to_owned()));
+
+        assert!(leases[1].is_release());
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use failure::Error;
+
+use crate::netflow::{is_netflow, parse_netflow};
+use crate::vpc::parse_vpc_flow_logs;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FlowAction {
+    Accept,
+    Reject,
+}
+
+impl FlowAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FlowAction::Accept => "ACCEPT",
+            FlowAction::Reject => "REJECT",
+        }
+    }
+}
+
+/// A unidirectional flow, from either a VPC Flow Log or a NetFlow/IPFIX export
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FlowRecord {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    /// The IANA protocol number
+    pub protocol: u8,
+    pub packets: u64,
+    pub bytes: u64,
+    /// When the first and last packets of the flow were seen, in milliseconds
+    pub start: u64,
+    pub end: u64,
+    pub action: Option<FlowAction>,
+}
+
+impl FlowRecord {
+    pub fn protocol_name(&self) -> String {
+        let name = match self.protocol {
+            1 => "icmp",
+            6 => "tcp",
+            17 => "udp",
+            58 => "ipv6-icmp",
+            132 => "sctp",
+            protocol => return protocol.to_string(),
+        };
+
+        name.to_owned()
+    }
+
+    /// Whether the ports of the flow are meaningful
+    pub fn has_ports(&self) -> bool {
+        matches!(self.protocol, 6 | 17 | 132)
+    }
+
+    pub fn identity(&self) -> String {
+        format!(
+            "{}:{}-{}:{}/{}/{}-{}/{}/{}/{}",
+            self.src_ip,
+            self.src_port,
+            self.dst_ip,
+            self.dst_port,
+            self.protocol,
+            self.start,
+            self.end,
+            self.packets,
+            self.bytes,
+            self.action.map(FlowAction::as_str).unwrap_or("-"),
+        )
+    }
+}
+
+/// Parses a file of flow records, either VPC Flow Logs or NetFlow v9/IPFIX exports
+pub fn parse_flows(payload: &[u8]) -> Result<Vec<FlowRecord>, Error> {
+    let flows = if is_netflow(payload) {
+        parse_netflow(payload)?
+    } else {
+        parse_vpc_flow_logs(&String::from_utf8_lossy(payload))?
+    };
+
+    Ok(dedup(flows))
+}
+
+// A flow between two interfaces in a VPC is logged by both of them, and exporters may
+// resend their records, so identical flows are only described once per file
+fn dedup(flows: Vec<FlowRecord>) -> Vec<FlowRecord> {
+    let mut seen = HashSet::with_capacity(flows.len());
+
+    flows.into_iter()
+        .filter(|flow| seen.insert(flow.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_flows_are_deduplicated() {
+        let payload = concat!(
+            "version account-id interface-id srcaddr dstaddr srcport dstport protocol packets bytes start end action log-status\n",
+            "2 123456789010 eni-1235b8ca123456789 172.31.16.139 172.31.16.21 20641 22 6 20 4249 1418530010 1418530070 ACCEPT OK\n",
+            "2 123456789010 eni-0f87a3cd987654321 172.31.16.139 172.31.16.21 20641 22 6 20 4249 1418530010 1418530070 ACCEPT OK\n",
+            "2 123456789010 eni-1235b8ca123456789 172.31.9.69 172.31.9.12 49761 3389 6 20 4249 1418530010 1418530070 REJECT OK\n",
+        );
+
+        let flows = parse_flows(payload.as_bytes()).expect("parse_flows");
+
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[1].action, Some(FlowAction::Reject));
+    }
+}
@@ -0,0 +1,198 @@
+use std::net::IpAddr;
+
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::ip_connection::IpConnectionState;
+use graph_descriptions::network_connection::NetworkConnectionState;
+use graph_descriptions::node::NodeT;
+use graph_descriptions::process_inbound_connection::ProcessInboundConnectionState;
+use graph_descriptions::process_outbound_connection::ProcessOutboundConnectionState;
+
+use crate::flows::FlowRecord;
+
+/// Generates the connections described by a flow.
+///
+/// A flow only covers the packets seen during its capture window, which is neither the start
+/// nor the end of the connection, so the connections are described as existing at its end.
+pub fn generate_subgraph(flow: &FlowRecord) -> Result<Graph, Error> {
+    let mut graph = Graph::new(flow.end);
+
+    let protocol = flow.protocol_name();
+    let action = flow.action.map(|action| action.as_str().to_owned()).unwrap_or_default();
+
+    let src_ip = ip_address(flow, &flow.src_ip.to_string())?;
+    let dst_ip = ip_address(flow, &flow.dst_ip.to_string())?;
+
+    let ip_connection = IpConnectionBuilder::default()
+        .state(IpConnectionState::Existing)
+        .src_ip_address(src_ip.ip_address.clone())
+        .dst_ip_address(dst_ip.ip_address.clone())
+        .protocol(protocol.clone())
+        .last_seen_timestamp(flow.end)
+        .bytes(flow.bytes)
+        .packets(flow.packets)
+        .flow_action(action.clone())
+        .build()
+        .map_err(|e| format_err!("ip_connection: {}", e))?;
+
+    graph.add_edge("ip_connection_to", src_ip.clone_node_key(), ip_connection.clone_node_key());
+    graph.add_edge("ip_connection_to", ip_connection.clone_node_key(), dst_ip.clone_node_key());
+
+    if flow.has_ports() {
+        let src_port = ip_port(&src_ip.ip_address, flow.src_port, &protocol)?;
+        let dst_port = ip_port(&dst_ip.ip_address, flow.dst_port, &protocol)?;
+
+        // The connection as seen by each host in our network. Flows carry no hostnames,
+        // so these are attributed to the asset that held the address at the time
+        if is_private(&flow.src_ip) {
+            let outbound = ProcessOutboundConnectionBuilder::default()
+                .host_ip(src_ip.ip_address.clone())
+                .state(ProcessOutboundConnectionState::Existing)
+                .ip_address(src_ip.ip_address.clone())
+                .port(flow.src_port as u32)
+                .protocol(protocol.clone())
+                .last_seen_timestamp(flow.end)
+                .build()
+                .map_err(|e| format_err!("outbound: {}", e))?;
+
+            graph.add_edge("connected_over", outbound.clone_node_key(), src_port.clone_node_key());
+            graph.add_edge("connected_to", outbound.clone_node_key(), dst_port.clone_node_key());
+            graph.add_node(outbound);
+        }
+
+        if is_private(&flow.dst_ip) {
+            let inbound = ProcessInboundConnectionBuilder::default()
+                .host_ip(dst_ip.ip_address.clone())
+                .state(ProcessInboundConnectionState::Existing)
+                .ip_address(dst_ip.ip_address.clone())
+                .port(flow.dst_port as u32)
+                .protocol(protocol.clone())
+                .last_seen_timestamp(flow.end)
+                .build()
+                .map_err(|e| format_err!("inbound: {}", e))?;
+
+            graph.add_edge("bound_port", inbound.clone_node_key(), dst_port.clone_node_key());
+            graph.add_node(inbound);
+        }
+
+        let network_connection = NetworkConnectionBuilder::default()
+            .state(NetworkConnectionState::Existing)
+            .src_ip_address(src_ip.ip_address.clone())
+            .src_port(flow.src_port as u32)
+            .dst_ip_address(dst_ip.ip_address.clone())
+            .dst_port(flow.dst_port as u32)
+            .protocol(protocol)
+            .last_seen_timestamp(flow.end)
+            .bytes(flow.bytes)
+            .packets(flow.packets)
+            .flow_action(action)
+            .build()
+            .map_err(|e| format_err!("network_connection: {}", e))?;
+
+        graph.add_edge(
+            "outbound_connection_to",
+            src_port.clone_node_key(),
+            network_connection.clone_node_key(),
+        );
+
+        graph.add_edge(
+            "inbound_connection_to",
+            network_connection.clone_node_key(),
+            dst_port.clone_node_key(),
+        );
+
+        graph.add_node(network_connection);
+        graph.add_node(src_port);
+        graph.add_node(dst_port);
+    }
+
+    graph.add_node(ip_connection);
+    graph.add_node(src_ip);
+    graph.add_node(dst_ip);
+
+    Ok(graph)
+}
+
+// Only addresses in our own network can be mapped to one of our assets
+fn is_private(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private(),
+        // Unique local addresses, fc00::/7
+        IpAddr::V6(ip) => ip.segments()[0] & 0xfe00 == 0xfc00,
+    }
+}
+
+fn ip_address(flow: &FlowRecord, ip: &str) -> Result<IpAddress, Error> {
+    IpAddressBuilder::default()
+        .ip_address(ip.to_owned())
+        .first_seen_timestamp(flow.start)
+        .last_seen_timestamp(flow.end)
+        .build()
+        .map_err(|e| format_err!("ip_address: {}", e))
+}
+
+fn ip_port(ip: &str, port: u16, protocol: &str) -> Result<IpPort, Error> {
+    IpPortBuilder::default()
+        .ip_address(ip.to_owned())
+        .port(port as u32)
+        .protocol(protocol.to_owned())
+        .build()
+        .map_err(|e| format_err!("ip_port: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flows::parse_flows;
+
+    fn generate(line: &str) -> Graph {
+        let flows = parse_flows(line.as_bytes()).expect("parse_flows");
+        generate_subgraph(&flows[0]).expect("generate_subgraph")
+    }
+
+    #[test]
+    fn tcp_flow_has_ports() {
+        let graph = generate(
+            "2 123456789010 eni-1235b8ca123456789 172.31.9.69 172.31.9.12 49761 3389 6 20 4249 1418530010 1418530070 REJECT OK"
+        );
+
+        // ip addresses, ip ports, both connections, and both hosts' view of the connection
+        assert_eq!(graph.nodes.len(), 8);
+
+        let network_connection = graph.nodes.values()
+            .filter_map(|node| node.as_network_connection())
+            .next()
+            .expect("network_connection");
+
+        assert_eq!(network_connection.bytes, 4249);
+        assert_eq!(network_connection.packets, 20);
+        assert_eq!(network_connection.flow_action, "REJECT");
+        assert_eq!(network_connection.last_seen_timestamp, 1418530070000);
+    }
+
+    #[test]
+    fn private_hosts_are_attributed_by_ip() {
+        let graph = generate(
+            "2 123456789010 eni-1235b8ca123456789 172.31.16.139 93.184.216.34 49761 443 6 20 4249 1418530010 1418530070 ACCEPT OK"
+        );
+
+        let outbound = graph.nodes.values()
+            .find_map(|node| node.as_process_outbound_connection())
+            .expect("outbound");
+        assert_eq!(outbound.host_ip, Some("172.31.16.139".to_owned()));
+        assert_eq!(outbound.last_seen_timestamp, 1418530070000);
+
+        // The public destination is not one of our assets
+        assert!(graph.nodes.values().all(|node| node.as_process_inbound_connection().is_none()));
+    }
+
+    #[test]
+    fn icmp_flow_has_no_ports() {
+        let graph = generate(
+            "2 123456789010 eni-1235b8ca123456789 203.0.113.12 172.31.16.139 0 0 1 4 336 1432917027 1432917142 ACCEPT OK"
+        );
+
+        assert_eq!(graph.nodes.len(), 3);
+        assert!(graph.nodes.values().all(|node| node.as_network_connection().is_none()));
+    }
+}
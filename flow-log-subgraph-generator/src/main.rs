@@ -0,0 +1,58 @@
+extern crate aws_lambda_events;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SniffingDecoder, SubgraphGenerator};
+
+use crate::flows::{parse_flows, FlowRecord};
+use crate::generator::generate_subgraph;
+
+mod flows;
+mod generator;
+mod netflow;
+mod vpc;
+
+#[derive(Clone)]
+struct FlowLogSubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for FlowLogSubgraphGenerator {
+    type Event = FlowRecord;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<FlowRecord>, failure::Error> {
+        // The payload is a file of VPC Flow Logs, or of NetFlow v9/IPFIX exports
+        parse_flows(payload)
+    }
+
+    fn identity(&self, event: &FlowRecord) -> String {
+        event.identity()
+    }
+
+    async fn generate_subgraph(&mut self, event: FlowRecord) -> Result<Option<Graph>, failure::Error> {
+        generate_subgraph(&event).map(Some)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "FLOW_LOG", FlowLogSubgraphGenerator, SniffingDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use failure::Error;
+
+use crate::flows::{FlowAction, FlowRecord};
+
+const NETFLOW_V9: u16 = 9;
+const IPFIX: u16 = 10;
+
+const V9_TEMPLATE_SET: u16 = 0;
+const V9_OPTIONS_TEMPLATE_SET: u16 = 1;
+const IPFIX_TEMPLATE_SET: u16 = 2;
+const IPFIX_OPTIONS_TEMPLATE_SET: u16 = 3;
+const MIN_DATA_SET: u16 = 256;
+
+// IPFIX fields whose length is carried in each record
+const VARIABLE_LENGTH: u16 = 65535;
+
+// The information elements we read, which NetFlow v9 and IPFIX share
+const OCTET_DELTA_COUNT: u16 = 1;
+const PACKET_DELTA_COUNT: u16 = 2;
+const PROTOCOL_IDENTIFIER: u16 = 4;
+const SOURCE_TRANSPORT_PORT: u16 = 7;
+const SOURCE_IPV4_ADDRESS: u16 = 8;
+const DESTINATION_TRANSPORT_PORT: u16 = 11;
+const DESTINATION_IPV4_ADDRESS: u16 = 12;
+const FLOW_END_SYS_UP_TIME: u16 = 21;
+const FLOW_START_SYS_UP_TIME: u16 = 22;
+const SOURCE_IPV6_ADDRESS: u16 = 27;
+const DESTINATION_IPV6_ADDRESS: u16 = 28;
+const OCTET_TOTAL_COUNT: u16 = 85;
+const PACKET_TOTAL_COUNT: u16 = 86;
+const FORWARDING_STATUS: u16 = 89;
+const FLOW_START_SECONDS: u16 = 150;
+const FLOW_END_SECONDS: u16 = 151;
+const FLOW_START_MILLISECONDS: u16 = 152;
+const FLOW_END_MILLISECONDS: u16 = 153;
+
+/// Whether the payload starts with a NetFlow v9 or IPFIX header
+pub fn is_netflow(payload: &[u8]) -> bool {
+    match payload {
+        [0, version, ..] => *version as u16 == NETFLOW_V9 || *version as u16 == IPFIX,
+        _ => false,
+    }
+}
+
+/// Parses a file of NetFlow v9 or IPFIX export packets, as written by a collector.
+///
+/// Templates apply to the rest of the file, so records exported before their template
+/// are skipped, as are records of options templates.
+pub fn parse_netflow(payload: &[u8]) -> Result<Vec<FlowRecord>, Error> {
+    let mut reader = Reader::new(payload);
+    let mut templates = Templates::new();
+    let mut flows = Vec::new();
+
+    while !reader.is_empty() {
+        match reader.peek_u16()? {
+            NETFLOW_V9 => parse_v9_packet(&mut reader, &mut templates, &mut flows)?,
+            IPFIX => parse_ipfix_message(&mut reader, &mut templates, &mut flows)?,
+            version => bail!("Unsupported NetFlow version {} at offset {}", version, reader.pos),
+        }
+    }
+
+    Ok(flows)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TemplateField {
+    id: u16,
+    length: u16,
+    // Fields of enterprise specific elements are skipped
+    enterprise: bool,
+}
+
+// Templates are scoped to the exporter's source id, or observation domain
+type Templates = HashMap<(u16, u32, u16), Vec<TemplateField>>;
+
+// The export time of the packet, which record timestamps are relative to
+#[derive(Clone, Copy, Debug)]
+struct ExportHeader {
+    version: u16,
+    domain: u32,
+    export_millis: u64,
+    sys_uptime: Option<u32>,
+}
+
+fn parse_v9_packet(
+    reader: &mut Reader,
+    templates: &mut Templates,
+    flows: &mut Vec<FlowRecord>,
+) -> Result<(), Error> {
+    let version = reader.u16()?;
+    let _count = reader.u16()?;
+    let sys_uptime = reader.u32()?;
+    let unix_secs = reader.u32()?;
+    let _sequence = reader.u32()?;
+    let source_id = reader.u32()?;
+
+    let header = ExportHeader {
+        version,
+        domain: source_id,
+        export_millis: unix_secs as u64 * 1000,
+        sys_uptime: Some(sys_uptime),
+    };
+
+    // v9 packets do not carry their length, but the next packet's version can never
+    // be a flowset id
+    while reader.remaining() >= 4 {
+        let set_id = reader.peek_u16()?;
+        if set_id > V9_OPTIONS_TEMPLATE_SET && set_id < MIN_DATA_SET {
+            break;
+        }
+
+        let (set_id, mut set) = reader.set()?;
+        match set_id {
+            V9_TEMPLATE_SET => parse_templates(&header, &mut set, templates, false)?,
+            V9_OPTIONS_TEMPLATE_SET => (),
+            _ => parse_data_set(&header, set_id, &mut set, templates, flows)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_ipfix_message(
+    reader: &mut Reader,
+    templates: &mut Templates,
+    flows: &mut Vec<FlowRecord>,
+) -> Result<(), Error> {
+    let version = reader.u16()?;
+    let length = reader.u16()? as usize;
+    let export_time = reader.u32()?;
+    let _sequence = reader.u32()?;
+    let domain = reader.u32()?;
+
+    if length < 16 {
+        bail!("Invalid IPFIX message length {}", length);
+    }
+
+    let header = ExportHeader {
+        version,
+        domain,
+        export_millis: export_time as u64 * 1000,
+        sys_uptime: None,
+    };
+
+    let mut message = Reader::new(reader.bytes(length - 16)?);
+
+    while message.remaining() >= 4 {
+        let (set_id, mut set) = message.set()?;
+        match set_id {
+            IPFIX_TEMPLATE_SET => parse_templates(&header, &mut set, templates, true)?,
+            IPFIX_OPTIONS_TEMPLATE_SET => (),
+            set_id if set_id >= MIN_DATA_SET => {
+                parse_data_set(&header, set_id, &mut set, templates, flows)?
+            }
+            set_id => bail!("Invalid IPFIX set id {}", set_id),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_templates(
+    header: &ExportHeader,
+    set: &mut Reader,
+    templates: &mut Templates,
+    ipfix: bool,
+) -> Result<(), Error> {
+    // Sets may be padded to a 4 byte boundary
+    while set.remaining() >= 4 {
+        let template_id = set.u16()?;
+        let field_count = set.u16()?;
+        let key = (header.version, header.domain, template_id);
+
+        // A template without fields withdraws it
+        if field_count == 0 {
+            templates.remove(&key);
+            continue;
+        }
+
+        let mut fields = Vec::with_capacity(field_count as usize);
+        for _ in 0..field_count {
+            let id = set.u16()?;
+            let length = set.u16()?;
+
+            let enterprise = ipfix && id & 0x8000 != 0;
+            if enterprise {
+                set.u32()?;
+            }
+
+            fields.push(TemplateField { id: id & 0x7fff, length, enterprise });
+        }
+
+        templates.insert(key, fields);
+    }
+
+    Ok(())
+}
+
+fn parse_data_set(
+    header: &ExportHeader,
+    template_id: u16,
+    set: &mut Reader,
+    templates: &Templates,
+    flows: &mut Vec<FlowRecord>,
+) -> Result<(), Error> {
+    let template = match templates.get(&(header.version, header.domain, template_id)) {
+        Some(template) => template,
+        None => return Ok(()),
+    };
+
+    // Anything shorter than a record is padding
+    let min_length: usize = template.iter()
+        .map(|field| if field.length == VARIABLE_LENGTH { 1 } else { field.length as usize })
+        .sum();
+
+    if min_length == 0 {
+        return Ok(());
+    }
+
+    while set.remaining() >= min_length {
+        let mut fields = FlowFields::default();
+
+        for field in template {
+            let length = if field.length == VARIABLE_LENGTH {
+                match set.u8()? {
+                    255 => set.u16()? as usize,
+                    length => length as usize,
+                }
+            } else {
+                field.length as usize
+            };
+
+            let value = set.bytes(length)?;
+            if !field.enterprise {
+                fields.set(field.id, value);
+            }
+        }
+
+        if let Some(flow) = fields.into_flow(header) {
+            flows.push(flow);
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+struct FlowFields {
+    src_ip: Option<IpAddr>,
+    dst_ip: Option<IpAddr>,
+    src_port: u16,
+    dst_port: u16,
+    protocol: u8,
+    octet_delta: Option<u64>,
+    octet_total: Option<u64>,
+    packet_delta: Option<u64>,
+    packet_total: Option<u64>,
+    start_uptime: Option<u32>,
+    end_uptime: Option<u32>,
+    start: Option<u64>,
+    end: Option<u64>,
+    forwarding_status: Option<u8>,
+}
+
+impl FlowFields {
+    fn set(&mut self, id: u16, value: &[u8]) {
+        match id {
+            OCTET_DELTA_COUNT => self.octet_delta = Some(uint(value)),
+            OCTET_TOTAL_COUNT => self.octet_total = Some(uint(value)),
+            PACKET_DELTA_COUNT => self.packet_delta = Some(uint(value)),
+            PACKET_TOTAL_COUNT => self.packet_total = Some(uint(value)),
+            PROTOCOL_IDENTIFIER => self.protocol = uint(value) as u8,
+            SOURCE_TRANSPORT_PORT => self.src_port = uint(value) as u16,
+            DESTINATION_TRANSPORT_PORT => self.dst_port = uint(value) as u16,
+            SOURCE_IPV4_ADDRESS | SOURCE_IPV6_ADDRESS => self.src_ip = ip(value).or(self.src_ip),
+            DESTINATION_IPV4_ADDRESS | DESTINATION_IPV6_ADDRESS => {
+                self.dst_ip = ip(value).or(self.dst_ip)
+            }
+            FLOW_START_SYS_UP_TIME => self.start_uptime = Some(uint(value) as u32),
+            FLOW_END_SYS_UP_TIME => self.end_uptime = Some(uint(value) as u32),
+            FLOW_START_SECONDS => self.start = Some(uint(value) * 1000),
+            FLOW_END_SECONDS => self.end = Some(uint(value) * 1000),
+            FLOW_START_MILLISECONDS => self.start = Some(uint(value)),
+            FLOW_END_MILLISECONDS => self.end = Some(uint(value)),
+            // The status is in the two high bits of the first byte
+            FORWARDING_STATUS => self.forwarding_status = value.first().map(|status| status >> 6),
+            _ => (),
+        }
+    }
+
+    fn into_flow(self, header: &ExportHeader) -> Option<FlowRecord> {
+        let (src_ip, dst_ip) = match (self.src_ip, self.dst_ip) {
+            (Some(src_ip), Some(dst_ip)) => (src_ip, dst_ip),
+            _ => return None,
+        };
+
+        // v9 timestamps are the exporter's uptime when the packet was seen
+        let uptime_millis = |uptime: u32| {
+            let ago = header.sys_uptime?.wrapping_sub(uptime) as u64;
+            Some(header.export_millis.saturating_sub(ago))
+        };
+
+        let start = self.start
+            .or_else(|| self.start_uptime.and_then(uptime_millis))
+            .unwrap_or(header.export_millis);
+        let end = self.end
+            .or_else(|| self.end_uptime.and_then(uptime_millis))
+            .unwrap_or(start);
+
+        let action = match self.forwarding_status {
+            Some(1) | Some(3) => Some(FlowAction::Accept),
+            Some(2) => Some(FlowAction::Reject),
+            _ => None,
+        };
+
+        Some(FlowRecord {
+            src_ip,
+            dst_ip,
+            src_port: self.src_port,
+            dst_port: self.dst_port,
+            protocol: self.protocol,
+            packets: self.packet_delta.or(self.packet_total).unwrap_or_default(),
+            bytes: self.octet_delta.or(self.octet_total).unwrap_or_default(),
+            start,
+            end,
+            action,
+        })
+    }
+}
+
+// Unsigned values may be sent in fewer bytes than their type
+fn uint(value: &[u8]) -> u64 {
+    value.iter()
+        .take(8)
+        .fold(0, |acc, byte| (acc << 8) | *byte as u64)
+}
+
+fn ip(value: &[u8]) -> Option<IpAddr> {
+    match value.len() {
+        4 => Some(Ipv4Addr::new(value[0], value[1], value[2], value[3]).into()),
+        16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(value);
+            Some(Ipv6Addr::from(octets).into())
+        }
+        _ => None,
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if self.remaining() < length {
+            bail!("Truncated NetFlow export at offset {}", self.pos);
+        }
+
+        let bytes = &self.buf[self.pos..self.pos + length];
+        self.pos += length;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(uint(self.bytes(2)?) as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(uint(self.bytes(4)?) as u32)
+    }
+
+    fn peek_u16(&self) -> Result<u16, Error> {
+        Reader { buf: self.buf, pos: self.pos }.u16()
+    }
+
+    // A set, or flowset, and its contents. The length includes the set header
+    fn set(&mut self) -> Result<(u16, Reader<'a>), Error> {
+        let set_id = self.u16()?;
+        let length = self.u16()? as usize;
+
+        if length < 4 {
+            bail!("Invalid length {} for set {}", length, set_id);
+        }
+
+        Ok((set_id, Reader::new(self.bytes(length - 4)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v9_packet() -> Vec<u8> {
+        let mut packet = vec![
+            0, 9, 0, 2, // version, count
+            0, 0, 0x27, 0x10, // sys_uptime 10000
+            0x5d, 0x64, 0x5f, 0x80, // unix_secs 1566859136
+            0, 0, 0, 1, // sequence
+            0, 0, 0, 7, // source id
+            // template flowset
+            0, 0, 0, 32,
+            1, 0, 0, 6, // template 256, 6 fields
+            0, 8, 0, 4, // src ipv4
+            0, 12, 0, 4, // dst ipv4
+            0, 7, 0, 2, // src port
+            0, 11, 0, 2, // dst port
+            0, 4, 0, 1, // protocol
+            0, 1, 0, 4, // bytes
+        ];
+
+        // data flowset, with one record and padding
+        packet.extend_from_slice(&[
+            1, 0, 0, 24,
+            10, 0, 0, 1,
+            10, 0, 0, 2,
+            0xc3, 0x50, 0, 22,
+            6,
+            0, 0, 0x10, 0,
+            0, 0, 0,
+        ]);
+
+        packet
+    }
+
+    #[test]
+    fn v9_records_follow_their_template() {
+        let mut payload = v9_packet();
+        payload.extend(v9_packet());
+
+        let flows = parse_netflow(&payload).expect("parse_netflow");
+
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[0].src_ip.to_string(), "10.0.0.1");
+        assert_eq!(flows[0].dst_ip.to_string(), "10.0.0.2");
+        assert_eq!(flows[0].src_port, 50000);
+        assert_eq!(flows[0].dst_port, 22);
+        assert_eq!(flows[0].protocol, 6);
+        assert_eq!(flows[0].bytes, 4096);
+        assert_eq!(flows[0].start, 1566859136000);
+    }
+
+    #[test]
+    fn ipfix_records_are_parsed() {
+        let payload = vec![
+            0, 10, 0, 108, // version, length
+            0x5d, 0x64, 0x5f, 0x80, // export time
+            0, 0, 0, 1, // sequence
+            0, 0, 0, 3, // observation domain
+            // template set
+            0, 2, 0, 36,
+            1, 1, 0, 6, // template 257, 6 fields
+            0, 27, 0, 16, // src ipv6
+            0, 28, 0, 16, // dst ipv6
+            0, 2, 0, 8, // packets
+            0, 89, 0, 1, // forwarding status
+            0, 150, 0, 4, // start seconds
+            0x80, 1, 0, 4, 0, 0, 0, 9, // enterprise specific
+            // data set, with one record and padding
+            1, 1, 0, 56,
+            0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2,
+            0, 0, 0, 0, 0, 0, 0, 3,
+            0x80, // dropped
+            0x5d, 0x64, 0x5f, 0x00,
+            0xde, 0xad, 0xbe, 0xef,
+            0, 0, 0,
+        ];
+
+        let flows = parse_netflow(&payload).expect("parse_netflow");
+
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].src_ip.to_string(), "2001:db8::1");
+        assert_eq!(flows[0].dst_ip.to_string(), "::2");
+        assert_eq!(flows[0].packets, 3);
+        assert_eq!(flows[0].action, Some(FlowAction::Reject));
+        assert_eq!(flows[0].start, 1566859008000);
+    }
+
+    #[test]
+    fn truncated_export_is_an_error() {
+        let payload = v9_packet();
+
+        assert!(parse_netflow(&payload[..payload.len() - 8]).is_err());
+    }
+}
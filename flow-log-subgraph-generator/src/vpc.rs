@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use failure::Error;
+
+use crate::flows::{FlowAction, FlowRecord};
+
+/// The fields of the default format, which logs without a header are assumed to use
+const DEFAULT_FIELDS: &[&str] = &[
+    "version",
+    "account-id",
+    "interface-id",
+    "srcaddr",
+    "dstaddr",
+    "srcport",
+    "dstport",
+    "protocol",
+    "packets",
+    "bytes",
+    "start",
+    "end",
+    "action",
+    "log-status",
+];
+
+/// Parses VPC Flow Logs in any of the version 2 to 5 formats.
+///
+/// Custom formats are only known by the header that starts every file delivered to S3,
+/// which names the fields of each line. Lines without data, ie: NODATA and SKIPDATA,
+/// are skipped.
+pub fn parse_vpc_flow_logs(payload: &str) -> Result<Vec<FlowRecord>, Error> {
+    let mut fields: Vec<String> = DEFAULT_FIELDS.iter().map(|field| field.to_string()).collect();
+    let mut flows = Vec::new();
+
+    for line in payload.lines() {
+        let values: Vec<&str> = line.split_whitespace().collect();
+        if values.is_empty() {
+            continue;
+        }
+
+        if is_header(&values) {
+            // Logs queried through Athena or CloudWatch name their fields with underscores
+            fields = values.iter().map(|field| field.replace('_', "-")).collect();
+            continue;
+        }
+
+        if values.len() != fields.len() {
+            bail!(
+                "Flow log line has {} fields, the format has {}: {}",
+                values.len(),
+                fields.len(),
+                line
+            );
+        }
+
+        // Fields that do not apply to a flow are written as '-'
+        let record: HashMap<&str, &str> = fields.iter()
+            .map(String::as_str)
+            .zip(values)
+            .filter(|(_, value)| *value != "-")
+            .collect();
+
+        if let Some(flow) = flow_from_record(&record)
+            .map_err(|e| format_err!("Invalid flow log line {}: {}", line, e))? {
+            flows.push(flow);
+        }
+    }
+
+    Ok(flows)
+}
+
+fn is_header(values: &[&str]) -> bool {
+    values.iter().any(|value| *value == "srcaddr" || *value == "pkt-srcaddr" || *value == "pkt_srcaddr")
+}
+
+fn field<T>(record: &HashMap<&str, &str>, name: &str) -> Result<Option<T>, Error>
+    where T: FromStr,
+          T::Err: std::fmt::Display,
+{
+    match record.get(name) {
+        Some(value) => value.parse()
+            .map(Some)
+            .map_err(|e| format_err!("{}: {}", name, e)),
+        None => Ok(None),
+    }
+}
+
+fn flow_from_record(record: &HashMap<&str, &str>) -> Result<Option<FlowRecord>, Error> {
+    if let Some(status) = record.get("log-status") {
+        if *status != "OK" {
+            return Ok(None);
+        }
+    }
+
+    // The packet addresses are those of the original endpoints, before any NAT by
+    // the interface that logged the flow
+    let src_ip = match field(record, "pkt-srcaddr")? {
+        Some(src_ip) => Some(src_ip),
+        None => field(record, "srcaddr")?,
+    };
+
+    let dst_ip = match field(record, "pkt-dstaddr")? {
+        Some(dst_ip) => Some(dst_ip),
+        None => field(record, "dstaddr")?,
+    };
+
+    let (src_ip, dst_ip) = match (src_ip, dst_ip) {
+        (Some(src_ip), Some(dst_ip)) => (src_ip, dst_ip),
+        _ => return Ok(None),
+    };
+
+    let start: u64 = match field(record, "start")? {
+        Some(start) => start,
+        None => bail!("missing start"),
+    };
+    let end: u64 = field(record, "end")?.unwrap_or(start);
+
+    let action = match record.get("action") {
+        Some(&"ACCEPT") => Some(FlowAction::Accept),
+        Some(&"REJECT") => Some(FlowAction::Reject),
+        _ => None,
+    };
+
+    Ok(Some(FlowRecord {
+        src_ip,
+        dst_ip,
+        src_port: field(record, "srcport")?.unwrap_or_default(),
+        dst_port: field(record, "dstport")?.unwrap_or_default(),
+        protocol: field(record, "protocol")?.unwrap_or_default(),
+        packets: field(record, "packets")?.unwrap_or_default(),
+        bytes: field(record, "bytes")?.unwrap_or_default(),
+        start: start * 1000,
+        end: end * 1000,
+        action,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_format_is_read_from_header() {
+        let payload = concat!(
+            "version vpc-id subnet-id instance-id interface-id account-id type srcaddr dstaddr srcport dstport pkt-srcaddr pkt-dstaddr protocol bytes packets start end action tcp-flags log-status\n",
+            "3 vpc-abcdefab012345678 subnet-aaaaaaaa012345678 i-01234567890123456 eni-1235b8ca123456789 123456789012 IPv4 10.40.2.236 10.20.33.164 49152 443 10.40.2.236 52.213.180.42 6 3208 12 1566848875 1566848933 ACCEPT 2 OK\n",
+            "3 vpc-abcdefab012345678 subnet-aaaaaaaa012345678 - eni-1235b8ca123456789 123456789012 - - - - - - - - - - 1566848875 1566848933 - - NODATA\n",
+        );
+
+        let flows = parse_vpc_flow_logs(payload).expect("parse_vpc_flow_logs");
+
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].dst_ip.to_string(), "52.213.180.42");
+        assert_eq!(flows[0].bytes, 3208);
+        assert_eq!(flows[0].packets, 12);
+        assert_eq!(flows[0].start, 1566848875000);
+        assert_eq!(flows[0].action, Some(FlowAction::Accept));
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, HashMap};
+
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use schemars::JsonSchema;
+
+use crate::schema::{default_schema_version, GenericEventError};
+
+/// A `DYNAMIC` event describes plugin nodes at runtime, rather than through
+/// a `#[derive(DynamicNode)]` struct, along with the edges between them.
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct DynamicEvent {
+    hostname: Option<String>,
+    asset_id: Option<String>,
+    nodes: Vec<DynamicNodeDescription>,
+    #[serde(default)]
+    edges: Vec<DynamicEdgeDescription>,
+    timestamp: u64,
+    #[schemars(regex(pattern = r"^DYNAMIC$"))]
+    eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
+}
+
+impl DynamicEvent {
+    /// Rejects nodes whose session strategy carries no timestamp, since a
+    /// session can't be placed on a timeline without one.
+    pub fn validate(&self, index: usize) -> Result<(), GenericEventError> {
+        for (i, description) in self.nodes.iter().enumerate() {
+            if let DynamicIdStrategy::Session { created_time: 0, last_seen_time: 0, terminated_time: 0, .. } =
+                description.id_strategy {
+                return Err(GenericEventError::InvalidField {
+                    index,
+                    field: format!("nodes[{}].id_strategy", i),
+                    reason: "a session requires a non-zero created_time, last_seen_time or terminated_time".to_owned(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct DynamicNodeDescription {
+    /// Identifies the node within this event, so that edges can refer to it
+    node_id: String,
+    node_type: String,
+    #[serde(default)]
+    properties: BTreeMap<String, DynamicProperty>,
+    id_strategy: DynamicIdStrategy,
+}
+
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum DynamicProperty {
+    Uint(u64),
+    Int(i64),
+    Str(String),
+}
+
+impl From<DynamicProperty> for NodeProperty {
+    fn from(property: DynamicProperty) -> NodeProperty {
+        match property {
+            DynamicProperty::Uint(i) => i.into(),
+            DynamicProperty::Int(i) => i.into(),
+            DynamicProperty::Str(s) => s.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DynamicIdStrategy {
+    Static {
+        primary_key_properties: Vec<String>,
+        #[serde(default)]
+        primary_key_requires_asset_id: bool,
+    },
+    Session {
+        primary_key_properties: Vec<String>,
+        #[serde(default)]
+        primary_key_requires_asset_id: bool,
+        #[serde(default)]
+        created_time: u64,
+        #[serde(default)]
+        last_seen_time: u64,
+        #[serde(default)]
+        terminated_time: u64,
+    },
+}
+
+impl DynamicIdStrategy {
+    fn requires_asset_id(&self) -> bool {
+        match self {
+            DynamicIdStrategy::Static { primary_key_requires_asset_id, .. } => *primary_key_requires_asset_id,
+            DynamicIdStrategy::Session { primary_key_requires_asset_id, .. } => *primary_key_requires_asset_id,
+        }
+    }
+
+    fn primary_key_properties(&self) -> &[String] {
+        match self {
+            DynamicIdStrategy::Static { primary_key_properties, .. } => primary_key_properties,
+            DynamicIdStrategy::Session { primary_key_properties, .. } => primary_key_properties,
+        }
+    }
+}
+
+impl From<DynamicIdStrategy> for IdStrategy {
+    fn from(strategy: DynamicIdStrategy) -> IdStrategy {
+        match strategy {
+            DynamicIdStrategy::Static {
+                primary_key_properties,
+                primary_key_requires_asset_id,
+            } => Static {
+                primary_key_properties,
+                primary_key_requires_asset_id,
+            }.into(),
+            DynamicIdStrategy::Session {
+                primary_key_properties,
+                primary_key_requires_asset_id,
+                created_time,
+                last_seen_time,
+                terminated_time,
+            } => Session {
+                primary_key_properties,
+                primary_key_requires_asset_id,
+                created_time,
+                last_seen_time,
+                terminated_time,
+            }.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct DynamicEdgeDescription {
+    from: String,
+    to: String,
+    edge_name: String,
+}
+
+pub fn handle_dynamic(dynamic_event: DynamicEvent) -> Result<Graph, Error> {
+    let mut graph = Graph::new(dynamic_event.timestamp);
+    let mut node_keys: HashMap<String, String> = HashMap::with_capacity(dynamic_event.nodes.len());
+
+    for description in dynamic_event.nodes {
+        if description.id_strategy.requires_asset_id()
+            && dynamic_event.hostname.is_none()
+            && dynamic_event.asset_id.is_none() {
+            bail!(
+                "Node {} requires an asset id, but the event has no hostname or asset_id",
+                description.node_id
+            );
+        }
+
+        for prop_name in description.id_strategy.primary_key_properties() {
+            if !description.properties.contains_key(prop_name) {
+                bail!(
+                    "Node {} is missing primary key property {}",
+                    description.node_id,
+                    prop_name
+                );
+            }
+        }
+
+        let node_key = uuid::Uuid::new_v4().to_string();
+        if node_keys.insert(description.node_id.clone(), node_key.clone()).is_some() {
+            bail!("Duplicate node_id: {}", description.node_id);
+        }
+
+        let mut node = DynamicNode {
+            properties: HashMap::with_capacity(description.properties.len()),
+            node_key,
+            node_type: description.node_type,
+            seen_at: dynamic_event.timestamp,
+            asset_id: dynamic_event.asset_id.clone(),
+            hostname: dynamic_event.hostname.clone(),
+            host_ip: None,
+            host_mac: None,
+            id_strategy: vec![description.id_strategy.into()],
+        };
+
+        for (name, property) in description.properties {
+            node.set_property(name, property);
+        }
+
+        graph.add_node(node);
+    }
+
+    for edge in dynamic_event.edges {
+        let from = match node_keys.get(&edge.from) {
+            Some(from) => from,
+            None => bail!("Edge {} refers to unknown node_id: {}", edge.edge_name, edge.from),
+        };
+        let to = match node_keys.get(&edge.to) {
+            Some(to) => to,
+            None => bail!("Edge {} refers to unknown node_id: {}", edge.edge_name, edge.to),
+        };
+
+        graph.add_edge(edge.edge_name, from.clone(), to.clone());
+    }
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GenericEvent;
+
+    #[test]
+    fn dynamic_event_generates_nodes_and_edges() {
+        let raw_log = serde_json::json!({
+            "eventname": "DYNAMIC",
+            "hostname": "host-a",
+            "timestamp": 1000,
+            "nodes": [
+                {
+                    "node_id": "instance",
+                    "node_type": "AwsEc2Instance",
+                    "properties": {"arn": "arn:aws:ec2:1", "launch_time": 1000},
+                    "id_strategy": {
+                        "static": {"primary_key_properties": ["arn"]}
+                    }
+                },
+                {
+                    "node_id": "session",
+                    "node_type": "ShellSession",
+                    "properties": {"tty": "pts/0"},
+                    "id_strategy": {
+                        "session": {
+                            "primary_key_properties": ["tty"],
+                            "primary_key_requires_asset_id": true,
+                            "created_time": 1000
+                        }
+                    }
+                }
+            ],
+            "edges": [
+                {"from": "session", "to": "instance", "edge_name": "session_on"}
+            ]
+        });
+
+        let event = match GenericEvent::from_value(0, raw_log).expect("from_value") {
+            GenericEvent::Dynamic(event) => event,
+            e => panic!("Expected Dynamic, got {:?}", e),
+        };
+        let graph = handle_dynamic(event).expect("handle_dynamic");
+
+        assert_eq!(graph.nodes.len(), 2);
+        let edges: usize = graph.edges.values().map(|e| e.edges.len()).sum();
+        assert_eq!(edges, 1);
+    }
+
+    #[test]
+    fn session_without_timestamps_is_rejected() {
+        let raw_log = serde_json::json!({
+            "eventname": "DYNAMIC",
+            "asset_id": "asset-a",
+            "timestamp": 1000,
+            "nodes": [
+                {
+                    "node_id": "instance",
+                    "node_type": "AwsEc2Instance",
+                    "properties": {"arn": "arn:aws:ec2:1"},
+                    "id_strategy": {
+                        "static": {"primary_key_properties": ["arn"]}
+                    }
+                },
+                {
+                    "node_id": "session",
+                    "node_type": "ShellSession",
+                    "properties": {"tty": "pts/0"},
+                    "id_strategy": {
+                        "session": {"primary_key_properties": ["tty"], "created_time": 0}
+                    }
+                }
+            ]
+        });
+
+        match GenericEvent::from_value(2, raw_log) {
+            Err(GenericEventError::InvalidField { index, field, .. }) => {
+                assert_eq!(index, 2);
+                assert_eq!(field, "nodes[1].id_strategy");
+            }
+            e => panic!("Expected InvalidField, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn edge_to_unknown_node_is_an_error() {
+        let raw_log = serde_json::json!({
+            "eventname": "DYNAMIC",
+            "asset_id": "asset-a",
+            "timestamp": 1000,
+            "nodes": [
+                {
+                    "node_id": "instance",
+                    "node_type": "AwsEc2Instance",
+                    "properties": {"arn": "arn:aws:ec2:1"},
+                    "id_strategy": {
+                        "static": {"primary_key_properties": ["arn"]}
+                    }
+                }
+            ],
+            "edges": [
+                {"from": "instance", "to": "missing", "edge_name": "edge"}
+            ]
+        });
+
+        match GenericEvent::from_value(0, raw_log).expect("from_value") {
+            GenericEvent::Dynamic(event) => assert!(handle_dynamic(event).is_err()),
+            e => panic!("Expected Dynamic, got {:?}", e),
+        }
+    }
+}
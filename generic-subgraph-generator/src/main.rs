@@ -12,10 +12,12 @@ extern crate regex;
 extern crate rusoto_core;
 extern crate rusoto_s3;
 extern crate rusoto_sqs;
+extern crate schemars;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate serde_path_to_error;
 extern crate simple_logger;
 extern crate sqs_lambda;
 extern crate uuid;
@@ -29,6 +31,7 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use aws_lambda_events::event::sqs::SqsEvent;
 use failure::Error;
+use graph_descriptions::container::{Container, ContainerBuilder};
 use graph_descriptions::file::FileState;
 use graph_descriptions::graph_description::*;
 use graph_descriptions::network_connection::NetworkConnectionState;
@@ -42,6 +45,7 @@ use regex::Regex;
 use rusoto_core::Region;
 use rusoto_s3::S3Client;
 use rusoto_sqs::SqsClient;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use sqs_lambda::completion_event_serializer::CompletionEventSerializer;
@@ -56,9 +60,21 @@ use sqs_lambda::sqs_consumer::{ConsumePolicy, SqsConsumer, SqsConsumerActor};
 
 use async_trait::async_trait;
 
+use crate::dynamic::{handle_dynamic, DynamicEvent};
 use crate::graph_descriptions::node::NodeT;
-
-#[derive(Clone, Debug, Hash)]
+use crate::schema::{
+    default_schema_version,
+    deserialize_event,
+    generic_event_schema,
+    GenericEventError,
+    GENERIC_SCHEMA_VERSION,
+};
+
+mod dynamic;
+mod schema;
+
+#[derive(Clone, Debug, Hash, JsonSchema)]
+#[schemars(untagged)]
 pub enum GenericEvent {
     ProcessStart(ProcessStart),
     ProcessStop(ProcessStop),
@@ -69,70 +85,101 @@ pub enum GenericEvent {
     ProcessOutboundConnectionLog(ProcessOutboundConnectionLog),
     ProcessInboundConnectionLog(ProcessInboundConnectionLog),
     ProcessPortBindLog(ProcessPortBindLog),
+    Dynamic(DynamicEvent),
 }
 
-use serde::de::Error as SerdeError;
 use sqs_lambda::cache::{Cache, CacheResponse};
 
 impl GenericEvent {
-    fn from_value(raw_log: serde_json::Value) -> Result<GenericEvent, serde_json::Error> {
+    /// Parses the event at `index` of a batch, validating its `schema_version`
+    /// and reporting deserialization failures against the offending field.
+    fn from_value(index: usize, raw_log: serde_json::Value) -> Result<GenericEvent, GenericEventError> {
         let eventname = match raw_log
             .get("eventname")
             .and_then(|eventname| eventname.as_str())
             {
                 Some(eventname) => eventname.to_owned(),
-                None => return Err(serde_json::Error::custom("missing event_type")),
+                None => return Err(GenericEventError::MissingEventName { index }),
             };
 
+        match raw_log.get("schema_version") {
+            None => (),
+            Some(version) => match version.as_u64() {
+                Some(version) if version >= 1 && version <= GENERIC_SCHEMA_VERSION as u64 => (),
+                Some(version) => {
+                    return Err(GenericEventError::UnsupportedSchemaVersion { index, schema_version: version });
+                }
+                None => {
+                    return Err(GenericEventError::InvalidField {
+                        index,
+                        field: "schema_version".to_owned(),
+                        reason: format!("expected an unsigned integer, got {}", version),
+                    });
+                }
+            }
+        }
+
         info!("Parsing log of type: {}", eventname);
         match &eventname[..] {
             "PROCESS_START" => {
                 Ok(
-                    GenericEvent::ProcessStart(serde_json::from_value(raw_log)?)
+                    GenericEvent::ProcessStart(deserialize_event(index, raw_log)?)
                 )
             },
             "PROCESS_STOP" => {
                 Ok(
-                    GenericEvent::ProcessStop(serde_json::from_value(raw_log)?)
+                    GenericEvent::ProcessStop(deserialize_event(index, raw_log)?)
                 )
             }
             "FILE_CREATE" => {
                 Ok(
-                    GenericEvent::FileCreate(serde_json::from_value(raw_log)?)
+                    GenericEvent::FileCreate(deserialize_event(index, raw_log)?)
                 )
             }
             "FILE_DELETE" => {
                 Ok(
-                    GenericEvent::FileDelete(serde_json::from_value(raw_log)?)
+                    GenericEvent::FileDelete(deserialize_event(index, raw_log)?)
                 )
             }
             "FILE_READ" => {
                 Ok(
-                    GenericEvent::FileRead(serde_json::from_value(raw_log)?)
+                    GenericEvent::FileRead(deserialize_event(index, raw_log)?)
                 )
             }
             "FILE_WRITE" => {
                 Ok(
-                    GenericEvent::FileWrite(serde_json::from_value(raw_log)?)
+                    GenericEvent::FileWrite(deserialize_event(index, raw_log)?)
                 )
             }
             "OUTBOUND_TCP" => {
                 Ok(
-                    GenericEvent::ProcessOutboundConnectionLog(serde_json::from_value(raw_log)?)
+                    GenericEvent::ProcessOutboundConnectionLog(deserialize_event(index, raw_log)?)
                 )
             }
             "INBOUND_TCP" => {
                 Ok(
-                    GenericEvent::ProcessInboundConnectionLog(serde_json::from_value(raw_log)?)
+                    GenericEvent::ProcessInboundConnectionLog(deserialize_event(index, raw_log)?)
+                )
+            }
+            "PORT_BIND" => {
+                Ok(
+                    GenericEvent::ProcessPortBindLog(deserialize_event(index, raw_log)?)
                 )
             }
-            e => Err(serde_json::Error::custom(format!("Invalid event type: {}", e))),
+            "DYNAMIC" => {
+                let event: DynamicEvent = deserialize_event(index, raw_log)?;
+                event.validate(index)?;
+                Ok(
+                    GenericEvent::Dynamic(event)
+                )
+            }
+            e => Err(GenericEventError::UnsupportedEventName { index, eventname: e.to_owned() }),
         }
     }
 }
 
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessStart {
     process_id: u64,
     parent_process_id: u64,
@@ -141,93 +188,139 @@ pub struct ProcessStart {
     arguments: String,
     timestamp: u64,
     exe: Option<String>,
+    /// Since schema version 2
+    container: Option<ContainerContext>,
+    #[schemars(regex(pattern = r"^PROCESS_START$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
+}
+
+/// The container a process was started in. The id may carry its runtime, ie: docker://<id>
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
+pub struct ContainerContext {
+    id: String,
+    image: Option<String>,
+    image_digest: Option<String>,
+    runtime: Option<String>,
+    pod: Option<String>,
 }
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessStop {
     process_id: u64,
     name: String,
     hostname: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^PROCESS_STOP$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct FileCreate {
     creator_process_id: u64,
     creator_process_name: Option<String>,
     path: String,
     hostname: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^FILE_CREATE$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct FileDelete {
     deleter_process_id: u64,
     deleter_process_name: Option<String>,
     path: String,
     hostname: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^FILE_DELETE$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct FileRead {
     reader_process_id: u64,
     reader_process_name: Option<String>,
     path: String,
     hostname: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^FILE_READ$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct FileWrite {
     writer_pid: u64,
     writer_process_name: Option<String>,
     path: String,
     hostname: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^FILE_WRITE$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessOutboundConnectionLog {
     pid: u64,
     protocol: String,
     src_port: u32,
     dst_port: u32,
-    src_hostname: String,
+    /// Since schema version 3, may be omitted, in which case the connection is
+    /// attributed to the asset that held src_ip_addr at the time
+    src_hostname: Option<String>,
     src_ip_addr: String,
     dst_ip_addr: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^OUTBOUND_TCP$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
 // In an inbound connection "src" is where the connection is coming from
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessInboundConnectionLog {
     /// The pid of the process receiving the connection
     pid: u64,
     src_ip_addr: String,
     src_port: u32,
     dst_port: u32,
-    dst_hostname: String,
+    /// Since schema version 3, may be omitted, in which case the connection is
+    /// attributed to the asset that held dst_ip_addr at the time
+    dst_hostname: Option<String>,
     dst_ip_addr: String,
     protocol: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^INBOUND_TCP$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
-#[derive(Clone, Debug, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessPortBindLog {
     pid: u64,
     bound_port: u64,
+    /// The address the port was bound on. Defaults to the wildcard address.
+    bound_ip_addr: Option<String>,
+    protocol: String,
     hostname: String,
     timestamp: u64,
+    #[schemars(regex(pattern = r"^PORT_BIND$"))]
     eventname: String,
+    #[serde(default = "default_schema_version")]
+    schema_version: u32,
 }
 
 fn is_internal_ip(ip: &str) -> bool {
@@ -243,30 +336,38 @@ fn is_internal_ip(ip: &str) -> bool {
 fn handle_outbound_traffic(conn_log: ProcessOutboundConnectionLog) -> Graph {
     let mut graph = Graph::new(conn_log.timestamp);
 
-    let asset = AssetBuilder::default()
-        .asset_id(conn_log.src_hostname.clone())
-        .hostname(conn_log.src_hostname.clone())
-        .build()
-        .expect("outbound_traffic.asset");
+    let asset = conn_log.src_hostname.as_ref().map(|hostname| {
+        AssetBuilder::default()
+            .asset_id(hostname.clone())
+            .hostname(hostname.clone())
+            .build()
+            .expect("outbound_traffic.asset")
+    });
 
     // A process creates an outbound connection to dst_port
-    let process = ProcessBuilder::default()
-        .asset_id(conn_log.src_hostname.clone())
+    let mut process = ProcessBuilder::default();
+    process
+        .host_ip(conn_log.src_ip_addr.clone())
         .state(ProcessState::Existing)
         .process_id(conn_log.pid)
-        .last_seen_timestamp(conn_log.timestamp)
-        .build()
-        .expect("outbound_traffic.process");
+        .last_seen_timestamp(conn_log.timestamp);
 
-    let outbound = ProcessOutboundConnectionBuilder::default()
-        .asset_id(conn_log.src_hostname.clone())
+    let mut outbound = ProcessOutboundConnectionBuilder::default();
+    outbound
+        .host_ip(conn_log.src_ip_addr.clone())
         .ip_address(conn_log.src_ip_addr.clone())
         .protocol(conn_log.protocol.clone())
         .state(ProcessOutboundConnectionState::Connected)
         .port(conn_log.src_port)
-        .created_timestamp(conn_log.timestamp)
-        .build()
-        .expect("outbound_traffic.inbound");
+        .created_timestamp(conn_log.timestamp);
+
+    if let Some(ref hostname) = conn_log.src_hostname {
+        process.asset_id(hostname.clone());
+        outbound.asset_id(hostname.clone());
+    }
+
+    let process = process.build().expect("outbound_traffic.process");
+    let outbound = outbound.build().expect("outbound_traffic.outbound");
 
     let src_ip = IpAddressBuilder::default()
         .ip_address(conn_log.src_ip_addr.clone())
@@ -305,19 +406,23 @@ fn handle_outbound_traffic(conn_log: ProcessOutboundConnectionLog) -> Graph {
         .build()
         .expect("outbound_traffic.network_connection");
 
-    // An asset is assigned an IP
-    graph.add_edge(
-        "asset_ip",
-        asset.clone_node_key(),
-        src_ip.clone_node_key(),
-    );
+    if let Some(asset) = asset {
+        // An asset is assigned an IP
+        graph.add_edge(
+            "asset_ip",
+            asset.clone_node_key(),
+            src_ip.clone_node_key(),
+        );
 
-    // A process spawns on an asset
-    graph.add_edge(
-        "asset_processes",
-        asset.clone_node_key(),
-        process.clone_node_key(),
-    );
+        // A process spawns on an asset
+        graph.add_edge(
+            "asset_processes",
+            asset.clone_node_key(),
+            process.clone_node_key(),
+        );
+
+        graph.add_node(asset);
+    }
 
     // A process creates a connection
     graph.add_edge(
@@ -353,7 +458,6 @@ fn handle_outbound_traffic(conn_log: ProcessOutboundConnectionLog) -> Graph {
         dst_port.clone_node_key(),
     );
 
-    graph.add_node(asset);
     graph.add_node(process);
     graph.add_node(outbound);
     graph.add_node(src_ip);
@@ -368,30 +472,38 @@ fn handle_outbound_traffic(conn_log: ProcessOutboundConnectionLog) -> Graph {
 fn handle_inbound_traffic(conn_log: ProcessInboundConnectionLog) -> Graph {
     let mut graph = Graph::new(conn_log.timestamp);
 
-    let asset = AssetBuilder::default()
-        .asset_id(conn_log.dst_hostname.clone())
-        .hostname(conn_log.dst_hostname.clone())
-        .build()
-        .expect("inbound_traffic.asset");
+    let asset = conn_log.dst_hostname.as_ref().map(|hostname| {
+        AssetBuilder::default()
+            .asset_id(hostname.clone())
+            .hostname(hostname.clone())
+            .build()
+            .expect("inbound_traffic.asset")
+    });
 
     // A process creates an outbound connection to dst_port
-    let process = ProcessBuilder::default()
-        .asset_id(conn_log.dst_hostname.clone())
+    let mut process = ProcessBuilder::default();
+    process
+        .host_ip(conn_log.dst_ip_addr.clone())
         .state(ProcessState::Existing)
         .process_id(conn_log.pid)
-        .last_seen_timestamp(conn_log.timestamp)
-        .build()
-        .expect("inbound_traffic.process");
+        .last_seen_timestamp(conn_log.timestamp);
 
-    let inbound = ProcessInboundConnectionBuilder::default()
-        .asset_id(conn_log.dst_hostname.clone())
+    let mut inbound = ProcessInboundConnectionBuilder::default();
+    inbound
+        .host_ip(conn_log.dst_ip_addr.clone())
         .state(ProcessInboundConnectionState::Existing)
         .ip_address(conn_log.dst_ip_addr.clone())
         .protocol(conn_log.protocol.clone())
         .port(conn_log.dst_port)
-        .created_timestamp(conn_log.timestamp)
-        .build()
-        .expect("inbound_traffic.inbound");
+        .created_timestamp(conn_log.timestamp);
+
+    if let Some(ref hostname) = conn_log.dst_hostname {
+        process.asset_id(hostname.clone());
+        inbound.asset_id(hostname.clone());
+    }
+
+    let process = process.build().expect("inbound_traffic.process");
+    let inbound = inbound.build().expect("inbound_traffic.inbound");
 
     let dst_ip = IpAddressBuilder::default()
         .ip_address(conn_log.dst_ip_addr.clone())
@@ -428,19 +540,23 @@ fn handle_inbound_traffic(conn_log: ProcessInboundConnectionLog) -> Graph {
         .build()
         .expect("inbound_traffic.network_connection");
 
-    // An asset is assigned an IP
-    graph.add_edge(
-        "asset_ip",
-        asset.clone_node_key(),
-        dst_ip.clone_node_key(),
-    );
+    if let Some(asset) = asset {
+        // An asset is assigned an IP
+        graph.add_edge(
+            "asset_ip",
+            asset.clone_node_key(),
+            dst_ip.clone_node_key(),
+        );
 
-    // A process spawns on an asset
-    graph.add_edge(
-        "asset_processes",
-        asset.clone_node_key(),
-        process.clone_node_key(),
-    );
+        // A process spawns on an asset
+        graph.add_edge(
+            "asset_processes",
+            asset.clone_node_key(),
+            process.clone_node_key(),
+        );
+
+        graph.add_node(asset);
+    }
 
     // A process creates a connection
     graph.add_edge(
@@ -476,7 +592,6 @@ fn handle_inbound_traffic(conn_log: ProcessInboundConnectionLog) -> Graph {
         dst_port.clone_node_key(),
     );
 
-    graph.add_node(asset);
     graph.add_node(process);
     graph.add_node(inbound);
     graph.add_node(dst_ip);
@@ -488,6 +603,77 @@ fn handle_inbound_traffic(conn_log: ProcessInboundConnectionLog) -> Graph {
     graph
 }
 
+fn handle_port_bind(bind_log: ProcessPortBindLog) -> Result<Graph, Error> {
+    if bind_log.bound_port > u16::max_value() as u64 {
+        bail!("Invalid bound_port: {}", bind_log.bound_port);
+    }
+
+    let bound_port = bind_log.bound_port as u32;
+    let bound_ip_addr = bind_log.bound_ip_addr
+        .unwrap_or_else(|| "0.0.0.0".to_owned());
+
+    let mut graph = Graph::new(bind_log.timestamp);
+
+    let asset = AssetBuilder::default()
+        .asset_id(bind_log.hostname.clone())
+        .hostname(bind_log.hostname.clone())
+        .build()
+        .map_err(|e| format_err!("port_bind.asset: {}", e))?;
+
+    let process = ProcessBuilder::default()
+        .asset_id(bind_log.hostname.clone())
+        .state(ProcessState::Existing)
+        .process_id(bind_log.pid)
+        .last_seen_timestamp(bind_log.timestamp)
+        .build()
+        .map_err(|e| format_err!("port_bind.process: {}", e))?;
+
+    let inbound = ProcessInboundConnectionBuilder::default()
+        .asset_id(bind_log.hostname.clone())
+        .state(ProcessInboundConnectionState::Bound)
+        .ip_address(bound_ip_addr.clone())
+        .protocol(bind_log.protocol.clone())
+        .port(bound_port)
+        .created_timestamp(bind_log.timestamp)
+        .build()
+        .map_err(|e| format_err!("port_bind.inbound: {}", e))?;
+
+    let port = IpPortBuilder::default()
+        .ip_address(bound_ip_addr)
+        .port(bound_port)
+        .protocol(bind_log.protocol)
+        .build()
+        .map_err(|e| format_err!("port_bind.port: {}", e))?;
+
+    // A process spawns on an asset
+    graph.add_edge(
+        "asset_processes",
+        asset.clone_node_key(),
+        process.clone_node_key(),
+    );
+
+    // A process binds a port
+    graph.add_edge(
+        "inbound_connections",
+        process.clone_node_key(),
+        inbound.clone_node_key(),
+    );
+
+    // The bound connection is over an IP + Port
+    graph.add_edge(
+        "bound_port",
+        inbound.clone_node_key(),
+        port.clone_node_key(),
+    );
+
+    graph.add_node(asset);
+    graph.add_node(process);
+    graph.add_node(inbound);
+    graph.add_node(port);
+
+    Ok(graph)
+}
+
 fn handle_process_start(process_start: ProcessStart) -> Graph {
     let mut graph = Graph::new(process_start.timestamp);
 
@@ -514,6 +700,22 @@ fn handle_process_start(process_start: ProcessStart) -> Graph {
         .build()
         .unwrap();
 
+    if let Some(container) = process_start.container {
+        let container = ContainerBuilder::default()
+            .container_id(container.id)
+            .hostname(process_start.hostname.clone())
+            .image(container.image)
+            .image_digest(container.image_digest)
+            .runtime(container.runtime)
+            .pod(container.pod)
+            .seen_at(process_start.timestamp)
+            .build()
+            .unwrap();
+
+        graph.add_edge("runs_in", child.clone_node_key(), container.clone_node_key());
+        graph.add_node(container);
+    }
+
     if let Some(exe_path) = process_start.exe {
         let child_exe = FileBuilder::default()
             .hostname(process_start.hostname)
@@ -541,6 +743,7 @@ fn handle_process_start(process_start: ProcessStart) -> Graph {
     );
 
     graph.add_edge("children", parent.clone_node_key(), child.clone_node_key());
+    graph.add_node(asset);
     graph.add_node(parent);
     graph.add_node(child);
 
@@ -702,7 +905,12 @@ fn handle_log(generic_event: GenericEvent) -> Result<Graph, Error> {
         GenericEvent::ProcessInboundConnectionLog(event) => {
             Ok(handle_inbound_traffic(event))
         }
-        GenericEvent::ProcessPortBindLog(_event) => unimplemented!()
+        GenericEvent::ProcessPortBindLog(event) => {
+            handle_port_bind(event)
+        }
+        GenericEvent::Dynamic(event) => {
+            handle_dynamic(event)
+        }
     }
 }
 
@@ -834,8 +1042,8 @@ impl EventHandler for GenericSubgraphGenerator
         let mut final_subgraph = Graph::new(0);
         let mut identities = Vec::with_capacity(events.len());
 
-        for event in events {
-            let event = match GenericEvent::from_value(event) {
+        for (index, event) in events.into_iter().enumerate() {
+            let event = match GenericEvent::from_value(index, event) {
                 Ok(event) => event,
                 Err(e) => {
                     error!("Failed to generate subgraph with: {}", e);
@@ -1080,6 +1288,150 @@ fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
 //}
 
 fn main() {
+    // The published schema is regenerated with `generic-subgraph-generator --print-schema`
+    if std::env::args().nth(1).as_ref().map(String::as_str) == Some("--print-schema") {
+        let schema = serde_json::to_string_pretty(&generic_event_schema())
+            .expect("generic_event_schema");
+        println!("{}", schema);
+        return;
+    }
+
     simple_logger::init_with_level(log::Level::Info).unwrap();
     lambda!(handler);
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_bind_generates_bound_connection() {
+        let raw_log = serde_json::json!({
+            "eventname": "PORT_BIND",
+            "pid": 100,
+            "bound_port": 8080,
+            "protocol": "tcp",
+            "hostname": "host-a",
+            "timestamp": 1000,
+        });
+
+        let event = GenericEvent::from_value(0, raw_log).expect("from_value");
+        let graph = handle_log(event).expect("handle_log");
+
+        assert_eq!(graph.nodes.len(), 4);
+
+        let key_of = |is_kind: fn(&Node) -> bool| {
+            graph.nodes.values()
+                .find(|node| is_kind(node))
+                .map(|node| node.clone_node_key())
+                .expect("Missing node")
+        };
+
+        let asset = key_of(|node| node.as_asset().is_some());
+        let process = key_of(|node| node.as_process().is_some());
+        let inbound = key_of(|node| node.as_process_inbound_connection().is_some());
+        let port = key_of(|node| node.as_ip_port().is_some());
+
+        let mut edges: Vec<_> = graph.edges.values()
+            .flat_map(|edges| edges.edges.iter())
+            .map(|edge| (edge.edge_name.as_str(), edge.from.clone(), edge.to.clone()))
+            .collect();
+        edges.sort();
+
+        let mut expected = vec![
+            ("asset_processes", asset, process.clone()),
+            ("bound_port", inbound.clone(), port),
+            ("inbound_connections", process, inbound),
+        ];
+        expected.sort();
+
+        assert_eq!(edges, expected);
+    }
+
+    #[test]
+    fn outbound_traffic_without_hostname_is_attributed_by_ip() {
+        let raw_log = serde_json::json!({
+            "eventname": "OUTBOUND_TCP",
+            "schema_version": 3,
+            "pid": 100,
+            "protocol": "tcp",
+            "src_port": 49152,
+            "dst_port": 443,
+            "src_ip_addr": "10.0.0.5",
+            "dst_ip_addr": "93.184.216.34",
+            "timestamp": 1000,
+        });
+
+        let event = GenericEvent::from_value(0, raw_log).expect("from_value");
+        let graph = handle_log(event).expect("handle_log");
+
+        assert!(graph.nodes.values().all(|node| node.as_asset().is_none()));
+
+        let process = graph.nodes.values()
+            .find_map(|node| node.as_process())
+            .expect("process");
+        assert_eq!(process.asset_id, None);
+        assert_eq!(process.host_ip, Some("10.0.0.5".to_owned()));
+
+        let outbound = graph.nodes.values()
+            .find_map(|node| node.as_process_outbound_connection())
+            .expect("outbound");
+        assert_eq!(outbound.asset_id, None);
+        assert_eq!(outbound.host_ip, Some("10.0.0.5".to_owned()));
+    }
+
+    #[test]
+    fn process_start_runs_in_container() {
+        let raw_log = serde_json::json!({
+            "eventname": "PROCESS_START",
+            "process_id": 200,
+            "parent_process_id": 100,
+            "name": "nginx",
+            "hostname": "host-a",
+            "arguments": "-g daemon off;",
+            "timestamp": 1000,
+            "container": {
+                "id": "docker://8d1f9b1c4a7e",
+                "image": "nginx:1.17",
+            },
+            "schema_version": 2,
+        });
+
+        let event = GenericEvent::from_value(0, raw_log).expect("from_value");
+        let graph = handle_log(event).expect("handle_log");
+
+        // asset, parent, child and container
+        assert_eq!(graph.nodes.len(), 4);
+        assert!(
+            graph.edges.values()
+                .flat_map(|edges| edges.edges.iter())
+                .any(|edge| edge.edge_name == "runs_in")
+        );
+    }
+
+    #[test]
+    fn invalid_bound_port_is_an_error() {
+        let raw_log = serde_json::json!({
+            "eventname": "PORT_BIND",
+            "pid": 100,
+            "bound_port": 70000,
+            "protocol": "tcp",
+            "hostname": "host-a",
+            "timestamp": 1000,
+        });
+
+        let event = GenericEvent::from_value(0, raw_log).expect("from_value");
+        assert!(handle_log(event).is_err());
+    }
+
+    #[test]
+    fn unknown_eventname_is_an_error() {
+        let raw_log = serde_json::json!({
+            "eventname": "NOT_A_REAL_EVENT",
+            "timestamp": 1000,
+        });
+
+        assert!(GenericEvent::from_value(0, raw_log).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use schemars::schema::RootSchema;
+use schemars::gen::SchemaSettings;
+
+use failure::Fail;
+use serde::Deserialize;
+
+use crate::GenericEvent;
+
+/// The newest version of the generic log format this generator understands.
+/// Events that omit `schema_version` are treated as version 1.
+///
+/// Version 2 adds the `container` a process was started in to PROCESS_START.
+/// Version 3 lets OUTBOUND_TCP and INBOUND_TCP omit the hostname, so that they are
+/// attributed by the host's ip address.
+pub const GENERIC_SCHEMA_VERSION: u32 = 3;
+
+pub fn default_schema_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Fail)]
+pub enum GenericEventError {
+    #[fail(display = "event {}: missing eventname", index)]
+    MissingEventName { index: usize },
+    #[fail(display = "event {}: unsupported eventname: {}", index, eventname)]
+    UnsupportedEventName { index: usize, eventname: String },
+    #[fail(display = "event {}: unsupported schema_version: {}", index, schema_version)]
+    UnsupportedSchemaVersion { index: usize, schema_version: u64 },
+    #[fail(display = "event {}: invalid field `{}`: {}", index, field, reason)]
+    InvalidField { index: usize, field: String, reason: String },
+}
+
+/// Deserializes a single generic event, tracking the path to whichever field fails
+pub fn deserialize_event<T>(index: usize, raw_log: serde_json::Value) -> Result<T, GenericEventError>
+    where for<'a> T: Deserialize<'a>
+{
+    serde_path_to_error::deserialize(raw_log)
+        .map_err(|e| {
+            let reason = e.inner().to_string();
+            let field = match e.path().to_string().as_str() {
+                // Missing fields are reported against the enclosing object
+                "." => missing_field(&reason).unwrap_or_else(|| ".".to_owned()),
+                path => path.to_owned(),
+            };
+
+            GenericEventError::InvalidField { index, field, reason }
+        })
+}
+
+fn missing_field(reason: &str) -> Option<String> {
+    let field = reason.trim_start_matches("missing field `");
+    if field.len() == reason.len() {
+        return None;
+    }
+
+    field.split('`').next().map(str::to_owned)
+}
+
+/// The JSON Schema for the generic log format, as published in
+/// `schema/generic-event.schema.json`
+pub fn generic_event_schema() -> RootSchema {
+    SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<GenericEvent>()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use serde_json::Value;
+
+    use super::*;
+
+    fn keys(value: &Value) -> BTreeSet<String> {
+        value
+            .as_object()
+            .map(|o| o.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn required(value: &Value) -> BTreeSet<String> {
+        value["required"]
+            .as_array()
+            .map(|r| r.iter().filter_map(|f| f.as_str()).map(str::to_owned).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn published_schema_matches_structs() {
+        let published: Value = serde_json::from_str(
+            include_str!("../schema/generic-event.schema.json")
+        ).expect("published schema");
+        let generated = serde_json::to_value(&generic_event_schema()).expect("generated schema");
+
+        let definitions = keys(&generated["definitions"]);
+        assert_eq!(keys(&published["definitions"]), definitions);
+
+        for definition in definitions {
+            let published = &published["definitions"][&definition];
+            let generated = &generated["definitions"][&definition];
+
+            assert_eq!(keys(&published["properties"]), keys(&generated["properties"]), "{}", definition);
+            assert_eq!(required(published), required(generated), "{}", definition);
+        }
+    }
+
+    #[test]
+    fn invalid_field_is_named() {
+        let raw_log = serde_json::json!({
+            "eventname": "PROCESS_STOP",
+            "process_id": "not a pid",
+            "name": "foo.exe",
+            "hostname": "host-a",
+            "timestamp": 1000,
+        });
+
+        match GenericEvent::from_value(3, raw_log) {
+            Err(GenericEventError::InvalidField { index, field, .. }) => {
+                assert_eq!(index, 3);
+                assert_eq!(field, "process_id");
+            }
+            e => panic!("Expected InvalidField, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn missing_field_is_named() {
+        let raw_log = serde_json::json!({
+            "eventname": "PROCESS_STOP",
+            "name": "foo.exe",
+            "hostname": "host-a",
+            "timestamp": 1000,
+        });
+
+        match GenericEvent::from_value(0, raw_log) {
+            Err(GenericEventError::InvalidField { field, .. }) => assert_eq!(field, "process_id"),
+            e => panic!("Expected InvalidField, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn newer_schema_version_is_rejected() {
+        let raw_log = serde_json::json!({
+            "eventname": "PROCESS_STOP",
+            "schema_version": GENERIC_SCHEMA_VERSION + 1,
+            "process_id": 100,
+            "name": "foo.exe",
+            "hostname": "host-a",
+            "timestamp": 1000,
+        });
+
+        match GenericEvent::from_value(0, raw_log) {
+            Err(GenericEventError::UnsupportedSchemaVersion { .. }) => (),
+            e => panic!("Expected UnsupportedSchemaVersion, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn zero_schema_version_is_rejected() {
+        let raw_log = serde_json::json!({
+            "eventname": "PROCESS_STOP",
+            "schema_version": 0,
+            "process_id": 100,
+            "name": "foo.exe",
+            "hostname": "host-a",
+            "timestamp": 1000,
+        });
+
+        match GenericEvent::from_value(0, raw_log) {
+            Err(GenericEventError::UnsupportedSchemaVersion { schema_version, .. }) => assert_eq!(schema_version, 0),
+            e => panic!("Expected UnsupportedSchemaVersion, got {:?}", e),
+        }
+    }
+}
@@ -54,6 +54,7 @@ fn main() {
     config.field_attribute(".graph_description.File.asset_id", "#[builder(default)]");
     config.field_attribute(".graph_description.File.hostname", "#[builder(default)]");
     config.field_attribute(".graph_description.File.host_ip", "#[builder(default)]");
+    config.field_attribute(".graph_description.File.host_mac", "#[builder(default)]");
 
     config.field_attribute(".graph_description.Process.node_key", "#[builder(field(private))]");
     config.field_attribute(".graph_description.Process.node_key",
@@ -72,6 +73,7 @@ fn main() {
     config.field_attribute(".graph_description.Process.asset_id", "#[builder(default)]");
     config.field_attribute(".graph_description.Process.hostname", "#[builder(default)]");
     config.field_attribute(".graph_description.Process.host_ip", "#[builder(default)]");
+    config.field_attribute(".graph_description.Process.host_mac", "#[builder(default)]");
 
 
     config.field_attribute(".graph_description.ProcessInboundConnection.node_key", "#[builder(field(private))]");
@@ -81,6 +83,7 @@ fn main() {
     config.field_attribute(".graph_description.ProcessInboundConnection.asset_id", "#[builder(default)]");
     config.field_attribute(".graph_description.ProcessInboundConnection.hostname", "#[builder(default)]");
     config.field_attribute(".graph_description.ProcessInboundConnection.host_ip", "#[builder(default)]");
+    config.field_attribute(".graph_description.ProcessInboundConnection.host_mac", "#[builder(default)]");
 
 
     config.field_attribute(".graph_description.ProcessInboundConnection.created_timestamp", "#[builder(default)]");
@@ -94,6 +97,7 @@ fn main() {
     config.field_attribute(".graph_description.ProcessOutboundConnection.asset_id", "#[builder(default)]");
     config.field_attribute(".graph_description.ProcessOutboundConnection.hostname", "#[builder(default)]");
     config.field_attribute(".graph_description.ProcessOutboundConnection.host_ip", "#[builder(default)]");
+    config.field_attribute(".graph_description.ProcessOutboundConnection.host_mac", "#[builder(default)]");
 
     config.field_attribute(".graph_description.ProcessOutboundConnection.created_timestamp", "#[builder(default)]");
     config.field_attribute(".graph_description.ProcessOutboundConnection.terminated_timestamp", "#[builder(default)]");
@@ -104,11 +108,18 @@ fn main() {
                            "#[builder(default = \"::uuid::Uuid::new_v4().to_string()\")]");
 
     config.field_attribute(".graph_description.Asset.asset_id", "#[builder(default)]");
-    config.field_attribute(".graph_description.Asset.ip_address", "#[builder(default)]");
     config.field_attribute(".graph_description.Asset.hostname", "#[builder(default)]");
     config.field_attribute(".graph_description.Asset.mac_address", "#[builder(default)]");
     config.field_attribute(".graph_description.Asset.first_seen_timestamp", "#[builder(default)]");
     config.field_attribute(".graph_description.Asset.last_seen_timestamp", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.fqdn", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.os", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.serial_number", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.owner", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.instance_id", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.ip_addresses", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.mac_addresses", "#[builder(default)]");
+    config.field_attribute(".graph_description.Asset.original_hostname", "#[builder(default)]");
 
 
     config.field_attribute(".graph_description.IpAddress.node_key", "#[builder(field(private))]");
@@ -133,6 +144,10 @@ fn main() {
     config.field_attribute(".graph_description.NetworkConnection.created_timestamp", "#[builder(default)]");
     config.field_attribute(".graph_description.NetworkConnection.terminated_timestamp", "#[builder(default)]");
     config.field_attribute(".graph_description.NetworkConnection.last_seen_timestamp", "#[builder(default)]");
+    config.field_attribute(".graph_description.NetworkConnection.connection_uid", "#[builder(default)]");
+    config.field_attribute(".graph_description.NetworkConnection.bytes", "#[builder(default)]");
+    config.field_attribute(".graph_description.NetworkConnection.packets", "#[builder(default)]");
+    config.field_attribute(".graph_description.NetworkConnection.flow_action", "#[builder(default)]");
 
     config.field_attribute(".graph_description.IpConnection.node_key", "#[builder(field(private))]");
     config.field_attribute(".graph_description.IpConnection.node_key",
@@ -141,6 +156,9 @@ fn main() {
     config.field_attribute(".graph_description.IpConnection.created_timestamp", "#[builder(default)]");
     config.field_attribute(".graph_description.IpConnection.terminated_timestamp", "#[builder(default)]");
     config.field_attribute(".graph_description.IpConnection.last_seen_timestamp", "#[builder(default)]");
+    config.field_attribute(".graph_description.IpConnection.bytes", "#[builder(default)]");
+    config.field_attribute(".graph_description.IpConnection.packets", "#[builder(default)]");
+    config.field_attribute(".graph_description.IpConnection.flow_action", "#[builder(default)]");
 
 
     config
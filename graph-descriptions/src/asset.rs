@@ -25,6 +25,14 @@ impl Asset {
             mac_address: mac_address.into(),
             first_seen_timestamp,
             last_seen_timestamp,
+            fqdn: None,
+            os: None,
+            serial_number: None,
+            owner: None,
+            instance_id: None,
+            ip_addresses: vec![],
+            mac_addresses: vec![],
+            original_hostname: None,
         }
     }
 
@@ -52,6 +60,38 @@ impl Asset {
             j["mac_address"] = Value::from(mac_address.clone());
         }
 
+        if let Some(fqdn) = self.fqdn {
+            j["fqdn"] = Value::from(fqdn);
+        }
+
+        if let Some(os) = self.os {
+            j["os"] = Value::from(os);
+        }
+
+        if let Some(serial_number) = self.serial_number {
+            j["serial_number"] = Value::from(serial_number);
+        }
+
+        if let Some(owner) = self.owner {
+            j["owner"] = Value::from(owner);
+        }
+
+        if let Some(instance_id) = self.instance_id {
+            j["instance_id"] = Value::from(instance_id);
+        }
+
+        if let Some(original_hostname) = self.original_hostname {
+            j["original_hostname"] = Value::from(original_hostname);
+        }
+
+        if !self.ip_addresses.is_empty() {
+            j["ip_addresses"] = Value::from(self.ip_addresses);
+        }
+
+        if !self.mac_addresses.is_empty() {
+            j["mac_addresses"] = Value::from(self.mac_addresses);
+        }
+
         j
     }
 }
@@ -96,10 +136,12 @@ impl NodeT for Asset {
             self.mac_address = other.mac_address.clone();
         }
 
+        merged |= merge_inventory(self, &mut other.clone());
+
         merged
     }
 
-    fn merge_into(&mut self, other: Self) -> bool {
+    fn merge_into(&mut self, mut other: Self) -> bool {
         if self.node_key != other.node_key {
             warn!("Attempted to merge two Asset Nodes with differing node_keys");
             return false
@@ -108,20 +150,22 @@ impl NodeT for Asset {
         let mut merged = false;
 
         if self.asset_id.is_none() && other.asset_id.is_some() {
-            self.asset_id = other.asset_id;
+            self.asset_id = other.asset_id.take();
             merged = true;
         }
 
         if self.hostname.is_none() && other.hostname.is_some() {
-            self.hostname = other.hostname;
+            self.hostname = other.hostname.take();
             merged = true;
         }
 
         if self.mac_address.is_none() && other.mac_address.is_some() {
-            self.mac_address = other.mac_address;
+            self.mac_address = other.mac_address.take();
             merged = true;
         }
 
+        merged |= merge_inventory(self, &mut other);
+
         if other.first_seen_timestamp != 0 && self.first_seen_timestamp > other.first_seen_timestamp {
             self.first_seen_timestamp = other.first_seen_timestamp;
             merged = true;
@@ -134,4 +178,38 @@ impl NodeT for Asset {
 
         merged
     }
-}
\ No newline at end of file
+}
+
+// Inventory fields are kept when already known, and addresses are unioned, as an asset
+// may have many interfaces
+fn merge_inventory(asset: &mut Asset, other: &mut Asset) -> bool {
+    let mut merged = false;
+
+    for (field, other_field) in vec![
+        (&mut asset.fqdn, other.fqdn.take()),
+        (&mut asset.os, other.os.take()),
+        (&mut asset.serial_number, other.serial_number.take()),
+        (&mut asset.owner, other.owner.take()),
+        (&mut asset.instance_id, other.instance_id.take()),
+        (&mut asset.original_hostname, other.original_hostname.take()),
+    ] {
+        if field.is_none() && other_field.is_some() {
+            *field = other_field;
+            merged = true;
+        }
+    }
+
+    for (addresses, other_addresses) in vec![
+        (&mut asset.ip_addresses, std::mem::replace(&mut other.ip_addresses, vec![])),
+        (&mut asset.mac_addresses, std::mem::replace(&mut other.mac_addresses, vec![])),
+    ] {
+        for address in other_addresses {
+            if !addresses.contains(&address) {
+                addresses.push(address);
+                merged = true;
+            }
+        }
+    }
+
+    merged
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use graph_description::{DynamicNode, Node, Static};
+
+/// A container, and the context its runtime knows it by.
+///
+/// Containers are described as DynamicNodes, so they are identified and merged like any
+/// other plugin node. A container id is only unique on the asset running it, so the
+/// identity is the container id plus the asset id.
+#[derive(Clone, Debug, Builder)]
+#[builder(setter(into))]
+pub struct Container {
+    #[builder(field(private))]
+    #[builder(default = "::uuid::Uuid::new_v4().to_string()")]
+    node_key: String,
+    container_id: String,
+    #[builder(default)]
+    asset_id: Option<String>,
+    #[builder(default)]
+    hostname: Option<String>,
+    #[builder(default)]
+    image: Option<String>,
+    #[builder(default)]
+    image_digest: Option<String>,
+    #[builder(default)]
+    runtime: Option<String>,
+    #[builder(default)]
+    pod: Option<String>,
+    seen_at: u64,
+}
+
+impl Container {
+    pub fn get_node_key(&self) -> &str {
+        &self.node_key
+    }
+
+    pub fn clone_node_key(&self) -> String {
+        self.node_key.clone()
+    }
+}
+
+/// Splits the runtime from a container id, as Kubernetes writes them, ie: docker://<id>
+pub fn split_container_id(container_id: &str) -> (Option<&str>, &str) {
+    match container_id.find("://") {
+        Some(index) => (Some(&container_id[..index]), &container_id[index + 3..]),
+        None => (None, container_id),
+    }
+}
+
+impl From<Container> for DynamicNode {
+    fn from(container: Container) -> DynamicNode {
+        let (runtime, container_id) = split_container_id(&container.container_id);
+        let runtime = container.runtime.clone().or_else(|| runtime.map(String::from));
+
+        let mut node = DynamicNode {
+            properties: HashMap::with_capacity(5),
+            node_key: container.node_key.clone(),
+            node_type: "Container".to_owned(),
+            seen_at: container.seen_at,
+            asset_id: container.asset_id.clone(),
+            hostname: container.hostname.clone(),
+            host_ip: None,
+            host_mac: None,
+            id_strategy: vec![
+                Static {
+                    primary_key_properties: vec!["container_id".to_owned()],
+                    primary_key_requires_asset_id: true,
+                }.into()
+            ],
+        };
+
+        node.set_property("container_id", container_id.to_lowercase());
+
+        if let Some(image) = container.image {
+            node.set_property("image", image);
+        }
+        if let Some(image_digest) = container.image_digest {
+            node.set_property("image_digest", image_digest);
+        }
+        if let Some(runtime) = runtime {
+            node.set_property("runtime", runtime);
+        }
+        if let Some(pod) = container.pod {
+            node.set_property("pod", pod);
+        }
+
+        node
+    }
+}
+
+impl From<Container> for Node {
+    fn from(container: Container) -> Node {
+        DynamicNode::from(container).into()
+    }
+}
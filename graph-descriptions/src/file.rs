@@ -68,6 +68,8 @@ impl File {
             node_key: Uuid::new_v4().to_string(),
             asset_id: asset_id.into(),
             hostname: hostname.into(),
+            host_ip: None,
+            host_mac: None,
             state: state.clone().into(),
             created_timestamp: 0,
             deleted_timestamp: 0,
@@ -224,6 +226,16 @@ impl NodeT for File {
             self.hostname = other.hostname.clone();
         }
 
+        if self.host_ip.is_none() && other.host_ip.is_some() {
+            merged = true;
+            self.host_ip = other.host_ip.clone();
+        }
+
+        if self.host_mac.is_none() && other.host_mac.is_some() {
+            merged = true;
+            self.host_mac = other.host_mac.clone();
+        }
+
         if self.file_name.is_empty() && !other.file_name.is_empty() {
             merged = true;
             self.file_name = other.file_name.clone();
@@ -333,6 +345,16 @@ impl NodeT for File {
             self.hostname = other.hostname;
         }
 
+        if self.host_ip.is_none() && other.host_ip.is_some() {
+            merged = true;
+            self.host_ip = other.host_ip;
+        }
+
+        if self.host_mac.is_none() && other.host_mac.is_some() {
+            merged = true;
+            self.host_mac = other.host_mac;
+        }
+
         if self.file_name.is_empty() && !other.file_name.is_empty() {
             merged = true;
             self.file_name = other.file_name;
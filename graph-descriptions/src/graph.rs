@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use graph_description::{Edge, EdgeList, GeneratedSubgraphs, Node};
+use graph_description::{Edge, EdgeList, GeneratedSubgraphs, Node, NodeProperty};
 use graph_description::Graph;
 use node::NodeT;
 
@@ -56,6 +56,15 @@ impl Graph {
                     edge_name: impl Into<String>,
                     from: impl Into<String>,
                     to: impl Into<String>)
+    {
+        self.add_edge_with_properties(edge_name, from, to, HashMap::new());
+    }
+
+    pub fn add_edge_with_properties(&mut self,
+                                    edge_name: impl Into<String>,
+                                    from: impl Into<String>,
+                                    to: impl Into<String>,
+                                    properties: HashMap<String, NodeProperty>)
     {
         let from = from.into();
         let to = to.into();
@@ -63,7 +72,8 @@ impl Graph {
         let edge = Edge {
             from: from.clone(),
             to,
-            edge_name
+            edge_name,
+            properties,
         };
 
         self.edges
@@ -60,6 +60,9 @@ impl IpConnection {
             created_timestamp,
             terminated_timestamp,
             last_seen_timestamp,
+            bytes: 0,
+            packets: 0,
+            flow_action: String::new(),
         }
     }
 
@@ -84,6 +87,17 @@ impl IpConnection {
             j["last_seen_timestamp"] = self.last_seen_timestamp.into();
         }
 
+        if self.bytes != 0 {
+            j["bytes"] = self.bytes.into();
+        }
+
+        if self.packets != 0 {
+            j["packets"] = self.packets.into();
+        }
+
+        if !self.flow_action.is_empty() {
+            j["flow_action"] = self.flow_action.into();
+        }
 
         j
     }
@@ -126,6 +140,20 @@ impl NodeT for IpConnection {
             self.last_seen_timestamp = other.last_seen_timestamp;
             merged = true;
         }
+        // Flow logs report the same flow from every interface that saw it, so the counts
+        // are not summed
+        if other.bytes > self.bytes {
+            self.bytes = other.bytes;
+            merged = true;
+        }
+        if other.packets > self.packets {
+            self.packets = other.packets;
+            merged = true;
+        }
+        if self.flow_action.is_empty() && !other.flow_action.is_empty() {
+            self.flow_action = other.flow_action.clone();
+            merged = true;
+        }
 
         merged
     }
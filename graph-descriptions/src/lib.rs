@@ -33,6 +33,8 @@ pub mod network_connection;
 pub mod process_outbound_connection;
 pub mod process_inbound_connection;
 pub mod dynamic_node;
+pub mod container;
+pub mod url;
 pub mod graph;
 
 
@@ -76,6 +78,17 @@ impl From<u64> for NodeProperty {
     }
 }
 
+impl NodeProperty {
+    pub fn into_json(self) -> serde_json::Value {
+        match self.property {
+            Some(node_property::Property::Intprop(i)) => i.into(),
+            Some(node_property::Property::Uintprop(i)) => i.into(),
+            Some(node_property::Property::Strprop(s)) => s.into(),
+            None => serde_json::Value::Null,
+        }
+    }
+}
+
 impl std::string::ToString for NodeProperty {
     fn to_string(&self) -> String {
         let prop = match &self.property {
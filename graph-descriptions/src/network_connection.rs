@@ -64,6 +64,10 @@ impl NetworkConnection {
             created_timestamp,
             terminated_timestamp,
             last_seen_timestamp,
+            connection_uid: String::new(),
+            bytes: 0,
+            packets: 0,
+            flow_action: String::new(),
         }
     }
 
@@ -89,6 +93,21 @@ impl NetworkConnection {
             j["last_seen_timestamp"] = self.last_seen_timestamp.into();
         }
 
+        if !self.connection_uid.is_empty() {
+            j["connection_uid"] = self.connection_uid.into();
+        }
+
+        if self.bytes != 0 {
+            j["bytes"] = self.bytes.into();
+        }
+
+        if self.packets != 0 {
+            j["packets"] = self.packets.into();
+        }
+
+        if !self.flow_action.is_empty() {
+            j["flow_action"] = self.flow_action.into();
+        }
 
         j
     }
@@ -131,6 +150,24 @@ impl NodeT for NetworkConnection {
             self.last_seen_timestamp = other.last_seen_timestamp;
             merged = true;
         }
+        if self.connection_uid.is_empty() && !other.connection_uid.is_empty() {
+            self.connection_uid = other.connection_uid.clone();
+            merged = true;
+        }
+        // Flow logs report the same flow from every interface that saw it, so the counts
+        // are not summed
+        if other.bytes > self.bytes {
+            self.bytes = other.bytes;
+            merged = true;
+        }
+        if other.packets > self.packets {
+            self.packets = other.packets;
+            merged = true;
+        }
+        if self.flow_action.is_empty() && !other.flow_action.is_empty() {
+            self.flow_action = other.flow_action.clone();
+            merged = true;
+        }
 
         merged
     }
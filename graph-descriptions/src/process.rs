@@ -58,6 +58,8 @@ impl Process {
             node_key: Uuid::new_v4().to_string(),
             asset_id: asset_id.into(),
             hostname: hostname.into(),
+            host_ip: None,
+            host_mac: None,
             state: state.clone().into(),
             process_id,
             process_name,
@@ -62,6 +62,8 @@ impl ProcessOutboundConnection {
             ip_address,
             asset_id,
             hostname,
+            host_ip: None,
+            host_mac: None,
             protocol,
             created_timestamp,
             terminated_timestamp,
@@ -132,6 +134,14 @@ impl NodeT for ProcessOutboundConnection {
             self.hostname = other.hostname.clone();
         }
 
+        if self.host_ip.is_none() && other.host_ip.is_some() {
+            self.host_ip = other.host_ip.clone();
+        }
+
+        if self.host_mac.is_none() && other.host_mac.is_some() {
+            self.host_mac = other.host_mac.clone();
+        }
+
         if self.created_timestamp != 0 && self.created_timestamp > other.created_timestamp {
             self.created_timestamp = other.created_timestamp;
             merged = true;
@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use graph_description::{DynamicNode, Static};
+
+// Domains and URLs have no dedicated node type, so they are described dynamically,
+// and identified globally by their value
+fn static_node(node_type: &str, key_property: &str, value: String, timestamp: u64) -> DynamicNode {
+    let mut node = DynamicNode {
+        properties: HashMap::with_capacity(1),
+        node_key: ::uuid::Uuid::new_v4().to_string(),
+        node_type: node_type.to_owned(),
+        seen_at: timestamp,
+        asset_id: None,
+        hostname: None,
+        host_ip: None,
+        host_mac: None,
+        id_strategy: vec![
+            Static {
+                primary_key_properties: vec![key_property.to_owned()],
+                primary_key_requires_asset_id: false,
+            }.into()
+        ],
+    };
+
+    node.set_property(key_property, value);
+
+    node
+}
+
+/// A DomainName node, normalized so that every source identifies the same domain
+pub fn domain_name(domain: &str, timestamp: u64) -> DynamicNode {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    static_node("DomainName", "domain", domain, timestamp)
+}
+
+/// A Url node, identified by the full url
+pub fn url(url: String, timestamp: u64) -> DynamicNode {
+    static_node("Url", "url", url, timestamp)
+}
+
+/// The domain of a host, as in a Host header or url authority, ie: example.com:8080.
+/// Hosts that are addresses have no domain.
+pub fn host_domain(host: &str) -> Option<&str> {
+    if host.starts_with('[') || host.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+
+    let hostname = host.rsplitn(2, ':').last().unwrap_or_default();
+    if hostname.is_empty() || hostname.parse::<IpAddr>().is_ok() {
+        return None;
+    }
+
+    Some(hostname)
+}
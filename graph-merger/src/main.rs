@@ -46,7 +46,7 @@ use futures::future::join_all;
 
 use graph_descriptions::node::NodeT;
 use graph_descriptions::graph_description::GeneratedSubgraphs;
-use graph_descriptions::graph_description::{Graph, Node};
+use graph_descriptions::graph_description::{Edge, Graph, Node};
 use graph_descriptions::graph_description::node::WhichNode;
 use graph_descriptions::process::ProcessState;
 use graph_descriptions::file::FileState;
@@ -105,12 +105,21 @@ macro_rules! log_time {
     };
 }
 
-fn generate_edge_insert(from: &str, to: &str, edge_name: &str) -> api::Mutation {
+fn generate_edge_insert(from: &str, to: &str, edge: &Edge) -> api::Mutation {
+    let edge_name = edge.edge_name.as_str();
+
+    let mut target = json!({
+        "uid": to
+    });
+
+    // Edge properties are stored as facets, ie: requested_url|method
+    for (name, property) in edge.properties.iter() {
+        target[format!("{}|{}", edge_name, name)] = property.clone().into_json();
+    }
+
     let mu = json!({
         "uid": from,
-        edge_name: {
-            "uid": to
-        }
+        edge_name: target
     }).to_string().into_bytes();
 
     let mut mutation = api::Mutation::new();
@@ -707,7 +716,7 @@ impl EventHandler for GraphMerger
                     }
                     (Some(from), Some(to)) => {
                         info!("Upserting edge: {} {} {}", &from, &to, &edge.edge_name);
-                        Some(generate_edge_insert(&from, &to, &edge.edge_name))
+                        Some(generate_edge_insert(&from, &to, edge))
                     }
                     (_, _) => {
                         edge_res = Some("Edge to uid failed".to_string()); None
@@ -7,7 +7,7 @@ cdk destroy -f --require-approval=never mastergraphcluster-stack && \
 cdk destroy -f --require-approval=never engagementgraphcluster-stack && \
 cdk destroy -f --require-approval=never grapl-generic-subgraph-generator-stack && \
 cdk destroy -f --require-approval=never grapl-sysmon-subgraph-generator-stack && \
-cdk destroy -f --require-approval=never grapl-node-identity-mapper-stack && \
+cdk destroy -f --require-approval=never grapl-dhcp-lease-subgraph-generator-stack && \
 cdk destroy -f --require-approval=never grapl-node-identifier-stack && \
 cdk destroy -f --require-approval=never grapl-graph-merger-stack && \
 cdk destroy -f --require-approval=never grapl-analyzer-dispatcher-stack && \
@@ -613,26 +613,40 @@ function addSubscription(scope, topic, subscription, raw?) {
 }
 
 
-class NodeIdentityMapper extends cdk.Stack {
+class DhcpLeaseSubgraphGenerator extends cdk.Stack {
 
     constructor(parent: cdk.App, id: string,
                 reads_from: s3.IBucket,
                 subscribes_to: sns.Topic,
+                writes_to: s3.IBucket,
+                history_db: HistoryDb,
                 vpc: ec2.Vpc
     ) {
         super(parent, id + '-stack');
 
+        const dhcp_lease_event_cache = new RedisCluster(this, id + 'dhcpleaseeventcache', vpc);
+        dhcp_lease_event_cache.connections.allowFromAnyIpv4(Port.allTcp());
 
         const environment = {
-            "BUCKET_PREFIX": process.env.BUCKET_PREFIX
+            "BUCKET_PREFIX": process.env.BUCKET_PREFIX,
+            "DHCP_LEASE_EVENT_CACHE_ADDR": dhcp_lease_event_cache.cluster.attrRedisEndpointAddress,
+            "DHCP_LEASE_EVENT_CACHE_PORT": dhcp_lease_event_cache.cluster.attrRedisEndpointPort,
         };
 
-        let service = new Service(this, 'node-identity-mapper', environment, vpc);
+        const service = new Service(this, 'dhcp-lease-subgraph-generator', environment, vpc);
+        service.readsFrom(reads_from);
 
+        history_db.allowReadWrite(service);
+        service.publishesToBucket(writes_to);
+        addSubscription(this, subscribes_to, new snsSubs.SqsSubscription(service.queues.queue), true);
 
-        service.readsFrom(reads_from);
+        service.event_handler.connections.allowToAnyIpv4(Port.tcp(
+            parseInt(dhcp_lease_event_cache.cluster.attrRedisEndpointPort)
+        ));
 
-        addSubscription(this, subscribes_to, new snsSubs.SqsSubscription(service.queues.queue));
+        service.event_retry_handler.connections.allowToAnyIpv4(Port.tcp(
+            parseInt(dhcp_lease_event_cache.cluster.attrRedisEndpointPort)
+        ));
 
         service.event_handler.connections.allowToAnyIpv4(ec2.Port.tcp(443), 'Allow outbound to S3');
         service.event_retry_handler.connections.allowToAnyIpv4(ec2.Port.tcp(443), 'Allow outbound to S3');
@@ -1232,6 +1246,7 @@ class HistoryDb extends cdk.Stack {
     network_connection_history: dynamodb.Table;
     ip_connection_history: dynamodb.Table;
     asset_history: dynamodb.Table;
+    asset_aliases: dynamodb.Table;
     node_id_retry_table: dynamodb.Table;
 
     dynamic_session_table: dynamodb.Table;
@@ -1333,6 +1348,15 @@ class HistoryDb extends cdk.Stack {
             billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
         });
 
+        this.asset_aliases = new dynamodb.Table(this, 'asset_aliases', {
+            tableName: "asset_aliases",
+            partitionKey: {
+                name: 'pseudo_key',
+                type: dynamodb.AttributeType.STRING
+            },
+            billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
+        });
+
 
         this.dynamic_session_table = new dynamodb.Table(this, 'dynamic_session_table', {
             tableName: "dynamic_session_table",
@@ -1378,6 +1402,7 @@ class HistoryDb extends cdk.Stack {
         this.network_connection_history.grantReadWriteData(service.event_handler.role);
         this.ip_connection_history.grantReadWriteData(service.event_handler.role);
         this.asset_history.grantReadWriteData(service.event_handler.role);
+        this.asset_aliases.grantReadWriteData(service.event_handler.role);
         this.node_id_retry_table.grantReadWriteData(service.event_handler.role);
         this.static_mapping_table.grantReadWriteData(service.event_handler.role);
         this.dynamic_session_table.grantReadWriteData(service.event_handler.role);
@@ -1389,6 +1414,7 @@ class HistoryDb extends cdk.Stack {
         this.network_connection_history.grantReadWriteData(service.event_retry_handler.role);
         this.ip_connection_history.grantReadWriteData(service.event_retry_handler.role);
         this.asset_history.grantReadWriteData(service.event_retry_handler.role);
+        this.asset_aliases.grantReadWriteData(service.event_retry_handler.role);
         this.node_id_retry_table.grantReadWriteData(service.event_retry_handler.role);
         this.static_mapping_table.grantReadWriteData(service.event_retry_handler.role);
         this.dynamic_session_table.grantReadWriteData(service.event_retry_handler.role);
@@ -1453,11 +1479,13 @@ class Grapl extends cdk.App {
         );
 
 
-        new NodeIdentityMapper(
+        new DhcpLeaseSubgraphGenerator(
             this,
-            'grapl-node-identity-mapper',
+            'grapl-dhcp-lease-subgraph-generator',
             event_emitters.identity_mappings_bucket,
             event_emitters.identity_mappings_topic,
+            event_emitters.unid_subgraphs_generated_bucket,
+            history_db,
             network.grapl_vpc
         );
 
@@ -0,0 +1,184 @@
+use chrono::prelude::*;
+use failure::Error;
+use serde_json::Value;
+
+const SERVICE_ACCOUNT_PREFIX: &str = "system:serviceaccount:";
+
+/// A single audit.k8s.io Event, as written by the log or webhook backends
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    #[serde(rename = "auditID")]
+    pub audit_id: String,
+    pub stage: String,
+    pub verb: String,
+    pub request_uri: Option<String>,
+    pub user: UserInfo,
+    pub impersonated_user: Option<UserInfo>,
+    #[serde(rename = "sourceIPs", default)]
+    pub source_ips: Vec<String>,
+    pub user_agent: Option<String>,
+    pub object_ref: Option<ObjectReference>,
+    pub response_status: Option<ResponseStatus>,
+    #[serde(default)]
+    pub request_object: Option<Value>,
+    #[serde(default)]
+    pub response_object: Option<Value>,
+    pub request_received_timestamp: String,
+}
+
+impl AuditEvent {
+    pub fn timestamp(&self) -> Result<u64, Error> {
+        let received = DateTime::parse_from_rfc3339(&self.request_received_timestamp)
+            .map_err(|e| format_err!(
+                "Invalid requestReceivedTimestamp {}: {}", self.request_received_timestamp, e
+            ))?;
+
+        let ts = received.timestamp_millis();
+        if ts < 0 {
+            bail!("Timestamp is negative")
+        }
+
+        Ok(ts as u64)
+    }
+
+    /// The same audit id is logged once per stage, so the stage is part of the identity
+    pub fn identity(&self) -> String {
+        format!("{}/{}", self.audit_id, self.stage)
+    }
+
+    /// Requests that have not been answered yet, or were rejected, did not act on the cluster
+    pub fn succeeded(&self) -> bool {
+        match self.response_status.as_ref().and_then(|status| status.code) {
+            Some(code) => code < 400,
+            None => false,
+        }
+    }
+
+    pub fn resource(&self) -> Option<&str> {
+        self.object_ref.as_ref()?.resource.as_deref()
+    }
+
+    pub fn subresource(&self) -> Option<&str> {
+        self.object_ref.as_ref()?.subresource.as_deref()
+    }
+
+    pub fn namespace(&self) -> Option<&str> {
+        self.object_ref.as_ref()?.namespace.as_deref()
+    }
+
+    /// The name of the object the request acted on. Creates do not carry the name in
+    /// the objectRef, so we fall back to the metadata of the object that was sent or returned
+    pub fn object_name(&self) -> Option<String> {
+        let object_ref_name = self.object_ref.as_ref()
+            .and_then(|object_ref| object_ref.name.clone());
+
+        object_ref_name
+            .or_else(|| metadata_name(self.response_object.as_ref()))
+            .or_else(|| metadata_name(self.request_object.as_ref()))
+    }
+
+    /// The object as the API server stored it, if it was logged, otherwise as it was sent
+    pub fn object(&self) -> Option<&Value> {
+        self.response_object.as_ref()
+            .filter(|object| object.get("metadata").is_some())
+            .or(self.request_object.as_ref())
+    }
+}
+
+fn metadata_name(object: Option<&Value>) -> Option<String> {
+    object?
+        .pointer("/metadata/name")
+        .and_then(Value::as_str)
+        .map(String::from)
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserInfo {
+    pub username: Option<String>,
+    pub uid: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+impl UserInfo {
+    /// Service accounts authenticate as system:serviceaccount:<namespace>:<name>
+    pub fn service_account(&self) -> Option<(&str, &str)> {
+        let username = self.username.as_ref()?;
+        if !username.starts_with(SERVICE_ACCOUNT_PREFIX) {
+            return None;
+        }
+
+        let mut parts = username[SERVICE_ACCOUNT_PREFIX.len()..].splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(namespace), Some(name)) if !namespace.is_empty() && !name.is_empty() => {
+                Some((namespace, name))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectReference {
+    pub resource: Option<String>,
+    pub namespace: Option<String>,
+    pub name: Option<String>,
+    pub subresource: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ResponseStatus {
+    pub code: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EventList {
+    pub items: Vec<AuditEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+enum AuditPayload {
+    List(EventList),
+    Event(Box<AuditEvent>),
+}
+
+/// Parses the events from a payload written by the log backend, one event per line,
+/// or by the webhook backend, which batches events into EventLists
+pub fn parse_events(payload: &[u8]) -> Result<Vec<AuditEvent>, Error> {
+    let mut events = Vec::new();
+
+    for audit_payload in serde_json::Deserializer::from_slice(payload).into_iter::<AuditPayload>() {
+        match audit_payload? {
+            AuditPayload::List(list) => events.extend(list.items),
+            AuditPayload::Event(event) => events.push(*event),
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_lists_and_lines_are_parsed() {
+        let payload = concat!(
+            r#"{"kind":"Event","apiVersion":"audit.k8s.io/v1","level":"Metadata","auditID":"a1","stage":"ResponseComplete","verb":"get","user":{"username":"system:serviceaccount:kube-system:coredns"},"requestReceivedTimestamp":"2020-01-02T03:04:05.678901Z"}"#,
+            "\n",
+            r#"{"kind":"EventList","apiVersion":"audit.k8s.io/v1","items":[{"auditID":"a2","stage":"RequestReceived","verb":"list","user":{"username":"alice"},"requestReceivedTimestamp":"2020-01-02T03:04:06Z"}]}"#,
+        );
+
+        let events = parse_events(payload.as_bytes()).expect("parse_events");
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].timestamp().expect("timestamp"), 1577934245678);
+        assert_eq!(events[0].user.service_account(), Some(("kube-system", "coredns")));
+        assert_eq!(events[1].identity(), "a2/RequestReceived");
+        assert_eq!(events[1].user.service_account(), None);
+    }
+}
@@ -0,0 +1,359 @@
+use failure::Error;
+use grapl_graph_descriptions::graph_description::*;
+use serde_json::Value;
+
+use crate::audit::{AuditEvent, UserInfo};
+use crate::nodes::*;
+
+pub fn generate_subgraph(cluster: &str, event: &AuditEvent) -> Result<Graph, Error> {
+    let timestamp = event.timestamp()?;
+
+    let mut graph = Graph::new(timestamp);
+
+    let mut api_call = K8sApiCallNode::new(K8sApiCallNode::static_strategy(), timestamp);
+    api_call
+        .with_audit_id(event.audit_id.clone())
+        .with_verb(event.verb.clone())
+        .with_request_time(timestamp);
+
+    if let Some(resource) = event.resource() {
+        api_call.with_resource(resource.to_owned());
+    }
+    if let Some(subresource) = event.subresource() {
+        api_call.with_subresource(subresource.to_owned());
+    }
+    if let Some(ref request_uri) = event.request_uri {
+        api_call.with_request_uri(request_uri.clone());
+    }
+    if let Some(code) = event.response_status.as_ref().and_then(|status| status.code) {
+        api_call.with_response_code(code);
+    }
+    if let Some(source_ip) = event.source_ips.first() {
+        api_call.with_source_ip(source_ip.clone());
+    }
+    if let Some(ref user_agent) = event.user_agent {
+        api_call.with_user_agent(user_agent.clone());
+    }
+
+    let user = principal(cluster, &event.user, timestamp)
+        .ok_or_else(|| format_err!("Audit event {} has no username", event.audit_id))?;
+
+    // An impersonated request is authorized, and acts, as the impersonated user
+    let actor = match event.impersonated_user.as_ref().and_then(|user| principal(cluster, user, timestamp)) {
+        Some(impersonated) => {
+            graph.add_edge("impersonated", user.node_key.clone(), impersonated.node_key.clone());
+            graph.add_node(user);
+            impersonated
+        }
+        None => user,
+    };
+
+    graph.add_edge("made_call", actor.node_key.clone(), api_call.clone_node_key());
+
+    if let Some(namespace) = event.namespace() {
+        let namespace = namespace_node(cluster, namespace, timestamp);
+        graph.add_edge("call_namespace", api_call.clone_node_key(), namespace.clone_node_key());
+        graph.add_node(namespace);
+    }
+
+    if event.succeeded() {
+        match (event.resource(), event.subresource()) {
+            (Some("pods"), None) if event.verb == "create" => {
+                if let Some(pod) = pod(cluster, event, timestamp) {
+                    graph.add_edge("created_pod", actor.node_key.clone(), pod.clone_node_key());
+                    graph.add_edge("target_pod", api_call.clone_node_key(), pod.clone_node_key());
+                    add_pod_relations(cluster, event, &pod, &mut graph, timestamp);
+                    graph.add_node(pod);
+                }
+            }
+            (Some("pods"), Some("exec")) | (Some("pods"), Some("attach")) => {
+                if let Some(pod) = pod(cluster, event, timestamp) {
+                    graph.add_edge("exec_into", actor.node_key.clone(), pod.clone_node_key());
+                    graph.add_edge("target_pod", api_call.clone_node_key(), pod.clone_node_key());
+                    graph.add_node(pod);
+                }
+            }
+            (Some("rolebindings"), None) | (Some("clusterrolebindings"), None) => {
+                match event.verb.as_str() {
+                    "create" | "update" | "patch" => add_role_binding(cluster, event, &mut graph, timestamp),
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    graph.add_node(actor);
+    graph.add_node(api_call);
+
+    Ok(graph)
+}
+
+fn principal(cluster: &str, user: &UserInfo, timestamp: u64) -> Option<DynamicNode> {
+    if let Some((namespace, name)) = user.service_account() {
+        return Some(service_account_node(cluster, namespace, name, timestamp).into_dyn_node());
+    }
+
+    let username = user.username.as_ref().filter(|username| !username.is_empty())?;
+
+    let mut user_node = K8sUserNode::new(K8sUserNode::static_strategy(), timestamp);
+    user_node
+        .with_cluster(cluster.to_owned())
+        .with_username(username.clone());
+
+    if let Some(ref uid) = user.uid {
+        user_node.with_uid(uid.clone());
+    }
+    if !user.groups.is_empty() {
+        user_node.with_groups(user.groups.join(","));
+    }
+
+    Some(user_node.into_dyn_node())
+}
+
+fn service_account_node(cluster: &str, namespace: &str, name: &str, timestamp: u64) -> K8sServiceAccountNode {
+    let mut service_account = K8sServiceAccountNode::new(K8sServiceAccountNode::static_strategy(), timestamp);
+    service_account
+        .with_cluster(cluster.to_owned())
+        .with_namespace(namespace.to_owned())
+        .with_name(name.to_owned());
+
+    service_account
+}
+
+fn namespace_node(cluster: &str, name: &str, timestamp: u64) -> K8sNamespaceNode {
+    let mut namespace = K8sNamespaceNode::new(K8sNamespaceNode::static_strategy(), timestamp);
+    namespace
+        .with_cluster(cluster.to_owned())
+        .with_name(name.to_owned());
+
+    namespace
+}
+
+fn pod(cluster: &str, event: &AuditEvent, timestamp: u64) -> Option<K8sPodNode> {
+    let namespace = event.namespace()?;
+    let name = event.object_name()?;
+
+    let mut pod = K8sPodNode::new(K8sPodNode::static_strategy(), timestamp);
+    pod
+        .with_cluster(cluster.to_owned())
+        .with_namespace(namespace.to_owned())
+        .with_name(name);
+
+    // Only a create carries the pod itself, an exec's request object is its options
+    if event.subresource().is_none() {
+        if let Some(object) = event.object() {
+            if let Some(uid) = object.pointer("/metadata/uid").and_then(Value::as_str) {
+                pod.with_uid(uid.to_owned());
+            }
+            if let Some(node_name) = object.pointer("/spec/nodeName").and_then(Value::as_str) {
+                pod.with_node_name(node_name.to_owned());
+            }
+
+            let images = container_images(object);
+            if !images.is_empty() {
+                pod.with_images(images.join(","));
+            }
+        }
+    }
+
+    Some(pod)
+}
+
+fn container_images(pod: &Value) -> Vec<&str> {
+    let containers = match pod.pointer("/spec/containers") {
+        Some(Value::Array(containers)) => containers,
+        _ => return vec![],
+    };
+
+    containers.iter()
+        .filter_map(|container| container.get("image"))
+        .filter_map(Value::as_str)
+        .collect()
+}
+
+fn add_pod_relations(
+    cluster: &str,
+    event: &AuditEvent,
+    pod: &K8sPodNode,
+    graph: &mut Graph,
+    timestamp: u64,
+) {
+    let namespace = match event.namespace() {
+        Some(namespace) => namespace,
+        None => return,
+    };
+
+    let namespace_node = namespace_node(cluster, namespace, timestamp);
+    graph.add_edge("pod_namespace", pod.clone_node_key(), namespace_node.clone_node_key());
+    graph.add_node(namespace_node);
+
+    // Pods that do not name a service account run as the namespace's default account
+    let service_account = event.object()
+        .and_then(|object| object.pointer("/spec/serviceAccountName"))
+        .and_then(Value::as_str)
+        .unwrap_or("default");
+
+    let service_account = service_account_node(cluster, namespace, service_account, timestamp);
+    graph.add_edge("pod_service_account", pod.clone_node_key(), service_account.clone_node_key());
+    graph.add_node(service_account);
+}
+
+// A binding grants its roleRef to each of its subjects
+fn add_role_binding(cluster: &str, event: &AuditEvent, graph: &mut Graph, timestamp: u64) {
+    let binding = match event.object() {
+        Some(binding) => binding,
+        None => return,
+    };
+
+    let role_kind = binding.pointer("/roleRef/kind").and_then(Value::as_str);
+    let role_name = binding.pointer("/roleRef/name").and_then(Value::as_str);
+
+    let (role_kind, role_name) = match (role_kind, role_name) {
+        (Some(role_kind), Some(role_name)) => (role_kind, role_name),
+        _ => return,
+    };
+
+    let binding_namespace = event.namespace().unwrap_or_default();
+
+    // ClusterRoles are not namespaced, even when a RoleBinding grants one within a namespace
+    let role_namespace = if role_kind == "Role" { binding_namespace } else { "" };
+
+    let mut role = K8sRoleNode::new(K8sRoleNode::static_strategy(), timestamp);
+    role
+        .with_cluster(cluster.to_owned())
+        .with_namespace(role_namespace.to_owned())
+        .with_name(role_name.to_owned())
+        .with_role_kind(role_kind.to_owned());
+
+    let subjects = match binding.get("subjects") {
+        Some(Value::Array(subjects)) => subjects.as_slice(),
+        _ => &[],
+    };
+
+    for subject in subjects {
+        let name = match subject.get("name").and_then(Value::as_str) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        // Groups are only known through the users that present them, so they have no node
+        let subject = match subject.get("kind").and_then(Value::as_str) {
+            Some("User") => {
+                let user = UserInfo { username: Some(name.to_owned()), uid: None, groups: vec![] };
+                principal(cluster, &user, timestamp)
+            }
+            Some("ServiceAccount") => {
+                let namespace = subject.get("namespace")
+                    .and_then(Value::as_str)
+                    .unwrap_or(binding_namespace);
+                Some(service_account_node(cluster, namespace, name, timestamp).into_dyn_node())
+            }
+            _ => None,
+        };
+
+        if let Some(subject) = subject {
+            graph.add_edge("bound_role", subject.node_key.clone(), role.clone_node_key());
+            graph.add_node(subject);
+        }
+    }
+
+    graph.add_node(role);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::parse_events;
+
+    fn recorded_event(audit_id: &str, stage: &str) -> AuditEvent {
+        parse_events(include_bytes!("../test_data/audit.log"))
+            .expect("recorded audit log")
+            .into_iter()
+            .find(|event| event.audit_id == audit_id && event.stage == stage)
+            .expect(audit_id)
+    }
+
+    fn node_types(graph: &Graph) -> Vec<String> {
+        let mut node_types: Vec<_> = graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .map(|node| node.node_type.clone())
+            .collect();
+        node_types.sort();
+        node_types
+    }
+
+    fn edge_names(graph: &Graph) -> Vec<String> {
+        let mut edge_names: Vec<_> = graph.edges.values()
+            .flat_map(|edges| edges.edges.iter())
+            .map(|edge| edge.edge_name.clone())
+            .collect();
+        edge_names.sort();
+        edge_names
+    }
+
+    #[test]
+    fn every_recorded_event_generates() {
+        let events = parse_events(include_bytes!("../test_data/audit.log")).expect("recorded audit log");
+
+        for event in events {
+            generate_subgraph("test", &event).expect(&event.audit_id);
+        }
+    }
+
+    #[test]
+    fn pod_creation() {
+        let event = recorded_event("0b6a2c55-1c1e-4a4d-9c7b-3f8a6d0e2b11", "ResponseComplete");
+        let graph = generate_subgraph("test", &event).expect("pod creation");
+
+        assert_eq!(
+            edge_names(&graph),
+            vec!["call_namespace", "created_pod", "made_call", "pod_namespace", "pod_service_account", "target_pod"]
+        );
+
+        let pod = graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .find(|node| node.node_type == "K8sPod")
+            .expect("pod");
+        assert_eq!(
+            pod.properties.get("name").map(|name| name.to_string()),
+            Some("debug-7c9f".to_owned())
+        );
+        assert_eq!(
+            pod.properties.get("images").map(|images| images.to_string()),
+            Some("alpine:3.11".to_owned())
+        );
+    }
+
+    #[test]
+    fn exec_into_pod() {
+        let event = recorded_event("5e2d9a0f-8b3c-4f6e-a1d7-c4b2e9f0a3d8", "ResponseStarted");
+        let graph = generate_subgraph("test", &event).expect("exec");
+
+        assert_eq!(node_types(&graph), vec!["K8sApiCall", "K8sNamespace", "K8sPod", "K8sUser"]);
+        assert!(edge_names(&graph).contains(&"exec_into".to_owned()));
+    }
+
+    #[test]
+    fn role_binding_binds_each_subject() {
+        let event = recorded_event("9f1c3b7e-2d4a-4e8b-b6c5-7a0d1e3f5b29", "ResponseComplete");
+        let graph = generate_subgraph("test", &event).expect("role binding");
+
+        assert_eq!(
+            node_types(&graph),
+            vec!["K8sApiCall", "K8sNamespace", "K8sRole", "K8sServiceAccount", "K8sUser", "K8sUser"]
+        );
+        assert_eq!(
+            edge_names(&graph).iter().filter(|edge| *edge == "bound_role").count(),
+            2
+        );
+    }
+
+    #[test]
+    fn forbidden_requests_do_not_act() {
+        let event = recorded_event("c3a8e1f2-6b4d-4c9a-8e7f-1d2b3a4c5e6f", "ResponseComplete");
+        let graph = generate_subgraph("test", &event).expect("forbidden");
+
+        assert_eq!(edge_names(&graph), vec!["call_namespace", "made_call"]);
+    }
+}
@@ -0,0 +1,86 @@
+extern crate aws_lambda_events;
+extern crate chrono;
+extern crate derive_dynamic_node;
+#[macro_use]
+extern crate failure;
+extern crate grapl_graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+extern crate uuid;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use grapl_graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::audit::{parse_events, AuditEvent};
+use crate::generator::generate_subgraph;
+
+mod audit;
+mod generator;
+mod nodes;
+
+#[derive(Clone)]
+struct KubernetesAuditSubgraphGenerator {
+    /// Audit events do not name the cluster they came from, so it scopes every node we create
+    cluster: String,
+}
+
+impl KubernetesAuditSubgraphGenerator {
+    pub fn new(cluster: String) -> Self {
+        Self { cluster }
+    }
+}
+
+#[async_trait]
+impl SubgraphGenerator for KubernetesAuditSubgraphGenerator {
+    type Event = AuditEvent;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<AuditEvent>, failure::Error> {
+        let events = parse_events(payload)?
+            .into_iter()
+            // Requests that have not been answered yet are logged again once they are
+            .filter(|event| event.stage != "RequestReceived")
+            .collect();
+
+        Ok(events)
+    }
+
+    fn identity(&self, event: &AuditEvent) -> String {
+        event.identity()
+    }
+
+    async fn generate_subgraph(&mut self, event: AuditEvent) -> Result<Option<Graph>, failure::Error> {
+        generate_subgraph(&self.cluster, &event).map(Some)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    let cluster = std::env::var("KUBERNETES_CLUSTER_NAME").expect("KUBERNETES_CLUSTER_NAME");
+
+    subgraph_generator_runtime::run(
+        event,
+        ctx,
+        "KUBERNETES_AUDIT",
+        KubernetesAuditSubgraphGenerator::new(cluster),
+        ZstdDecoder,
+    )
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
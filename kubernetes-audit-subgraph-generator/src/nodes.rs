@@ -0,0 +1,110 @@
+// These structs only describe the nodes, the derived *Node types are what we build
+#![allow(dead_code)]
+
+use derive_dynamic_node::{DynamicNode, GraplStaticId};
+use grapl_graph_descriptions::graph_description::*;
+
+/// A user authenticated by the cluster, ie: through a client certificate or OIDC
+#[derive(DynamicNode, GraplStaticId)]
+pub struct K8sUser {
+    #[grapl(static_id)]
+    cluster: String,
+    #[grapl(static_id)]
+    username: String,
+    uid: String,
+    groups: String,
+}
+
+impl IK8sUserNode for K8sUserNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+#[derive(DynamicNode, GraplStaticId)]
+pub struct K8sServiceAccount {
+    #[grapl(static_id)]
+    cluster: String,
+    #[grapl(static_id)]
+    namespace: String,
+    #[grapl(static_id)]
+    name: String,
+}
+
+impl IK8sServiceAccountNode for K8sServiceAccountNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+#[derive(DynamicNode, GraplStaticId)]
+pub struct K8sNamespace {
+    #[grapl(static_id)]
+    cluster: String,
+    #[grapl(static_id)]
+    name: String,
+}
+
+impl IK8sNamespaceNode for K8sNamespaceNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+#[derive(DynamicNode, GraplStaticId)]
+pub struct K8sPod {
+    #[grapl(static_id)]
+    cluster: String,
+    #[grapl(static_id)]
+    namespace: String,
+    #[grapl(static_id)]
+    name: String,
+    uid: String,
+    node_name: String,
+    images: String,
+}
+
+impl IK8sPodNode for K8sPodNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+/// A Role or ClusterRole. ClusterRoles have an empty namespace
+#[derive(DynamicNode, GraplStaticId)]
+pub struct K8sRole {
+    #[grapl(static_id)]
+    cluster: String,
+    #[grapl(static_id)]
+    namespace: String,
+    #[grapl(static_id)]
+    name: String,
+    role_kind: String,
+}
+
+impl IK8sRoleNode for K8sRoleNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
+
+/// A single request to the API server, identified by its audit id
+#[derive(DynamicNode, GraplStaticId)]
+pub struct K8sApiCall {
+    #[grapl(static_id)]
+    audit_id: String,
+    verb: String,
+    resource: String,
+    subresource: String,
+    request_uri: String,
+    response_code: u64,
+    source_ip: String,
+    user_agent: String,
+    request_time: u64,
+}
+
+impl IK8sApiCallNode for K8sApiCallNode {
+    fn get_mut_dynamic_node(&mut self) -> &mut DynamicNode {
+        &mut self.dynamic_node
+    }
+}
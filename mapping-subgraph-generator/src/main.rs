@@ -0,0 +1,102 @@
+extern crate aws_lambda_events;
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::mapping::Mapper;
+
+mod mapping;
+
+#[derive(Clone)]
+struct MappingSubgraphGenerator {
+    mapper: Arc<Mapper>,
+}
+
+impl MappingSubgraphGenerator {
+    pub fn new(mapper: Arc<Mapper>) -> Self {
+        Self { mapper }
+    }
+}
+
+#[async_trait]
+impl SubgraphGenerator for MappingSubgraphGenerator {
+    type Event = String;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<String>, Error> {
+        // Each line of the payload is one json record
+        let events = payload.split(|i| *i == b'\n')
+            .map(String::from_utf8_lossy)
+            .filter(|event| !event.trim().is_empty())
+            .map(|event| event.into_owned())
+            .collect();
+
+        Ok(events)
+    }
+
+    fn identity(&self, event: &String) -> String {
+        event.clone()
+    }
+
+    async fn generate_subgraph(&mut self, event: String) -> Result<Option<Graph>, Error> {
+        let record: serde_json::Value = serde_json::from_str(&event)?;
+
+        let graph = self.mapper.map_record(&record)?;
+
+        if graph.is_none() {
+            warn!("No mapping applies to event: {}", event);
+        }
+
+        Ok(graph)
+    }
+}
+
+fn load_mapper() -> Result<Mapper, Error> {
+    let config_path = std::env::var("MAPPING_CONFIG_PATH").expect("MAPPING_CONFIG_PATH");
+    info!("Loading mappings from: {}", config_path);
+
+    let config = std::fs::read_to_string(&config_path)?;
+    let mapper = Mapper::from_yaml(&config)?;
+
+    Ok(mapper)
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    let mapper = match load_mapper() {
+        Ok(mapper) => Arc::new(mapper),
+        Err(e) => {
+            error!("Failed to load mappings: {}", e);
+            return Err(lambda::error::HandlerError::from("Failed to load mappings"));
+        }
+    };
+
+    subgraph_generator_runtime::run(event, ctx, "MAPPING", MappingSubgraphGenerator::new(mapper), ZstdDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
@@ -0,0 +1,787 @@
+use std::collections::{BTreeMap, HashMap};
+
+use failure::{bail, format_err, Error};
+use graph_descriptions::file::FileState;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::ip_connection::IpConnectionState;
+use graph_descriptions::network_connection::NetworkConnectionState;
+use graph_descriptions::node::NodeT;
+use graph_descriptions::process::ProcessState;
+use graph_descriptions::process_inbound_connection::ProcessInboundConnectionState;
+use graph_descriptions::process_outbound_connection::ProcessOutboundConnectionState;
+use regex::Regex;
+use serde_json::Value;
+
+/// A set of declarative log-to-graph mappings, loaded from YAML.
+///
+/// ```yaml
+/// mappings:
+///   - name: file_write
+///     when:
+///       - field: eventname
+///         equals: FILE_WRITE
+///     timestamp: timestamp
+///     nodes:
+///       writer:
+///         type: Process
+///         state: Existing
+///         fields:
+///           hostname: hostname
+///           process_id: writer_pid
+///           last_seen_timestamp: timestamp
+///       file:
+///         type: File
+///         state: Existing
+///         fields:
+///           hostname: hostname
+///           file_path: path
+///           last_seen_timestamp: timestamp
+///     edges:
+///       - from: writer
+///         to: file
+///         name: wrote_files
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+pub struct MappingConfig {
+    pub mappings: Vec<Mapping>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Mapping {
+    pub name: String,
+    /// Every condition must hold for a record to be mapped
+    #[serde(default)]
+    pub when: Vec<Condition>,
+    /// Path to the field holding the record's timestamp
+    pub timestamp: Option<String>,
+    pub nodes: BTreeMap<String, NodeMapping>,
+    #[serde(default)]
+    pub edges: Vec<EdgeMapping>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Condition {
+    pub field: String,
+    pub equals: Option<Value>,
+    pub exists: Option<bool>,
+    pub matches: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NodeMapping {
+    #[serde(rename = "type")]
+    pub node_type: NodeType,
+    pub state: Option<String>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldSource>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FieldSource {
+    /// A dotted path into the record, such as `event_data.image`
+    Path(String),
+    /// A constant, such as a protocol the log source does not report
+    Value { value: Value },
+    Field {
+        field: String,
+        #[serde(default)]
+        optional: bool,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EdgeMapping {
+    pub from: String,
+    pub to: String,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum NodeType {
+    Asset,
+    Process,
+    File,
+    IpAddress,
+    IpPort,
+    NetworkConnection,
+    IpConnection,
+    ProcessOutboundConnection,
+    ProcessInboundConnection,
+}
+
+/// Declares the graph-descriptions builder behind each `NodeType`, along with the
+/// fields a mapping may set on it and how record values are converted for each
+macro_rules! node_builders {
+    ($($node_type:ident => $builder:ident { $($field:ident: $kind:ident),* $(,)? }),* $(,)?) => {
+        enum NodeBuilder {
+            $($node_type($builder)),*
+        }
+
+        impl NodeType {
+            fn builder(self) -> NodeBuilder {
+                match self {
+                    $(NodeType::$node_type => NodeBuilder::$node_type($builder::default())),*
+                }
+            }
+
+            /// A placeholder for `field`, used to check a mapping against the builder
+            /// at load time. `None` if the node type has no such field.
+            fn sample(self, field: &str) -> Option<Value> {
+                match self {
+                    $(NodeType::$node_type => match field {
+                        $(stringify!($field) => Some($kind::sample()),)*
+                        _ => None,
+                    }),*
+                }
+            }
+        }
+
+        impl NodeBuilder {
+            fn set(&mut self, field: &str, value: &Value) -> Result<(), Error> {
+                match self {
+                    $(NodeBuilder::$node_type(builder) => match field {
+                        $(stringify!($field) => {
+                            builder.$field($kind::convert(value)?);
+                        })*
+                        field => bail!("{} has no mappable field {}", stringify!($node_type), field),
+                    }),*
+                }
+
+                Ok(())
+            }
+        }
+    };
+}
+
+node_builders! {
+    Asset => AssetBuilder {
+        asset_id: string,
+        hostname: string,
+        mac_address: string,
+        first_seen_timestamp: uint64,
+        last_seen_timestamp: uint64,
+        fqdn: string,
+        os: string,
+        serial_number: string,
+        owner: string,
+        instance_id: string,
+        ip_addresses: strings,
+        mac_addresses: strings,
+        original_hostname: string,
+    },
+    Process => ProcessBuilder {
+        asset_id: string,
+        hostname: string,
+        host_ip: string,
+        host_mac: string,
+        state: uint32,
+        process_id: uint64,
+        process_guid: string,
+        created_timestamp: uint64,
+        terminated_timestamp: uint64,
+        last_seen_timestamp: uint64,
+        process_name: string,
+        process_command_line: string,
+        operating_system: string,
+    },
+    File => FileBuilder {
+        asset_id: string,
+        hostname: string,
+        host_ip: string,
+        host_mac: string,
+        state: uint32,
+        created_timestamp: uint64,
+        deleted_timestamp: uint64,
+        last_seen_timestamp: uint64,
+        file_name: string,
+        file_path: string,
+        file_extension: string,
+        file_mime_type: string,
+        file_size: uint64,
+        file_version: string,
+        file_description: string,
+        file_product: string,
+        file_company: string,
+        file_directory: string,
+        file_inode: uint64,
+        file_hard_links: uint64,
+        md5_hash: string,
+        sha1_hash: string,
+        sha256_hash: string,
+    },
+    IpAddress => IpAddressBuilder {
+        ip_address: string,
+        first_seen_timestamp: uint64,
+        last_seen_timestamp: uint64,
+    },
+    IpPort => IpPortBuilder {
+        ip_address: string,
+        port: uint32,
+        protocol: string,
+    },
+    NetworkConnection => NetworkConnectionBuilder {
+        src_ip_address: string,
+        dst_ip_address: string,
+        protocol: string,
+        src_port: uint32,
+        dst_port: uint32,
+        created_timestamp: uint64,
+        terminated_timestamp: uint64,
+        last_seen_timestamp: uint64,
+        state: uint32,
+        connection_uid: string,
+        bytes: uint64,
+        packets: uint64,
+        flow_action: string,
+    },
+    IpConnection => IpConnectionBuilder {
+        src_ip_address: string,
+        dst_ip_address: string,
+        protocol: string,
+        created_timestamp: uint64,
+        terminated_timestamp: uint64,
+        last_seen_timestamp: uint64,
+        state: uint32,
+        bytes: uint64,
+        packets: uint64,
+        flow_action: string,
+    },
+    ProcessOutboundConnection => ProcessOutboundConnectionBuilder {
+        asset_id: string,
+        hostname: string,
+        host_ip: string,
+        host_mac: string,
+        state: uint32,
+        created_timestamp: uint64,
+        terminated_timestamp: uint64,
+        last_seen_timestamp: uint64,
+        port: uint32,
+        ip_address: string,
+        protocol: string,
+    },
+    ProcessInboundConnection => ProcessInboundConnectionBuilder {
+        asset_id: string,
+        hostname: string,
+        host_ip: string,
+        host_mac: string,
+        state: uint32,
+        created_timestamp: uint64,
+        terminated_timestamp: uint64,
+        last_seen_timestamp: uint64,
+        port: uint32,
+        ip_address: string,
+        protocol: string,
+    },
+}
+
+impl NodeType {
+    fn state(self, state: &str) -> Result<u32, Error> {
+        let state = match (self, state) {
+            (NodeType::Process, "Created") => ProcessState::Created.into(),
+            (NodeType::Process, "Terminated") => ProcessState::Terminated.into(),
+            (NodeType::Process, "Existing") => ProcessState::Existing.into(),
+            (NodeType::File, "Created") => FileState::Created.into(),
+            (NodeType::File, "Deleted") => FileState::Deleted.into(),
+            (NodeType::File, "Existing") => FileState::Existing.into(),
+            (NodeType::NetworkConnection, "Created") => NetworkConnectionState::Created.into(),
+            (NodeType::NetworkConnection, "Terminated") => NetworkConnectionState::Terminated.into(),
+            (NodeType::NetworkConnection, "Existing") => NetworkConnectionState::Existing.into(),
+            (NodeType::IpConnection, "Created") => IpConnectionState::Created.into(),
+            (NodeType::IpConnection, "Terminated") => IpConnectionState::Terminated.into(),
+            (NodeType::IpConnection, "Existing") => IpConnectionState::Existing.into(),
+            (NodeType::ProcessOutboundConnection, "Connected") => ProcessOutboundConnectionState::Connected.into(),
+            (NodeType::ProcessOutboundConnection, "Closed") => ProcessOutboundConnectionState::Closed.into(),
+            (NodeType::ProcessOutboundConnection, "Existing") => ProcessOutboundConnectionState::Existing.into(),
+            (NodeType::ProcessInboundConnection, "Bound") => ProcessInboundConnectionState::Bound.into(),
+            (NodeType::ProcessInboundConnection, "Closed") => ProcessInboundConnectionState::Closed.into(),
+            (NodeType::ProcessInboundConnection, "Existing") => ProcessInboundConnectionState::Existing.into(),
+            (node_type, state) => bail!("Invalid state for {:?}: {}", node_type, state),
+        };
+
+        Ok(state)
+    }
+}
+
+impl NodeBuilder {
+    /// Builds the node through its graph-descriptions builder, so that required
+    /// fields are enforced, and checks that the node can be identified
+    fn build(self) -> Result<Node, Error> {
+        let node = match self {
+            NodeBuilder::Asset(builder) => {
+                let asset = builder.build().map_err(|e| format_err!("{}", e))?;
+                if asset.asset_id.is_none() && asset.hostname.is_none() {
+                    bail!("Asset requires an asset_id or hostname");
+                }
+                asset.into()
+            }
+            NodeBuilder::Process(builder) => {
+                let process = builder.build().map_err(|e| format_err!("{}", e))?;
+                require_host("Process", &[&process.asset_id, &process.hostname, &process.host_ip, &process.host_mac])?;
+                process.into()
+            }
+            NodeBuilder::File(builder) => {
+                let file = builder.build().map_err(|e| format_err!("{}", e))?;
+                require_host("File", &[&file.asset_id, &file.hostname, &file.host_ip, &file.host_mac])?;
+                file.into()
+            }
+            NodeBuilder::IpAddress(builder) => builder.build().map_err(|e| format_err!("{}", e))?.into(),
+            NodeBuilder::IpPort(builder) => builder.build().map_err(|e| format_err!("{}", e))?.into(),
+            NodeBuilder::NetworkConnection(builder) => builder.build().map_err(|e| format_err!("{}", e))?.into(),
+            NodeBuilder::IpConnection(builder) => builder.build().map_err(|e| format_err!("{}", e))?.into(),
+            NodeBuilder::ProcessOutboundConnection(builder) => {
+                let outbound = builder.build().map_err(|e| format_err!("{}", e))?;
+                require_host("ProcessOutboundConnection", &[&outbound.asset_id, &outbound.hostname, &outbound.host_ip, &outbound.host_mac])?;
+                outbound.into()
+            }
+            NodeBuilder::ProcessInboundConnection(builder) => {
+                let inbound = builder.build().map_err(|e| format_err!("{}", e))?;
+                require_host("ProcessInboundConnection", &[&inbound.asset_id, &inbound.hostname, &inbound.host_ip, &inbound.host_mac])?;
+                inbound.into()
+            }
+        };
+
+        Ok(node)
+    }
+}
+
+/// Nodes that are identified per asset need some way of attributing them to one
+fn require_host(node_type: &str, host_ids: &[&Option<String>]) -> Result<(), Error> {
+    if host_ids.iter().all(|host_id| host_id.is_none()) {
+        bail!("{} requires an asset_id, hostname, host_ip or host_mac", node_type);
+    }
+
+    Ok(())
+}
+
+mod string {
+    use failure::{bail, Error};
+    use serde_json::Value;
+
+    pub fn sample() -> Value {
+        Value::String(String::new())
+    }
+
+    /// Numbers are accepted for string fields, as log sources are loose with types
+    pub fn convert(value: &Value) -> Result<String, Error> {
+        match value {
+            Value::String(s) => Ok(s.clone()),
+            Value::Number(n) => Ok(n.to_string()),
+            value => bail!("expected a string, got {}", value),
+        }
+    }
+}
+
+mod strings {
+    use failure::{bail, Error};
+    use serde_json::Value;
+
+    pub fn sample() -> Value {
+        Value::Array(vec![])
+    }
+
+    /// A single string is accepted as a list of one
+    pub fn convert(value: &Value) -> Result<Vec<String>, Error> {
+        match value {
+            Value::Array(values) => values.iter().map(super::string::convert).collect(),
+            Value::String(s) => Ok(vec![s.clone()]),
+            value => bail!("expected a list of strings, got {}", value),
+        }
+    }
+}
+
+mod uint64 {
+    use failure::{bail, Error};
+    use serde_json::Value;
+
+    pub fn sample() -> Value {
+        Value::from(0)
+    }
+
+    /// Numeric strings are accepted for numeric fields
+    pub fn convert(value: &Value) -> Result<u64, Error> {
+        match super::as_u64(value) {
+            Some(n) => Ok(n),
+            None => bail!("expected an unsigned integer, got {}", value),
+        }
+    }
+}
+
+mod uint32 {
+    use failure::{bail, Error};
+    use serde_json::Value;
+
+    pub fn sample() -> Value {
+        Value::from(0)
+    }
+
+    pub fn convert(value: &Value) -> Result<u32, Error> {
+        match super::as_u64(value) {
+            Some(n) if n <= u64::from(u32::MAX) => Ok(n as u32),
+            _ => bail!("expected a 32 bit unsigned integer, got {}", value),
+        }
+    }
+}
+
+/// Executes a `MappingConfig` against individual json records
+#[derive(Clone, Debug)]
+pub struct Mapper {
+    mappings: Vec<Mapping>,
+    patterns: HashMap<String, Regex>,
+}
+
+impl Mapper {
+    pub fn from_yaml(config: &str) -> Result<Self, Error> {
+        let config: MappingConfig = serde_yaml::from_str(config)?;
+        Self::new(config)
+    }
+
+    /// Validates every mapping up front, so that a bad config fails at startup
+    /// rather than on the first matching record
+    pub fn new(config: MappingConfig) -> Result<Self, Error> {
+        let mut patterns = HashMap::new();
+
+        for mapping in config.mappings.iter() {
+            for condition in mapping.when.iter() {
+                if let Some(ref pattern) = condition.matches {
+                    let regex = Regex::new(pattern)
+                        .map_err(|e| format_err!("mapping {}: invalid pattern {}: {}", mapping.name, pattern, e))?;
+                    patterns.insert(pattern.clone(), regex);
+                }
+            }
+
+            for (node_name, node) in mapping.nodes.iter() {
+                check_node(node)
+                    .map_err(|e| format_err!("mapping {} node {}: {}", mapping.name, node_name, e))?;
+            }
+
+            for edge in mapping.edges.iter() {
+                for node_name in &[&edge.from, &edge.to] {
+                    if !mapping.nodes.contains_key(*node_name) {
+                        bail!("mapping {} edge {}: unknown node {}", mapping.name, edge.name, node_name);
+                    }
+                }
+            }
+        }
+
+        Ok(Self { mappings: config.mappings, patterns })
+    }
+
+    /// Maps a record with the first mapping whose conditions it satisfies.
+    /// Records that no mapping applies to produce `None`.
+    pub fn map_record(&self, record: &Value) -> Result<Option<Graph>, Error> {
+        let mapping = match self.mappings.iter().find(|mapping| self.applies(mapping, record)) {
+            Some(mapping) => mapping,
+            None => return Ok(None),
+        };
+
+        let timestamp = match mapping.timestamp {
+            Some(ref path) => match get_path(record, path).and_then(as_u64) {
+                Some(timestamp) => timestamp,
+                None => bail!("mapping {}: missing or invalid timestamp {}", mapping.name, path),
+            },
+            None => 0,
+        };
+
+        let mut graph = Graph::new(timestamp);
+        let mut node_keys = HashMap::with_capacity(mapping.nodes.len());
+
+        for (node_name, node_mapping) in mapping.nodes.iter() {
+            let node = map_node(node_mapping, record)
+                .map_err(|e| format_err!("mapping {} node {}: {}", mapping.name, node_name, e))?;
+
+            node_keys.insert(node_name.as_str(), node.clone_node_key());
+            graph.add_node(node);
+        }
+
+        for edge in mapping.edges.iter() {
+            graph.add_edge(
+                edge.name.clone(),
+                node_keys[edge.from.as_str()].clone(),
+                node_keys[edge.to.as_str()].clone(),
+            );
+        }
+
+        Ok(Some(graph))
+    }
+
+    fn applies(&self, mapping: &Mapping, record: &Value) -> bool {
+        mapping.when.iter().all(|condition| {
+            let value = get_path(record, &condition.field);
+
+            if let Some(exists) = condition.exists {
+                if exists != value.is_some() {
+                    return false;
+                }
+            }
+
+            if let Some(ref equals) = condition.equals {
+                if value != Some(equals) {
+                    return false;
+                }
+            }
+
+            if let Some(ref pattern) = condition.matches {
+                let matched = value
+                    .and_then(Value::as_str)
+                    .map(|value| self.patterns[pattern].is_match(value))
+                    .unwrap_or(false);
+
+                if !matched {
+                    return false;
+                }
+            }
+
+            true
+        })
+    }
+}
+
+/// Builds the node from placeholder values for each mapped field, so that unknown
+/// fields and missing required fields are rejected when the config is loaded
+fn check_node(node_mapping: &NodeMapping) -> Result<(), Error> {
+    let mut builder = node_mapping.node_type.builder();
+
+    for field in node_mapping.fields.keys() {
+        let sample = match node_mapping.node_type.sample(field) {
+            Some(sample) if field != "state" => sample,
+            _ => bail!("{:?} has no mappable field {}", node_mapping.node_type, field),
+        };
+
+        builder.set(field, &sample)?;
+    }
+
+    if let Some(ref state) = node_mapping.state {
+        builder.set("state", &node_mapping.node_type.state(state)?.into())?;
+    }
+
+    builder.build()?;
+
+    Ok(())
+}
+
+fn map_node(node_mapping: &NodeMapping, record: &Value) -> Result<Node, Error> {
+    let mut builder = node_mapping.node_type.builder();
+
+    for (field, source) in node_mapping.fields.iter() {
+        let value = match source {
+            FieldSource::Path(path) => match get_path(record, path) {
+                Some(value) => value,
+                None => bail!("missing field {}", path),
+            },
+            FieldSource::Value { value } => value,
+            FieldSource::Field { field: path, optional } => match (get_path(record, path), *optional) {
+                (Some(value), _) => value,
+                (None, true) => continue,
+                (None, false) => bail!("missing field {}", path),
+            },
+        };
+
+        builder.set(field, value)
+            .map_err(|e| format_err!("field {}: {}", field, e))?;
+    }
+
+    if let Some(ref state) = node_mapping.state {
+        builder.set("state", &node_mapping.node_type.state(state)?.into())?;
+    }
+
+    builder.build()
+}
+
+fn get_path<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(record, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
+
+fn as_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generic_mapper() -> Mapper {
+        Mapper::from_yaml(include_str!("../mappings/generic.yaml")).expect("generic.yaml")
+    }
+
+    #[test]
+    fn maps_outbound_connection() {
+        let record = serde_json::json!({
+            "eventname": "OUTBOUND_TCP",
+            "pid": 100,
+            "protocol": "tcp",
+            "src_port": "49152",
+            "dst_port": 443,
+            "src_hostname": "host-a",
+            "src_ip_addr": "10.0.0.1",
+            "dst_ip_addr": "8.8.8.8",
+            "timestamp": 1000,
+        });
+
+        let graph = generic_mapper()
+            .map_record(&record)
+            .expect("map_record")
+            .expect("no mapping applied");
+
+        assert_eq!(graph.timestamp, 1000);
+        assert_eq!(graph.nodes.len(), 8);
+
+        let edges: usize = graph.edges.values().map(|e| e.edges.len()).sum();
+        assert_eq!(edges, 7);
+    }
+
+    #[test]
+    fn unmatched_record_is_skipped() {
+        let record = serde_json::json!({
+            "eventname": "NOT_MAPPED",
+            "timestamp": 1000,
+        });
+
+        assert!(generic_mapper().map_record(&record).expect("map_record").is_none());
+    }
+
+    #[test]
+    fn missing_field_is_an_error() {
+        let record = serde_json::json!({
+            "eventname": "FILE_WRITE",
+            "hostname": "host-a",
+            "path": "/tmp/file",
+            "timestamp": 1000,
+        });
+
+        assert!(generic_mapper().map_record(&record).is_err());
+    }
+
+    #[test]
+    fn unknown_field_is_rejected_at_load() {
+        let config = r#"
+mappings:
+  - name: bad
+    nodes:
+      process:
+        type: Process
+        fields:
+          not_a_field: pid
+"#;
+
+        assert!(Mapper::from_yaml(config).is_err());
+    }
+
+    #[test]
+    fn edge_to_unknown_node_is_rejected_at_load() {
+        let config = r#"
+mappings:
+  - name: bad
+    nodes:
+      process:
+        type: Process
+        fields:
+          process_id: pid
+    edges:
+      - from: process
+        to: file
+        name: wrote_files
+"#;
+
+        assert!(Mapper::from_yaml(config).is_err());
+    }
+
+    #[test]
+    fn missing_required_field_is_rejected_at_load() {
+        // An IpPort is keyed on its ip_address, port and protocol
+        let config = r#"
+mappings:
+  - name: bad
+    nodes:
+      port:
+        type: IpPort
+        fields:
+          ip_address: dst_ip_addr
+          protocol: protocol
+"#;
+
+        let e = Mapper::from_yaml(config).expect_err("missing port was accepted");
+        assert!(e.to_string().contains("port"), "{}", e);
+    }
+
+    #[test]
+    fn missing_state_is_rejected_at_load() {
+        let config = r#"
+mappings:
+  - name: bad
+    nodes:
+      process:
+        type: Process
+        fields:
+          hostname: hostname
+          process_id: pid
+"#;
+
+        assert!(Mapper::from_yaml(config).is_err());
+    }
+
+    #[test]
+    fn unattributable_node_is_rejected_at_load() {
+        let config = r#"
+mappings:
+  - name: bad
+    nodes:
+      process:
+        type: Process
+        state: Existing
+        fields:
+          process_id: pid
+"#;
+
+        let e = Mapper::from_yaml(config).expect_err("process without a host was accepted");
+        assert!(e.to_string().contains("requires an asset_id, hostname, host_ip or host_mac"), "{}", e);
+    }
+
+    #[test]
+    fn missing_optional_host_is_an_error() {
+        let config = r#"
+mappings:
+  - name: optional_host
+    nodes:
+      process:
+        type: Process
+        state: Existing
+        fields:
+          hostname:
+            field: hostname
+            optional: true
+          process_id: pid
+"#;
+
+        let mapper = Mapper::from_yaml(config).expect("from_yaml");
+
+        let record = serde_json::json!({"pid": 100, "hostname": "host-a"});
+        assert!(mapper.map_record(&record).expect("map_record").is_some());
+
+        let record = serde_json::json!({"pid": 100});
+        assert!(mapper.map_record(&record).is_err());
+    }
+
+    #[test]
+    fn invalid_field_type_is_an_error() {
+        let record = serde_json::json!({
+            "eventname": "OUTBOUND_TCP",
+            "pid": 100,
+            "protocol": "tcp",
+            "src_port": "not a port",
+            "dst_port": 443,
+            "src_hostname": "host-a",
+            "src_ip_addr": "10.0.0.1",
+            "dst_ip_addr": "8.8.8.8",
+            "timestamp": 1000,
+        });
+
+        assert!(generic_mapper().map_record(&record).is_err());
+    }
+}
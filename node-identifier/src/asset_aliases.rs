@@ -0,0 +1,371 @@
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::graph_description::host::*;
+use graph_descriptions::graph_description::node::WhichNode;
+
+use crate::assetdb::AssetIdDb;
+use crate::storage::{AliasCondition, AssetAliasStore, AssetMappingStore};
+
+// Merges are made by many node-identifiers at once, so a merge that loses a race to
+// another merge is retried, and aliases are followed, at most this many times
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// An asset id that is an alias of another asset id, as both are the same machine
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AssetAlias {
+    pub pseudo_key: String,
+    pub canonical_asset_id: String,
+}
+
+/// Records that asset ids are the same machine, ie: the computer name Sysmon attributes
+/// to, the hostname of the generic format, and a cloud instance id.
+///
+/// Each alias points at the canonical asset id of its machine, which is not an alias
+/// itself, and nodes are attributed to the canonical asset id.
+#[derive(Debug, Clone)]
+pub struct AssetAliasDb<S>
+    where S: AssetAliasStore
+{
+    store: S,
+    table_name: String,
+}
+
+impl<S> AssetAliasDb<S>
+    where S: AssetAliasStore
+{
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            table_name: "asset_aliases".to_owned(),
+        }
+    }
+
+    pub fn with_table_name(mut self, table_name: impl Into<String>) -> Self {
+        self.table_name = table_name.into();
+        self
+    }
+
+    async fn find_alias(&self, asset_id: &str) -> Result<Option<String>, Error> {
+        self.store.get_alias(&self.table_name, asset_id).await
+    }
+
+    /// The canonical asset id of the machine `asset_id` is, or `asset_id` if it has no aliases
+    pub async fn canonical_asset_id(&self, asset_id: &str) -> Result<String, Error> {
+        let mut canonical = asset_id.to_owned();
+        let mut first_alias = None;
+
+        for depth in 0..=MAX_ALIAS_DEPTH {
+            match self.find_alias(&canonical).await? {
+                Some(alias) => {
+                    first_alias.get_or_insert_with(|| alias.clone());
+                    canonical = alias;
+                }
+                None => {
+                    // Merging two machines points one canonical asset id at the other,
+                    // so aliases of the first are two hops from their canonical asset id.
+                    // The alias is only shortened if no merge has moved it since it was read.
+                    if let Some(first_alias) = first_alias.filter(|_| depth > 1) {
+                        self.put_alias(asset_id, &canonical, AliasCondition::AliasOf(&first_alias)).await?;
+                    }
+
+                    return Ok(canonical);
+                }
+            }
+        }
+
+        bail!("Asset {} has more than {} aliases to follow", asset_id, MAX_ALIAS_DEPTH)
+    }
+
+    /// Records that the asset ids are all one machine, and returns its canonical asset id.
+    ///
+    /// The canonical asset id is the least of the canonical asset ids being merged, so that
+    /// concurrent merges of the same machines agree on it.
+    pub async fn merge_assets(&self, asset_ids: &[String]) -> Result<Option<String>, Error> {
+        for _ in 0..MAX_ALIAS_DEPTH {
+            let mut canonical_ids = Vec::with_capacity(asset_ids.len());
+
+            for asset_id in asset_ids {
+                canonical_ids.push(self.canonical_asset_id(asset_id).await?);
+            }
+
+            canonical_ids.sort();
+            canonical_ids.dedup();
+
+            let (canonical, aliases) = match canonical_ids.split_first() {
+                Some((canonical, aliases)) => (canonical, aliases),
+                None => return Ok(None),
+            };
+
+            let mut raced = false;
+
+            for alias in aliases {
+                info!("Merging asset {} into {}", alias, canonical);
+
+                // Another merge may have made the alias an alias of some other machine
+                // since it was resolved, in which case the merge is made again
+                if !self.put_alias(alias, canonical, AliasCondition::Absent).await? {
+                    raced = true;
+                    break;
+                }
+            }
+
+            if !raced {
+                return Ok(Some(canonical.to_owned()));
+            }
+        }
+
+        bail!("Failed to merge assets {:?}, as they are being merged concurrently", asset_ids)
+    }
+
+    // Returns false when the alias no longer holds `condition`
+    async fn put_alias(&self, asset_id: &str, canonical: &str, condition: AliasCondition<'_>) -> Result<bool, Error> {
+        self.store.put_alias(&self.table_name, asset_id, canonical, condition).await
+    }
+}
+
+/// Merges the asset ids an Asset is known by, ie: its instance id, and the assets its
+/// hostname, fqdn, and mac addresses are mapped to
+pub async fn create_asset_aliases(
+    alias_db: &AssetAliasDb<impl AssetAliasStore>,
+    asset_id_db: &AssetIdDb<impl AssetMappingStore>,
+    unid_graph: &Graph,
+) -> Result<(), Error> {
+    for node in unid_graph.nodes.values() {
+        let asset = match node.which_node {
+            Some(WhichNode::AssetNode(ref asset)) => asset,
+            _ => continue,
+        };
+
+        let asset_id = match asset.asset_id {
+            Some(ref asset_id) => asset_id,
+            None => continue,
+        };
+
+        let timestamp = if asset.last_seen_timestamp != 0 {
+            asset.last_seen_timestamp
+        } else {
+            unid_graph.timestamp
+        };
+
+        let mut asset_ids = vec![asset_id.clone()];
+        asset_ids.extend(asset.instance_id.clone());
+
+        let host_ids = asset.hostname.iter()
+            .chain(asset.fqdn.iter())
+            .cloned()
+            .map(HostId::Hostname)
+            .chain(asset.mac_addresses.iter().cloned().map(HostId::Mac));
+
+        for host_id in host_ids {
+            asset_ids.extend(asset_id_db.resolve_asset_id(&host_id, timestamp).await?);
+        }
+
+        asset_ids.sort();
+        asset_ids.dedup();
+
+        if asset_ids.len() > 1 {
+            alias_db.merge_assets(&asset_ids).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+
+    use crate::memory_store::InMemoryStore;
+
+    const TABLE: &str = "asset_aliases";
+
+    fn alias(store: &InMemoryStore, asset_id: &str, canonical: &str) {
+        let written = block_on(store.put_alias(TABLE, asset_id, canonical, AliasCondition::Absent))
+            .expect("put_alias");
+        assert!(written);
+    }
+
+    fn canonical(alias_db: &AssetAliasDb<impl AssetAliasStore>, asset_id: &str) -> String {
+        block_on(alias_db.canonical_asset_id(asset_id)).expect("canonical_asset_id")
+    }
+
+    // Before its next write, makes the write that a concurrent node-identifier won the race with
+    #[derive(Clone)]
+    struct RacingStore {
+        store: InMemoryStore,
+        race: Arc<Mutex<Option<(String, String)>>>,
+    }
+
+    impl RacingStore {
+        fn new(store: InMemoryStore, asset_id: &str, canonical: &str) -> Self {
+            Self {
+                store,
+                race: Arc::new(Mutex::new(Some((asset_id.to_owned(), canonical.to_owned())))),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl AssetAliasStore for RacingStore {
+        async fn get_alias(&self, table_name: &str, asset_id: &str) -> Result<Option<String>, Error> {
+            self.store.get_alias(table_name, asset_id).await
+        }
+
+        async fn put_alias(
+            &self,
+            table_name: &str,
+            asset_id: &str,
+            canonical_asset_id: &str,
+            condition: AliasCondition<'_>,
+        ) -> Result<bool, Error> {
+            let race = self.race.lock().unwrap().take();
+
+            if let Some((raced_asset_id, raced_canonical)) = race {
+                let current = self.store.get_alias(table_name, &raced_asset_id).await?;
+                let raced_condition = match current {
+                    Some(ref current) => AliasCondition::AliasOf(current),
+                    None => AliasCondition::Absent,
+                };
+                assert!(self.store.put_alias(table_name, &raced_asset_id, &raced_canonical, raced_condition).await?);
+            }
+
+            self.store.put_alias(table_name, asset_id, canonical_asset_id, condition).await
+        }
+    }
+
+    #[test]
+    fn unaliased_asset_is_its_own_canonical_asset_id() {
+        let alias_db = AssetAliasDb::new(InMemoryStore::new());
+
+        assert_eq!(canonical(&alias_db, "asset_id_a"), "asset_id_a");
+    }
+
+    #[test]
+    fn chain_of_aliases_is_followed() {
+        let store = InMemoryStore::new();
+        alias(&store, "asset_id_d", "asset_id_c");
+        alias(&store, "asset_id_c", "asset_id_b");
+        alias(&store, "asset_id_b", "asset_id_a");
+
+        let alias_db = AssetAliasDb::new(store);
+
+        assert_eq!(canonical(&alias_db, "asset_id_d"), "asset_id_a");
+        assert_eq!(canonical(&alias_db, "asset_id_c"), "asset_id_a");
+        assert_eq!(canonical(&alias_db, "asset_id_b"), "asset_id_a");
+    }
+
+    #[test]
+    fn aliases_are_followed_up_to_the_hop_limit() {
+        let store = InMemoryStore::new();
+        let asset_ids: Vec<String> = (0..=MAX_ALIAS_DEPTH + 1)
+            .map(|i| format!("asset_id_{}", i))
+            .collect();
+
+        // asset_id_1 is an alias of asset_id_0, asset_id_2 of asset_id_1, and so on
+        for pair in asset_ids.windows(2) {
+            alias(&store, &pair[1], &pair[0]);
+        }
+
+        let alias_db = AssetAliasDb::new(store);
+
+        // Checked first, as following the aliases of asset_id_8 compresses them
+        let too_deep = block_on(alias_db.canonical_asset_id(&asset_ids[MAX_ALIAS_DEPTH + 1]));
+        assert!(too_deep.is_err());
+
+        assert_eq!(canonical(&alias_db, &asset_ids[MAX_ALIAS_DEPTH]), "asset_id_0");
+    }
+
+    #[test]
+    fn long_paths_are_compressed() {
+        let store = InMemoryStore::new();
+        alias(&store, "asset_id_c", "asset_id_b");
+        alias(&store, "asset_id_b", "asset_id_a");
+
+        let alias_db = AssetAliasDb::new(store.clone());
+
+        assert_eq!(canonical(&alias_db, "asset_id_c"), "asset_id_a");
+
+        let compressed = block_on(store.get_alias(TABLE, "asset_id_c")).expect("get_alias");
+        assert_eq!(compressed, Some("asset_id_a".to_owned()));
+    }
+
+    #[test]
+    fn compression_does_not_overwrite_a_moved_alias() {
+        let store = InMemoryStore::new();
+        alias(&store, "asset_id_d", "asset_id_c");
+        alias(&store, "asset_id_c", "asset_id_b");
+
+        // Another node-identifier has compressed asset_id_d further since it was read
+        let racing = RacingStore::new(store.clone(), "asset_id_d", "asset_id_a");
+        let alias_db = AssetAliasDb::new(racing);
+
+        assert_eq!(canonical(&alias_db, "asset_id_d"), "asset_id_b");
+
+        let alias = block_on(store.get_alias(TABLE, "asset_id_d")).expect("get_alias");
+        assert_eq!(alias, Some("asset_id_a".to_owned()));
+    }
+
+    #[test]
+    fn merge_chooses_the_least_asset_id() {
+        let store = InMemoryStore::new();
+        let alias_db = AssetAliasDb::new(store);
+
+        let asset_ids = vec!["asset_id_c".to_owned(), "asset_id_a".to_owned(), "asset_id_b".to_owned()];
+        let merged = block_on(alias_db.merge_assets(&asset_ids)).expect("merge_assets");
+
+        assert_eq!(merged, Some("asset_id_a".to_owned()));
+        for asset_id in &asset_ids {
+            assert_eq!(canonical(&alias_db, asset_id), "asset_id_a");
+        }
+
+        assert_eq!(block_on(alias_db.merge_assets(&[])).expect("merge_assets"), None);
+    }
+
+    #[test]
+    fn merging_two_merged_groups_merges_their_canonical_asset_ids() {
+        let store = InMemoryStore::new();
+        let alias_db = AssetAliasDb::new(store.clone());
+
+        block_on(alias_db.merge_assets(&["asset_id_b".to_owned(), "asset_id_d".to_owned()]))
+            .expect("merge_assets");
+        block_on(alias_db.merge_assets(&["asset_id_a".to_owned(), "asset_id_c".to_owned()]))
+            .expect("merge_assets");
+
+        // Merged through their aliases, rather than their canonical asset ids
+        let merged = block_on(alias_db.merge_assets(&["asset_id_d".to_owned(), "asset_id_c".to_owned()]))
+            .expect("merge_assets");
+        assert_eq!(merged, Some("asset_id_a".to_owned()));
+
+        for asset_id in &["asset_id_a", "asset_id_b", "asset_id_c", "asset_id_d"] {
+            assert_eq!(canonical(&alias_db, asset_id), "asset_id_a");
+        }
+
+        // Only the canonical asset id of the second group was moved
+        let alias = block_on(store.get_alias(TABLE, "asset_id_b")).expect("get_alias");
+        assert_eq!(alias, Some("asset_id_a".to_owned()));
+    }
+
+    #[test]
+    fn merge_that_loses_a_race_is_made_again() {
+        let store = InMemoryStore::new();
+
+        // A concurrent merge makes asset_id_c an alias of asset_id_a, just before this
+        // merge would have made it an alias of asset_id_b
+        let racing = RacingStore::new(store.clone(), "asset_id_c", "asset_id_a");
+        let alias_db = AssetAliasDb::new(racing);
+
+        let merged = block_on(alias_db.merge_assets(&["asset_id_b".to_owned(), "asset_id_c".to_owned()]))
+            .expect("merge_assets");
+        assert_eq!(merged, Some("asset_id_a".to_owned()));
+
+        let alias_db = AssetAliasDb::new(store);
+        for asset_id in &["asset_id_b", "asset_id_c"] {
+            assert_eq!(canonical(&alias_db, asset_id), "asset_id_a");
+        }
+    }
+}
@@ -4,16 +4,31 @@ use graph_descriptions::graph_description::host::*;
 
 use failure::Error;
 
-use rusoto_dynamodb::{
-    AttributeValue, DynamoDb,
-    PutItemInput, QueryInput,
-};
-use std::time::Duration;
 use graph_descriptions::graph_description::node::WhichNode;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::asset_aliases::AssetAliasDb;
+use crate::hostname::HostnameNormalizer;
+use std::sync::Arc;
+
+use crate::mapping_conflicts::{AssetMappingConflict, ConflictPolicy, ConflictSink, LogConflictSink};
+use crate::storage::{AssetAliasStore, AssetMappingStore};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResolvedAssetId {
     pub asset_id: String,
+    #[serde(default)]
+    pub c_timestamp: u64,
+    #[serde(default)]
+    pub e_timestamp: Option<u64>,
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+impl ResolvedAssetId {
+    /// Whether the mapping had ended, ie: its lease had expired or been released, by `ts`
+    pub fn expired_at(&self, ts: u64) -> bool {
+        self.e_timestamp.map(|e_timestamp| e_timestamp < ts).unwrap_or(false)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,22 +37,93 @@ pub struct AssetIdMapping<'a> {
     #[serde(borrow)]
     pub asset_id: &'a str,
     pub c_timestamp: u64,
+    /// When the mapping ends. Mappings without an end hold until the next mapping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e_timestamp: Option<u64>,
+    /// A quarantined hostname's mapping ends where it is quarantined, and the hostname
+    /// stays in conflict with every asset, see ConflictPolicy::Quarantine
+    #[serde(default)]
+    pub quarantined: bool,
+}
+
+// Mappings are keyed on the kind of host id and its value, ie: hostname<hostname>, ip<ip>
+// An asset id is already resolved, and is never mapped
+fn mapping_key(host_id: &HostId, normalizer: &HostnameNormalizer) -> Option<String> {
+    match host_id {
+        HostId::AssetId(_) => None,
+        HostId::Hostname(hostname) => Some(format!("hostname{}", normalizer.normalize(hostname))),
+        HostId::Ip(ip) => Some(format!("ip{}", ip)),
+        // Mac addresses are logged in either case, and with ':' or '-' separators
+        HostId::Mac(mac) => Some(format!("mac{}", mac.to_lowercase().replace('-', ":"))),
+    }
+}
+
+// Nodes only set the timestamp for their state, ie: a process that was seen, rather than
+// created, has no created_timestamp. Ranged mappings have to be resolved at the time the
+// node was observed, so the first timestamp that was set is used
+fn observed_at(timestamps: &[u64]) -> u64 {
+    timestamps.iter().cloned().find(|ts| *ts != 0).unwrap_or(0)
+}
+
+fn host_id_asset_id(host_id: &HostId) -> Option<String> {
+    match host_id {
+        HostId::AssetId(asset_id) => Some(asset_id.to_owned()),
+        _ => None,
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct AssetIdDb<D>
+pub struct AssetIdDb<S>
 where
-    D: DynamoDb,
+    S: AssetMappingStore,
 {
-    dynamo: D,
+    store: S,
+    table_name: String,
+    normalizer: HostnameNormalizer,
+    conflict_policy: ConflictPolicy,
+    conflict_sink: Arc<dyn ConflictSink>,
 }
 
-impl<D> AssetIdDb<D>
+impl<S> AssetIdDb<S>
 where
-    D: DynamoDb,
+    S: AssetMappingStore,
 {
-    pub fn new(dynamo: D) -> Self {
-        Self { dynamo }
+    /// Hostnames are normalized as configured by the environment, see
+    /// `HostnameNormalizer::from_env`. Conflicting mappings are handled by the default
+    /// policy, unless one is set with `with_conflict_policy`, and are logged, unless they
+    /// are sent elsewhere with `with_conflict_sink`
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            table_name: "asset_id_mappings".to_owned(),
+            normalizer: HostnameNormalizer::from_env(),
+            conflict_policy: ConflictPolicy::default(),
+            conflict_sink: Arc::new(LogConflictSink),
+        }
+    }
+
+    pub fn with_table_name(mut self, table_name: impl Into<String>) -> Self {
+        self.table_name = table_name.into();
+        self
+    }
+
+    pub fn with_normalizer(mut self, normalizer: HostnameNormalizer) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    pub fn with_conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    pub fn with_conflict_sink(mut self, conflict_sink: Arc<dyn ConflictSink>) -> Self {
+        self.conflict_sink = conflict_sink;
+        self
+    }
+
+    pub fn normalizer(&self) -> &HostnameNormalizer {
+        &self.normalizer
     }
 
     pub async fn find_first_mapping_after(
@@ -45,45 +131,15 @@ where
         host_id: &HostId,
         ts: u64,
     ) -> Result<Option<String>, Error> {
-        let (table_key, pseudo_key) = match host_id {
-            HostId::AssetId(asset_id) => return Ok(Some(asset_id.to_owned())),
-            HostId::Hostname(hostname) => ("hostname", hostname.as_str()),
+        let pseudo_key = match mapping_key(host_id, &self.normalizer) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(host_id_asset_id(host_id)),
         };
 
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            table_name: "asset_id_mappings".to_owned(),
-            key_condition_expression: Some(
-                "pseudo_key = :pkey_val AND c_timestamp >= :c_timestamp".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pkey_val".to_owned() => AttributeValue {
-                    s: format!("{}{}", table_key, pseudo_key).into(),
-                    ..Default::default()
-                },
-                ":c_timestamp".to_owned() => AttributeValue {
-                    n: ts.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        let res = wait_on!(self.dynamo.query(query))?;
+        let mapping = self.store.find_first_mapping_after(&self.table_name, &pseudo_key, ts).await?;
 
-        match res.items {
-            Some(mut items) if items.len() == 1 => {
-                let item = items.remove(0);
-                let asset_id: ResolvedAssetId = serde_dynamodb::from_hashmap(item)?;
-                Ok(Some(asset_id.asset_id))
-            }
-            Some(items) if items.is_empty() => {
-                Ok(None)
-            }
-            Some(items) => bail!("Unexpected number of items returned: {}", items.len()),
-            None => Ok(None)
-        }
+        // A quarantined hostname is mapped to neither asset
+        Ok(mapping.filter(|mapping| !mapping.quarantined).map(|mapping| mapping.asset_id))
     }
 
     pub async fn find_last_mapping_before(
@@ -92,99 +148,215 @@ where
         ts: u64,
     ) -> Result<Option<String>, Error> {
         //        info!("Finding last mapping before");
-        let (table_key, pseudo_key) = match host_id {
-            HostId::AssetId(asset_id) => return Ok(Some(asset_id.to_owned())),
-            HostId::Hostname(hostname) => ("hostname", hostname.as_str()),
+        if let HostId::AssetId(asset_id) = host_id {
+            return Ok(Some(asset_id.to_owned()));
+        }
+
+        match self.find_last_mapping(host_id, ts).await? {
+            // The host id was unmapped when the last mapping ended, until it is mapped again
+            Some(ref mapping) if mapping.expired_at(ts) || mapping.quarantined => Ok(None),
+            Some(mapping) => Ok(Some(mapping.asset_id)),
+            None => Ok(None),
+        }
+    }
+
+    // The last mapping created for the host id before `ts`, whether or not it has ended
+    async fn find_last_mapping(
+        &self,
+        host_id: &HostId,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error> {
+        let pseudo_key = match mapping_key(host_id, &self.normalizer) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(None),
         };
 
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            scan_index_forward: Some(false),
-            table_name: "asset_id_mappings".to_owned(),
-            key_condition_expression: Some(
-                "pseudo_key = :pseudo_key AND c_timestamp <= :c_timestamp".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pseudo_key".to_owned() => AttributeValue {
-                    s: format!("{}{}", table_key, pseudo_key).into(),
-                    ..Default::default()
-                },
-                ":c_timestamp".to_owned() => AttributeValue {
-                    n: ts.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
+        self.store.find_last_mapping_before(&self.table_name, &pseudo_key, ts).await
+    }
+
+    pub async fn resolve_asset_id(&self, host_id: &HostId, ts: u64) -> Result<Option<String>, Error> {
+        if let Some(session_id) = self.find_last_mapping_before(host_id, ts).await? {
+            return Ok(Some(session_id));
+        }
+
+        match host_id {
+            // Addresses are reassigned, so their mappings are time ranged: a mapping holds
+            // from when it was created until it ends, or the next mapping for the address.
+            // Before the first mapping the address may have belonged to any host.
+            HostId::Ip(_) | HostId::Mac(_) => Ok(None),
+            _ => self.find_first_mapping_after(host_id, ts).await,
+        }
+    }
+
+    /// Maps the host id to the asset id from `ts`.
+    ///
+    /// A hostname that is mapped to another asset, before or after `ts`, is a conflict, which
+    /// is sent to the conflict sink, and returned, and is handled by the conflict policy.
+    pub async fn create_mapping(
+        &self,
+        host_id: &HostId,
+        asset_id: String,
+        ts: u64,
+    ) -> Result<Option<AssetMappingConflict>, Error> {
+        let conflict = match host_id {
+            HostId::Hostname(hostname) => self.find_conflict(host_id, hostname, &asset_id, ts).await?,
+            _ => None,
         };
 
-        let res = wait_on!(self.dynamo.query(query))?;
+        let (conflict, existing) = match conflict {
+            Some(conflict) => conflict,
+            None => {
+                self.put_mapping(host_id, asset_id, ts, None).await?;
+                return Ok(None);
+            }
+        };
 
-        if let Some(items) = res.items {
-            match &items[..] {
-                [] => Ok(None),
-                [item] => {
-                    let asset_id: ResolvedAssetId = serde_dynamodb::from_hashmap(item.clone())?;
-                    Ok(Some(asset_id.asset_id))
+        // Sent before the policy is applied, so that a conflict that fails to send is
+        // found again when the mapping is retried
+        self.conflict_sink.emit(&conflict).await?;
+
+        match conflict.policy {
+            ConflictPolicy::Reject => (),
+            ConflictPolicy::AcceptNew => self.put_mapping(host_id, asset_id, ts, None).await?,
+            // The hostname is quarantined from the conflict, so that it is not attributed to
+            // either asset. A mapping made after the conflict, that arrived before it, is
+            // quarantined as well.
+            ConflictPolicy::Quarantine => {
+                self.put_quarantine(host_id, conflict.existing_asset_id.clone(), ts).await?;
+
+                if existing.c_timestamp > ts {
+                    self.put_quarantine(host_id, existing.asset_id, existing.c_timestamp).await?;
                 }
-                _ => bail!("Unexpected number of items returned"),
             }
-        } else {
-            Ok(None)
         }
-    }
 
-    pub async fn resolve_asset_id(&self, host_id: &HostId, ts: u64) -> Result<Option<String>, Error> {
-        if let Some(session_id) = self.find_last_mapping_before(host_id, ts).await? {
-            Ok(Some(session_id))
-        } else {
-            self.find_first_mapping_after(host_id, ts).await
-        }
+        Ok(Some(conflict))
     }
 
-    pub async fn create_mapping(&self, host_id: &HostId, asset_id: String, ts: u64) -> Result<(), Error> {
-        let (table_key, host_id) = match host_id {
-            HostId::AssetId(id) => return Ok(()),
-            HostId::Hostname(hostname) => ("hostname", hostname.as_str()),
+    // Mappings are created as logs arrive, which is not always the order they were written
+    // in, so the mapping after `ts` is checked as well as the one before it
+    async fn find_conflict(
+        &self,
+        host_id: &HostId,
+        hostname: &str,
+        asset_id: &str,
+        ts: u64,
+    ) -> Result<Option<(AssetMappingConflict, ResolvedAssetId)>, Error> {
+        let pseudo_key = match mapping_key(host_id, &self.normalizer) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(None),
         };
 
-        let mapping = AssetIdMapping {
-            pseudo_key: format!("{}{}", table_key, host_id),
-            asset_id: &asset_id,
-            c_timestamp: ts,
+        // An ended, or quarantined, mapping is in conflict with every asset, so that a
+        // quarantined hostname stays quarantined when it is mapped to its old asset again
+        let before = self.store.find_last_mapping_before(&self.table_name, &pseudo_key, ts).await?
+            .filter(|before| before.quarantined || before.expired_at(ts) || before.asset_id != asset_id);
+
+        let existing = match before {
+            Some(before) => before,
+            None => {
+                let after = self.store.find_first_mapping_after(&self.table_name, &pseudo_key, ts).await?
+                    .filter(|after| after.quarantined || after.asset_id != asset_id);
+
+                match after {
+                    Some(after) => after,
+                    None => return Ok(None),
+                }
+            }
         };
 
-        let put_req = PutItemInput {
-            item: serde_dynamodb::to_hashmap(&mapping).unwrap(),
-            table_name: "asset_id_mappings".to_owned(),
-            ..Default::default()
+        let conflict = AssetMappingConflict {
+            hostname: hostname.to_owned(),
+            existing_asset_id: existing.asset_id.clone(),
+            new_asset_id: asset_id.to_owned(),
+            timestamp: ts,
+            policy: self.conflict_policy,
         };
 
-        let put_item_response = wait_on!(self.dynamo.put_item(put_req))?;
+        Ok(Some((conflict, existing)))
+    }
 
-        info!(
-            "PutItemResponse for {:?} {}: {:?}",
-            host_id, asset_id, put_item_response
-        );
+    /// Maps the host id to the asset id from `start` until `end`, ie: for a DHCP lease.
+    /// A mapping that ends where it starts unmaps the host id, as for a released lease.
+    pub async fn create_ranged_mapping(
+        &self,
+        host_id: &HostId,
+        asset_id: String,
+        start: u64,
+        end: u64,
+    ) -> Result<(), Error> {
+        if end < start {
+            bail!("Mapping for {:?} ends at {} before it starts at {}", host_id, end, start);
+        }
+
+        self.put_mapping(host_id, asset_id, start, Some(end)).await
+    }
 
-        Ok(())
+    async fn put_mapping(
+        &self,
+        host_id: &HostId,
+        asset_id: String,
+        ts: u64,
+        end: Option<u64>,
+    ) -> Result<(), Error> {
+        info!("Mapping {:?} to {} at {}", host_id, asset_id, ts);
+
+        self.write_mapping(host_id, &asset_id, ts, end, false).await
+    }
+
+    // Ends the mapping to `asset_id` at `ts`, and quarantines the host id from then on
+    async fn put_quarantine(
+        &self,
+        host_id: &HostId,
+        asset_id: String,
+        ts: u64,
+    ) -> Result<(), Error> {
+        info!("Quarantining {:?} from {} at {}", host_id, asset_id, ts);
+
+        self.write_mapping(host_id, &asset_id, ts, Some(ts), true).await
+    }
+
+    async fn write_mapping(
+        &self,
+        host_id: &HostId,
+        asset_id: &str,
+        ts: u64,
+        end: Option<u64>,
+        quarantined: bool,
+    ) -> Result<(), Error> {
+        let pseudo_key = match mapping_key(host_id, &self.normalizer) {
+            Some(pseudo_key) => pseudo_key,
+            None => return Ok(()),
+        };
+
+        let mapping = AssetIdMapping {
+            pseudo_key,
+            asset_id,
+            c_timestamp: ts,
+            e_timestamp: end,
+            quarantined,
+        };
+
+        self.store.put_mapping(&self.table_name, &mapping).await
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct AssetIdentifier<D>
-    where D: DynamoDb
+    where D: AssetMappingStore + AssetAliasStore
 {
     assetdb: AssetIdDb<D>,
+    aliasdb: AssetAliasDb<D>,
 }
 
 impl<D> AssetIdentifier<D>
-    where D: DynamoDb
+    where D: AssetMappingStore + AssetAliasStore
 {
     pub fn new(
-        assetdb: AssetIdDb<D>
+        assetdb: AssetIdDb<D>,
+        aliasdb: AssetAliasDb<D>,
     ) -> Self {
-        Self {assetdb}
+        Self {assetdb, aliasdb}
     }
 
     pub async fn attribute_asset_id(
@@ -194,17 +366,25 @@ impl<D> AssetIdentifier<D>
 
         let ids = match &node.which_node {
             Some(WhichNode::AssetNode(ref node)) =>
-                (&node.asset_id, &node.hostname, node.first_seen_timestamp),
+                (&node.asset_id, &node.hostname, &None, &node.mac_address, node.first_seen_timestamp),
             Some(WhichNode::ProcessNode(ref node)) =>
-                (&node.asset_id, &node.hostname, node.created_timestamp),
+                (&node.asset_id, &node.hostname, &node.host_ip, &node.host_mac, observed_at(&[
+                    node.created_timestamp, node.last_seen_timestamp, node.terminated_timestamp
+                ])),
             Some(WhichNode::FileNode(ref node)) =>
-                (&node.asset_id, &node.hostname, node.created_timestamp),
+                (&node.asset_id, &node.hostname, &node.host_ip, &node.host_mac, observed_at(&[
+                    node.created_timestamp, node.last_seen_timestamp, node.deleted_timestamp
+                ])),
             Some(WhichNode::ProcessOutboundConnectionNode(ref node)) =>
-                (&node.asset_id, &node.hostname, node.created_timestamp),
+                (&node.asset_id, &node.hostname, &node.host_ip, &node.host_mac, observed_at(&[
+                    node.created_timestamp, node.last_seen_timestamp, node.terminated_timestamp
+                ])),
             Some(WhichNode::DynamicNode(ref node)) =>
-                (&node.asset_id, &node.hostname, node.seen_at),
+                (&node.asset_id, &node.hostname, &node.host_ip, &node.host_mac, node.seen_at),
             Some(WhichNode::ProcessInboundConnectionNode(ref node)) =>
-                (&node.asset_id, &node.hostname, node.created_timestamp),
+                (&node.asset_id, &node.hostname, &node.host_ip, &node.host_mac, observed_at(&[
+                    node.created_timestamp, node.last_seen_timestamp, node.terminated_timestamp
+                ])),
             Some(WhichNode::IpAddressNode(_)) => {
                 bail!("Can not call attribute_asset_id with IpAddressNode")
             }
@@ -220,24 +400,34 @@ impl<D> AssetIdentifier<D>
             None => bail!("Could not determine node variant")
         };
 
-        let (host_id, timestamp) = match ids {
-            (Some(asset_id), _, timestamp) => return Ok(asset_id.clone()),
-            (_, Some(hostname), timestamp) => (HostId::Hostname(hostname.clone()), timestamp),
-            (_,_,_) => {
-                bail!("Must provide at least one of: asset_id, hostname, host_ip");
-            }
-        };
+        let (asset_id, hostname, host_ip, host_mac, timestamp) = ids;
+
+        if let Some(asset_id) = asset_id {
+            return self.aliasdb.canonical_asset_id(asset_id).await;
+        }
+
+        // A hostname is preferred to an ip, as ips are more often shared, or reassigned.
+        // Each host id falls back to the next when it has no mapping at the timestamp.
+        let host_ids: Vec<HostId> = hostname.iter().cloned().map(HostId::Hostname)
+            .chain(host_ip.iter().cloned().map(HostId::Ip))
+            .chain(host_mac.iter().cloned().map(HostId::Mac))
+            .collect();
+
+        if host_ids.is_empty() {
+            bail!("Must provide at least one of: asset_id, hostname, host_ip, host_mac");
+        }
 
         // map host_id to asset_id
         // If we don't find an asset id we'll have to mark the node as dead
-        let asset_id = self.assetdb.resolve_asset_id(&host_id, timestamp).await;
-
-        match asset_id {
-            Ok(Some(asset_id)) => Ok(asset_id),
-            Ok(None) => bail!("Failed to resolve assetid"),
-            Err(e) => bail!("Failed to resolve assetid {}", e),
+        for host_id in &host_ids {
+            match self.assetdb.resolve_asset_id(host_id, timestamp).await {
+                Ok(Some(asset_id)) => return self.aliasdb.canonical_asset_id(&asset_id).await,
+                Ok(None) => continue,
+                Err(e) => bail!("Failed to resolve assetid {}", e),
+            }
         }
 
+        bail!("Failed to resolve assetid")
     }
 }
 
@@ -245,33 +435,312 @@ impl<D> AssetIdentifier<D>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rusoto_core::Region;
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+
+    use graph_descriptions::process_outbound_connection::ProcessOutboundConnectionState;
+
+    use crate::fake_dynamodb::FakeDynamoDb;
 
     // Given a hostname 'H' to asset id 'A' mapping at c_timestamp 'X'
     // When attributing 'H' at c_timestamp 'Y', where 'Y' > 'X'
     // Then we should retrieve asset id 'A'
     #[test]
     fn map_hostname_to_asset_id() {
-        let region = Region::Custom {
-            endpoint: "http://localhost:8000".to_owned(),
-            name: "us-east-9".to_owned(),
-        };
+        let dynamo = FakeDynamoDb::new()
+            .with_table("asset_id_mappings", "pseudo_key", Some("c_timestamp"));
 
-        let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
+        let asset_id_db = AssetIdDb::new(dynamo);
 
-        asset_id_db
-            .create_mapping(
-                &HostId::Hostname("fakehostname".to_owned()),
-                "asset_id_a".into(),
-                1500,
-            )
+        block_on(asset_id_db.create_mapping(
+            &HostId::Hostname("fakehostname".to_owned()),
+            "asset_id_a".into(),
+            1500,
+        ))
             .expect("Mapping creation failed");
 
-        let mapping = asset_id_db
-            .resolve_asset_id(&HostId::Hostname("fakehostname".to_owned()), 1510)
+        let mapping = block_on(asset_id_db.resolve_asset_id(&HostId::Hostname("fakehostname".to_owned()), 1510))
             .expect("Failed to resolve asset id mapping")
             .expect("Failed to resolve asset id mapping");
 
         assert_eq!(mapping, "asset_id_a");
     }
+
+    // Given 'fakehostname' mapped to asset 'A' at 1000, maps it to asset 'B' at 2000
+    fn remap_hostname(policy: ConflictPolicy) -> (AssetIdDb<FakeDynamoDb>, Option<AssetMappingConflict>) {
+        let dynamo = FakeDynamoDb::new()
+            .with_table("asset_id_mappings", "pseudo_key", Some("c_timestamp"));
+
+        let asset_id_db = AssetIdDb::new(dynamo).with_conflict_policy(policy);
+        let hostname = HostId::Hostname("fakehostname".to_owned());
+
+        let conflict = block_on(asset_id_db.create_mapping(&hostname, "asset_id_a".into(), 1000))
+            .expect("Mapping creation failed");
+        assert!(conflict.is_none());
+
+        let conflict = block_on(asset_id_db.create_mapping(&hostname, "asset_id_b".into(), 2000))
+            .expect("Mapping creation failed");
+
+        (asset_id_db, conflict)
+    }
+
+    fn resolve_hostname(asset_id_db: &AssetIdDb<FakeDynamoDb>, ts: u64) -> Option<String> {
+        block_on(asset_id_db.resolve_asset_id(&HostId::Hostname("fakehostname".to_owned()), ts))
+            .expect("resolve_asset_id")
+    }
+
+    #[test]
+    fn rejected_conflict_keeps_the_existing_mapping() {
+        let (asset_id_db, conflict) = remap_hostname(ConflictPolicy::Reject);
+
+        let conflict = conflict.expect("conflict");
+        assert_eq!(conflict.existing_asset_id, "asset_id_a");
+        assert_eq!(conflict.new_asset_id, "asset_id_b");
+        assert_eq!(conflict.policy, ConflictPolicy::Reject);
+
+        assert_eq!(resolve_hostname(&asset_id_db, 2500), Some("asset_id_a".to_owned()));
+    }
+
+    #[test]
+    fn accepted_conflict_maps_the_new_asset_from_the_conflict() {
+        let (asset_id_db, conflict) = remap_hostname(ConflictPolicy::AcceptNew);
+
+        assert_eq!(conflict.expect("conflict").policy, ConflictPolicy::AcceptNew);
+
+        assert_eq!(resolve_hostname(&asset_id_db, 1500), Some("asset_id_a".to_owned()));
+        assert_eq!(resolve_hostname(&asset_id_db, 2500), Some("asset_id_b".to_owned()));
+    }
+
+    #[test]
+    fn quarantined_hostname_resolves_to_neither_asset() {
+        let (asset_id_db, conflict) = remap_hostname(ConflictPolicy::Quarantine);
+
+        assert_eq!(conflict.expect("conflict").policy, ConflictPolicy::Quarantine);
+
+        // Until the conflict the hostname was the existing asset's
+        assert_eq!(resolve_hostname(&asset_id_db, 1500), Some("asset_id_a".to_owned()));
+        assert_eq!(resolve_hostname(&asset_id_db, 2500), None);
+
+        // And it stays in conflict
+        let conflict = block_on(asset_id_db.create_mapping(
+            &HostId::Hostname("fakehostname".to_owned()),
+            "asset_id_b".into(),
+            3000,
+        ))
+            .expect("Mapping creation failed");
+        assert!(conflict.is_some());
+        assert_eq!(resolve_hostname(&asset_id_db, 3500), None);
+    }
+
+    #[test]
+    fn quarantined_hostname_stays_quarantined_when_mapped_to_its_old_asset() {
+        let (asset_id_db, _) = remap_hostname(ConflictPolicy::Quarantine);
+
+        let conflict = block_on(asset_id_db.create_mapping(
+            &HostId::Hostname("fakehostname".to_owned()),
+            "asset_id_a".into(),
+            3000,
+        ))
+            .expect("Mapping creation failed")
+            .expect("conflict");
+
+        assert_eq!(conflict.existing_asset_id, "asset_id_a");
+        assert_eq!(conflict.new_asset_id, "asset_id_a");
+        assert_eq!(resolve_hostname(&asset_id_db, 3500), None);
+    }
+
+    // Given 'fakehostname' mapped to asset 'B' at 2000, maps it to asset 'A' at 1000, as when
+    // the logs of the two mappings arrive out of order
+    fn remap_hostname_out_of_order(policy: ConflictPolicy) -> (AssetIdDb<FakeDynamoDb>, Option<AssetMappingConflict>) {
+        let dynamo = FakeDynamoDb::new()
+            .with_table("asset_id_mappings", "pseudo_key", Some("c_timestamp"));
+
+        let asset_id_db = AssetIdDb::new(dynamo).with_conflict_policy(policy);
+        let hostname = HostId::Hostname("fakehostname".to_owned());
+
+        let conflict = block_on(asset_id_db.create_mapping(&hostname, "asset_id_b".into(), 2000))
+            .expect("Mapping creation failed");
+        assert!(conflict.is_none());
+
+        let conflict = block_on(asset_id_db.create_mapping(&hostname, "asset_id_a".into(), 1000))
+            .expect("Mapping creation failed");
+
+        (asset_id_db, conflict)
+    }
+
+    #[test]
+    fn mapping_before_a_later_mapping_is_a_conflict() {
+        let (asset_id_db, conflict) = remap_hostname_out_of_order(ConflictPolicy::AcceptNew);
+
+        let conflict = conflict.expect("conflict");
+        assert_eq!(conflict.existing_asset_id, "asset_id_b");
+        assert_eq!(conflict.new_asset_id, "asset_id_a");
+        assert_eq!(conflict.timestamp, 1000);
+
+        assert_eq!(resolve_hostname(&asset_id_db, 1500), Some("asset_id_a".to_owned()));
+        assert_eq!(resolve_hostname(&asset_id_db, 2500), Some("asset_id_b".to_owned()));
+    }
+
+    #[test]
+    fn quarantine_before_a_later_mapping_quarantines_it() {
+        let (asset_id_db, conflict) = remap_hostname_out_of_order(ConflictPolicy::Quarantine);
+
+        assert!(conflict.is_some());
+
+        assert_eq!(resolve_hostname(&asset_id_db, 1500), None);
+        assert_eq!(resolve_hostname(&asset_id_db, 2500), None);
+    }
+
+    #[derive(Debug, Default)]
+    struct CollectingSink {
+        conflicts: Mutex<Vec<AssetMappingConflict>>,
+    }
+
+    #[async_trait]
+    impl ConflictSink for CollectingSink {
+        async fn emit(&self, conflict: &AssetMappingConflict) -> Result<(), Error> {
+            self.conflicts.lock().unwrap().push(conflict.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn conflicts_are_sent_to_the_conflict_sink() {
+        let dynamo = FakeDynamoDb::new()
+            .with_table("asset_id_mappings", "pseudo_key", Some("c_timestamp"));
+
+        let sink = Arc::new(CollectingSink::default());
+        let asset_id_db = AssetIdDb::new(dynamo)
+            .with_conflict_policy(ConflictPolicy::Reject)
+            .with_conflict_sink(sink.clone());
+        let hostname = HostId::Hostname("fakehostname".to_owned());
+
+        block_on(asset_id_db.create_mapping(&hostname, "asset_id_a".into(), 1000))
+            .expect("Mapping creation failed");
+        assert!(sink.conflicts.lock().unwrap().is_empty());
+
+        let conflict = block_on(asset_id_db.create_mapping(&hostname, "asset_id_b".into(), 2000))
+            .expect("Mapping creation failed");
+
+        assert_eq!(*sink.conflicts.lock().unwrap(), vec![conflict.expect("conflict")]);
+    }
+
+    // A connection seen from 10.0.0.5, which was leased to asset 'A' from 1000 until 2000
+    fn outbound_from_leased_ip(last_seen_timestamp: u64) -> Node {
+        ProcessOutboundConnectionBuilder::default()
+            .host_ip("10.0.0.5".to_owned())
+            .state(ProcessOutboundConnectionState::Existing)
+            .ip_address("10.0.0.5")
+            .port(49152u32)
+            .protocol("tcp")
+            .last_seen_timestamp(last_seen_timestamp)
+            .build()
+            .expect("outbound")
+            .into()
+    }
+
+    #[test]
+    fn ip_only_node_is_attributed_within_its_lease() {
+        let dynamo = FakeDynamoDb::new()
+            .with_table("asset_id_mappings", "pseudo_key", Some("c_timestamp"))
+            .with_table("asset_aliases", "pseudo_key", None);
+
+        let asset_id_db = AssetIdDb::new(dynamo.clone());
+        block_on(asset_id_db.create_ranged_mapping(
+            &HostId::Ip("10.0.0.5".to_owned()),
+            "asset_id_a".into(),
+            1000,
+            2000,
+        ))
+            .expect("Mapping creation failed");
+
+        let identifier = AssetIdentifier::new(asset_id_db, AssetAliasDb::new(dynamo));
+
+        let asset_id = block_on(identifier.attribute_asset_id(&outbound_from_leased_ip(1500)))
+            .expect("attribute_asset_id");
+        assert_eq!(asset_id, "asset_id_a");
+
+        // Before the lease the ip may have belonged to any host, and after it ends it is unmapped
+        assert!(block_on(identifier.attribute_asset_id(&outbound_from_leased_ip(500))).is_err());
+        assert!(block_on(identifier.attribute_asset_id(&outbound_from_leased_ip(2500))).is_err());
+    }
+
+    #[test]
+    fn mac_is_the_fallback_when_the_ip_is_unmapped() {
+        let dynamo = FakeDynamoDb::new()
+            .with_table("asset_id_mappings", "pseudo_key", Some("c_timestamp"))
+            .with_table("asset_aliases", "pseudo_key", None);
+
+        let asset_id_db = AssetIdDb::new(dynamo.clone());
+        block_on(asset_id_db.create_ranged_mapping(
+            &HostId::Ip("10.0.0.5".to_owned()),
+            "asset_id_a".into(),
+            1000,
+            2000,
+        ))
+            .expect("Mapping creation failed");
+        block_on(asset_id_db.create_mapping(
+            &HostId::Mac("00:1a:2b:3c:4d:5e".to_owned()),
+            "asset_id_b".into(),
+            1000,
+        ))
+            .expect("Mapping creation failed");
+
+        let identifier = AssetIdentifier::new(asset_id_db, AssetAliasDb::new(dynamo));
+
+        let outbound_from = |host_mac: &str, last_seen_timestamp: u64| -> Node {
+            let mut node = outbound_from_leased_ip(last_seen_timestamp);
+            if let Some(WhichNode::ProcessOutboundConnectionNode(ref mut outbound)) = node.which_node {
+                outbound.host_mac = Some(host_mac.to_owned());
+            }
+            node
+        };
+
+        // The ip is preferred while it is leased, and the mac is used once the lease has ended
+        let asset_id = block_on(identifier.attribute_asset_id(&outbound_from("00-1A-2B-3C-4D-5E", 1500)))
+            .expect("attribute_asset_id");
+        assert_eq!(asset_id, "asset_id_a");
+
+        let asset_id = block_on(identifier.attribute_asset_id(&outbound_from("00-1A-2B-3C-4D-5E", 2500)))
+            .expect("attribute_asset_id");
+        assert_eq!(asset_id, "asset_id_b");
+
+        assert!(block_on(identifier.attribute_asset_id(&outbound_from("00:00:00:00:00:01", 2500))).is_err());
+    }
+
+    #[test]
+    fn released_lease_is_expired_after_release() {
+        let released = ResolvedAssetId {
+            asset_id: "asset_id_a".to_owned(),
+            c_timestamp: 1000,
+            e_timestamp: Some(1500),
+            quarantined: false,
+        };
+
+        assert!(!released.expired_at(1500));
+        assert!(released.expired_at(1501));
+
+        let unbounded = ResolvedAssetId {
+            asset_id: "asset_id_a".to_owned(),
+            c_timestamp: 1000,
+            e_timestamp: None,
+            quarantined: false,
+        };
+
+        assert!(!unbounded.expired_at(u64::max_value()));
+    }
+
+    #[test]
+    fn mac_mapping_key_is_normalized() {
+        let normalizer = HostnameNormalizer::default();
+
+        assert_eq!(
+            mapping_key(&HostId::Mac("00-1A-2B-3C-4D-5E".to_owned()), &normalizer),
+            mapping_key(&HostId::Mac("00:1a:2b:3c:4d:5e".to_owned()), &normalizer),
+        );
+
+        assert_eq!(mapping_key(&HostId::AssetId("asset_id_a".to_owned()), &normalizer), None);
+    }
 }
@@ -0,0 +1,120 @@
+extern crate failure;
+extern crate node_identifier;
+extern crate rusoto_core;
+extern crate rusoto_dynamodb;
+extern crate simple_logger;
+extern crate tokio_compat;
+
+use std::str::FromStr;
+
+use failure::Error;
+use log::*;
+use node_identifier::asset_aliases::AssetAliasDb;
+use node_identifier::storage::AssetAliasStore;
+use rusoto_core::Region;
+use rusoto_dynamodb::DynamoDbClient;
+
+const USAGE: &str = "usage: merge-assets <asset_id> <asset_id> [<asset_id>...]";
+
+// The asset ids to merge, of which there must be at least two
+fn parse_asset_ids(args: impl Iterator<Item = String>) -> Result<Vec<String>, &'static str> {
+    let mut asset_ids: Vec<String> = args
+        .map(|asset_id| asset_id.trim().to_owned())
+        .filter(|asset_id| !asset_id.is_empty())
+        .collect();
+
+    asset_ids.sort();
+    asset_ids.dedup();
+
+    if asset_ids.len() < 2 {
+        return Err(USAGE);
+    }
+
+    Ok(asset_ids)
+}
+
+async fn merge_assets(
+    alias_db: &AssetAliasDb<impl AssetAliasStore>,
+    asset_ids: &[String],
+) -> Result<Option<String>, Error> {
+    let canonical = alias_db.merge_assets(asset_ids).await?;
+
+    match canonical {
+        Some(ref canonical) => info!("Merged {:?} into {}", asset_ids, canonical),
+        None => info!("No assets to merge"),
+    }
+
+    Ok(canonical)
+}
+
+// Merges assets that are the same machine, ie: a host that was renamed, or that is
+// logged by its computer name by one source and its instance id by another.
+//
+// Nodes that have already been attributed keep their asset id, and any nodes attributed
+// from then on are attributed to the canonical asset id of the merged assets.
+//
+// usage: AWS_REGION=<region> merge-assets <asset_id> <asset_id> [<asset_id>...]
+fn main() {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    let asset_ids = match parse_asset_ids(std::env::args().skip(1)) {
+        Ok(asset_ids) => asset_ids,
+        Err(usage) => {
+            eprintln!("{}", usage);
+            std::process::exit(2);
+        }
+    };
+
+    let region = {
+        let region_str = std::env::var("AWS_REGION").expect("AWS_REGION");
+        Region::from_str(&region_str).expect("Region error")
+    };
+
+    tokio_compat::run_std(async move {
+        let alias_db = AssetAliasDb::new(DynamoDbClient::new(region));
+
+        if let Err(e) = merge_assets(&alias_db, &asset_ids).await {
+            error!("Failed to merge {:?}: {}", asset_ids, e);
+            std::process::exit(1);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use node_identifier::memory_store::InMemoryStore;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn at_least_two_distinct_asset_ids_are_required() {
+        assert_eq!(parse_asset_ids(args(&[])), Err(USAGE));
+        assert_eq!(parse_asset_ids(args(&["asset_id_a"])), Err(USAGE));
+        assert_eq!(parse_asset_ids(args(&["asset_id_a", "asset_id_a", " "])), Err(USAGE));
+
+        assert_eq!(
+            parse_asset_ids(args(&["asset_id_b", "asset_id_a", "asset_id_b"])),
+            Ok(vec!["asset_id_a".to_owned(), "asset_id_b".to_owned()]),
+        );
+    }
+
+    #[test]
+    fn merged_assets_resolve_to_the_least_asset_id() {
+        let alias_db = AssetAliasDb::new(InMemoryStore::new());
+        let asset_ids = parse_asset_ids(args(&["i-0123456789", "asset_id_b", "asset_id_a"]))
+            .expect("parse_asset_ids");
+
+        let canonical = block_on(merge_assets(&alias_db, &asset_ids)).expect("merge_assets");
+        assert_eq!(canonical, Some("asset_id_a".to_owned()));
+
+        for asset_id in &asset_ids {
+            let canonical = block_on(alias_db.canonical_asset_id(asset_id)).expect("canonical_asset_id");
+            assert_eq!(canonical, "asset_id_a");
+        }
+    }
+}
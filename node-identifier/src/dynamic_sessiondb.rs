@@ -6,25 +6,20 @@ use graph_descriptions::graph_description::id_strategy;
 use graph_descriptions::graph_description::Session as SessionStrategy;
 use failure::Error;
 
-use rusoto_dynamodb::{
-    AttributeValue, DynamoDb, GetItemInput,
-    PutItemInput,
-};
-use std::time::Duration;
-
-
 use std::collections::{HashSet, HashMap};
 use graph_descriptions::node::NodeT;
 use crate::sessiondb::SessionDb;
 use crate::assetdb::AssetIdentifier;
 use crate::sessions::UnidSession;
+use crate::storage::{IdentityStore, StaticMappingStore};
 
 
 #[derive(Debug, Clone)]
-pub struct DynamicMappingDb<D>
-    where D: DynamoDb
+pub struct DynamicMappingDb<S>
+    where S: StaticMappingStore
 {
-    dyn_mapping_db: D
+    store: S,
+    table_name: String,
 }
 
 
@@ -39,69 +34,76 @@ pub struct DirectMapping {
     pub mapping: String,
 }
 
-impl<D> DynamicMappingDb<D> where D: DynamoDb {
-    pub fn new(dyn_mapping_db: D) -> Self {
+impl<S> DynamicMappingDb<S> where S: StaticMappingStore {
+    pub fn new(store: S) -> Self {
         Self {
-            dyn_mapping_db
+            store,
+            table_name: "static_mapping_table".to_owned(),
         }
     }
 
-    pub fn direct_map(
+    pub fn with_table_name(mut self, table_name: impl Into<String>) -> Self {
+        self.table_name = table_name.into();
+        self
+    }
+
+    pub async fn direct_map(
         &self,
         input: &str,
     ) -> Result<Option<String>, Error> {
-        let mut key: HashMap<String, AttributeValue> = HashMap::new();
-
-        key.insert(
-            "pseudo_key".to_owned(),
-            AttributeValue {
-                s: Some(input.to_owned()),
-                ..Default::default()
-            },
-        );
-
-        let query = GetItemInput {
-            consistent_read: Some(true),
-            table_name: "static_mapping_table".to_owned(),
-            key,
-            ..Default::default()
-        };
-
-        let item = self.dyn_mapping_db.get_item(query).sync()?.item;
-
-        match item {
-            Some(item) => {
-                let mapping: ResolvedMapping = serde_dynamodb::from_hashmap(item.clone())?;
-                Ok(Some(mapping.mapping))
-            }
-            None => Ok(None)
-        }
+        self.store.get_static_mapping(&self.table_name, input).await
     }
 
     pub async fn create_mapping(&self, input: String, maps_to: String) -> Result<(), Error> {
         info!("Creating dynamic mapping for: {} {}", input, maps_to);
-        let mapping = DirectMapping {
-            pseudo_key: input,
-            mapping: maps_to,
-        };
+        self.store.put_static_mapping(&self.table_name, &input, &maps_to).await
+    }
+
+}
 
-        let put_req = PutItemInput {
-            item: serde_dynamodb::to_hashmap(&mapping).unwrap(),
-            table_name: "static_mapping_table".to_owned(),
-            ..Default::default()
-        };
 
-        let put_item_response = wait_on!(self.dyn_mapping_db.put_item(put_req))?;
+// The node's identifying properties, and its type, as a natural partition. The node_key
+// of an unidentified node is random, so it can not be part of its identity.
+fn push_key_properties(
+    primary_key: &mut String,
+    node: &DynamicNode,
+    primary_key_properties: &[String],
+) -> Result<(), Error> {
+    for prop_name in primary_key_properties {
+        let prop_val = node.properties.get(prop_name);
 
-        Ok(())
+        match prop_val {
+            Some(val) => primary_key.push_str(&val.to_string()),
+            None => bail!(
+                format!("Node is missing required propery {} for identity", prop_name)
+            )
+        }
     }
 
+    primary_key.push_str(&node.node_type);
+
+    Ok(())
 }
 
+fn unid_for_session(pseudo_key: String, strategy: &SessionStrategy) -> Result<UnidSession, Error> {
+    // As with processes, a termination attributes to the session that was live at the time
+    let (timestamp, is_creation) = match (
+        strategy.created_time != 0,
+        strategy.last_seen_time != 0,
+        strategy.terminated_time != 0,
+    ) {
+        (true, _, _) => (strategy.created_time, true),
+        (_, _, true) => (strategy.terminated_time, false),
+        (_, true, _) => (strategy.last_seen_time, false),
+        _ => bail!("At least one timestamp must be set"),
+    };
+
+    Ok(UnidSession { pseudo_key, timestamp, is_creation })
+}
 
 #[derive(Debug, Clone)]
 pub struct DynamicNodeIdentifier<D>
-    where D: DynamoDb
+    where D: IdentityStore
 {
     asset_identifier: AssetIdentifier<D>,
     dyn_session_db: SessionDb<D>,
@@ -110,7 +112,7 @@ pub struct DynamicNodeIdentifier<D>
 }
 
 impl<D> DynamicNodeIdentifier<D>
-    where D: DynamoDb
+    where D: IdentityStore
 {
 
     pub fn new(
@@ -148,19 +150,7 @@ impl<D> DynamicNodeIdentifier<D>
             node.set_asset_id(asset_id);
         }
 
-        for prop_name in &strategy.primary_key_properties {
-            let prop_val = node.properties.get(prop_name);
-
-            match prop_val {
-                Some(val) => primary_key.push_str(&val.to_string()),
-                None => bail!(
-                format!("Node is missing required propery {} for identity", prop_name)
-            )
-            }
-        }
-
-        // Push node type, as a natural partition
-        primary_key.push_str(&node.node_key);
+        push_key_properties(&mut primary_key, node, &strategy.primary_key_properties)?;
 
         Ok(primary_key)
     }
@@ -183,17 +173,7 @@ impl<D> DynamicNodeIdentifier<D>
             node.set_asset_id(asset_id);
         }
 
-        for prop_name in &strategy.primary_key_properties {
-            let prop_val = node.properties.get(prop_name);
-
-            match prop_val {
-                Some(val) => primary_key.push_str(&val.to_string()),
-                None => bail!(
-                format!("Node is missing required propery {} for identity", prop_name)
-            )
-            }
-        }
-        primary_key.push_str(&node.node_type);
+        push_key_properties(&mut primary_key, node, &strategy.primary_key_properties)?;
 
         Ok(primary_key)
     }
@@ -207,23 +187,7 @@ impl<D> DynamicNodeIdentifier<D>
 
         let primary_key = self.primary_session_key(&mut attributed_node, strategy).await?;
 
-        let unid = match (strategy.created_time != 0, strategy.last_seen_time != 0) {
-            (true, _) => {
-                UnidSession {
-                    pseudo_key: primary_key,
-                    timestamp: strategy.created_time,
-                    is_creation: true
-                }
-            }
-            (_, true) => {
-                UnidSession {
-                    pseudo_key: primary_key,
-                    timestamp: strategy.last_seen_time,
-                    is_creation: false
-                }
-            }
-            _ => bail!("Terminating sessions not yet supported")
-        };
+        let unid = unid_for_session(primary_key, strategy)?;
 
         let session_id = self.dyn_session_db.handle_unid_session(
             unid,
@@ -244,7 +208,7 @@ impl<D> DynamicNodeIdentifier<D>
         let mut attributed_node = node.clone();
         let key = self.primary_mapping_key(&mut attributed_node, strategy).await?;
 
-        let node_key = self.dyn_mapping_db.direct_map(&key)?;
+        let node_key = self.dyn_mapping_db.direct_map(&key).await?;
 
         match node_key {
             Some(node_key) => attributed_node.set_key(node_key),
@@ -335,3 +299,46 @@ impl<D> DynamicNodeIdentifier<D>
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(created_time: u64, last_seen_time: u64, terminated_time: u64) -> SessionStrategy {
+        SessionStrategy {
+            created_time,
+            last_seen_time,
+            terminated_time,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn primary_key_is_independent_of_node_key() {
+        let node = |node_key: &str| DynamicNode {
+            node_key: node_key.to_owned(),
+            node_type: "Pod".to_owned(),
+            ..Default::default()
+        };
+
+        let mut first = String::new();
+        let mut second = String::new();
+
+        push_key_properties(&mut first, &node("a"), &[]).unwrap();
+        push_key_properties(&mut second, &node("b"), &[]).unwrap();
+
+        assert_eq!(first, "Pod");
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn terminations_attribute_to_the_live_session() {
+        let unid = unid_for_session("key".to_owned(), &session(0, 1_000, 2_000)).unwrap();
+        assert_eq!((unid.timestamp, unid.is_creation), (2_000, false));
+
+        let unid = unid_for_session("key".to_owned(), &session(500, 1_000, 2_000)).unwrap();
+        assert_eq!((unid.timestamp, unid.is_creation), (500, true));
+
+        assert!(unid_for_session("key".to_owned(), &session(0, 0, 0)).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use failure::Error;
+use rusoto_core::RusotoError;
+use rusoto_dynamodb::{
+    AttributeValue, AttributeValueUpdate, Delete, DeleteItemInput, DynamoDb, GetItemInput, Put,
+    PutItemError, PutItemInput, QueryInput, TransactWriteItem, TransactWriteItemsError,
+    TransactWriteItemsInput, UpdateItemError, UpdateItemInput, UpdateItemOutput,
+};
+
+use crate::asset_aliases::AssetAlias;
+use crate::assetdb::{AssetIdMapping, ResolvedAssetId};
+use crate::dynamic_sessiondb::{DirectMapping, ResolvedMapping};
+use crate::sessions::Session;
+use crate::storage::{AliasCondition, AssetAliasStore, AssetMappingStore, SessionStore, StaticMappingStore, VersionConflict};
+
+// Sessions, and asset id mappings, are queried for the one item before, or after, a
+// timestamp, on a table keyed on pseudo_key and the named sort key
+async fn query_one(
+    dynamo: &impl DynamoDb,
+    table_name: &str,
+    sort_key: &str,
+    pseudo_key: &str,
+    ts: u64,
+    before: bool,
+) -> Result<Option<HashMap<String, AttributeValue>>, Error> {
+    let key_condition_expression = if before {
+        format!("pseudo_key = :pseudo_key AND {} <= :ts", sort_key)
+    } else {
+        format!("pseudo_key = :pseudo_key AND {} >= :ts", sort_key)
+    };
+
+    let query = QueryInput {
+        consistent_read: Some(true),
+        limit: Some(1),
+        scan_index_forward: Some(!before),
+        table_name: table_name.to_owned(),
+        key_condition_expression: Some(key_condition_expression),
+        expression_attribute_values: Some(hmap! {
+            ":pseudo_key".to_owned() => AttributeValue {
+                s: pseudo_key.to_owned().into(),
+                ..Default::default()
+            },
+            ":ts".to_owned() => AttributeValue {
+                n: ts.to_string().into(),
+                ..Default::default()
+            }
+        }),
+        ..Default::default()
+    };
+
+    let res = wait_on!(dynamo.query(query));
+
+    if let Err(RusotoError::Unknown(ref e)) = res {
+        bail!("Query failed with error: {:?}", e);
+    };
+
+    if let Some(mut items) = res?.items {
+        match items.len() {
+            0 => Ok(None),
+            1 => Ok(Some(items.remove(0))),
+            _ => bail!("Unexpected number of items returned: {}", items.len()),
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+fn session_key(session: &Session) -> HashMap<String, AttributeValue> {
+    hmap! {
+        "pseudo_key".to_owned() => AttributeValue {
+            s: session.pseudo_key.clone().into(),
+            ..Default::default()
+        },
+        "create_time".to_owned() => AttributeValue {
+            n: session.create_time.to_string().into(),
+            ..Default::default()
+        }
+    }
+}
+
+fn version_condition(session: &Session) -> HashMap<String, AttributeValue> {
+    hmap! {
+        ":version".to_owned() => AttributeValue {
+            n: session.version.to_string().into(),
+            ..Default::default()
+        }
+    }
+}
+
+// A versioned update fails its condition when the session was updated since it was read
+fn versioned_update(
+    session: &Session,
+    res: Result<UpdateItemOutput, RusotoError<UpdateItemError>>,
+) -> Result<(), Error> {
+    match res {
+        Ok(_) => Ok(()),
+        Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => {
+            Err(VersionConflict::new(session).into())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn pseudo_key(pseudo_key: &str) -> HashMap<String, AttributeValue> {
+    hmap! {
+        "pseudo_key".to_owned() => AttributeValue {
+            s: Some(pseudo_key.to_owned()),
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl<D> SessionStore for D
+    where D: DynamoDb + Send + Sync
+{
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error> {
+        info!("Finding first session after");
+        match query_one(self, table_name, "create_time", pseudo_key, ts, false).await? {
+            Some(item) => Session::try_from(item).map(Option::from),
+            None => Ok(None),
+        }
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error> {
+        info!("Finding last session before");
+        match query_one(self, table_name, "create_time", pseudo_key, ts, true).await? {
+            Some(item) => Session::try_from(item).map(Option::from),
+            None => Ok(None),
+        }
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let put_req = PutItemInput {
+            item: serde_dynamodb::to_hashmap(session).unwrap(),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        wait_on!(self.put_item(put_req))?;
+
+        Ok(())
+    }
+
+    // `create_time` is the sort key in the table, so updating it is not possible.
+    // Instead, in one transaction, the row must be deleted and recreated with the
+    // new create_time
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let mut new_session = session.to_owned();
+        new_session.create_time = new_time;
+        new_session.is_create_canon = is_canon;
+        new_session.version += 1;
+
+        let put_req = Put {
+            item: serde_dynamodb::to_hashmap(&new_session).unwrap(),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        // Use version as a constraint
+        let del_req = Delete {
+            key: session_key(session),
+            table_name: table_name.to_owned(),
+            condition_expression: Some("version = :version".into()),
+            expression_attribute_values: Some(version_condition(session)),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.transact_write_items(TransactWriteItemsInput {
+            transact_items: vec![
+                TransactWriteItem {
+                    delete: del_req.into(),
+                    ..Default::default()
+                },
+                TransactWriteItem {
+                    put: put_req.into(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }));
+
+        match res {
+            Ok(_) => Ok(()),
+            // The only condition in the transaction is the session's version
+            Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(_))) => {
+                Err(VersionConflict::new(session).into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        // Use version as a constraint
+        let upd_req = UpdateItemInput {
+            key: session_key(session),
+            attribute_updates: Some(hmap! {
+                "end_time".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                        n: new_time.to_string().into(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                "is_end_canon".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                            bool: is_canon.into(),
+                            ..Default::default()
+                        }),
+                    ..Default::default()
+                },
+                "version".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                        n: (session.version + 1).to_string().into(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+
+                }
+            }),
+            table_name: table_name.to_owned(),
+            condition_expression: Some("version = :version".into()),
+            expression_attribute_values: Some(version_condition(session)),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.update_item(upd_req));
+
+        versioned_update(session, res)
+    }
+
+    async fn make_create_time_canonical(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        // Use version as a constraint
+        let upd_req = UpdateItemInput {
+            key: session_key(session),
+            attribute_updates: Some(hmap! {
+                "is_create_canon".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                            bool: true.into(),
+                            ..Default::default()
+                        }),
+                    ..Default::default()
+                },
+                "version".to_owned() => AttributeValueUpdate {
+                    value: Some(AttributeValue {
+                        n: (session.version + 1).to_string().into(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }
+            }),
+            table_name: table_name.to_owned(),
+            condition_expression: Some("version = :version".into()),
+            expression_attribute_values: Some(version_condition(session)),
+            ..Default::default()
+        };
+
+        let res = wait_on!(self.update_item(upd_req));
+
+        versioned_update(session, res)
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let del_req = DeleteItemInput {
+            key: session_key(session),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        wait_on!(self.delete_item(del_req))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<D> AssetMappingStore for D
+    where D: DynamoDb + Send + Sync
+{
+    async fn find_first_mapping_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error> {
+        match query_one(self, table_name, "c_timestamp", pseudo_key, ts, false).await? {
+            Some(item) => Ok(Some(serde_dynamodb::from_hashmap(item)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn find_last_mapping_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error> {
+        match query_one(self, table_name, "c_timestamp", pseudo_key, ts, true).await? {
+            Some(item) => Ok(Some(serde_dynamodb::from_hashmap(item)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn put_mapping(&self, table_name: &str, mapping: &AssetIdMapping<'_>) -> Result<(), Error> {
+        let put_req = PutItemInput {
+            item: serde_dynamodb::to_hashmap(mapping).unwrap(),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        let put_item_response = wait_on!(self.put_item(put_req))?;
+
+        info!("PutItemResponse for {}: {:?}", mapping.pseudo_key, put_item_response);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<D> StaticMappingStore for D
+    where D: DynamoDb + Send + Sync
+{
+    async fn get_static_mapping(&self, table_name: &str, key: &str) -> Result<Option<String>, Error> {
+        let query = GetItemInput {
+            consistent_read: Some(true),
+            table_name: table_name.to_owned(),
+            key: pseudo_key(key),
+            ..Default::default()
+        };
+
+        match wait_on!(self.get_item(query))?.item {
+            Some(item) => {
+                let mapping: ResolvedMapping = serde_dynamodb::from_hashmap(item)?;
+                Ok(Some(mapping.mapping))
+            }
+            None => Ok(None)
+        }
+    }
+
+    async fn put_static_mapping(&self, table_name: &str, key: &str, mapping: &str) -> Result<(), Error> {
+        let mapping = DirectMapping {
+            pseudo_key: key.to_owned(),
+            mapping: mapping.to_owned(),
+        };
+
+        let put_req = PutItemInput {
+            item: serde_dynamodb::to_hashmap(&mapping).unwrap(),
+            table_name: table_name.to_owned(),
+            ..Default::default()
+        };
+
+        wait_on!(self.put_item(put_req))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<D> AssetAliasStore for D
+    where D: DynamoDb + Send + Sync
+{
+    async fn get_alias(&self, table_name: &str, asset_id: &str) -> Result<Option<String>, Error> {
+        let query = GetItemInput {
+            consistent_read: Some(true),
+            table_name: table_name.to_owned(),
+            key: pseudo_key(asset_id),
+            ..Default::default()
+        };
+
+        match wait_on!(self.get_item(query))?.item {
+            Some(item) => {
+                let alias: AssetAlias = serde_dynamodb::from_hashmap(item)?;
+                Ok(Some(alias.canonical_asset_id))
+            }
+            None => Ok(None)
+        }
+    }
+
+    async fn put_alias(
+        &self,
+        table_name: &str,
+        asset_id: &str,
+        canonical_asset_id: &str,
+        condition: AliasCondition<'_>,
+    ) -> Result<bool, Error> {
+        let alias = AssetAlias {
+            pseudo_key: asset_id.to_owned(),
+            canonical_asset_id: canonical_asset_id.to_owned(),
+        };
+
+        let (condition_expression, expression_attribute_values) = match condition {
+            AliasCondition::Absent => ("attribute_not_exists(pseudo_key)", None),
+            AliasCondition::AliasOf(expected) => (
+                "canonical_asset_id = :expected",
+                Some(hmap! {
+                    ":expected".to_owned() => AttributeValue {
+                        s: expected.to_owned().into(),
+                        ..Default::default()
+                    }
+                }),
+            ),
+        };
+
+        let put_req = PutItemInput {
+            item: serde_dynamodb::to_hashmap(&alias).unwrap(),
+            table_name: table_name.to_owned(),
+            condition_expression: Some(condition_expression.to_owned()),
+            expression_attribute_values,
+            ..Default::default()
+        };
+
+        match wait_on!(self.put_item(put_req)) {
+            Ok(_) => Ok(true),
+            Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(_))) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,770 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures01::future;
+use rusoto_core::{RusotoError, RusotoFuture};
+use rusoto_dynamodb::*;
+
+// A DynamoDb that keeps its tables in memory, so that the identity tests run without a
+// local DynamoDB. It supports the requests node-identifier makes:
+//
+// * create_table, and delete_table
+// * get_item, put_item, update_item, and delete_item, with condition expressions
+// * query, with key condition expressions, ScanIndexForward, and Limit
+// * transact_write_items, which is applied only if all of its conditions hold
+//
+// Expressions are limited to comparisons of an attribute and a value, attribute_exists,
+// and attribute_not_exists, joined with AND, and update expressions to SET.
+
+type Item = HashMap<String, AttributeValue>;
+
+// The rest of the DynamoDb trait, which node-identifier does not use, fails as an
+// invalid request would
+macro_rules! unsupported {
+    ($($method:ident($input:ty) -> $output:ty, $error:ty;)*) => {
+        $(
+            fn $method(&self, _input: $input) -> RusotoFuture<$output, $error> {
+                unsupported(stringify!($method))
+            }
+        )*
+    };
+}
+
+#[derive(Debug, Default)]
+struct Table {
+    hash_key: String,
+    range_key: Option<String>,
+    items: Vec<Item>,
+}
+
+impl Table {
+    fn key_names(&self) -> Vec<&str> {
+        let mut names = vec![self.hash_key.as_str()];
+        names.extend(self.range_key.as_ref().map(String::as_str));
+        names
+    }
+
+    fn position(&self, key: &Item) -> Option<usize> {
+        let key_names = self.key_names();
+
+        self.items.iter().position(|item| {
+            key_names.iter().all(|name| match (item.get(*name), key.get(*name)) {
+                (Some(a), Some(b)) => compare(a, b) == Some(Ordering::Equal),
+                _ => false,
+            })
+        })
+    }
+
+    fn get(&self, key: &Item) -> Option<&Item> {
+        self.position(key).map(|i| &self.items[i])
+    }
+
+    fn put(&mut self, item: Item) {
+        match self.position(&item) {
+            Some(i) => self.items[i] = item,
+            None => self.items.push(item),
+        }
+    }
+
+    fn delete(&mut self, key: &Item) -> Option<Item> {
+        self.position(key).map(|i| self.items.remove(i))
+    }
+}
+
+/// An in memory DynamoDb for tests. Clones share their tables.
+#[derive(Debug, Clone, Default)]
+pub struct FakeDynamoDb {
+    tables: Arc<Mutex<HashMap<String, Table>>>,
+}
+
+impl FakeDynamoDb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a table keyed on `hash_key`, and `range_key`, if there is one
+    pub fn with_table(self, table_name: &str, hash_key: &str, range_key: Option<&str>) -> Self {
+        self.tables().insert(
+            table_name.to_owned(),
+            Table {
+                hash_key: hash_key.to_owned(),
+                range_key: range_key.map(str::to_owned),
+                items: vec![],
+            },
+        );
+        self
+    }
+
+    fn tables(&self) -> MutexGuard<HashMap<String, Table>> {
+        match self.tables.lock() {
+            Ok(tables) => tables,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+fn done<T, E>(result: Result<T, RusotoError<E>>) -> RusotoFuture<T, E>
+    where T: Send + 'static,
+          E: Send + 'static,
+{
+    RusotoFuture::from_future(future::result(result))
+}
+
+fn validation<E>(message: impl Into<String>) -> RusotoError<E> {
+    RusotoError::Validation(message.into())
+}
+
+fn unsupported<T, E>(method: &str) -> RusotoFuture<T, E>
+    where T: Send + 'static,
+          E: Send + 'static,
+{
+    done(Err(validation(format!("FakeDynamoDb does not support {}", method))))
+}
+
+fn compare(a: &AttributeValue, b: &AttributeValue) -> Option<Ordering> {
+    match (a, b) {
+        (AttributeValue { n: Some(a), .. }, AttributeValue { n: Some(b), .. }) => {
+            match (a.parse::<i128>(), b.parse::<i128>()) {
+                (Ok(a), Ok(b)) => Some(a.cmp(&b)),
+                _ => a.parse::<f64>().ok()?.partial_cmp(&b.parse::<f64>().ok()?),
+            }
+        }
+        (AttributeValue { s: Some(a), .. }, AttributeValue { s: Some(b), .. }) => Some(a.cmp(b)),
+        (AttributeValue { bool: Some(a), .. }, AttributeValue { bool: Some(b), .. }) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+// The values, and names, an expression's placeholders refer to
+struct Placeholders<'a> {
+    names: Option<&'a HashMap<String, String>>,
+    values: Option<&'a HashMap<String, AttributeValue>>,
+}
+
+impl<'a> Placeholders<'a> {
+    fn name(&self, name: &'a str) -> &'a str {
+        match self.names.and_then(|names| names.get(name)) {
+            Some(name) => name,
+            None => name,
+        }
+    }
+
+    fn value(&self, placeholder: &str) -> Result<&'a AttributeValue, String> {
+        self.values
+            .and_then(|values| values.get(placeholder))
+            .ok_or_else(|| format!("Missing expression attribute value {}", placeholder))
+    }
+}
+
+fn split_and(expression: &str) -> Vec<&str> {
+    expression
+        .split(" AND ")
+        .flat_map(|term| term.split(" and "))
+        .map(str::trim)
+        .filter(|term| !term.is_empty())
+        .collect()
+}
+
+fn function_argument<'a>(term: &'a str, function: &str) -> Option<&'a str> {
+    if term.starts_with(function) && term.ends_with(')') {
+        Some(term[function.len()..term.len() - 1].trim_start_matches('(').trim())
+    } else {
+        None
+    }
+}
+
+// Parses `name <op> :value` into its parts
+fn comparison(term: &str) -> Result<(&str, &str, &str), String> {
+    for op in &["<=", ">=", "<>", "=", "<", ">"] {
+        if let Some(i) = term.find(op) {
+            return Ok((term[..i].trim(), *op, term[i + op.len()..].trim()));
+        }
+    }
+
+    Err(format!("Unsupported expression: {}", term))
+}
+
+fn holds(ordering: Option<Ordering>, op: &str) -> bool {
+    match (ordering, op) {
+        (Some(Ordering::Equal), "=") | (Some(Ordering::Equal), "<=") | (Some(Ordering::Equal), ">=") => true,
+        (Some(Ordering::Less), "<") | (Some(Ordering::Less), "<=") => true,
+        (Some(Ordering::Greater), ">") | (Some(Ordering::Greater), ">=") => true,
+        (Some(Ordering::Less), "<>") | (Some(Ordering::Greater), "<>") => true,
+        _ => false,
+    }
+}
+
+fn matches(item: Option<&Item>, expression: &str, placeholders: &Placeholders) -> Result<bool, String> {
+    for term in split_and(expression) {
+        let holds = if let Some(name) = function_argument(term, "attribute_not_exists") {
+            item.and_then(|item| item.get(placeholders.name(name))).is_none()
+        } else if let Some(name) = function_argument(term, "attribute_exists") {
+            item.and_then(|item| item.get(placeholders.name(name))).is_some()
+        } else {
+            let (name, op, value) = comparison(term)?;
+            let value = placeholders.value(value)?;
+
+            match item.and_then(|item| item.get(placeholders.name(name))) {
+                Some(attribute) => holds(compare(attribute, value), op),
+                None => false,
+            }
+        };
+
+        if !holds {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn check(item: Option<&Item>, condition: &Option<String>, placeholders: &Placeholders) -> Result<bool, String> {
+    match condition {
+        Some(condition) => matches(item, condition, placeholders),
+        None => Ok(true),
+    }
+}
+
+// Applies `SET a = :a, b = :b`
+fn apply_update_expression(item: &mut Item, expression: &str, placeholders: &Placeholders) -> Result<(), String> {
+    let expression = expression.trim();
+
+    if !expression.starts_with("SET ") {
+        return Err(format!("Unsupported update expression: {}", expression));
+    }
+
+    for assignment in expression[4..].split(',') {
+        match comparison(assignment.trim())? {
+            (name, "=", value) => {
+                let value = placeholders.value(value)?.clone();
+                item.insert(placeholders.name(name).to_owned(), value);
+            }
+            _ => return Err(format!("Unsupported update expression: {}", expression)),
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_attribute_updates(item: &mut Item, updates: &HashMap<String, AttributeValueUpdate>) -> Result<(), String> {
+    for (name, update) in updates {
+        match (update.action.as_ref().map(String::as_str), &update.value) {
+            (None, Some(value)) | (Some("PUT"), Some(value)) => {
+                item.insert(name.to_owned(), value.clone());
+            }
+            (Some("DELETE"), None) => {
+                item.remove(name);
+            }
+            _ => return Err(format!("Unsupported attribute update for {}: {:?}", name, update)),
+        }
+    }
+
+    Ok(())
+}
+
+// A copy of the item, or of its key if it does not exist, with the update applied
+fn updated_item(
+    existing: Option<&Item>,
+    key: &Item,
+    update_expression: &Option<String>,
+    attribute_updates: &Option<HashMap<String, AttributeValueUpdate>>,
+    placeholders: &Placeholders,
+) -> Result<Item, String> {
+    let mut item = existing.cloned().unwrap_or_else(|| key.clone());
+
+    if let Some(update_expression) = update_expression {
+        apply_update_expression(&mut item, update_expression, placeholders)?;
+    }
+
+    if let Some(attribute_updates) = attribute_updates {
+        apply_attribute_updates(&mut item, attribute_updates)?;
+    }
+
+    Ok(item)
+}
+
+impl DynamoDb for FakeDynamoDb {
+    fn create_table(&self, input: CreateTableInput) -> RusotoFuture<CreateTableOutput, CreateTableError> {
+        let mut tables = self.tables();
+
+        if tables.contains_key(&input.table_name) {
+            return done(Err(RusotoError::Service(CreateTableError::ResourceInUse(input.table_name))));
+        }
+
+        let key = |key_type: &str| {
+            input.key_schema.iter()
+                .find(|key| key.key_type == key_type)
+                .map(|key| key.attribute_name.clone())
+        };
+
+        let hash_key = match key("HASH") {
+            Some(hash_key) => hash_key,
+            None => return done(Err(validation("Table must have a HASH key"))),
+        };
+
+        tables.insert(input.table_name.clone(), Table { hash_key, range_key: key("RANGE"), items: vec![] });
+
+        done(Ok(CreateTableOutput::default()))
+    }
+
+    fn delete_table(&self, input: DeleteTableInput) -> RusotoFuture<DeleteTableOutput, DeleteTableError> {
+        match self.tables().remove(&input.table_name) {
+            Some(_) => done(Ok(DeleteTableOutput::default())),
+            None => done(Err(RusotoError::Service(DeleteTableError::ResourceNotFound(input.table_name)))),
+        }
+    }
+
+    fn get_item(&self, input: GetItemInput) -> RusotoFuture<GetItemOutput, GetItemError> {
+        let tables = self.tables();
+
+        let table = match tables.get(&input.table_name) {
+            Some(table) => table,
+            None => return done(Err(RusotoError::Service(GetItemError::ResourceNotFound(input.table_name)))),
+        };
+
+        done(Ok(GetItemOutput {
+            item: table.get(&input.key).cloned(),
+            ..Default::default()
+        }))
+    }
+
+    fn put_item(&self, input: PutItemInput) -> RusotoFuture<PutItemOutput, PutItemError> {
+        let mut tables = self.tables();
+
+        let table = match tables.get_mut(&input.table_name) {
+            Some(table) => table,
+            None => return done(Err(RusotoError::Service(PutItemError::ResourceNotFound(input.table_name)))),
+        };
+
+        let placeholders = Placeholders {
+            names: input.expression_attribute_names.as_ref(),
+            values: input.expression_attribute_values.as_ref(),
+        };
+
+        match check(table.get(&input.item), &input.condition_expression, &placeholders) {
+            Ok(true) => (),
+            Ok(false) => {
+                return done(Err(RusotoError::Service(PutItemError::ConditionalCheckFailed(
+                    "The conditional request failed".to_owned(),
+                ))));
+            }
+            Err(e) => return done(Err(validation(e))),
+        }
+
+        table.put(input.item);
+
+        done(Ok(PutItemOutput::default()))
+    }
+
+    fn update_item(&self, input: UpdateItemInput) -> RusotoFuture<UpdateItemOutput, UpdateItemError> {
+        let mut tables = self.tables();
+
+        let table = match tables.get_mut(&input.table_name) {
+            Some(table) => table,
+            None => return done(Err(RusotoError::Service(UpdateItemError::ResourceNotFound(input.table_name)))),
+        };
+
+        let placeholders = Placeholders {
+            names: input.expression_attribute_names.as_ref(),
+            values: input.expression_attribute_values.as_ref(),
+        };
+
+        let existing = table.get(&input.key);
+
+        match check(existing, &input.condition_expression, &placeholders) {
+            Ok(true) => (),
+            Ok(false) => {
+                return done(Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(
+                    "The conditional request failed".to_owned(),
+                ))));
+            }
+            Err(e) => return done(Err(validation(e))),
+        }
+
+        let item = updated_item(
+            existing,
+            &input.key,
+            &input.update_expression,
+            &input.attribute_updates,
+            &placeholders,
+        );
+
+        match item {
+            Ok(item) => table.put(item),
+            Err(e) => return done(Err(validation(e))),
+        }
+
+        done(Ok(UpdateItemOutput::default()))
+    }
+
+    fn delete_item(&self, input: DeleteItemInput) -> RusotoFuture<DeleteItemOutput, DeleteItemError> {
+        let mut tables = self.tables();
+
+        let table = match tables.get_mut(&input.table_name) {
+            Some(table) => table,
+            None => return done(Err(RusotoError::Service(DeleteItemError::ResourceNotFound(input.table_name)))),
+        };
+
+        let placeholders = Placeholders {
+            names: input.expression_attribute_names.as_ref(),
+            values: input.expression_attribute_values.as_ref(),
+        };
+
+        match check(table.get(&input.key), &input.condition_expression, &placeholders) {
+            Ok(true) => (),
+            Ok(false) => {
+                return done(Err(RusotoError::Service(DeleteItemError::ConditionalCheckFailed(
+                    "The conditional request failed".to_owned(),
+                ))));
+            }
+            Err(e) => return done(Err(validation(e))),
+        }
+
+        table.delete(&input.key);
+
+        done(Ok(DeleteItemOutput::default()))
+    }
+
+    fn query(&self, input: QueryInput) -> RusotoFuture<QueryOutput, QueryError> {
+        let tables = self.tables();
+
+        let table = match tables.get(&input.table_name) {
+            Some(table) => table,
+            None => return done(Err(RusotoError::Service(QueryError::ResourceNotFound(input.table_name)))),
+        };
+
+        let key_condition_expression = match input.key_condition_expression {
+            Some(ref key_condition_expression) => key_condition_expression,
+            None => return done(Err(validation("Query must have a KeyConditionExpression"))),
+        };
+
+        let placeholders = Placeholders {
+            names: input.expression_attribute_names.as_ref(),
+            values: input.expression_attribute_values.as_ref(),
+        };
+
+        let mut items = vec![];
+
+        for item in table.items.iter() {
+            match matches(Some(item), key_condition_expression, &placeholders) {
+                Ok(true) => items.push(item.clone()),
+                Ok(false) => (),
+                Err(e) => return done(Err(validation(e))),
+            }
+        }
+
+        if let Some(ref range_key) = table.range_key {
+            items.sort_by(|a, b| {
+                match (a.get(range_key), b.get(range_key)) {
+                    (Some(a), Some(b)) => compare(a, b).unwrap_or(Ordering::Equal),
+                    _ => Ordering::Equal,
+                }
+            });
+        }
+
+        if input.scan_index_forward == Some(false) {
+            items.reverse();
+        }
+
+        if let Some(limit) = input.limit {
+            items.truncate(limit as usize);
+        }
+
+        done(Ok(QueryOutput {
+            count: Some(items.len() as i64),
+            items: Some(items),
+            ..Default::default()
+        }))
+    }
+
+    fn transact_write_items(
+        &self,
+        input: TransactWriteItemsInput,
+    ) -> RusotoFuture<TransactWriteItemsOutput, TransactWriteItemsError> {
+        let mut tables = self.tables();
+
+        // Every write is made to a copy of the tables it touches, which replace the tables
+        // only if all of the writes succeed
+        let mut written: HashMap<String, Vec<Item>> = HashMap::new();
+
+        for transact_item in input.transact_items {
+            let (table_name, key, condition, placeholders_of) = match transact_item {
+                TransactWriteItem { put: Some(ref put), .. } => (
+                    &put.table_name, &put.item, &put.condition_expression,
+                    (&put.expression_attribute_names, &put.expression_attribute_values),
+                ),
+                TransactWriteItem { delete: Some(ref delete), .. } => (
+                    &delete.table_name, &delete.key, &delete.condition_expression,
+                    (&delete.expression_attribute_names, &delete.expression_attribute_values),
+                ),
+                TransactWriteItem { update: Some(ref update), .. } => (
+                    &update.table_name, &update.key, &update.condition_expression,
+                    (&update.expression_attribute_names, &update.expression_attribute_values),
+                ),
+                TransactWriteItem { condition_check: Some(ref condition_check), .. } => (
+                    &condition_check.table_name,
+                    &condition_check.key,
+                    // A ConditionCheck always has a condition
+                    &None,
+                    (&condition_check.expression_attribute_names, &condition_check.expression_attribute_values),
+                ),
+                _ => return done(Err(validation("TransactWriteItem must have one action"))),
+            };
+
+            let table = match tables.get(table_name) {
+                Some(table) => table,
+                None => return done(Err(validation(format!("Table not found: {}", table_name)))),
+            };
+
+            let mut copy = Table {
+                hash_key: table.hash_key.clone(),
+                range_key: table.range_key.clone(),
+                items: written.remove(table_name).unwrap_or_else(|| table.items.clone()),
+            };
+
+            let placeholders = Placeholders {
+                names: placeholders_of.0.as_ref(),
+                values: placeholders_of.1.as_ref(),
+            };
+
+            let condition = match transact_item.condition_check {
+                Some(ref condition_check) => Some(condition_check.condition_expression.clone()),
+                None => condition.clone(),
+            };
+
+            match check(copy.get(key), &condition, &placeholders) {
+                Ok(true) => (),
+                Ok(false) => {
+                    return done(Err(RusotoError::Service(TransactWriteItemsError::TransactionCanceled(
+                        "Transaction cancelled, please refer cancellation reasons for specific reasons [ConditionalCheckFailed]".to_owned(),
+                    ))));
+                }
+                Err(e) => return done(Err(validation(e))),
+            }
+
+            if let Some(ref put) = transact_item.put {
+                copy.put(put.item.clone());
+            } else if let Some(ref delete) = transact_item.delete {
+                copy.delete(&delete.key);
+            } else if let Some(ref update) = transact_item.update {
+                let item = updated_item(
+                    copy.get(&update.key),
+                    &update.key,
+                    &Some(update.update_expression.clone()),
+                    &None,
+                    &placeholders,
+                );
+
+                match item {
+                    Ok(item) => copy.put(item),
+                    Err(e) => return done(Err(validation(e))),
+                }
+            }
+
+            written.insert(table_name.to_owned(), copy.items);
+        }
+
+        for (table_name, items) in written {
+            if let Some(table) = tables.get_mut(&table_name) {
+                table.items = items;
+            }
+        }
+
+        done(Ok(TransactWriteItemsOutput::default()))
+    }
+
+    unsupported! {
+        batch_get_item(BatchGetItemInput) -> BatchGetItemOutput, BatchGetItemError;
+        batch_write_item(BatchWriteItemInput) -> BatchWriteItemOutput, BatchWriteItemError;
+        create_backup(CreateBackupInput) -> CreateBackupOutput, CreateBackupError;
+        create_global_table(CreateGlobalTableInput) -> CreateGlobalTableOutput, CreateGlobalTableError;
+        delete_backup(DeleteBackupInput) -> DeleteBackupOutput, DeleteBackupError;
+        describe_backup(DescribeBackupInput) -> DescribeBackupOutput, DescribeBackupError;
+        describe_continuous_backups(DescribeContinuousBackupsInput)
+            -> DescribeContinuousBackupsOutput, DescribeContinuousBackupsError;
+        describe_global_table(DescribeGlobalTableInput) -> DescribeGlobalTableOutput, DescribeGlobalTableError;
+        describe_global_table_settings(DescribeGlobalTableSettingsInput)
+            -> DescribeGlobalTableSettingsOutput, DescribeGlobalTableSettingsError;
+        describe_table(DescribeTableInput) -> DescribeTableOutput, DescribeTableError;
+        describe_time_to_live(DescribeTimeToLiveInput) -> DescribeTimeToLiveOutput, DescribeTimeToLiveError;
+        list_backups(ListBackupsInput) -> ListBackupsOutput, ListBackupsError;
+        list_global_tables(ListGlobalTablesInput) -> ListGlobalTablesOutput, ListGlobalTablesError;
+        list_tables(ListTablesInput) -> ListTablesOutput, ListTablesError;
+        list_tags_of_resource(ListTagsOfResourceInput) -> ListTagsOfResourceOutput, ListTagsOfResourceError;
+        restore_table_from_backup(RestoreTableFromBackupInput)
+            -> RestoreTableFromBackupOutput, RestoreTableFromBackupError;
+        restore_table_to_point_in_time(RestoreTableToPointInTimeInput)
+            -> RestoreTableToPointInTimeOutput, RestoreTableToPointInTimeError;
+        scan(ScanInput) -> ScanOutput, ScanError;
+        tag_resource(TagResourceInput) -> (), TagResourceError;
+        transact_get_items(TransactGetItemsInput) -> TransactGetItemsOutput, TransactGetItemsError;
+        untag_resource(UntagResourceInput) -> (), UntagResourceError;
+        update_continuous_backups(UpdateContinuousBackupsInput)
+            -> UpdateContinuousBackupsOutput, UpdateContinuousBackupsError;
+        update_global_table(UpdateGlobalTableInput) -> UpdateGlobalTableOutput, UpdateGlobalTableError;
+        update_global_table_settings(UpdateGlobalTableSettingsInput)
+            -> UpdateGlobalTableSettingsOutput, UpdateGlobalTableSettingsError;
+        update_table(UpdateTableInput) -> UpdateTableOutput, UpdateTableError;
+        update_time_to_live(UpdateTimeToLiveInput) -> UpdateTimeToLiveOutput, UpdateTimeToLiveError;
+    }
+
+    fn describe_endpoints(&self) -> RusotoFuture<DescribeEndpointsResponse, DescribeEndpointsError> {
+        unsupported("describe_endpoints")
+    }
+
+    fn describe_limits(&self) -> RusotoFuture<DescribeLimitsOutput, DescribeLimitsError> {
+        unsupported("describe_limits")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn n(n: u64) -> AttributeValue {
+        AttributeValue {
+            n: Some(n.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn s(s: &str) -> AttributeValue {
+        AttributeValue {
+            s: Some(s.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    fn item(pseudo_key: &str, create_time: u64, version: u64) -> Item {
+        hmap! {
+            "pseudo_key".to_owned() => s(pseudo_key),
+            "create_time".to_owned() => n(create_time),
+            "version".to_owned() => n(version)
+        }
+    }
+
+    fn fake() -> FakeDynamoDb {
+        FakeDynamoDb::new().with_table("sessions", "pseudo_key", Some("create_time"))
+    }
+
+    #[test]
+    fn query_orders_on_the_range_key() {
+        let dynamo = fake();
+
+        for create_time in &[900, 1100, 1000] {
+            dynamo.put_item(PutItemInput {
+                table_name: "sessions".to_owned(),
+                item: item("asset_id_a1234", *create_time, 0),
+                ..Default::default()
+            })
+                .sync()
+                .unwrap();
+        }
+
+        let query = |scan_index_forward| QueryInput {
+            table_name: "sessions".to_owned(),
+            key_condition_expression: Some("pseudo_key = :pseudo_key AND create_time <= :ts".to_owned()),
+            expression_attribute_values: Some(hmap! {
+                ":pseudo_key".to_owned() => s("asset_id_a1234"),
+                ":ts".to_owned() => n(1000)
+            }),
+            scan_index_forward: Some(scan_index_forward),
+            limit: Some(1),
+            ..Default::default()
+        };
+
+        let first = dynamo.query(query(true)).sync().unwrap().items.unwrap();
+        let last = dynamo.query(query(false)).sync().unwrap().items.unwrap();
+
+        assert_eq!(first, vec![item("asset_id_a1234", 900, 0)]);
+        assert_eq!(last, vec![item("asset_id_a1234", 1000, 0)]);
+    }
+
+    #[test]
+    fn unsupported_requests_are_invalid() {
+        match fake().describe_limits().sync() {
+            Err(RusotoError::Validation(message)) => assert!(message.contains("describe_limits")),
+            other => panic!("Expected a validation error, got {:?}", other),
+        }
+
+        match fake().scan(ScanInput::default()).sync() {
+            Err(RusotoError::Validation(message)) => assert!(message.contains("scan")),
+            other => panic!("Expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn failed_conditions_do_not_write() {
+        let dynamo = fake();
+
+        dynamo.put_item(PutItemInput {
+            table_name: "sessions".to_owned(),
+            item: item("asset_id_a1234", 1000, 1),
+            ..Default::default()
+        })
+            .sync()
+            .unwrap();
+
+        let stale_update = dynamo.update_item(UpdateItemInput {
+            table_name: "sessions".to_owned(),
+            key: hmap! {
+                "pseudo_key".to_owned() => s("asset_id_a1234"),
+                "create_time".to_owned() => n(1000)
+            },
+            update_expression: Some("SET version = :new_version".to_owned()),
+            condition_expression: Some("version = :version".to_owned()),
+            expression_attribute_values: Some(hmap! {
+                ":version".to_owned() => n(0),
+                ":new_version".to_owned() => n(1)
+            }),
+            ..Default::default()
+        })
+            .sync();
+
+        match stale_update {
+            Err(RusotoError::Service(UpdateItemError::ConditionalCheckFailed(_))) => (),
+            other => panic!("Expected a failed condition, got {:?}", other),
+        }
+
+        // The put succeeds, but the transaction is cancelled by the failed delete
+        let transaction = dynamo.transact_write_items(TransactWriteItemsInput {
+            transact_items: vec![
+                TransactWriteItem {
+                    put: Some(Put {
+                        table_name: "sessions".to_owned(),
+                        item: item("asset_id_a1234", 2000, 0),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                TransactWriteItem {
+                    delete: Some(Delete {
+                        table_name: "sessions".to_owned(),
+                        key: item("asset_id_a1234", 1000, 1),
+                        condition_expression: Some("attribute_not_exists(pseudo_key)".to_owned()),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        })
+            .sync();
+
+        assert!(transaction.is_err());
+
+        let stored = dynamo.get_item(GetItemInput {
+            table_name: "sessions".to_owned(),
+            key: hmap! {
+                "pseudo_key".to_owned() => s("asset_id_a1234"),
+                "create_time".to_owned() => n(2000)
+            },
+            ..Default::default()
+        })
+            .sync()
+            .unwrap();
+
+        assert!(stored.item.is_none());
+    }
+}
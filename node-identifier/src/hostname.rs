@@ -0,0 +1,112 @@
+use std::env;
+
+// NetBIOS names are at most 15 characters, with a 16th reserved for the service type
+const NETBIOS_NAME_LEN: usize = 15;
+
+/// Normalizes hostnames, so that the forms a host is logged by map to one asset id.
+///
+/// Hostnames are case folded, and internationalized names are encoded with punycode.
+/// A hostname in one of the configured domains is stripped of its domain, so that
+/// `win-01.corp.local` is `win-01`, as it would be logged by its NetBIOS name.
+#[derive(Debug, Clone, Default)]
+pub struct HostnameNormalizer {
+    domain_suffixes: Vec<String>,
+    netbios_truncation: bool,
+}
+
+impl HostnameNormalizer {
+    /// `domain_suffixes` are stripped from hostnames, and with `netbios_truncation`
+    /// hostnames are truncated to the length of a NetBIOS name
+    pub fn new(domain_suffixes: Vec<String>, netbios_truncation: bool) -> Self {
+        let mut domain_suffixes: Vec<_> = domain_suffixes.iter()
+            .map(|suffix| encode_hostname(suffix.trim().trim_matches('.')))
+            .filter(|suffix| !suffix.is_empty())
+            .collect();
+
+        // The longest suffix is stripped when domains are nested, ie: corp.local and local
+        domain_suffixes.sort_by(|a, b| b.len().cmp(&a.len()));
+
+        Self { domain_suffixes, netbios_truncation }
+    }
+
+    /// Reads the comma separated `HOSTNAME_DOMAIN_SUFFIXES`, and `HOSTNAME_NETBIOS_TRUNCATION`
+    pub fn from_env() -> Self {
+        let domain_suffixes = env::var("HOSTNAME_DOMAIN_SUFFIXES")
+            .map(|suffixes| suffixes.split(',').map(str::to_owned).collect())
+            .unwrap_or_default();
+
+        let netbios_truncation = env::var("HOSTNAME_NETBIOS_TRUNCATION")
+            .map(|truncation| truncation == "1" || truncation.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        Self::new(domain_suffixes, netbios_truncation)
+    }
+
+    pub fn normalize(&self, hostname: &str) -> String {
+        // Windows logs hostnames qualified by their NetBIOS domain, ie: CORP\WIN-01
+        let hostname = match hostname.rfind('\\') {
+            Some(i) => &hostname[i + 1..],
+            None => hostname,
+        };
+
+        // A fully qualified name may end with the root domain, ie: win-01.corp.local.
+        let mut hostname = encode_hostname(hostname.trim().trim_end_matches('.'));
+
+        for suffix in self.domain_suffixes.iter() {
+            if hostname.len() > suffix.len()
+                && hostname.ends_with(suffix.as_str())
+                && hostname[..hostname.len() - suffix.len()].ends_with('.')
+            {
+                hostname.truncate(hostname.len() - suffix.len() - 1);
+                break;
+            }
+        }
+
+        // A host with a longer name is logged by the first 15 characters of it, by NetBIOS
+        if self.netbios_truncation && !hostname.contains('.') {
+            if let Some((i, _)) = hostname.char_indices().nth(NETBIOS_NAME_LEN) {
+                hostname.truncate(i);
+            }
+        }
+
+        hostname
+    }
+}
+
+// Case folds the hostname, and encodes internationalized labels with punycode, ie:
+// bücher.example is xn--bcher-kva.example
+fn encode_hostname(hostname: &str) -> String {
+    match idna::domain_to_ascii(hostname) {
+        Ok(hostname) => hostname,
+        // Names that are not valid domains, ie: NetBIOS names with reserved characters,
+        // are only case folded
+        Err(_) => hostname.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hostname_forms_are_normalized() {
+        let normalizer = HostnameNormalizer::new(vec![".corp.local".to_owned()], false);
+
+        assert_eq!(normalizer.normalize("WIN-01"), "win-01");
+        assert_eq!(normalizer.normalize("win-01.corp.local"), "win-01");
+        assert_eq!(normalizer.normalize("WIN-01.CORP.LOCAL."), "win-01");
+        assert_eq!(normalizer.normalize("CORP\\WIN-01"), "win-01");
+        assert_eq!(normalizer.normalize("win-01.other.local"), "win-01.other.local");
+        assert_eq!(normalizer.normalize("Bücher.corp.local"), "xn--bcher-kva");
+    }
+
+    #[test]
+    fn netbios_names_are_truncated() {
+        let normalizer = HostnameNormalizer::new(vec!["corp.local".to_owned()], true);
+
+        assert_eq!(
+            normalizer.normalize("finance-workstation-01.corp.local"),
+            normalizer.normalize("FINANCE-WORKSTA"),
+        );
+    }
+}
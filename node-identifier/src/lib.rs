@@ -9,6 +9,7 @@ extern crate graph_descriptions;
 extern crate hex;
 #[macro_use]
 extern crate hmap;
+extern crate idna;
 extern crate lambda_runtime as lambda;
 #[macro_use]
 extern crate log;
@@ -39,11 +40,11 @@ extern crate zstd;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::convert::TryFrom;
-use std::env;
 use std::io::Cursor;
 use std::ops::Deref;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::mpsc::SyncSender;
 use std::time::Duration;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
@@ -63,7 +64,7 @@ use lambda::Context;
 use lambda::error::HandlerError;
 use prost::Message;
 use rusoto_core::Region;
-use rusoto_dynamodb::{DynamoDb, DynamoDbClient};
+use rusoto_dynamodb::DynamoDbClient;
 use rusoto_s3::S3Client;
 use rusoto_sqs::SqsClient;
 use sha2::Digest;
@@ -78,10 +79,17 @@ use sqs_lambda::redis_cache::RedisCache;
 use sqs_lambda::sqs_completion_handler::{CompletionPolicy, SqsCompletionHandler, SqsCompletionHandlerActor};
 use sqs_lambda::sqs_consumer::{ConsumePolicy, SqsConsumer, SqsConsumerActor};
 
+use asset_aliases::{AssetAliasDb, create_asset_aliases};
 use assetdb::{AssetIdDb, AssetIdentifier};
 use async_trait::async_trait;
 use dynamic_sessiondb::{DynamicMappingDb, DynamicNodeIdentifier};
-use sessiondb::SessionDb;
+use hostname::HostnameNormalizer;
+use mapping_conflicts::{conflict_sink_from_env, ConflictPolicy, ConflictSink};
+use sessiondb::{SessionDb, TimeResolutions};
+use memory_store::InMemoryStore;
+#[cfg(feature = "sqlite")]
+use sqlite_store::SqliteStore;
+use storage::{AssetAliasStore, AssetMappingStore, IdentityStore, IdentityStoreBackend};
 use sessions::UnidSession;
 
 use crate::graph_descriptions::node::NodeT;
@@ -107,8 +115,18 @@ macro_rules! wait_on {
     }};
 }
 
+pub mod asset_aliases;
 pub mod assetdb;
 pub mod dynamic_sessiondb;
+pub mod dynamodb_store;
+#[cfg(test)]
+mod fake_dynamodb;
+pub mod hostname;
+pub mod mapping_conflicts;
+pub mod memory_store;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+pub mod storage;
 
 pub mod sessiondb;
 pub mod sessions;
@@ -116,34 +134,40 @@ pub mod sessions;
 #[derive(Clone)]
 struct NodeIdentifier<D>
     where
-        D: DynamoDb + Clone + Send + Sync + 'static,
+        D: IdentityStore + Clone + Send + Sync + 'static,
 {
     asset_mapping_db: AssetIdDb<D>,
+    asset_alias_db: AssetAliasDb<D>,
     dynamic_identifier: DynamicNodeIdentifier<D>,
     asset_identifier: AssetIdentifier<D>,
     node_id_db: D,
+    time_resolutions: TimeResolutions,
     should_default: bool,
     cache: RedisCache,
 }
 
 impl<D> NodeIdentifier<D>
     where
-        D: DynamoDb + Clone + Send + Sync + 'static,
+        D: IdentityStore + Clone + Send + Sync + 'static,
 
 {
     pub fn new(
         asset_mapping_db: AssetIdDb<D>,
+        asset_alias_db: AssetAliasDb<D>,
         dynamic_identifier: DynamicNodeIdentifier<D>,
         asset_identifier: AssetIdentifier<D>,
         node_id_db: D,
+        time_resolutions: TimeResolutions,
         should_default: bool,
         cache: RedisCache,
     ) -> Self {
         Self {
             asset_mapping_db,
+            asset_alias_db,
             dynamic_identifier,
             asset_identifier,
             node_id_db,
+            time_resolutions,
             should_default,
             cache,
         }
@@ -159,7 +183,8 @@ impl<D> NodeIdentifier<D>
                     Some(unid) => unid,
                     None => bail!("Could not identify ProcessNode")
                 };
-                let session_db = SessionDb::new(self.node_id_db.clone(), "process_history_table");
+                let session_db = SessionDb::new(self.node_id_db.clone(), "process_history_table")
+                    .with_time_resolution(self.time_resolutions.process);
                 let node_key = session_db.handle_unid_session(unid, self.should_default).await?;
 
                 info!(
@@ -176,7 +201,8 @@ impl<D> NodeIdentifier<D>
                     Some(unid) => unid,
                     None => bail!("Could not identify FileNode")
                 };
-                let session_db = SessionDb::new(self.node_id_db.clone(), "file_history_table");
+                let session_db = SessionDb::new(self.node_id_db.clone(), "file_history_table")
+                    .with_time_resolution(self.time_resolutions.file);
                 let node_key = session_db.handle_unid_session(unid, self.should_default).await?;
 
                 file_node.set_node_key(node_key);
@@ -188,7 +214,8 @@ impl<D> NodeIdentifier<D>
                     Some(unid) => unid,
                     None => bail!("Could not identify ProcessInboundConnectionNode")
                 };
-                let session_db = SessionDb::new(self.node_id_db.clone(), "outbound_connection_history_table");
+                let session_db = SessionDb::new(self.node_id_db.clone(), "outbound_connection_history_table")
+                    .with_time_resolution(self.time_resolutions.connection);
                 let node_key = session_db.handle_unid_session(unid, self.should_default).await?;
 
                 inbound_node.set_node_key(node_key);
@@ -200,7 +227,8 @@ impl<D> NodeIdentifier<D>
                     Some(unid) => unid,
                     None => bail!("Could not identify ProcessOutboundConnectionNode")
                 };
-                let session_db = SessionDb::new(self.node_id_db.clone(), "outbound_connection_history_table");
+                let session_db = SessionDb::new(self.node_id_db.clone(), "outbound_connection_history_table")
+                    .with_time_resolution(self.time_resolutions.connection);
                 let node_key = session_db.handle_unid_session(unid, self.should_default).await?;
 
                 outbound_node.set_node_key(node_key);
@@ -246,7 +274,8 @@ impl<D> NodeIdentifier<D>
                     Some(unid) => unid,
                     None => bail!("Could not identify NetworkConnectionNode")
                 };
-                let session_db = SessionDb::new(self.node_id_db.clone(), "network_connection_history_table");
+                let session_db = SessionDb::new(self.node_id_db.clone(), "network_connection_history_table")
+                    .with_time_resolution(self.time_resolutions.connection);
                 let node_key = session_db.handle_unid_session(unid, self.should_default).await?;
 
                 network_connection_node.set_node_key(node_key);
@@ -258,7 +287,8 @@ impl<D> NodeIdentifier<D>
                     Some(unid) => unid,
                     None => bail!("Could not identify IpConnectionNode")
                 };
-                let session_db = SessionDb::new(self.node_id_db.clone(), "ip_connection_history_table");
+                let session_db = SessionDb::new(self.node_id_db.clone(), "ip_connection_history_table")
+                    .with_time_resolution(self.time_resolutions.connection);
                 let node_key = session_db.handle_unid_session(unid, self.should_default).await?;
 
                 ip_connection_node.set_node_key(node_key);
@@ -447,6 +477,35 @@ fn remove_dead_edges(graph: &mut Graph) {
     }
 }
 
+// Rebuilds the unidentified graph's edges between the identified nodes, keeping their facets
+fn add_identified_edges(
+    identified_graph: &mut Graph,
+    unid_graph: &Graph,
+    dead_node_ids: &HashSet<String>,
+    unid_id_map: &HashMap<String, String>,
+) {
+    for (old_key, edge_list) in unid_graph.edges.iter() {
+        if dead_node_ids.contains(old_key) { continue; };
+
+        for edge in &edge_list.edges {
+            let from_key = unid_id_map.get(&edge.from);
+            let to_key = unid_id_map.get(&edge.to);
+
+            let (from_key, to_key) = match (from_key, to_key) {
+                (Some(from_key), Some(to_key)) => (from_key, to_key),
+                _ => continue
+            };
+
+            identified_graph.add_edge_with_properties(
+                edge.edge_name.to_owned(),
+                from_key.to_owned(),
+                to_key.to_owned(),
+                edge.properties.clone(),
+            );
+        }
+    }
+}
+
 fn remap_edges(graph: &mut Graph, unid_id_map: &HashMap<String, String>) {
     for (node_key, edge_list) in graph.edges.iter_mut() {
         for edge in edge_list.edges.iter_mut() {
@@ -466,11 +525,8 @@ fn remap_edges(graph: &mut Graph, unid_id_map: &HashMap<String, String>) {
                 }
             };
 
-            *edge = Edge {
-                from: from.to_owned(),
-                to: to.to_owned(),
-                edge_name: edge.edge_name.clone(),
-            };
+            edge.from = from.to_owned();
+            edge.to = to.to_owned();
         }
     }
 }
@@ -510,7 +566,7 @@ fn remap_nodes(graph: &mut Graph, unid_id_map: &HashMap<String, String>) {
 }
 
 async fn create_asset_id_mappings(
-    assetid_db: &AssetIdDb<impl DynamoDb>,
+    assetid_db: &AssetIdDb<impl AssetMappingStore>,
     unid_graph: &Graph,
 ) -> Result<(), Error> {
     for node in unid_graph.nodes.values() {
@@ -552,8 +608,8 @@ async fn create_asset_id_mappings(
             (Some(asset_id), Some(hostname), timestamp) => {
                 info!("Creating asset id {} mapping for: {}", asset_id, hostname);
                 assetid_db.create_mapping(
-                    &HostId::AssetId(asset_id.clone()),
-                    hostname.clone(),
+                    &HostId::Hostname(hostname.clone()),
+                    asset_id.clone(),
                     timestamp,
                 ).await?;
             }
@@ -564,13 +620,61 @@ async fn create_asset_id_mappings(
     Ok(())
 }
 
+// Merges any assets that have been found to be the same machine, so that they are
+// attributed to one asset id, then creates any implicit asset id mappings.
+//
+// The merge reads the mappings from before this subgraph, as once its own mappings are
+// written an asset's hostname resolves to itself. A failed merge is returned, so that
+// attribution continues without it, while a failed mapping fails the subgraph.
+async fn create_asset_identities(
+    alias_db: &AssetAliasDb<impl AssetAliasStore>,
+    assetid_db: &AssetIdDb<impl AssetMappingStore>,
+    unid_graph: &Graph,
+) -> Result<Option<Error>, Error> {
+    let alias_failure = match create_asset_aliases(alias_db, assetid_db, unid_graph).await {
+        Ok(()) => None,
+        Err(e) => {
+            error!("Asset alias creation failed with {}", e);
+            Some(e)
+        }
+    };
+
+    create_asset_id_mappings(assetid_db, unid_graph).await?;
+
+    Ok(alias_failure)
+}
+
+// Nodes are stored with their normalized hostname, so that a node's hostname is the one
+// it was attributed by. Assets also keep the hostname as it was logged.
+fn normalize_hostnames(normalizer: &HostnameNormalizer, unid_graph: &mut Graph) {
+    for node in unid_graph.nodes.values_mut() {
+        let hostname = match node.which_node {
+            Some(WhichNode::AssetNode(ref mut asset)) => {
+                if asset.original_hostname.is_none() {
+                    asset.original_hostname = asset.hostname.clone();
+                }
+                &mut asset.hostname
+            }
+            Some(WhichNode::ProcessNode(ref mut node)) => &mut node.hostname,
+            Some(WhichNode::FileNode(ref mut node)) => &mut node.hostname,
+            Some(WhichNode::ProcessOutboundConnectionNode(ref mut node)) => &mut node.hostname,
+            Some(WhichNode::ProcessInboundConnectionNode(ref mut node)) => &mut node.hostname,
+            Some(WhichNode::DynamicNode(ref mut node)) => &mut node.hostname,
+            _ => continue,
+        };
+
+        if let Some(ref mut hostname) = hostname {
+            *hostname = normalizer.normalize(hostname);
+        }
+    }
+}
 
 // Takes a Graph, attributes all nodes with an asset id
 // When atribution fails, attribution continues, but the Graph returned will contain
 // only the nodes that were successful
 // Edges will also be fixed up
 async fn attribute_asset_ids(
-    asset_identifier: &AssetIdentifier<impl DynamoDb>,
+    asset_identifier: &AssetIdentifier<impl AssetMappingStore + AssetAliasStore>,
     unid_graph: Graph,
 ) -> Result<Graph, (Error, Graph)> {
     info!("Attributing asset ids");
@@ -637,21 +741,13 @@ async fn attribute_asset_ids(
 
 #[async_trait]
 impl<D> EventHandler for NodeIdentifier<D>
-    where D: DynamoDb + Clone + Send + Sync + 'static,
+    where D: IdentityStore + Clone + Send + Sync + 'static,
 {
     type InputEvent = GeneratedSubgraphs;
     type OutputEvent = GeneratedSubgraphs;
     type Error = Arc<failure::Error>;
 
     async fn handle_event(&mut self, subgraphs: GeneratedSubgraphs) -> OutputEvent<Self::OutputEvent, Self::Error> {
-        let region = {
-            let region_str = env::var("AWS_REGION").expect("AWS_REGION");
-            match Region::from_str(&region_str) {
-                Ok(region) => region,
-                Err(e) => return OutputEvent::new(Completion::Error(Arc::new(e.into())))
-            }
-        };
-
         let mut attribution_failure = None;
 
         info!("Handling raw event");
@@ -661,12 +757,11 @@ impl<D> EventHandler for NodeIdentifier<D>
             return OutputEvent::new(Completion::Total(GeneratedSubgraphs::new(vec![])));
         }
 
-        let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
-//        let dynamo = DynamoDbClient::new(region.clone());
+        let asset_id_db = &self.asset_mapping_db;
 
         // Merge all of the subgraphs into one subgraph to avoid
         // redundant work
-        let unid_subgraph = subgraphs.subgraphs.into_iter().fold(
+        let mut unid_subgraph = subgraphs.subgraphs.into_iter().fold(
             Graph::new(0),
             |mut total_graph, subgraph| {
                 info!("Merging subgraph with: {} nodes {} edges", subgraph.nodes.len(), subgraph.edges.len());
@@ -686,10 +781,14 @@ impl<D> EventHandler for NodeIdentifier<D>
             unid_subgraph.edges.len(),
         );
 
-        // Create any implicit asset id mappings
-        if let Err(e) = create_asset_id_mappings(&asset_id_db, &unid_subgraph).await {
-            error!("Asset mapping creation failed with {}", e);
-            return OutputEvent::new(Completion::Error(Arc::new(e.into())));
+        normalize_hostnames(asset_id_db.normalizer(), &mut unid_subgraph);
+
+        match create_asset_identities(&self.asset_alias_db, asset_id_db, &unid_subgraph).await {
+            Ok(alias_failure) => attribution_failure = alias_failure,
+            Err(e) => {
+                error!("Asset mapping creation failed with {}", e);
+                return OutputEvent::new(Completion::Error(Arc::new(e.into())));
+            }
         }
 
         // Map all host_ids into asset_ids. This has to happen before node key
@@ -739,25 +838,7 @@ impl<D> EventHandler for NodeIdentifier<D>
         println!("PRE: identified_graph.edges.len() {}", identified_graph.edges.len());
 
 
-        for (old_key, edge_list) in output_subgraph.edges.iter() {
-            if dead_node_ids.contains(old_key) { continue; };
-
-            for edge in &edge_list.edges {
-                let from_key = unid_id_map.get(&edge.from);
-                let to_key = unid_id_map.get(&edge.to);
-
-                let (from_key, to_key) = match (from_key, to_key) {
-                    (Some(from_key), Some(to_key)) => (from_key, to_key),
-                    _ => continue
-                };
-
-                identified_graph.add_edge(
-                    edge.edge_name.to_owned(),
-                    from_key.to_owned(),
-                    to_key.to_owned(),
-                );
-            }
-        }
+        add_identified_edges(&mut identified_graph, &output_subgraph, &dead_node_ids, &unid_id_map);
 
         println!("POST: identified_graph.edges.len() {}", identified_graph.edges.len());
         info!("Identified Graph: {:?}", &identified_graph);
@@ -932,6 +1013,150 @@ fn map_sqs_message(event: aws_lambda_events::event::sqs::SqsMessage) -> rusoto_s
     }
 }
 
+// Every db is built on the one store, so that identities can be stored in DynamoDB, or
+// without AWS, see IdentityStoreBackend
+fn build_node_identifier<D>(
+    store: D,
+    cache: RedisCache,
+    conflict_sink: Arc<dyn ConflictSink>,
+    should_default: bool,
+) -> Result<NodeIdentifier<D>, Error>
+    where D: IdentityStore + Clone + Send + Sync + 'static
+{
+    let conflict_policy = ConflictPolicy::from_env()?;
+    let time_resolutions = TimeResolutions::from_env()?;
+
+    let asset_identifier = AssetIdentifier::new(
+        AssetIdDb::new(store.clone())
+            .with_conflict_policy(conflict_policy)
+            .with_conflict_sink(conflict_sink.clone()),
+        AssetAliasDb::new(store.clone()),
+    );
+
+    let dyn_session_db = SessionDb::new(
+        store.clone(),
+        "dynamic_session_table",
+    ).with_time_resolution(time_resolutions.dynamic);
+
+    let dyn_node_identifier = DynamicNodeIdentifier::new(
+        asset_identifier.clone(),
+        dyn_session_db,
+        DynamicMappingDb::new(store.clone()),
+        should_default,
+    );
+
+    Ok(NodeIdentifier::new(
+        AssetIdDb::new(store.clone())
+            .with_conflict_policy(conflict_policy)
+            .with_conflict_sink(conflict_sink),
+        AssetAliasDb::new(store.clone()),
+        dyn_node_identifier,
+        asset_identifier,
+        store,
+        time_resolutions,
+        should_default,
+        cache,
+    ))
+}
+
+async fn identify_events<D>(
+    node_identifier: NodeIdentifier<D>,
+    event: SqsEvent,
+    ctx: Context,
+    region: Region,
+    queue_url: String,
+    bucket: String,
+    tx: SyncSender<String>,
+)
+    where D: IdentityStore + Clone + Send + Sync + 'static
+{
+    info!("SqsCompletionHandler");
+
+    let finished_tx = tx.clone();
+    let sqs_completion_handler = SqsCompletionHandlerActor::new(
+        SqsCompletionHandler::new(
+            SqsClient::new(region.clone()),
+            queue_url.to_string(),
+            SubgraphSerializer { proto: Vec::with_capacity(1024) },
+            S3EventEmitter::new(
+                S3Client::new(region.clone()),
+                bucket.to_owned(),
+                time_based_key_fn,
+            ),
+            CompletionPolicy::new(
+                1000, // Buffer up to 1000 messages
+                Duration::from_secs(30), // Buffer for up to 30 seconds
+            ),
+            move |_self_actor, result: Result<String, String>| {
+                match result {
+                    Ok(worked) => {
+                        info!("Handled an event, which was successfully deleted: {}", &worked);
+                        tx.send(worked).unwrap();
+                    }
+                    Err(worked) => {
+                        info!("Handled an initial_event, though we failed to delete it: {}", &worked);
+                        tx.send(worked).unwrap();
+                    }
+                }
+            },
+            node_identifier.cache.clone(),
+        )
+    );
+
+
+    info!("Defining consume policy");
+    let consume_policy = ConsumePolicy::new(
+        ctx, // Use the Context.deadline from the lambda_runtime
+        Duration::from_secs(10), // Stop consuming when there's 2 seconds left in the runtime
+        3, // If we get 3 empty receives in a row, stop consuming
+    );
+
+    info!("Defining consume policy");
+    let (shutdown_tx, shutdown_notify) = tokio::sync::oneshot::channel();
+
+    info!("SqsConsumer");
+    let sqs_consumer = SqsConsumerActor::new(
+        SqsConsumer::new(
+            SqsClient::new(region.clone()),
+            queue_url.clone(),
+            consume_policy,
+            sqs_completion_handler.clone(),
+            shutdown_tx,
+        )
+    );
+
+    info!("EventProcessors");
+    let event_processors: Vec<_> = (0..10)
+        .map(|_| {
+            EventProcessorActor::new(EventProcessor::new(
+                sqs_consumer.clone(),
+                sqs_completion_handler.clone(),
+                node_identifier.clone(),
+                S3PayloadRetriever::new(S3Client::new(region.clone()), ZstdProtoDecoder::default()),
+            ))
+        })
+        .collect();
+
+    info!("Start Processing");
+
+    futures::future::join_all(event_processors.iter().map(|ep| ep.start_processing())).await;
+
+    let mut proc_iter = event_processors.iter().cycle();
+    for event in event.records {
+        let next_proc = proc_iter.next().unwrap();
+        next_proc.process_event(
+            map_sqs_message(event)
+        ).await;
+    }
+
+    info!("Waiting for shutdown notification");
+
+    // Wait for the consumers to shutdown
+    let _ = shutdown_notify.await;
+    info!("Consumer shutdown");
+    finished_tx.send("Completed".to_owned()).unwrap();
+}
+
 fn _handler(event: SqsEvent, ctx: Context, should_default: bool) -> Result<(), HandlerError> {
     info!("Handling event");
 
@@ -971,124 +1196,42 @@ fn _handler(event: SqsEvent, ctx: Context, should_default: bool) -> Result<(), H
 
                 let cache = RedisCache::new(cache_address.to_owned()).await.expect("Could not create redis client");
 
-                let dynamo = DynamoDbClient::new(region.clone());
-
-                let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
-
-                let dynamo = DynamoDbClient::new(region.clone());
-                let dyn_session_db = SessionDb::new(
-                    dynamo.clone(),
-                    "dynamic_session_table",
-                );
-                let dyn_mapping_db = DynamicMappingDb::new(DynamoDbClient::new(region.clone()));
-                let asset_identifier = AssetIdentifier::new(asset_id_db);
-
-                let dyn_node_identifier = DynamicNodeIdentifier::new(
-                    asset_identifier,
-                    dyn_session_db,
-                    dyn_mapping_db,
-                    should_default,
-                );
-
-                let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
-
-                let asset_identifier = AssetIdentifier::new(asset_id_db);
-
-                let asset_id_db = AssetIdDb::new(DynamoDbClient::new(region.clone()));
-                let node_identifier = NodeIdentifier::new(
-                    asset_id_db,
-                    dyn_node_identifier,
-                    asset_identifier,
-                    dynamo.clone(),
-                    should_default,
-                    cache.clone(),
-                );
-
-                info!("SqsCompletionHandler");
-
-                let finished_tx = tx.clone();
-                let sqs_completion_handler = SqsCompletionHandlerActor::new(
-                    SqsCompletionHandler::new(
-                        SqsClient::new(region.clone()),
-                        queue_url.to_string(),
-                        SubgraphSerializer { proto: Vec::with_capacity(1024) },
-                        S3EventEmitter::new(
-                            S3Client::new(region.clone()),
-                            bucket.to_owned(),
-                            time_based_key_fn,
-                        ),
-                        CompletionPolicy::new(
-                            1000, // Buffer up to 1000 messages
-                            Duration::from_secs(30), // Buffer for up to 30 seconds
-                        ),
-                        move |_self_actor, result: Result<String, String>| {
-                            match result {
-                                Ok(worked) => {
-                                    info!("Handled an event, which was successfully deleted: {}", &worked);
-                                    tx.send(worked).unwrap();
-                                }
-                                Err(worked) => {
-                                    info!("Handled an initial_event, though we failed to delete it: {}", &worked);
-                                    tx.send(worked).unwrap();
-                                }
-                            }
-                        },
-                        cache.clone(),
-                    )
-                );
-
-
-                info!("Defining consume policy");
-                let consume_policy = ConsumePolicy::new(
-                    ctx, // Use the Context.deadline from the lambda_runtime
-                    Duration::from_secs(10), // Stop consuming when there's 2 seconds left in the runtime
-                    3, // If we get 3 empty receives in a row, stop consuming
-                );
-
-                info!("Defining consume policy");
-                let (shutdown_tx, shutdown_notify) = tokio::sync::oneshot::channel();
-
-                info!("SqsConsumer");
-                let sqs_consumer = SqsConsumerActor::new(
-                    SqsConsumer::new(
-                        SqsClient::new(region.clone()),
-                        queue_url.clone(),
-                        consume_policy,
-                        sqs_completion_handler.clone(),
-                        shutdown_tx,
-                    )
-                );
-
-                info!("EventProcessors");
-                let event_processors: Vec<_> = (0..10)
-                    .map(|_| {
-                        EventProcessorActor::new(EventProcessor::new(
-                            sqs_consumer.clone(),
-                            sqs_completion_handler.clone(),
-                            node_identifier.clone(),
-                            S3PayloadRetriever::new(S3Client::new(region.clone()), ZstdProtoDecoder::default()),
-                        ))
-                    })
-                    .collect();
-
-                info!("Start Processing");
-
-                futures::future::join_all(event_processors.iter().map(|ep| ep.start_processing())).await;
-
-                let mut proc_iter = event_processors.iter().cycle();
-                for event in event.records {
-                    let next_proc = proc_iter.next().unwrap();
-                    next_proc.process_event(
-                        map_sqs_message(event)
-                    ).await;
+                let backend = IdentityStoreBackend::from_env().expect("IDENTITY_STORE");
+                info!("Identity store: {:?}", backend);
+
+                let conflict_sink = conflict_sink_from_env(region.clone());
+                info!("Asset mapping conflicts are sent to: {:?}", conflict_sink);
+
+                match backend {
+                    IdentityStoreBackend::DynamoDb => {
+                        let node_identifier = build_node_identifier(
+                            DynamoDbClient::new(region.clone()),
+                            cache,
+                            conflict_sink,
+                            should_default,
+                        ).expect("node_identifier");
+                        identify_events(node_identifier, event, ctx, region, queue_url, bucket, tx).await
+                    }
+                    IdentityStoreBackend::Memory => {
+                        let node_identifier = build_node_identifier(
+                            InMemoryStore::new(),
+                            cache,
+                            conflict_sink,
+                            should_default,
+                        ).expect("node_identifier");
+                        identify_events(node_identifier, event, ctx, region, queue_url, bucket, tx).await
+                    }
+                    #[cfg(feature = "sqlite")]
+                    IdentityStoreBackend::Sqlite(path) => {
+                        let node_identifier = build_node_identifier(
+                            SqliteStore::open(path).expect("IDENTITY_STORE_PATH"),
+                            cache,
+                            conflict_sink,
+                            should_default,
+                        ).expect("node_identifier");
+                        identify_events(node_identifier, event, ctx, region, queue_url, bucket, tx).await
+                    }
                 }
-
-                info!("Waiting for shutdown notification");
-
-                // Wait for the consumers to shutdown
-                let _ = shutdown_notify.await;
-                info!("Consumer shutdown");
-                finished_tx.send("Completed".to_owned()).unwrap();
             });
     });
 
@@ -1119,3 +1262,116 @@ fn _handler(event: SqsEvent, ctx: Context, should_default: bool) -> Result<(), H
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assets_are_merged_before_their_hostnames_are_remapped() {
+        let store = InMemoryStore::new();
+        let asset_id_db = AssetIdDb::new(store.clone());
+        let alias_db = AssetAliasDb::new(store);
+
+        futures::executor::block_on(asset_id_db.create_mapping(
+            &HostId::Hostname("web-1".to_owned()),
+            "asset_id_a".into(),
+            1000,
+        ))
+            .expect("create_mapping");
+
+        // The same machine, seen again under its instance id
+        let asset = AssetBuilder::default()
+            .asset_id("i-0123456789".to_owned())
+            .hostname("web-1".to_owned())
+            .first_seen_timestamp(2000u64)
+            .last_seen_timestamp(2000u64)
+            .build()
+            .expect("asset");
+
+        let mut unid_graph = Graph::new(2000);
+        unid_graph.add_node(asset);
+
+        let alias_failure = futures::executor::block_on(
+            create_asset_identities(&alias_db, &asset_id_db, &unid_graph)
+        )
+            .expect("create_asset_identities");
+        assert!(alias_failure.is_none());
+
+        let canonical = futures::executor::block_on(alias_db.canonical_asset_id("i-0123456789"))
+            .expect("canonical_asset_id");
+        assert_eq!(canonical, "asset_id_a");
+    }
+
+    #[test]
+    fn hostnames_are_normalized_on_every_node() {
+        let normalizer = HostnameNormalizer::new(vec!["corp.local".to_owned()], false);
+
+        let asset = AssetBuilder::default()
+            .asset_id("i-0123456789".to_owned())
+            .hostname("WIN-01.corp.local".to_owned())
+            .first_seen_timestamp(1000u64)
+            .last_seen_timestamp(1000u64)
+            .build()
+            .expect("asset");
+
+        let process = ProcessBuilder::default()
+            .hostname("CORP\\WIN-01".to_owned())
+            .state(ProcessState::Existing)
+            .process_id(4u64)
+            .last_seen_timestamp(1000u64)
+            .build()
+            .expect("process");
+
+        let asset_key = asset.node_key.clone();
+        let process_key = process.node_key.clone();
+
+        let mut unid_graph = Graph::new(1000);
+        unid_graph.add_node(asset);
+        unid_graph.add_node(process);
+
+        normalize_hostnames(&normalizer, &mut unid_graph);
+
+        match unid_graph.nodes[&asset_key].which_node {
+            Some(WhichNode::AssetNode(ref asset)) => {
+                assert_eq!(asset.hostname.as_deref(), Some("win-01"));
+                assert_eq!(asset.original_hostname.as_deref(), Some("WIN-01.corp.local"));
+            }
+            ref node => panic!("Expected an asset, found {:?}", node),
+        }
+
+        match unid_graph.nodes[&process_key].which_node {
+            Some(WhichNode::ProcessNode(ref process)) => {
+                assert_eq!(process.hostname.as_deref(), Some("win-01"));
+            }
+            ref node => panic!("Expected a process, found {:?}", node),
+        }
+    }
+
+    #[test]
+    fn identified_edges_keep_their_facets() {
+        let mut unid_graph = Graph::new(0);
+        unid_graph.add_edge_with_properties(
+            "requested_url",
+            "unid_process",
+            "unid_url",
+            hmap! {
+                "status_code".to_owned() => NodeProperty::from(200u64)
+            },
+        );
+        unid_graph.add_edge("requested_url", "unid_process", "dead_url");
+
+        let unid_id_map = hmap! {
+            "unid_process".to_owned() => "process".to_owned(),
+            "unid_url".to_owned() => "url".to_owned()
+        };
+
+        let mut identified_graph = Graph::new(0);
+        add_identified_edges(&mut identified_graph, &unid_graph, &HashSet::new(), &unid_id_map);
+
+        let edges = &identified_graph.edges["process"].edges;
+
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].to, "url");
+        assert_eq!(edges[0].properties["status_code"], NodeProperty::from(200u64));
+    }
+}
@@ -0,0 +1,155 @@
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use failure::Error;
+use rusoto_core::Region;
+use rusoto_sqs::{SendMessageRequest, Sqs, SqsClient};
+
+/// How a hostname that is mapped to one asset, and is then mapped to another, is handled.
+///
+/// Hostnames are remapped when machines are reimaged or renamed, and when they are spoofed.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// The hostname stays mapped to the asset it was mapped to
+    Reject,
+    /// The hostname is mapped to the new asset
+    AcceptNew,
+    /// The hostname is mapped to neither asset from the conflict on. A quarantined hostname
+    /// is in conflict with every asset, until it is mapped under another policy
+    Quarantine,
+}
+
+impl ConflictPolicy {
+    /// Reads `ASSET_MAPPING_CONFLICT_POLICY`, defaulting to accept-new
+    pub fn from_env() -> Result<Self, Error> {
+        match env::var("ASSET_MAPPING_CONFLICT_POLICY") {
+            Ok(policy) => policy.parse(),
+            Err(_) => Ok(ConflictPolicy::AcceptNew),
+        }
+    }
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> Self {
+        ConflictPolicy::AcceptNew
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(policy: &str) -> Result<Self, Error> {
+        match policy.trim().to_lowercase().as_str() {
+            "reject" => Ok(ConflictPolicy::Reject),
+            "accept-new" | "accept_new" => Ok(ConflictPolicy::AcceptNew),
+            "quarantine" => Ok(ConflictPolicy::Quarantine),
+            _ => bail!("Invalid asset mapping conflict policy: {}", policy),
+        }
+    }
+}
+
+/// A hostname that was mapped to one asset being mapped to another
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetMappingConflict {
+    pub hostname: String,
+    pub existing_asset_id: String,
+    pub new_asset_id: String,
+    pub timestamp: u64,
+    pub policy: ConflictPolicy,
+}
+
+/// Where conflicts are sent, so that they can be alerted on, and resolved
+#[async_trait]
+pub trait ConflictSink: fmt::Debug + Send + Sync {
+    async fn emit(&self, conflict: &AssetMappingConflict) -> Result<(), Error>;
+}
+
+/// Logs conflicts as JSON, so that conflicts can be filtered for
+#[derive(Debug, Clone, Default)]
+pub struct LogConflictSink;
+
+#[async_trait]
+impl ConflictSink for LogConflictSink {
+    async fn emit(&self, conflict: &AssetMappingConflict) -> Result<(), Error> {
+        warn!("AssetMappingConflict {}", serde_json::to_string(conflict)?);
+        Ok(())
+    }
+}
+
+/// Sends conflicts, as JSON, to an SQS queue
+#[derive(Clone)]
+pub struct SqsConflictSink<S>
+    where S: Sqs + Send + Sync
+{
+    sqs: S,
+    queue_url: String,
+}
+
+impl<S> SqsConflictSink<S>
+    where S: Sqs + Send + Sync
+{
+    pub fn new(sqs: S, queue_url: impl Into<String>) -> Self {
+        Self { sqs, queue_url: queue_url.into() }
+    }
+}
+
+impl<S> fmt::Debug for SqsConflictSink<S>
+    where S: Sqs + Send + Sync
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SqsConflictSink")
+            .field("queue_url", &self.queue_url)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<S> ConflictSink for SqsConflictSink<S>
+    where S: Sqs + Send + Sync
+{
+    async fn emit(&self, conflict: &AssetMappingConflict) -> Result<(), Error> {
+        let request = SendMessageRequest {
+            queue_url: self.queue_url.clone(),
+            message_body: serde_json::to_string(conflict)?,
+            ..Default::default()
+        };
+
+        wait_on!(self.sqs.send_message(request))?;
+        Ok(())
+    }
+}
+
+/// Conflicts are sent to the queue at `ASSET_MAPPING_CONFLICT_QUEUE_URL` when it is set,
+/// and are logged otherwise
+pub fn conflict_sink_from_env(region: Region) -> Arc<dyn ConflictSink> {
+    match env::var("ASSET_MAPPING_CONFLICT_QUEUE_URL") {
+        Ok(queue_url) => Arc::new(SqsConflictSink::new(SqsClient::new(region), queue_url)),
+        Err(_) => Arc::new(LogConflictSink),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflicts_are_emitted_with_their_policy() {
+        let conflict = AssetMappingConflict {
+            hostname: "win-01".to_owned(),
+            existing_asset_id: "asset_id_a".to_owned(),
+            new_asset_id: "asset_id_b".to_owned(),
+            timestamp: 1500,
+            policy: "accept-new".parse().expect("policy"),
+        };
+
+        let conflict = serde_json::to_value(&conflict).expect("to_value");
+
+        assert_eq!(conflict["policy"], "accept-new");
+        assert!("replace".parse::<ConflictPolicy>().is_err());
+    }
+}
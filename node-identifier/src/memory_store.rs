@@ -0,0 +1,254 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use failure::Error;
+
+use crate::assetdb::{AssetIdMapping, ResolvedAssetId};
+use crate::sessions::Session;
+use crate::storage::{AliasCondition, AssetAliasStore, AssetMappingStore, SessionStore, StaticMappingStore, VersionConflict};
+
+// Items are keyed as they are in DynamoDB, on their pseudo key and their sort key
+type Timeline<T> = BTreeMap<(String, u64), T>;
+
+#[derive(Debug, Default)]
+struct Tables {
+    sessions: HashMap<String, Timeline<Session>>,
+    asset_mappings: HashMap<String, Timeline<ResolvedAssetId>>,
+    static_mappings: HashMap<String, HashMap<String, String>>,
+    asset_aliases: HashMap<String, HashMap<String, String>>,
+}
+
+/// Stores identities in memory, ie: for tests, or for a single node-identifier that does
+/// not need its identities to outlive it. Clones share their tables.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryStore {
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn tables(&self) -> MutexGuard<Tables> {
+        // A panic while the tables are held can not leave them partially written
+        match self.tables.lock() {
+            Ok(tables) => tables,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+fn first_after<T: Clone>(timeline: Option<&Timeline<T>>, pseudo_key: &str, ts: u64) -> Option<T> {
+    timeline?
+        .range((pseudo_key.to_owned(), ts)..=(pseudo_key.to_owned(), u64::max_value()))
+        .next()
+        .map(|(_, item)| item.clone())
+}
+
+fn last_before<T: Clone>(timeline: Option<&Timeline<T>>, pseudo_key: &str, ts: u64) -> Option<T> {
+    timeline?
+        .range((pseudo_key.to_owned(), 0)..=(pseudo_key.to_owned(), ts))
+        .next_back()
+        .map(|(_, item)| item.clone())
+}
+
+// Conditional updates fail, as they do in DynamoDB, when the session has been updated,
+// or moved, since it was read
+fn versioned_session<'a>(
+    tables: &'a mut Tables,
+    table_name: &str,
+    session: &Session,
+) -> Result<&'a mut Session, Error> {
+    let stored = tables.sessions
+        .get_mut(table_name)
+        .and_then(|timeline| timeline.get_mut(&(session.pseudo_key.clone(), session.create_time)));
+
+    match stored {
+        Some(stored) if stored.version == session.version => Ok(stored),
+        _ => Err(VersionConflict::new(session).into()),
+    }
+}
+
+#[async_trait]
+impl SessionStore for InMemoryStore {
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error> {
+        Ok(first_after(self.tables().sessions.get(table_name), pseudo_key, ts))
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error> {
+        Ok(last_before(self.tables().sessions.get(table_name), pseudo_key, ts))
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        self.tables().sessions
+            .entry(table_name.to_owned())
+            .or_default()
+            .insert((session.pseudo_key.clone(), session.create_time), session.clone());
+
+        Ok(())
+    }
+
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let mut new_session = session.to_owned();
+        new_session.create_time = new_time;
+        new_session.is_create_canon = is_canon;
+        new_session.version += 1;
+
+        let mut tables = self.tables();
+        versioned_session(&mut tables, table_name, session)?;
+
+        let timeline = tables.sessions.entry(table_name.to_owned()).or_default();
+
+        timeline.remove(&(session.pseudo_key.clone(), session.create_time));
+        timeline.insert((new_session.pseudo_key.clone(), new_time), new_session);
+
+        Ok(())
+    }
+
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let stored = versioned_session(&mut tables, table_name, session)?;
+
+        stored.end_time = new_time;
+        stored.is_end_canon = is_canon;
+        stored.version = session.version + 1;
+
+        Ok(())
+    }
+
+    async fn make_create_time_canonical(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let mut tables = self.tables();
+        let stored = versioned_session(&mut tables, table_name, session)?;
+
+        stored.is_create_canon = true;
+        stored.version = session.version + 1;
+
+        Ok(())
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        if let Some(timeline) = self.tables().sessions.get_mut(table_name) {
+            timeline.remove(&(session.pseudo_key.clone(), session.create_time));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetMappingStore for InMemoryStore {
+    async fn find_first_mapping_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error> {
+        Ok(first_after(self.tables().asset_mappings.get(table_name), pseudo_key, ts))
+    }
+
+    async fn find_last_mapping_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error> {
+        Ok(last_before(self.tables().asset_mappings.get(table_name), pseudo_key, ts))
+    }
+
+    async fn put_mapping(&self, table_name: &str, mapping: &AssetIdMapping<'_>) -> Result<(), Error> {
+        let resolved = ResolvedAssetId {
+            asset_id: mapping.asset_id.to_owned(),
+            c_timestamp: mapping.c_timestamp,
+            e_timestamp: mapping.e_timestamp,
+            quarantined: mapping.quarantined,
+        };
+
+        self.tables().asset_mappings
+            .entry(table_name.to_owned())
+            .or_default()
+            .insert((mapping.pseudo_key.clone(), mapping.c_timestamp), resolved);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StaticMappingStore for InMemoryStore {
+    async fn get_static_mapping(&self, table_name: &str, pseudo_key: &str) -> Result<Option<String>, Error> {
+        Ok(
+            self.tables().static_mappings
+                .get(table_name)
+                .and_then(|mappings| mappings.get(pseudo_key))
+                .cloned()
+        )
+    }
+
+    async fn put_static_mapping(&self, table_name: &str, pseudo_key: &str, mapping: &str) -> Result<(), Error> {
+        self.tables().static_mappings
+            .entry(table_name.to_owned())
+            .or_default()
+            .insert(pseudo_key.to_owned(), mapping.to_owned());
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetAliasStore for InMemoryStore {
+    async fn get_alias(&self, table_name: &str, asset_id: &str) -> Result<Option<String>, Error> {
+        Ok(
+            self.tables().asset_aliases
+                .get(table_name)
+                .and_then(|aliases| aliases.get(asset_id))
+                .cloned()
+        )
+    }
+
+    async fn put_alias(
+        &self,
+        table_name: &str,
+        asset_id: &str,
+        canonical_asset_id: &str,
+        condition: AliasCondition<'_>,
+    ) -> Result<bool, Error> {
+        let mut tables = self.tables();
+        let aliases = tables.asset_aliases.entry(table_name.to_owned()).or_default();
+
+        let holds = match condition {
+            AliasCondition::Absent => !aliases.contains_key(asset_id),
+            AliasCondition::AliasOf(expected) => aliases.get(asset_id).map(String::as_str) == Some(expected),
+        };
+
+        if !holds {
+            return Ok(false);
+        }
+
+        aliases.insert(asset_id.to_owned(), canonical_asset_id.to_owned());
+
+        Ok(true)
+    }
+}
@@ -1,115 +1,159 @@
-use failure::Error;
-use futures::future::Future;
-use rusoto_core::{Region, RusotoError};
-use rusoto_dynamodb::{
-    AttributeValue, AttributeValueUpdate, Condition, Delete, DeleteItemInput, DynamoDb,
-    DynamoDbClient, ExpectedAttributeValue, GetItemInput, ListTablesInput, Put, PutItemInput,
-    QueryError, QueryInput, TransactWriteItem, TransactWriteItemsInput, Update, UpdateItemInput,
-};
-use std::convert::TryFrom;
+use std::cmp::min;
+use std::env;
 use std::time::Duration;
+
+use failure::Error;
 use uuid::Uuid;
 
 use crate::sessions::*;
+use crate::storage::{SessionStore, VersionConflict};
+
+/// How often, and how long after, a unid session is retried when one of its versioned
+/// writes conflicts with another node-identifier's
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    // Doubles from base_delay, up to max_delay
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::max_value());
+
+        min(self.base_delay.checked_mul(factor).unwrap_or(self.max_delay), self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(250),
+        }
+    }
+}
+
+/// How precisely a timeline's timestamps are compared. Sources that log with a coarse,
+/// or skewed, clock need a coarser resolution for their events to meet on one session.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeResolution {
+    skew_ms: u64,
+    granularity_ms: u64,
+}
+
+impl TimeResolution {
+    /// Timestamps less than `skew_ms` apart are the same time, so a skew of 1 only
+    /// matches equal timestamps. Timestamps are truncated to a multiple of `granularity_ms`.
+    pub fn new(skew_ms: u64, granularity_ms: u64) -> Self {
+        Self {
+            skew_ms: skew_ms.max(1),
+            granularity_ms: granularity_ms.max(1),
+        }
+    }
+
+    /// Reads `<prefix>_SESSION_SKEW_MS` and `<prefix>_SESSION_GRANULARITY_MS`,
+    /// ie: PROCESS_SESSION_SKEW_MS, defaulting to the default resolution
+    pub fn from_env(prefix: &str) -> Result<Self, Error> {
+        let default = Self::default();
+
+        let var = |name: &str, default: u64| -> Result<u64, Error> {
+            let name = format!("{}_SESSION_{}", prefix, name);
+
+            match env::var(&name) {
+                Ok(value) => value.trim().parse()
+                    .map_err(|e| format_err!("Invalid {}: {:?} {}", name, value, e)),
+                Err(_) => Ok(default),
+            }
+        };
+
+        Ok(Self::new(var("SKEW_MS", default.skew_ms)?, var("GRANULARITY_MS", default.granularity_ms)?))
+    }
+
+    pub fn truncate(&self, ts: u64) -> u64 {
+        ts - (ts % self.granularity_ms)
+    }
+
+    pub fn same_time(&self, ts_1: u64, ts_2: u64) -> bool {
+        skewed_cmp(ts_1, ts_2, self.skew_ms)
+    }
+}
+
+impl Default for TimeResolution {
+    // Every timeline has been compared within 10ms, and truncated to 10s
+    fn default() -> Self {
+        Self::new(10, 10_000)
+    }
+}
+
+/// The time resolution of each kind of session
+#[derive(Debug, Clone, Default)]
+pub struct TimeResolutions {
+    pub process: TimeResolution,
+    pub file: TimeResolution,
+    /// Inbound, outbound, network, and ip connections
+    pub connection: TimeResolution,
+    pub dynamic: TimeResolution,
+}
+
+impl TimeResolutions {
+    /// Reads each resolution with `TimeResolution::from_env`, prefixed with PROCESS,
+    /// FILE, CONNECTION, and DYNAMIC
+    pub fn from_env() -> Result<Self, Error> {
+        Ok(Self {
+            process: TimeResolution::from_env("PROCESS")?,
+            file: TimeResolution::from_env("FILE")?,
+            connection: TimeResolution::from_env("CONNECTION")?,
+            dynamic: TimeResolution::from_env("DYNAMIC")?,
+        })
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct SessionDb<D>
+pub struct SessionDb<S>
     where
-        D: DynamoDb,
+        S: SessionStore,
 {
-    dynamo: D,
+    store: S,
     table_name: String,
+    retry_policy: RetryPolicy,
+    time_resolution: TimeResolution,
 }
 
-impl<D> SessionDb<D>
+impl<S> SessionDb<S>
     where
-        D: DynamoDb,
+        S: SessionStore,
 {
-    pub fn new(dynamo: D, table_name: impl Into<String>) -> Self {
+    pub fn new(store: S, table_name: impl Into<String>) -> Self {
         Self {
-            dynamo,
+            store,
             table_name: table_name.into(),
+            retry_policy: RetryPolicy::default(),
+            time_resolution: TimeResolution::default(),
         }
     }
 
-    pub async fn find_first_session_after(&self, unid: &UnidSession) -> Result<Option<Session>, Error> {
-        info!("Finding first session after");
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            table_name: self.table_name.clone(),
-            key_condition_expression: Some(
-                "pseudo_key = :pseudo_key AND create_time >= :create_time".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pseudo_key".to_owned() => AttributeValue {
-                    s: unid.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                ":create_time".to_owned() => AttributeValue {
-                    n: unid.timestamp.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        let res = wait_on!(self.dynamo.query(query));
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 
-        if let Err(RusotoError::Unknown(ref e)) = res {
-            bail!("Query failed with error: {:?}", e);
-        };
+    pub fn with_time_resolution(mut self, time_resolution: TimeResolution) -> Self {
+        self.time_resolution = time_resolution;
+        self
+    }
 
-        if let Some(items) = res?.items {
-            match &items[..] {
-                [] => Ok(None),
-                [item] => Session::try_from(item.clone()).map(Option::from),
-                _ => bail!("Unexpected number of items returned"),
-            }
-        } else {
-            Ok(None)
-        }
+    pub async fn find_first_session_after(&self, unid: &UnidSession) -> Result<Option<Session>, Error> {
+        self.store.find_first_session_after(&self.table_name, &unid.pseudo_key, unid.timestamp).await
     }
 
     pub async fn find_last_session_before(&self, unid: &UnidSession) -> Result<Option<Session>, Error> {
-        info!("Finding last session before");
-        let query = QueryInput {
-            consistent_read: Some(true),
-            limit: Some(1),
-            scan_index_forward: Some(false),
-            table_name: self.table_name.clone(),
-            key_condition_expression: Some(
-                "pseudo_key = :pseudo_key AND create_time <= :create_time".into(),
-            ),
-            expression_attribute_values: Some(hmap! {
-                ":pseudo_key".to_owned() => AttributeValue {
-                    s: unid.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                ":create_time".to_owned() => AttributeValue {
-                    n: unid.timestamp.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        let res = wait_on!(self.dynamo.query(query))?;
-
-        if let Some(items) = res.items {
-            match &items[..] {
-                [] => Ok(None),
-                [item] => Session::try_from(item.clone()).map(Option::from),
-                _ => bail!("Unexpected number of items returned"),
-            }
-        } else {
-            Ok(None)
-        }
+        self.store.find_last_session_before(&self.table_name, &unid.pseudo_key, unid.timestamp).await
     }
 
-    // `create_time` is the sort key in the table, so updating it is not possible.
-    // Instead, in one transaction, the row must be deleted and recreated with the
-    // new create_time
+    // `create_time` is the sort key in the table, so it is updated by replacing the session
     // This method assumes that the `session` passed in has already been modified
     pub async fn update_session_create_time(
         &self,
@@ -118,48 +162,7 @@ impl<D> SessionDb<D>
         is_canon: bool,
     ) -> Result<(), Error> {
         info!("Updating session create time");
-        let mut new_session = session.to_owned();
-        new_session.create_time = new_time;
-        new_session.is_create_canon = is_canon;
-        new_session.version += 1;
-        // Create new session with new create_time, increment version
-
-        let put_req = Put {
-            item: serde_dynamodb::to_hashmap(&new_session).unwrap(),
-            table_name: self.table_name.clone(),
-            ..Default::default()
-        };
-
-        let del_req = Delete {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            table_name: self.table_name.clone(),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.transact_write_items(TransactWriteItemsInput {
-            transact_items: vec![
-                TransactWriteItem {
-                    delete: del_req.into(),
-                    ..Default::default()
-                },
-                TransactWriteItem {
-                    put: put_req.into(),
-                    ..Default::default()
-                },
-            ],
-            ..Default::default()
-        }))?;
-
-        Ok(())
+        self.store.update_session_create_time(&self.table_name, session, new_time, is_canon).await
     }
 
     pub async fn make_create_time_canonical(
@@ -167,48 +170,7 @@ impl<D> SessionDb<D>
         session: &Session,
     ) -> Result<(), Error> {
         info!("Updating session end time");
-        // Use version as a constraint
-        let upd_req = UpdateItemInput {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            attribute_updates: Some(hmap! {
-                "is_create_canon".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                            bool: true.into(),
-                            ..Default::default()
-                        }),
-                    ..Default::default()
-                },
-                "version".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                        n: (session.version + 1).to_string().into(),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-                }
-            }),
-            table_name: self.table_name.clone(),
-            condition_expression: Some("version = :version".into()),
-            expression_attribute_values: Some(hmap! {
-                ":version".to_owned() => AttributeValue {
-                    n: session.version.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.update_item(upd_req))?;
-
-        Ok(())
+        self.store.make_create_time_canonical(&self.table_name, session).await
     }
 
     // Update version, and use it as a constraint
@@ -219,90 +181,62 @@ impl<D> SessionDb<D>
         is_canon: bool,
     ) -> Result<(), Error> {
         info!("Updating session end time");
-        // Use version as a constraint
-        let upd_req = UpdateItemInput {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            attribute_updates: Some(hmap! {
-                "end_time".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                        n: new_time.to_string().into(),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-                },
-                "is_end_canon".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                            bool: is_canon.into(),
-                            ..Default::default()
-                        }),
-                    ..Default::default()
-                },
-                "version".to_owned() => AttributeValueUpdate {
-                    value: Some(AttributeValue {
-                        n: (session.version + 1).to_string().into(),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-
-                }
-            }),
-            table_name: self.table_name.clone(),
-            condition_expression: Some("version = :version".into()),
-            expression_attribute_values: Some(hmap! {
-                ":version".to_owned() => AttributeValue {
-                    n: session.version.to_string().into(),
-                    ..Default::default()
-                }
-            }),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.update_item(upd_req))?;
-
-        Ok(())
+        self.store.update_session_end_time(&self.table_name, session, new_time, is_canon).await
     }
 
     pub async fn create_session(&self, session: &Session) -> Result<(), Error> {
         info!("create session");
-        let put_req = PutItemInput {
-            item: serde_dynamodb::to_hashmap(session).unwrap(),
-            table_name: self.table_name.clone(),
-            ..Default::default()
-        };
-
-        wait_on!(self.dynamo.put_item(put_req))?;
-
-        Ok(())
+        self.store.create_session(&self.table_name, session).await
     }
 
     pub async fn delete_session(&self, session: &Session) -> Result<(), Error> {
         info!("delete session");
-        let del_req = DeleteItemInput {
-            key: hmap! {
-                "pseudo_key".to_owned() => AttributeValue {
-                    s: session.pseudo_key.clone().into(),
-                    ..Default::default()
-                },
-                "create_time".to_owned() => AttributeValue {
-                    n: session.create_time.to_string().into(),
-                    ..Default::default()
-                }
-            },
-            table_name: self.table_name.clone(),
-            ..Default::default()
+        self.store.delete_session(&self.table_name, session).await
+    }
+
+    // A session's end time is only known once it is canonical, so a session created after
+    // it, within its non canonical end time, ends it.
+    // A canonical end time, with a session canonically created before it, must belong to
+    // that later session. The overlapping session is split at the later session's create
+    // time, and the canonical end time is returned for the later session to take.
+    //
+    // The split session's end is always written as non canonical, even when it had a
+    // canonical end, as it is inferred from the later session rather than logged. Its
+    // end can then still be moved, ie: when the later session is moved or deleted.
+    async fn truncate_overlapping_session(&self, unid: &UnidSession) -> Result<Option<u64>, Error> {
+        if unid.timestamp == 0 {
+            return Ok(None);
+        }
+
+        let before = UnidSession {
+            timestamp: unid.timestamp - 1,
+            ..unid.clone()
+        };
+
+        let session = match self.find_last_session_before(&before).await? {
+            Some(session) => session,
+            None => return Ok(None),
         };
 
-        wait_on!(self.dynamo.delete_item(del_req))?;
-        Ok(())
+        if session.end_time < unid.timestamp {
+            return Ok(None);
+        }
+
+        warn!(
+            "Found session created before new session. Fixing overlapping end_time.
+                {:?}
+                {:?}
+            ",
+            session, unid
+        );
+
+        self.update_session_end_time(&session, unid.timestamp - 1, false).await?;
+
+        if session.is_end_canon {
+            Ok(Some(session.end_time))
+        } else {
+            Ok(None)
+        }
     }
 
     pub async fn handle_creation_event(&self, unid: UnidSession) -> Result<String, Error> {
@@ -312,24 +246,26 @@ impl<D> SessionDb<D>
         );
 
         // Look for first session where session.create_time >= unid.create_time
-        let session = self.find_first_session_after(&unid).await?;
+        let next_session = self.find_first_session_after(&unid).await?;
 
-        if let Some(session) = session {
+        if let Some(ref session) = next_session {
             // If session.is_create_canon is false,
             // This means that there is a 'Guessed' session in the future,
             // and we should consider this the canonical ID for that session
             if !session.is_create_canon && session.create_time != unid.timestamp {
                 info!("Extending session create_time");
-                self.update_session_create_time(&session, unid.timestamp, true).await?;
-                return Ok(session.session_id);
+                // The extended session keeps its own end time
+                self.truncate_overlapping_session(&unid).await?;
+                self.update_session_create_time(session, unid.timestamp, true).await?;
+                return Ok(session.session_id.clone());
             }
 
             // If the timestamps are the same, we've found the session_id
             // No need to update the database here - it's already canonical,
             // with an accurate timestamp
-            if skewed_cmp(unid.timestamp, session.create_time) {
+            if self.time_resolution.same_time(unid.timestamp, session.create_time) {
                 info!("Found existing session with exact create time");
-                return Ok(session.session_id);
+                return Ok(session.session_id.clone());
             }
 
             // We should never be looking at a case where the query returned
@@ -343,32 +279,22 @@ impl<D> SessionDb<D>
             }
         }
 
-        // Look for last session where session.create_time <= unid.create_time
-        let session = self.find_last_session_before(&unid).await?;
+        // Look for last session where session.create_time < unid.create_time, and end it
+        // before the new session if they overlap
+        let canonical_end_time = self.truncate_overlapping_session(&unid).await?;
 
-        if let Some(session) = session {
-            // If session.end_time >= unid.create_time (indicates overlapping sessions, error)
-            // This will correct that session so that it does not overlap anymore.
-            if session.end_time >= unid.timestamp {
-                warn!(
-                    "Found session created before new session. Fixing overlapping end_time.
-                    {:?}
-                    {:?}
-                ",
-                    session, unid
-                );
-                // if session.end_time is NOT canonical, we can update it
-                //                self.update_session_end_time(&session, unid.timestamp - 100, session.is_end_canon)?;
-            }
-        }
+        let (end_time, is_end_canon) = match canonical_end_time {
+            Some(end_time) => end_before(end_time, true, next_session.as_ref()),
+            None => end_before(unid.timestamp.saturating_add(101), false, next_session.as_ref()),
+        };
 
         // Create new session, return new session id
         let session = Session {
             session_id: Uuid::new_v4().to_string(),
             create_time: unid.timestamp,
-            end_time: unid.timestamp + 101,
+            end_time,
             is_create_canon: true,
-            is_end_canon: false,
+            is_end_canon,
             version: 0,
             pseudo_key: unid.pseudo_key,
         };
@@ -391,38 +317,43 @@ impl<D> SessionDb<D>
         // Look for session where session.create_time <= unid.create_time <= session.end_time
         // Look for last session where session.create_time <= unid.create_time
         let session = self.find_last_session_before(&unid).await?;
-        if let Some(mut session) = session {
-            if unid.timestamp < session.end_time || skewed_cmp(unid.timestamp, session.end_time) {
+        if let Some(session) = session {
+            if unid.timestamp < session.end_time
+                || self.time_resolution.same_time(unid.timestamp, session.end_time) {
                 info!("Identified session because it fell within a timeline.");
                 return Ok(session.session_id);
             }
 
+            // The next session is created after unid, so extending the end to unid can not
+            // overlap it. A conflicting update is retried by handle_unid_session.
             if !session.is_end_canon {
-                session.end_time = unid.timestamp;
                 info!("Updating session end_time.");
-                //                self.update_session_end_time(&session, unid.timestamp, false)?;
+                self.update_session_end_time(&session, unid.timestamp, false).await?;
 
                 return Ok(session.session_id);
             }
         }
 
-        let session = self.find_first_session_after(&unid).await?;
-        if let Some(session) = session {
+        // Any session before unid has ended, canonically, before it, so extending the
+        // next session back to unid can not overlap it
+        let next_session = self.find_first_session_after(&unid).await?;
+        if let Some(ref session) = next_session {
             if !session.is_create_canon {
                 info!("Found a later, non canonical session. Extending create_time..");
 
-                self.update_session_create_time(&session, unid.timestamp, false).await?;
-                return Ok(session.session_id);
+                self.update_session_create_time(session, unid.timestamp, false).await?;
+                return Ok(session.session_id.clone());
             }
         }
 
         if should_default {
             info!("Defaulting and creating new session.");
+            let (end_time, _) = end_before(unid.timestamp.saturating_add(101), false, next_session.as_ref());
             let session_id = Uuid::new_v4().to_string();
             let session = Session {
                 session_id: session_id.clone(),
                 create_time: unid.timestamp,
-                end_time: unid.timestamp + 101,
+                end_time,
                 is_create_canon: false,
                 is_end_canon: false,
                 version: 0,
@@ -440,86 +371,428 @@ impl<D> SessionDb<D>
         }
     }
 
+    // Each handler's writes move the timeline towards the same result, whether or not an
+    // earlier attempt made some of them, so on a conflict the unid session is handled
+    // again, from a fresh read of the timeline
     pub async fn handle_unid_session(
         &self,
         mut unid: UnidSession,
         should_default: bool,
     ) -> Result<String, Error> {
-        unid.timestamp = shave_int(unid.timestamp, 3);
-        if unid.is_creation {
-            self.handle_creation_event(unid).await
-        } else {
-            self.handle_last_seen(unid, should_default).await
+        unid.timestamp = self.time_resolution.truncate(unid.timestamp);
+
+        let mut attempt = 0;
+        loop {
+            let res = if unid.is_creation {
+                self.handle_creation_event(unid.clone()).await
+            } else {
+                self.handle_last_seen(unid.clone(), should_default).await
+            };
+
+            match res {
+                Err(ref e) if e.downcast_ref::<VersionConflict>().is_some()
+                    && attempt + 1 < self.retry_policy.max_attempts => {
+                    let delay = self.retry_policy.delay(attempt);
+                    warn!("Retrying unid session in {:?} after: {}", delay, e);
+
+                    // Yields to the other events on this runtime while we back off.
+                    // A zero delay, as in tests, does not need the runtime's timer
+                    if delay > Duration::from_millis(0) {
+                        tokio::time::delay_for(delay).await;
+                    }
+                    attempt += 1;
+                }
+                res => return res,
+            }
         }
     }
 }
 
-pub fn skewed_cmp(ts_1: u64, ts_2: u64) -> bool {
-    ts_1 - 10 < ts_2 && ts_1 + 10 > ts_2
+// Sessions end before the next session on their timeline is created. An end time moved
+// to do so is no longer canonical.
+fn end_before(end_time: u64, is_end_canon: bool, next_session: Option<&Session>) -> (u64, bool) {
+    match next_session {
+        Some(next_session) if next_session.create_time <= end_time => {
+            (next_session.create_time.saturating_sub(1), false)
+        }
+        _ => (end_time, is_end_canon),
+    }
+}
+
+pub fn skewed_cmp(ts_1: u64, ts_2: u64, skew: u64) -> bool {
+    if ts_1 > ts_2 {
+        ts_1 - ts_2 < skew
+    } else {
+        ts_2 - ts_1 < skew
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rusoto_dynamodb::KeySchemaElement;
-    use rusoto_dynamodb::{
-        AttributeDefinition, CreateTableError, CreateTableInput, DeleteTableInput,
-        ProvisionedThroughput,
-    };
-
-    fn create_or_empty_table(dynamo: &impl DynamoDb, table_name: impl Into<String>) {
-        let table_name = table_name.into();
-
-        let _ = dynamo
-            .delete_table(DeleteTableInput {
-                table_name: table_name.clone(),
-            })
-            .with_timeout(Duration::from_secs(1))
-            .sync();
-
-        std::thread::sleep(Duration::from_millis(155));
-
-        let res = dynamo
-            .create_table(CreateTableInput {
-                table_name: table_name.clone(),
-                attribute_definitions: vec![
-                    AttributeDefinition {
-                        attribute_name: "pseudo_key".into(),
-                        attribute_type: "S".into(),
-                    },
-                    AttributeDefinition {
-                        attribute_name: "create_time".into(),
-                        attribute_type: "N".into(),
-                    },
-                ],
-                key_schema: vec![
-                    KeySchemaElement {
-                        attribute_name: "pseudo_key".into(),
-                        key_type: "HASH".into(),
-                    },
-                    KeySchemaElement {
-                        attribute_name: "create_time".into(),
-                        key_type: "RANGE".into(),
-                    },
-                ],
-                provisioned_throughput: Some(ProvisionedThroughput {
-                    read_capacity_units: 3,
-                    write_capacity_units: 3,
-                }),
-                ..Default::default()
-            })
-            .with_timeout(Duration::from_secs(1))
-            .sync()
-            .expect("Failed to crate table");
-    }
-
-    fn local_dynamo() -> impl DynamoDb {
-        let region = Region::Custom {
-            endpoint: "http://localhost:8000".to_owned(),
-            name: "us-east-9".to_owned(),
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+
+    use crate::fake_dynamodb::FakeDynamoDb;
+
+    fn fake_dynamo(table_name: &str) -> FakeDynamoDb {
+        FakeDynamoDb::new().with_table(table_name, "pseudo_key", Some("create_time"))
+    }
+
+    fn no_delay(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(0),
+            max_delay: Duration::from_millis(0),
+        }
+    }
+
+    // Another node-identifier updates the session before each of the first `conflicts`
+    // create time updates
+    #[derive(Clone)]
+    struct ConflictingStore {
+        store: FakeDynamoDb,
+        conflicts: Arc<AtomicU32>,
+        attempts: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl SessionStore for ConflictingStore {
+        async fn find_first_session_after(
+            &self,
+            table_name: &str,
+            pseudo_key: &str,
+            ts: u64,
+        ) -> Result<Option<Session>, Error> {
+            self.store.find_first_session_after(table_name, pseudo_key, ts).await
+        }
+
+        async fn find_last_session_before(
+            &self,
+            table_name: &str,
+            pseudo_key: &str,
+            ts: u64,
+        ) -> Result<Option<Session>, Error> {
+            self.store.find_last_session_before(table_name, pseudo_key, ts).await
+        }
+
+        async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+            self.store.create_session(table_name, session).await
+        }
+
+        async fn update_session_create_time(
+            &self,
+            table_name: &str,
+            session: &Session,
+            new_time: u64,
+            is_canon: bool,
+        ) -> Result<(), Error> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+
+            if self.conflicts.load(Ordering::SeqCst) > 0 {
+                self.conflicts.fetch_sub(1, Ordering::SeqCst);
+                self.store.update_session_end_time(table_name, session, session.end_time, false).await?;
+            }
+
+            self.store.update_session_create_time(table_name, session, new_time, is_canon).await
+        }
+
+        async fn update_session_end_time(
+            &self,
+            table_name: &str,
+            session: &Session,
+            new_time: u64,
+            is_canon: bool,
+        ) -> Result<(), Error> {
+            self.store.update_session_end_time(table_name, session, new_time, is_canon).await
+        }
+
+        async fn make_create_time_canonical(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+            self.store.make_create_time_canonical(table_name, session).await
+        }
+
+        async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+            self.store.delete_session(table_name, session).await
+        }
+    }
+
+    fn conflicting_session_db(table_name: &str, conflicts: u32, max_attempts: u32) -> SessionDb<ConflictingStore> {
+        let store = ConflictingStore {
+            store: fake_dynamo(table_name),
+            conflicts: Arc::new(AtomicU32::new(conflicts)),
+            attempts: Arc::new(AtomicU32::new(0)),
+        };
+
+        let session = Session {
+            pseudo_key: "asset_id_a1234".into(),
+            create_time: 1_544_301_484_600,
+            is_create_canon: false,
+            session_id: "SessionId".into(),
+            is_end_canon: false,
+            end_time: 1_544_301_484_700,
+            version: 0,
         };
 
-        DynamoDbClient::new(region)
+        let session_db = SessionDb::new(store, table_name).with_retry_policy(no_delay(max_attempts));
+        block_on(session_db.create_session(&session)).expect("Failed to create session");
+
+        session_db
+    }
+
+    fn canon_create() -> UnidSession {
+        UnidSession {
+            pseudo_key: "asset_id_a1234".into(),
+            timestamp: 1_544_301_484_500,
+            is_creation: true,
+        }
+    }
+
+    // Given a session that another node-identifier updates after it is read
+    // When a canonical creation event extends its create time
+    // Then the event should be retried against the updated session
+    #[test]
+    fn version_conflicts_are_retried() {
+        let session_db = conflicting_session_db("process_history_version_conflicts_are_retried", 2, 5);
+
+        let session_id = block_on(session_db.handle_unid_session(canon_create(), false))
+            .expect("Failed to handle unid");
+
+        assert_eq!(session_id, "SessionId");
+        assert_eq!(session_db.store.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn version_conflicts_are_retried_a_bounded_number_of_times() {
+        let session_db = conflicting_session_db("process_history_version_conflicts_are_bounded", 10, 3);
+
+        let err = block_on(session_db.handle_unid_session(canon_create(), false))
+            .expect_err("Conflicts should not be retried forever");
+
+        assert!(err.downcast_ref::<VersionConflict>().is_some());
+        assert_eq!(session_db.store.attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn version_conflicts_are_retried_after_a_delay() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(2),
+            max_delay: Duration::from_millis(5),
+        };
+
+        let session_db = conflicting_session_db("process_history_version_conflicts_are_delayed", 3, 5)
+            .with_retry_policy(retry_policy);
+
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_time()
+            .build()
+            .expect("runtime");
+
+        let started = std::time::Instant::now();
+        let session_id = runtime.block_on(session_db.handle_unid_session(canon_create(), false))
+            .expect("Failed to handle unid");
+
+        assert_eq!(session_id, "SessionId");
+        assert_eq!(session_db.store.attempts.load(Ordering::SeqCst), 4);
+        // 2ms, 4ms, and then 5ms rather than 8ms
+        assert!(started.elapsed() >= Duration::from_millis(11));
+    }
+
+    // Sessions on the timeline, in order of their create time
+    fn timeline(session_db: &SessionDb<impl SessionStore>, pseudo_key: &str) -> Vec<Session> {
+        let mut sessions: Vec<Session> = vec![];
+        let mut unid = UnidSession {
+            pseudo_key: pseudo_key.to_owned(),
+            timestamp: 0,
+            is_creation: false,
+        };
+
+        while let Some(session) = block_on(session_db.find_first_session_after(&unid))
+            .expect("Failed to find session") {
+            unid.timestamp = session.create_time + 1;
+            sessions.push(session);
+        }
+
+        sessions
+    }
+
+    fn overlapping(sessions: &[Session]) -> bool {
+        sessions.windows(2).any(|pair| pair[0].end_time >= pair[1].create_time)
+    }
+
+    // Timestamps are not truncated, so that sessions are close enough to overlap
+    fn fine_session_db(table_name: &str) -> SessionDb<FakeDynamoDb> {
+        SessionDb::new(fake_dynamo(table_name), table_name)
+            .with_time_resolution(TimeResolution::new(10, 1))
+    }
+
+    fn handle_all(session_db: &SessionDb<impl SessionStore>, unids: Vec<(u16, bool, bool)>) {
+        for (timestamp, is_creation, should_default) in unids {
+            let unid = UnidSession {
+                pseudo_key: "asset_id_a1234".into(),
+                timestamp: u64::from(timestamp) % 2_000,
+                is_creation,
+            };
+
+            // Unattributed sessions are errors, which leave the timeline as it was
+            let _ = block_on(session_db.handle_unid_session(unid, should_default));
+        }
+    }
+
+    // Given an empty timeline
+    // When any sequence of unid sessions comes in
+    // Then no two sessions on the timeline should overlap
+    #[quickcheck]
+    fn timelines_never_overlap(unids: Vec<(u16, bool, bool)>) -> bool {
+        let session_db = fine_session_db("process_history_timelines_never_overlap");
+
+        handle_all(&session_db, unids);
+
+        !overlapping(&timeline(&session_db, "asset_id_a1234"))
+    }
+
+    // Given a timeline of sessions, some of which have canonical end times
+    // When any sequence of unid sessions comes in
+    // Then no two sessions on the timeline should overlap
+    #[quickcheck]
+    fn timelines_with_canonical_ends_never_overlap(
+        sessions: Vec<(u8, u8, bool)>,
+        unids: Vec<(u16, bool, bool)>,
+    ) -> bool {
+        let session_db = fine_session_db("process_history_timelines_with_canonical_ends_never_overlap");
+
+        let mut create_time = 0;
+        for (i, (gap, length, is_end_canon)) in sessions.into_iter().enumerate() {
+            create_time += u64::from(gap) + 1;
+
+            let session = Session {
+                pseudo_key: "asset_id_a1234".into(),
+                create_time,
+                is_create_canon: true,
+                session_id: format!("Session{}", i),
+                is_end_canon,
+                end_time: create_time + u64::from(length),
+                version: 0,
+            };
+
+            block_on(session_db.create_session(&session)).expect("Failed to create session");
+            create_time = session.end_time;
+        }
+
+        handle_all(&session_db, unids);
+
+        !overlapping(&timeline(&session_db, "asset_id_a1234"))
+    }
+
+    // Given a session with a canonical end time 'E'
+    // When a canonical creation event comes in at 'X', where 'X' < 'E'
+    // Then the session should end, non canonically, before 'X', and the new session
+    //      should end, canonically, at 'E'
+    #[test]
+    fn canon_create_splits_session_with_canonical_end() {
+        let table_name = "process_history_canon_create_splits_session_with_canonical_end";
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
+
+        let session = Session {
+            pseudo_key: "asset_id_a1234".into(),
+            create_time: 1_000,
+            is_create_canon: true,
+            session_id: "SessionId".into(),
+            is_end_canon: true,
+            end_time: 2_000,
+            version: 0,
+        };
+
+        block_on(session_db.create_session(&session)).expect("Failed to create session");
+
+        let unid = UnidSession {
+            pseudo_key: "asset_id_a1234".into(),
+            timestamp: 1_500,
+            is_creation: true,
+        };
+
+        let session_id = block_on(session_db.handle_creation_event(unid))
+            .expect("Failed to handle unid");
+
+        let sessions = timeline(&session_db, "asset_id_a1234");
+
+        assert_eq!(sessions.len(), 2);
+        assert_eq!((sessions[0].end_time, sessions[0].is_end_canon), (1_499, false));
+        assert_eq!(sessions[1].session_id, session_id);
+        assert_eq!((sessions[1].end_time, sessions[1].is_end_canon), (2_000, true));
+    }
+
+    // Given canonical creation events 'X' and 'Y', 5ms apart
+    // When they are identified at a coarse, and then a fine, time resolution
+    // Then they should be one session at the coarse resolution, and two at the fine
+    #[test]
+    fn time_resolution_is_per_timeline() {
+        let create = |timestamp| UnidSession {
+            pseudo_key: "asset_id_a1234".into(),
+            timestamp,
+            is_creation: true,
+        };
+
+        let table_name = "process_history_time_resolution_is_per_timeline";
+        let coarse = SessionDb::new(fake_dynamo(table_name), table_name);
+
+        let first = block_on(coarse.handle_unid_session(create(1_544_301_484_500), false));
+        let second = block_on(coarse.handle_unid_session(create(1_544_301_484_505), false));
+
+        assert_eq!(first.unwrap(), second.unwrap());
+
+        let table_name = "ebpf_process_history_time_resolution_is_per_timeline";
+        let fine = SessionDb::new(fake_dynamo(table_name), table_name)
+            .with_time_resolution(TimeResolution::new(1, 1));
+
+        let first = block_on(fine.handle_unid_session(create(1_544_301_484_500), false));
+        let second = block_on(fine.handle_unid_session(create(1_544_301_484_505), false));
+
+        assert_ne!(first.unwrap(), second.unwrap());
+    }
+
+    #[test]
+    fn skewed_cmp_does_not_underflow() {
+        assert!(skewed_cmp(3, 5, 10));
+        assert!(skewed_cmp(5, 3, 10));
+        assert!(!skewed_cmp(0, 10, 10));
+        assert!(!skewed_cmp(u64::max_value(), 0, 10));
+        assert!(skewed_cmp(7, 7, 1));
+        assert!(!skewed_cmp(7, 8, 1));
+    }
+
+    #[test]
+    fn retry_delay_is_bounded() {
+        let retry_policy = RetryPolicy::default();
+
+        assert_eq!(retry_policy.delay(0), Duration::from_millis(10));
+        assert_eq!(retry_policy.delay(2), Duration::from_millis(40));
+        assert_eq!(retry_policy.delay(40), Duration::from_millis(250));
+    }
+
+    #[test]
+    fn retry_delay_saturates_at_max_delay() {
+        let retry_policy = RetryPolicy::default();
+
+        assert_eq!(retry_policy.delay(4), Duration::from_millis(160));
+        assert_eq!(retry_policy.delay(5), Duration::from_millis(250));
+        // The factor no longer fits in a u32
+        assert_eq!(retry_policy.delay(32), Duration::from_millis(250));
+        assert_eq!(retry_policy.delay(u32::MAX), Duration::from_millis(250));
+
+        // The delay no longer fits in a Duration
+        let retry_policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(u64::MAX / 2),
+            max_delay: Duration::from_secs(60),
+        };
+
+        assert_eq!(retry_policy.delay(0), Duration::from_secs(60));
+        assert_eq!(retry_policy.delay(1), Duration::from_secs(60));
     }
 
     // Given an empty timeline
@@ -528,11 +801,7 @@ mod tests {
     #[quickcheck]
     fn canon_create_on_empty_timeline(asset_id: String, pid: u64) {
         let table_name = "process_history_canon_create_on_empty_timeline";
-        let dynamo = local_dynamo();
-
-        create_or_empty_table(&dynamo, table_name);
-
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
 
         let unid = UnidSession {
             pseudo_key: format!("{}{}", asset_id, pid),
@@ -540,8 +809,7 @@ mod tests {
             is_creation: true,
         };
 
-        let session_id = session_db
-            .handle_unid_session(unid, false)
+        let session_id = block_on(session_db.handle_unid_session(unid, false))
             .expect("Failed to create session");
 
         assert!(!session_id.is_empty());
@@ -555,11 +823,7 @@ mod tests {
     #[quickcheck]
     fn canon_create_update_existing_non_canon_create(asset_id: String, pid: u64) {
         let table_name = "process_history_canon_create_update_existing_non_canon_create";
-        let dynamo = local_dynamo();
-
-        create_or_empty_table(&dynamo, table_name);
-
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
 
         // Given a timeline with a single session, where that session has a non canon
         //      creation time 'X'
@@ -573,8 +837,7 @@ mod tests {
             version: 0,
         };
 
-        session_db
-            .create_session(&session)
+        block_on(session_db.create_session(&session))
             .expect("Failed to create session");
 
         // When a canonical creation event comes in with a creation time of 'Y'
@@ -585,8 +848,7 @@ mod tests {
             is_creation: true,
         };
 
-        let session_id = session_db
-            .handle_unid_session(unid, false)
+        let session_id = block_on(session_db.handle_unid_session(unid, false))
             .expect("Failed to handle unid");
 
         assert_eq!(session_id, "SessionId");
@@ -600,11 +862,7 @@ mod tests {
     #[quickcheck]
     fn noncanon_create_update_existing_non_canon_create(asset_id: String, pid: u64) {
         let table_name = "process_history_noncanon_create_update_existing_non_canon_create";
-        let dynamo = local_dynamo();
-
-        create_or_empty_table(&dynamo, table_name);
-
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
 
         // Given a timeline with a single session, where that session has a non canon
         //      creation time 'X'
@@ -618,8 +876,7 @@ mod tests {
             version: 0,
         };
 
-        session_db
-            .create_session(&session)
+        block_on(session_db.create_session(&session))
             .expect("Failed to create session");
 
         // When a noncanonical creation event comes in with a creation time of 'Y'
@@ -630,8 +887,7 @@ mod tests {
             is_creation: false,
         };
 
-        let session_id = session_db
-            .handle_unid_session(unid, false)
+        let session_id = block_on(session_db.handle_unid_session(unid, false))
             .expect("Failed to handle unid");
 
         // TODO: Assert that the create time was updated correctly
@@ -646,11 +902,50 @@ mod tests {
     #[test]
     fn canon_create_on_timeline_with_surrounding_canon_sessions() {
         let table_name = "process_history_canon_create_on_timeline_with_surrounding_canon_sessions";
-        let dynamo = local_dynamo();
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
 
-        create_or_empty_table(&dynamo, table_name);
+        // Given a timeline with two existing sessions, session A and session B
+        // where A.create_time = X and B.create_time = Y
+        let session_a = Session {
+            pseudo_key: "asset_id_a1234".into(),
+            create_time: 1_544_301_470_000,
+            is_create_canon: true,
+            session_id: "SessionA".into(),
+            is_end_canon: true,
+            end_time: 1_544_301_471_000,
+            version: 0,
+        };
 
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_b = Session {
+            create_time: 1_544_301_490_000,
+            session_id: "SessionB".into(),
+            end_time: 1_544_301_491_000,
+            ..session_a.clone()
+        };
+
+        block_on(session_db.create_session(&session_a)).expect("Failed to create session");
+        block_on(session_db.create_session(&session_b)).expect("Failed to create session");
+
+        // When a canonical creation event comes in with a creation time of 'Z'
+        //      where 'X' < 'Z' < 'Y'
+        let unid = UnidSession {
+            pseudo_key: "asset_id_a1234".into(),
+            timestamp: 1_544_301_480_000,
+            is_creation: true,
+        };
+
+        let session_id = block_on(session_db.handle_unid_session(unid.clone(), false))
+            .expect("Failed to handle unid");
+
+        assert_ne!(session_id, "SessionA");
+        assert_ne!(session_id, "SessionB");
+
+        let created = block_on(session_db.find_last_session_before(&unid))
+            .expect("Failed to find session")
+            .expect("Session was not created");
+
+        assert_eq!(created.session_id, session_id);
+        assert!(created.is_create_canon);
     }
 
     // Given an empty timeline
@@ -659,11 +954,7 @@ mod tests {
     #[quickcheck]
     fn noncanon_create_on_empty_timeline_with_default(asset_id: String, pid: u64) {
         let table_name = "process_history_noncanon_create_on_empty_timeline_with_default";
-        let dynamo = local_dynamo();
-
-        create_or_empty_table(&dynamo, table_name);
-
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
 
         let unid = UnidSession {
             pseudo_key: format!("{}{}", asset_id, pid),
@@ -671,8 +962,7 @@ mod tests {
             is_creation: false,
         };
 
-        let session_id = session_db
-            .handle_unid_session(unid, true)
+        let session_id = block_on(session_db.handle_unid_session(unid, true))
             .expect("Failed to create session");
 
         assert!(!session_id.is_empty());
@@ -684,11 +974,7 @@ mod tests {
     #[test]
     fn noncanon_create_on_empty_timeline_without_default() {
         let table_name = "process_history_noncanon_create_on_empty_timeline_without_default";
-        let dynamo = local_dynamo();
-
-        create_or_empty_table(&dynamo, table_name);
-
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
 
         let unid = UnidSession {
             pseudo_key: "asset_id_a1234".into(),
@@ -696,7 +982,7 @@ mod tests {
             is_creation: false,
         };
 
-        let session_id = session_db.handle_unid_session(unid, false);
+        let session_id = block_on(session_db.handle_unid_session(unid, false));
         assert!(session_id.is_err());
     }
 
@@ -708,21 +994,45 @@ mod tests {
     fn canon_create_on_timeline_with_existing_session_within_skew() {
         let table_name =
             "process_history_canon_create_on_timeline_with_existing_session_within_skew";
-        let dynamo = local_dynamo();
+        let session_db = SessionDb::new(fake_dynamo(table_name), table_name);
 
-        create_or_empty_table(&dynamo, table_name);
+        // Given a timeline with one session, where the session has a create_time
+        //      of X
+        let session = Session {
+            pseudo_key: "asset_id_a1234".into(),
+            create_time: 1_544_301_480_005,
+            is_create_canon: false,
+            session_id: "SessionId".into(),
+            is_end_canon: false,
+            end_time: 1_544_301_480_106,
+            version: 0,
+        };
+
+        block_on(session_db.create_session(&session)).expect("Failed to create session");
+
+        // When a canon create event comes in with a create time within ~100ms of X
+        let unid = UnidSession {
+            pseudo_key: "asset_id_a1234".into(),
+            timestamp: 1_544_301_480_000,
+            is_creation: true,
+        };
 
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_id = block_on(session_db.handle_unid_session(unid.clone(), false))
+            .expect("Failed to handle unid");
+
+        assert_eq!(session_id, "SessionId");
+
+        let updated = block_on(session_db.find_last_session_before(&unid))
+            .expect("Failed to find session")
+            .expect("Session was removed");
+
+        assert!(updated.is_create_canon);
+        assert_eq!(updated.version, 1);
     }
 
     #[quickcheck]
     fn update_end_time(asset_id: String, pid: u64) {
-        let table_name = "process_history_update_end_time";
-        let dynamo = local_dynamo();
-
-        create_or_empty_table(&dynamo, table_name);
-
-        let session_db = SessionDb::new(&dynamo, table_name);
+        let session_db = fine_session_db("process_history_update_end_time");
 
         // Given a timeline with a single session, where that session has a non canon
         //      end time 'X'
@@ -736,8 +1046,7 @@ mod tests {
             version: 0,
         };
 
-        session_db
-            .create_session(&session)
+        block_on(session_db.create_session(&session))
             .expect("Failed to create session");
 
         // When a canonical creation event comes in with an end time of 'Y'
@@ -748,10 +1057,15 @@ mod tests {
             is_creation: false,
         };
 
-        let session_id = session_db
-            .handle_unid_session(unid, false)
+        let session_id = block_on(session_db.handle_unid_session(unid, false))
             .expect("Failed to handle unid");
 
         assert_eq!(session_id, "SessionId");
+
+        // Then the session should be extended, non canonically, to 'Y'
+        let sessions = timeline(&session_db, &format!("{}{}", asset_id, pid));
+
+        assert_eq!(sessions.len(), 1);
+        assert_eq!((sessions[0].end_time, sessions[0].is_end_canon), (1_544_301_484_800, false));
     }
 }
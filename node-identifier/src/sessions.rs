@@ -14,7 +14,7 @@ use std::convert::TryFrom;
 use std::time::Duration;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UnidSession {
     pub pseudo_key: String,
     pub timestamp: u64,
@@ -38,8 +38,3 @@ impl TryFrom<HashMap<String, AttributeValue>> for Session {
         Ok(serde_dynamodb::from_hashmap(map)?)
     }
 }
-
-pub fn shave_int(input: u64, digits: u8) -> u64 {
-    let digits = 10u64.pow((digits as u32) + 1u32);
-    (input - (input % digits))
-}
@@ -0,0 +1,374 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use async_trait::async_trait;
+use failure::Error;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::assetdb::{AssetIdMapping, ResolvedAssetId};
+use crate::sessions::Session;
+use crate::storage::{AliasCondition, AssetAliasStore, AssetMappingStore, SessionStore, StaticMappingStore, VersionConflict};
+
+// Every table node-identifier writes to is a partition of one SQLite table, keyed as the
+// DynamoDB table is, on its pseudo key and sort key.
+// Timestamps are stored as i64, which holds milliseconds since the epoch.
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    table_name TEXT NOT NULL,
+    pseudo_key TEXT NOT NULL,
+    create_time INTEGER NOT NULL,
+    session_id TEXT NOT NULL,
+    end_time INTEGER NOT NULL,
+    is_create_canon INTEGER NOT NULL,
+    is_end_canon INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    PRIMARY KEY (table_name, pseudo_key, create_time)
+);
+
+CREATE TABLE IF NOT EXISTS asset_id_mappings (
+    table_name TEXT NOT NULL,
+    pseudo_key TEXT NOT NULL,
+    c_timestamp INTEGER NOT NULL,
+    asset_id TEXT NOT NULL,
+    e_timestamp INTEGER,
+    quarantined INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (table_name, pseudo_key, c_timestamp)
+);
+
+CREATE TABLE IF NOT EXISTS static_mappings (
+    table_name TEXT NOT NULL,
+    pseudo_key TEXT NOT NULL,
+    mapping TEXT NOT NULL,
+    PRIMARY KEY (table_name, pseudo_key)
+);
+
+CREATE TABLE IF NOT EXISTS asset_aliases (
+    table_name TEXT NOT NULL,
+    pseudo_key TEXT NOT NULL,
+    canonical_asset_id TEXT NOT NULL,
+    PRIMARY KEY (table_name, pseudo_key)
+);
+";
+
+const SESSION_COLUMNS: &str =
+    "session_id, pseudo_key, create_time, end_time, is_create_canon, is_end_canon, version";
+
+/// Stores identities in a SQLite database, ie: for a single node deployment
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, Error> {
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    fn connection(&self) -> MutexGuard<Connection> {
+        match self.connection.lock() {
+            Ok(connection) => connection,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn find_session(&self, query: &str, table_name: &str, pseudo_key: &str, ts: u64) -> Result<Option<Session>, Error> {
+        let query = format!(
+            "SELECT {} FROM sessions WHERE table_name = ?1 AND pseudo_key = ?2 AND {}",
+            SESSION_COLUMNS,
+            query,
+        );
+
+        Ok(
+            self.connection()
+                .query_row(&query, params![table_name, pseudo_key, ts as i64], session_from_row)
+                .optional()?
+        )
+    }
+
+    fn find_mapping(&self, query: &str, table_name: &str, pseudo_key: &str, ts: u64) -> Result<Option<ResolvedAssetId>, Error> {
+        let query = format!(
+            "SELECT asset_id, c_timestamp, e_timestamp, quarantined FROM asset_id_mappings \
+             WHERE table_name = ?1 AND pseudo_key = ?2 AND {}",
+            query,
+        );
+
+        let mapping = self.connection()
+            .query_row(&query, params![table_name, pseudo_key, ts as i64], |row| {
+                let c_timestamp: i64 = row.get(1)?;
+                let e_timestamp: Option<i64> = row.get(2)?;
+
+                Ok(ResolvedAssetId {
+                    asset_id: row.get(0)?,
+                    c_timestamp: c_timestamp as u64,
+                    e_timestamp: e_timestamp.map(|e_timestamp| e_timestamp as u64),
+                    quarantined: row.get(3)?,
+                })
+            })
+            .optional()?;
+
+        Ok(mapping)
+    }
+}
+
+// Conditional updates fail, as they do in DynamoDB, when the session has been updated,
+// or moved, since it was read
+fn check_version(updated: usize, session: &Session) -> Result<(), Error> {
+    if updated == 0 {
+        return Err(VersionConflict::new(session).into());
+    }
+
+    Ok(())
+}
+
+fn session_from_row(row: &Row) -> rusqlite::Result<Session> {
+    let create_time: i64 = row.get(2)?;
+    let end_time: i64 = row.get(3)?;
+    let version: i64 = row.get(6)?;
+
+    Ok(Session {
+        session_id: row.get(0)?,
+        pseudo_key: row.get(1)?,
+        create_time: create_time as u64,
+        end_time: end_time as u64,
+        is_create_canon: row.get(4)?,
+        is_end_canon: row.get(5)?,
+        version: version as u64,
+    })
+}
+
+fn insert_session(connection: &Connection, table_name: &str, session: &Session) -> rusqlite::Result<usize> {
+    connection.execute(
+        &format!(
+            "INSERT OR REPLACE INTO sessions (table_name, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            SESSION_COLUMNS,
+        ),
+        params![
+            table_name,
+            session.session_id,
+            session.pseudo_key,
+            session.create_time as i64,
+            session.end_time as i64,
+            session.is_create_canon,
+            session.is_end_canon,
+            session.version as i64,
+        ],
+    )
+}
+
+#[async_trait]
+impl SessionStore for SqliteStore {
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error> {
+        self.find_session("create_time >= ?3 ORDER BY create_time ASC LIMIT 1", table_name, pseudo_key, ts)
+    }
+
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error> {
+        self.find_session("create_time <= ?3 ORDER BY create_time DESC LIMIT 1", table_name, pseudo_key, ts)
+    }
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        insert_session(&self.connection(), table_name, session)?;
+
+        Ok(())
+    }
+
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let mut new_session = session.to_owned();
+        new_session.create_time = new_time;
+        new_session.is_create_canon = is_canon;
+        new_session.version += 1;
+
+        let mut connection = self.connection();
+        let transaction = connection.transaction()?;
+
+        // Dropping the transaction, when the version check fails, rolls it back
+        let deleted = transaction.execute(
+            "DELETE FROM sessions \
+             WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time = ?3 AND version = ?4",
+            params![
+                table_name,
+                session.pseudo_key,
+                session.create_time as i64,
+                session.version as i64,
+            ],
+        )?;
+        check_version(deleted, session)?;
+        insert_session(&transaction, table_name, &new_session)?;
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error> {
+        let updated = self.connection().execute(
+            "UPDATE sessions SET end_time = ?5, is_end_canon = ?6, version = ?4 + 1 \
+             WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time = ?3 AND version = ?4",
+            params![
+                table_name,
+                session.pseudo_key,
+                session.create_time as i64,
+                session.version as i64,
+                new_time as i64,
+                is_canon,
+            ],
+        )?;
+
+        check_version(updated, session)
+    }
+
+    async fn make_create_time_canonical(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        let updated = self.connection().execute(
+            "UPDATE sessions SET is_create_canon = 1, version = ?4 + 1 \
+             WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time = ?3 AND version = ?4",
+            params![
+                table_name,
+                session.pseudo_key,
+                session.create_time as i64,
+                session.version as i64,
+            ],
+        )?;
+
+        check_version(updated, session)
+    }
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error> {
+        self.connection().execute(
+            "DELETE FROM sessions WHERE table_name = ?1 AND pseudo_key = ?2 AND create_time = ?3",
+            params![table_name, session.pseudo_key, session.create_time as i64],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetMappingStore for SqliteStore {
+    async fn find_first_mapping_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error> {
+        self.find_mapping("c_timestamp >= ?3 ORDER BY c_timestamp ASC LIMIT 1", table_name, pseudo_key, ts)
+    }
+
+    async fn find_last_mapping_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error> {
+        self.find_mapping("c_timestamp <= ?3 ORDER BY c_timestamp DESC LIMIT 1", table_name, pseudo_key, ts)
+    }
+
+    async fn put_mapping(&self, table_name: &str, mapping: &AssetIdMapping<'_>) -> Result<(), Error> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO asset_id_mappings \
+             (table_name, pseudo_key, c_timestamp, asset_id, e_timestamp, quarantined) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                table_name,
+                mapping.pseudo_key,
+                mapping.c_timestamp as i64,
+                mapping.asset_id,
+                mapping.e_timestamp.map(|e_timestamp| e_timestamp as i64),
+                mapping.quarantined,
+            ],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StaticMappingStore for SqliteStore {
+    async fn get_static_mapping(&self, table_name: &str, pseudo_key: &str) -> Result<Option<String>, Error> {
+        Ok(
+            self.connection()
+                .query_row(
+                    "SELECT mapping FROM static_mappings WHERE table_name = ?1 AND pseudo_key = ?2",
+                    params![table_name, pseudo_key],
+                    |row| row.get(0),
+                )
+                .optional()?
+        )
+    }
+
+    async fn put_static_mapping(&self, table_name: &str, pseudo_key: &str, mapping: &str) -> Result<(), Error> {
+        self.connection().execute(
+            "INSERT OR REPLACE INTO static_mappings (table_name, pseudo_key, mapping) VALUES (?1, ?2, ?3)",
+            params![table_name, pseudo_key, mapping],
+        )?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AssetAliasStore for SqliteStore {
+    async fn get_alias(&self, table_name: &str, asset_id: &str) -> Result<Option<String>, Error> {
+        Ok(
+            self.connection()
+                .query_row(
+                    "SELECT canonical_asset_id FROM asset_aliases WHERE table_name = ?1 AND pseudo_key = ?2",
+                    params![table_name, asset_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+        )
+    }
+
+    async fn put_alias(
+        &self,
+        table_name: &str,
+        asset_id: &str,
+        canonical_asset_id: &str,
+        condition: AliasCondition<'_>,
+    ) -> Result<bool, Error> {
+        let written = match condition {
+            AliasCondition::Absent => self.connection().execute(
+                "INSERT OR IGNORE INTO asset_aliases (table_name, pseudo_key, canonical_asset_id) VALUES (?1, ?2, ?3)",
+                params![table_name, asset_id, canonical_asset_id],
+            )?,
+            AliasCondition::AliasOf(expected) => self.connection().execute(
+                "UPDATE asset_aliases SET canonical_asset_id = ?3
+                 WHERE table_name = ?1 AND pseudo_key = ?2 AND canonical_asset_id = ?4",
+                params![table_name, asset_id, canonical_asset_id, expected],
+            )?,
+        };
+
+        Ok(written != 0)
+    }
+}
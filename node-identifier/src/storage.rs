@@ -0,0 +1,434 @@
+use std::env;
+#[cfg(feature = "sqlite")]
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use failure::{Error, Fail};
+
+use crate::assetdb::{AssetIdMapping, ResolvedAssetId};
+use crate::sessions::Session;
+
+// Each store is given the name of the table it reads and writes, so that one store can
+// hold many timelines, ie: process_history_table and file_history_table.
+//
+// Any DynamoDb client is a store, see dynamodb_store. InMemoryStore, and SqliteStore,
+// have the same semantics, so that node-identifier can run without AWS.
+
+/// Returned by a SessionStore when a versioned write finds that the session has been
+/// updated, or moved, since it was read
+#[derive(Debug, Fail)]
+#[fail(display = "Conditional check failed for session {} at version {}", session_id, version)]
+pub struct VersionConflict {
+    pub session_id: String,
+    pub version: u64,
+}
+
+impl VersionConflict {
+    pub fn new(session: &Session) -> Self {
+        Self {
+            session_id: session.session_id.clone(),
+            version: session.version,
+        }
+    }
+}
+
+/// Session timelines, keyed on the session's pseudo key and its create time
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    /// The first session for the pseudo key created at, or after, `ts`
+    async fn find_first_session_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error>;
+
+    /// The last session for the pseudo key created at, or before, `ts`
+    async fn find_last_session_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<Session>, Error>;
+
+    async fn create_session(&self, table_name: &str, session: &Session) -> Result<(), Error>;
+
+    /// Moves the session to `new_time`, incrementing its version. The create time is part
+    /// of the session's key, so the session is deleted and recreated in one write.
+    /// Fails with VersionConflict if the session is no longer at `session.version`
+    async fn update_session_create_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error>;
+
+    /// Fails with VersionConflict if the session is no longer at `session.version`
+    async fn update_session_end_time(
+        &self,
+        table_name: &str,
+        session: &Session,
+        new_time: u64,
+        is_canon: bool,
+    ) -> Result<(), Error>;
+
+    /// Fails with VersionConflict if the session is no longer at `session.version`
+    async fn make_create_time_canonical(&self, table_name: &str, session: &Session) -> Result<(), Error>;
+
+    async fn delete_session(&self, table_name: &str, session: &Session) -> Result<(), Error>;
+}
+
+/// Host id to asset id mappings, keyed on the host id's pseudo key and the mapping's c_timestamp
+#[async_trait]
+pub trait AssetMappingStore: Send + Sync {
+    /// The first mapping for the pseudo key created at, or after, `ts`
+    async fn find_first_mapping_after(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error>;
+
+    /// The last mapping for the pseudo key created at, or before, `ts`, whether or not it has ended
+    async fn find_last_mapping_before(
+        &self,
+        table_name: &str,
+        pseudo_key: &str,
+        ts: u64,
+    ) -> Result<Option<ResolvedAssetId>, Error>;
+
+    /// Replaces any mapping for the pseudo key at the same c_timestamp
+    async fn put_mapping(&self, table_name: &str, mapping: &AssetIdMapping<'_>) -> Result<(), Error>;
+}
+
+/// Static mappings from a dynamic node's pseudo key to its node key
+#[async_trait]
+pub trait StaticMappingStore: Send + Sync {
+    async fn get_static_mapping(&self, table_name: &str, pseudo_key: &str) -> Result<Option<String>, Error>;
+
+    async fn put_static_mapping(&self, table_name: &str, pseudo_key: &str, mapping: &str) -> Result<(), Error>;
+}
+
+/// Aliases from an asset id to the canonical asset id of its machine
+#[async_trait]
+pub trait AssetAliasStore: Send + Sync {
+    async fn get_alias(&self, table_name: &str, asset_id: &str) -> Result<Option<String>, Error>;
+
+    /// Returns false, without writing, when the alias no longer holds `condition`
+    async fn put_alias(
+        &self,
+        table_name: &str,
+        asset_id: &str,
+        canonical_asset_id: &str,
+        condition: AliasCondition<'_>,
+    ) -> Result<bool, Error>;
+}
+
+/// What an asset id's alias must be for `put_alias` to replace it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AliasCondition<'a> {
+    /// The asset id is not an alias yet
+    Absent,
+    /// The asset id is still an alias of this asset id
+    AliasOf(&'a str),
+}
+
+/// Everything node-identifier stores
+pub trait IdentityStore: SessionStore + AssetMappingStore + StaticMappingStore + AssetAliasStore {}
+
+impl<S> IdentityStore for S
+    where S: SessionStore + AssetMappingStore + StaticMappingStore + AssetAliasStore
+{}
+
+/// The store node-identifier is run with, as configured by `IDENTITY_STORE`
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdentityStoreBackend {
+    DynamoDb,
+    /// Identities only last as long as the store, ie: for local testing
+    Memory,
+    /// The database at `IDENTITY_STORE_PATH`
+    #[cfg(feature = "sqlite")]
+    Sqlite(PathBuf),
+}
+
+impl IdentityStoreBackend {
+    /// Reads `IDENTITY_STORE`, one of dynamodb, memory or sqlite, defaulting to dynamodb
+    pub fn from_env() -> Result<Self, Error> {
+        let backend = match env::var("IDENTITY_STORE") {
+            Ok(backend) => backend,
+            Err(_) => return Ok(IdentityStoreBackend::DynamoDb),
+        };
+
+        match backend.trim().to_lowercase().as_str() {
+            "dynamodb" => Ok(IdentityStoreBackend::DynamoDb),
+            "memory" => Ok(IdentityStoreBackend::Memory),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => {
+                let path = env::var("IDENTITY_STORE_PATH")
+                    .map_err(|_| format_err!("IDENTITY_STORE_PATH must be set for the sqlite store"))?;
+                Ok(IdentityStoreBackend::Sqlite(path.into()))
+            }
+            #[cfg(not(feature = "sqlite"))]
+            "sqlite" => bail!("node-identifier was built without the sqlite feature"),
+            _ => bail!("Invalid identity store: {}", backend),
+        }
+    }
+}
+
+// The semantics every store must have, checked against each of them
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    use crate::fake_dynamodb::FakeDynamoDb;
+    use crate::memory_store::InMemoryStore;
+    #[cfg(feature = "sqlite")]
+    use crate::sqlite_store::SqliteStore;
+
+    fn session(create_time: u64, version: u64) -> Session {
+        Session {
+            session_id: format!("session{}", create_time),
+            pseudo_key: "asset_id_a1234".to_owned(),
+            create_time,
+            end_time: create_time + 101,
+            is_create_canon: false,
+            is_end_canon: false,
+            version,
+        }
+    }
+
+    fn mapping(asset_id: &str, c_timestamp: u64, e_timestamp: Option<u64>, quarantined: bool) -> AssetIdMapping<'_> {
+        AssetIdMapping {
+            pseudo_key: "hostnamewin-01".to_owned(),
+            asset_id,
+            c_timestamp,
+            e_timestamp,
+            quarantined,
+        }
+    }
+
+    fn is_version_conflict(result: Result<(), Error>) -> bool {
+        match result {
+            Err(e) => e.downcast_ref::<VersionConflict>().is_some(),
+            Ok(()) => false,
+        }
+    }
+
+    async fn sessions_are_found_around_a_timestamp(store: &impl SessionStore) {
+        store.create_session("process_history_table", &session(1000, 0)).await.unwrap();
+        store.create_session("process_history_table", &session(2000, 0)).await.unwrap();
+
+        let before = store.find_last_session_before("process_history_table", "asset_id_a1234", 1500)
+            .await
+            .unwrap();
+        let after = store.find_first_session_after("process_history_table", "asset_id_a1234", 1500)
+            .await
+            .unwrap();
+        let at = store.find_first_session_after("process_history_table", "asset_id_a1234", 2000)
+            .await
+            .unwrap();
+        let other_table = store.find_first_session_after("file_history_table", "asset_id_a1234", 0)
+            .await
+            .unwrap();
+
+        assert_eq!(before.map(|s| s.create_time), Some(1000));
+        assert_eq!(after.map(|s| s.create_time), Some(2000));
+        assert_eq!(at.map(|s| s.create_time), Some(2000));
+        assert!(other_table.is_none());
+    }
+
+    async fn sessions_are_moved_and_updated(store: &impl SessionStore) {
+        store.create_session("process_history_table", &session(1000, 0)).await.unwrap();
+
+        store.update_session_create_time("process_history_table", &session(1000, 0), 900, true)
+            .await
+            .unwrap();
+
+        let moved = store.find_first_session_after("process_history_table", "asset_id_a1234", 0)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((moved.create_time, moved.is_create_canon, moved.version), (900, true, 1));
+        assert!(store.find_first_session_after("process_history_table", "asset_id_a1234", 901)
+            .await
+            .unwrap()
+            .is_none());
+
+        store.update_session_end_time("process_history_table", &moved, 1500, true)
+            .await
+            .unwrap();
+
+        let updated = store.find_last_session_before("process_history_table", "asset_id_a1234", 900)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((updated.end_time, updated.is_end_canon, updated.version), (1500, true, 2));
+
+        store.delete_session("process_history_table", &updated).await.unwrap();
+
+        assert!(store.find_last_session_before("process_history_table", "asset_id_a1234", 2000)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    async fn stale_versions_are_not_updated(store: &impl SessionStore) {
+        store.create_session("process_history_table", &session(1000, 1)).await.unwrap();
+
+        let stale = session(1000, 0);
+
+        assert!(is_version_conflict(
+            store.update_session_end_time("process_history_table", &stale, 1500, true).await
+        ));
+        assert!(is_version_conflict(
+            store.update_session_create_time("process_history_table", &stale, 900, true).await
+        ));
+        assert!(is_version_conflict(
+            store.make_create_time_canonical("process_history_table", &stale).await
+        ));
+
+        let unchanged = store.find_last_session_before("process_history_table", "asset_id_a1234", 2000)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((unchanged.create_time, unchanged.end_time, unchanged.version), (1000, 1101, 1));
+        assert!(!unchanged.is_create_canon);
+
+        store.update_session_end_time("process_history_table", &session(1000, 1), 1500, true)
+            .await
+            .unwrap();
+
+        let updated = store.find_last_session_before("process_history_table", "asset_id_a1234", 1000)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((updated.end_time, updated.version), (1500, 2));
+    }
+
+    async fn mappings_are_found_around_a_timestamp(store: &impl AssetMappingStore) {
+        store.put_mapping("asset_id_mappings", &mapping("asset_id_a", 1000, Some(2000), false)).await.unwrap();
+        store.put_mapping("asset_id_mappings", &mapping("asset_id_b", 3000, Some(3000), true)).await.unwrap();
+
+        let before = store.find_last_mapping_before("asset_id_mappings", "hostnamewin-01", 1500)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(before.asset_id, "asset_id_a");
+        assert_eq!((before.c_timestamp, before.e_timestamp, before.quarantined), (1000, Some(2000), false));
+
+        let after = store.find_first_mapping_after("asset_id_mappings", "hostnamewin-01", 1500)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(after.asset_id, "asset_id_b");
+        assert_eq!((after.c_timestamp, after.e_timestamp, after.quarantined), (3000, Some(3000), true));
+
+        assert!(store.find_first_mapping_after("asset_id_mappings", "hostnamewin-01", 3001)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store.find_last_mapping_before("asset_id_mappings", "hostnamewin-01", 999)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store.find_last_mapping_before("asset_id_mappings", "hostnamewin-02", 1500)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    async fn mappings_are_replaced_at_their_c_timestamp(store: &impl AssetMappingStore) {
+        store.put_mapping("asset_id_mappings", &mapping("asset_id_a", 1000, None, false)).await.unwrap();
+        store.put_mapping("asset_id_mappings", &mapping("asset_id_b", 1000, None, false)).await.unwrap();
+
+        let replaced = store.find_last_mapping_before("asset_id_mappings", "hostnamewin-01", 1000)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((replaced.asset_id.as_str(), replaced.e_timestamp), ("asset_id_b", None));
+    }
+
+    async fn aliases_are_replaced_only_when_they_hold_their_condition(store: &impl AssetAliasStore) {
+        assert_eq!(store.get_alias("asset_aliases", "asset_id_c").await.unwrap(), None);
+
+        // An alias that does not exist is not an alias of anything
+        assert!(!store.put_alias("asset_aliases", "asset_id_c", "asset_id_a", AliasCondition::AliasOf("asset_id_b"))
+            .await
+            .unwrap());
+        assert_eq!(store.get_alias("asset_aliases", "asset_id_c").await.unwrap(), None);
+
+        assert!(store.put_alias("asset_aliases", "asset_id_c", "asset_id_b", AliasCondition::Absent)
+            .await
+            .unwrap());
+        assert!(!store.put_alias("asset_aliases", "asset_id_c", "asset_id_a", AliasCondition::Absent)
+            .await
+            .unwrap());
+        assert!(!store.put_alias("asset_aliases", "asset_id_c", "asset_id_a", AliasCondition::AliasOf("asset_id_d"))
+            .await
+            .unwrap());
+        assert_eq!(store.get_alias("asset_aliases", "asset_id_c").await.unwrap(), Some("asset_id_b".to_owned()));
+
+        assert!(store.put_alias("asset_aliases", "asset_id_c", "asset_id_a", AliasCondition::AliasOf("asset_id_b"))
+            .await
+            .unwrap());
+        assert_eq!(store.get_alias("asset_aliases", "asset_id_c").await.unwrap(), Some("asset_id_a".to_owned()));
+    }
+
+    async fn static_mappings_are_replaced(store: &impl StaticMappingStore) {
+        assert_eq!(store.get_static_mapping("static_mapping_table", "node_a").await.unwrap(), None);
+
+        store.put_static_mapping("static_mapping_table", "node_a", "node_key_a").await.unwrap();
+        store.put_static_mapping("static_mapping_table", "node_a", "node_key_b").await.unwrap();
+
+        assert_eq!(
+            store.get_static_mapping("static_mapping_table", "node_a").await.unwrap(),
+            Some("node_key_b".to_owned()),
+        );
+    }
+
+    // Each check is given a new store
+    fn check_store<S: IdentityStore>(new_store: impl Fn() -> S) {
+        block_on(async {
+            sessions_are_found_around_a_timestamp(&new_store()).await;
+            sessions_are_moved_and_updated(&new_store()).await;
+            stale_versions_are_not_updated(&new_store()).await;
+            mappings_are_found_around_a_timestamp(&new_store()).await;
+            mappings_are_replaced_at_their_c_timestamp(&new_store()).await;
+            aliases_are_replaced_only_when_they_hold_their_condition(&new_store()).await;
+            static_mappings_are_replaced(&new_store()).await;
+        });
+    }
+
+    #[test]
+    fn in_memory_store_has_store_semantics() {
+        check_store(InMemoryStore::new);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_store_has_store_semantics() {
+        check_store(|| SqliteStore::open_in_memory().expect("open_in_memory"));
+    }
+
+    #[test]
+    fn dynamodb_store_has_store_semantics() {
+        check_store(|| {
+            FakeDynamoDb::new()
+                .with_table("process_history_table", "pseudo_key", Some("create_time"))
+                .with_table("file_history_table", "pseudo_key", Some("create_time"))
+                .with_table("asset_id_mappings", "pseudo_key", Some("c_timestamp"))
+                .with_table("asset_aliases", "pseudo_key", None)
+                .with_table("static_mapping_table", "pseudo_key", None)
+        });
+    }
+}
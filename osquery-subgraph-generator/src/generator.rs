@@ -0,0 +1,637 @@
+use failure::Error;
+use graph_descriptions::container::{Container, ContainerBuilder};
+use graph_descriptions::file::FileState;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::network_connection::NetworkConnectionState;
+use graph_descriptions::node::NodeT;
+use graph_descriptions::process::ProcessState;
+use graph_descriptions::process_inbound_connection::ProcessInboundConnectionState;
+use graph_descriptions::process_outbound_connection::ProcessOutboundConnectionState;
+
+use crate::results::{QueryResult, Row, RowAction};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Table {
+    Processes,
+    ProcessEvents,
+    SocketEvents,
+    FileEvents,
+}
+
+impl Table {
+    // Queries are usually named after their pack, ie: pack_grapl_process_events
+    fn from_query_name(name: &str) -> Option<Table> {
+        let table = if name.ends_with("process_events") {
+            Table::ProcessEvents
+        } else if name.ends_with("socket_events") {
+            Table::SocketEvents
+        } else if name.ends_with("file_events") {
+            Table::FileEvents
+        } else if name.ends_with("processes") {
+            Table::Processes
+        } else {
+            return None;
+        };
+
+        Some(table)
+    }
+}
+
+/// Generates a subgraph for every row of a query result.
+/// Results of queries against tables we do not model produce no graph.
+pub fn generate_subgraph(result: QueryResult) -> Result<Option<Graph>, Error> {
+    let table = match Table::from_query_name(&result.name) {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+
+    let hostname = result.hostname().to_owned();
+    let query_time = result.unix_time * 1000;
+
+    let mut graph = Graph::new(query_time);
+
+    for (action, row) in result.into_rows() {
+        // Event tables record when the event happened, rather than when it was queried
+        let timestamp = match table {
+            Table::Processes => query_time,
+            _ => row.u64("time").map(|time| time * 1000).unwrap_or(query_time),
+        };
+
+        let row_graph = match table {
+            Table::Processes => handle_process_row(&hostname, timestamp, action, &row)?,
+            Table::ProcessEvents => handle_process_event(&hostname, timestamp, &row)?,
+            Table::SocketEvents => handle_socket_event(&hostname, timestamp, &row)?,
+            Table::FileEvents => handle_file_event(&hostname, timestamp, &row)?,
+        };
+
+        if let Some(row_graph) = row_graph {
+            graph.merge(&row_graph);
+        }
+    }
+
+    if graph.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(graph))
+}
+
+fn required_u64(row: &Row, column: &str) -> Result<u64, Error> {
+    match row.u64(column) {
+        Some(value) => Ok(value),
+        None => bail!("Row is missing column: {}", column),
+    }
+}
+
+fn asset(hostname: &str) -> Asset {
+    AssetBuilder::default()
+        .asset_id(hostname.to_owned())
+        .hostname(hostname.to_owned())
+        .build()
+        .unwrap()
+}
+
+fn existing_process(hostname: &str, pid: u64, timestamp: u64) -> Process {
+    ProcessBuilder::default()
+        .hostname(hostname.to_owned())
+        .state(ProcessState::Existing)
+        .process_id(pid)
+        .last_seen_timestamp(timestamp)
+        .build()
+        .unwrap()
+}
+
+fn bin_file(hostname: &str, path: String, timestamp: u64) -> File {
+    FileBuilder::default()
+        .hostname(hostname.to_owned())
+        .state(FileState::Existing)
+        .last_seen_timestamp(timestamp)
+        .file_path(path)
+        .build()
+        .unwrap()
+}
+
+// osquery has no container context of its own, but queries that join against the
+// docker_* tables, or are decorated by the runtime, can report it in these columns
+fn container(hostname: &str, timestamp: u64, row: &Row) -> Option<Container> {
+    let container_id = row.string("container_id")?;
+
+    let container = ContainerBuilder::default()
+        .container_id(container_id)
+        .hostname(hostname.to_owned())
+        .image(row.string("container_image"))
+        .image_digest(row.string("container_image_digest"))
+        .runtime(row.string("container_runtime"))
+        .pod(row.string("pod_name"))
+        .seen_at(timestamp)
+        .build()
+        .unwrap();
+
+    Some(container)
+}
+
+fn add_container(graph: &mut Graph, process: &Process, container: Option<Container>) {
+    if let Some(container) = container {
+        graph.add_edge("runs_in", process.clone_node_key(), container.clone_node_key());
+        graph.add_node(container);
+    }
+}
+
+// The processes table only tells us that a process exists, or that it no longer does
+fn handle_process_row(
+    hostname: &str,
+    timestamp: u64,
+    action: RowAction,
+    row: &Row,
+) -> Result<Option<Graph>, Error> {
+    let pid = required_u64(row, "pid")?;
+
+    let mut graph = Graph::new(timestamp);
+
+    if action == RowAction::Removed {
+        let terminated = ProcessBuilder::default()
+            .hostname(hostname.to_owned())
+            .process_name(row.string("name").unwrap_or_default())
+            .state(ProcessState::Terminated)
+            .process_id(pid)
+            .terminated_timestamp(timestamp)
+            .build()
+            .unwrap();
+
+        graph.add_node(terminated);
+        return Ok(Some(graph));
+    }
+
+    let asset = asset(hostname);
+
+    let process = ProcessBuilder::default()
+        .hostname(hostname.to_owned())
+        .process_name(row.string("name").unwrap_or_default())
+        .process_command_line(row.string("cmdline").unwrap_or_default())
+        .state(ProcessState::Existing)
+        .process_id(pid)
+        .last_seen_timestamp(timestamp)
+        .build()
+        .unwrap();
+
+    if let Some(path) = row.string("path") {
+        let exe = bin_file(hostname, path, timestamp);
+        graph.add_edge("bin_file", process.clone_node_key(), exe.clone_node_key());
+        graph.add_node(exe);
+    }
+
+    if let Some(parent_pid) = row.i64("parent").filter(|ppid| *ppid > 0) {
+        let parent = existing_process(hostname, parent_pid as u64, timestamp);
+
+        graph.add_edge(
+            "asset_processes",
+            asset.clone_node_key(),
+            parent.clone_node_key(),
+        );
+        graph.add_edge("children", parent.clone_node_key(), process.clone_node_key());
+        graph.add_node(parent);
+    }
+
+    graph.add_edge(
+        "asset_processes",
+        asset.clone_node_key(),
+        process.clone_node_key(),
+    );
+
+    add_container(&mut graph, &process, container(hostname, timestamp, row));
+
+    graph.add_node(process);
+    graph.add_node(asset);
+
+    Ok(Some(graph))
+}
+
+fn handle_process_event(hostname: &str, timestamp: u64, row: &Row) -> Result<Option<Graph>, Error> {
+    let pid = required_u64(row, "pid")?;
+
+    let mut graph = Graph::new(timestamp);
+
+    // Only the audit based (Linux) table has a syscall column
+    match row.str("syscall") {
+        Some("exit") | Some("exit_group") => {
+            let terminated = ProcessBuilder::default()
+                .hostname(hostname.to_owned())
+                .state(ProcessState::Terminated)
+                .process_id(pid)
+                .terminated_timestamp(timestamp)
+                .build()
+                .unwrap();
+
+            graph.add_node(terminated);
+            return Ok(Some(graph));
+        }
+        Some("execve") | Some("execveat") | Some("fork") | Some("vfork") | Some("clone") | None => (),
+        Some(_) => return Ok(None),
+    }
+
+    let asset = asset(hostname);
+
+    let process_name = row.str("path")
+        .and_then(|path| path.rsplit('/').next())
+        .unwrap_or_default()
+        .to_owned();
+
+    let child = ProcessBuilder::default()
+        .hostname(hostname.to_owned())
+        .process_name(process_name)
+        .process_command_line(row.string("cmdline").unwrap_or_default())
+        .state(ProcessState::Created)
+        .process_id(pid)
+        .created_timestamp(timestamp)
+        .build()
+        .unwrap();
+
+    if let Some(path) = row.string("path") {
+        let exe = bin_file(hostname, path, timestamp);
+        graph.add_edge("bin_file", child.clone_node_key(), exe.clone_node_key());
+        graph.add_node(exe);
+    }
+
+    if let Some(parent_pid) = row.i64("parent").filter(|ppid| *ppid > 0) {
+        let parent = existing_process(hostname, parent_pid as u64, timestamp);
+
+        graph.add_edge(
+            "asset_processes",
+            asset.clone_node_key(),
+            parent.clone_node_key(),
+        );
+        graph.add_edge("children", parent.clone_node_key(), child.clone_node_key());
+        graph.add_node(parent);
+    }
+
+    graph.add_edge(
+        "asset_processes",
+        asset.clone_node_key(),
+        child.clone_node_key(),
+    );
+
+    add_container(&mut graph, &child, container(hostname, timestamp, row));
+
+    graph.add_node(child);
+    graph.add_node(asset);
+
+    Ok(Some(graph))
+}
+
+fn protocol_name(row: &Row) -> String {
+    match row.u64("protocol") {
+        Some(6) => "tcp".to_owned(),
+        Some(17) => "udp".to_owned(),
+        _ => row.string("protocol").unwrap_or_else(|| "tcp".to_owned()),
+    }
+}
+
+fn handle_socket_event(hostname: &str, timestamp: u64, row: &Row) -> Result<Option<Graph>, Error> {
+    if row.i64("success") == Some(0) {
+        return Ok(None);
+    }
+
+    let pid = required_u64(row, "pid")?;
+    let protocol = protocol_name(row);
+
+    let local_ip = row.string("local_address");
+    let local_port = row.u64("local_port").filter(|port| *port != 0).map(|port| port as u32);
+    let remote_ip = row.string("remote_address");
+    let remote_port = row.u64("remote_port").filter(|port| *port != 0).map(|port| port as u32);
+
+    match (row.str("action"), local_ip, local_port, remote_ip, remote_port) {
+        (Some("connect"), Some(local_ip), Some(local_port), Some(remote_ip), Some(remote_port)) => {
+            let connection = Connection { protocol, local_ip, local_port, remote_ip, remote_port };
+            Ok(Some(handle_connect(hostname, timestamp, pid, connection)))
+        }
+        (Some("bind"), local_ip, Some(local_port), _, _) => {
+            Ok(Some(handle_bind(hostname, timestamp, pid, local_ip, local_port, protocol)))
+        }
+        (Some("accept"), Some(local_ip), Some(local_port), Some(remote_ip), Some(remote_port)) => {
+            let connection = Connection { protocol, local_ip, local_port, remote_ip, remote_port };
+            Ok(Some(handle_accept(hostname, timestamp, pid, connection)))
+        }
+        _ => Ok(None),
+    }
+}
+
+struct Connection {
+    protocol: String,
+    local_ip: String,
+    local_port: u32,
+    remote_ip: String,
+    remote_port: u32,
+}
+
+fn ip_address(ip: &str, timestamp: u64) -> IpAddress {
+    IpAddressBuilder::default()
+        .ip_address(ip.to_owned())
+        .last_seen_timestamp(timestamp)
+        .build()
+        .unwrap()
+}
+
+fn ip_port(ip: &str, port: u32, protocol: &str) -> IpPort {
+    IpPortBuilder::default()
+        .ip_address(ip.to_owned())
+        .port(port)
+        .protocol(protocol.to_owned())
+        .build()
+        .unwrap()
+}
+
+fn handle_connect(hostname: &str, timestamp: u64, pid: u64, conn: Connection) -> Graph {
+    let mut graph = Graph::new(timestamp);
+
+    let asset = asset(hostname);
+    let process = existing_process(hostname, pid, timestamp);
+
+    let outbound = ProcessOutboundConnectionBuilder::default()
+        .hostname(hostname.to_owned())
+        .ip_address(conn.local_ip.clone())
+        .protocol(conn.protocol.clone())
+        .state(ProcessOutboundConnectionState::Connected)
+        .port(conn.local_port)
+        .created_timestamp(timestamp)
+        .build()
+        .unwrap();
+
+    let src_ip = ip_address(&conn.local_ip, timestamp);
+    let dst_ip = ip_address(&conn.remote_ip, timestamp);
+    let src_port = ip_port(&conn.local_ip, conn.local_port, &conn.protocol);
+    let dst_port = ip_port(&conn.remote_ip, conn.remote_port, &conn.protocol);
+
+    let network_connection = NetworkConnectionBuilder::default()
+        .state(NetworkConnectionState::Created)
+        .src_ip_address(conn.local_ip)
+        .src_port(conn.local_port)
+        .dst_ip_address(conn.remote_ip)
+        .dst_port(conn.remote_port)
+        .protocol(conn.protocol)
+        .created_timestamp(timestamp)
+        .build()
+        .unwrap();
+
+    graph.add_edge("asset_ip", asset.clone_node_key(), src_ip.clone_node_key());
+    graph.add_edge("asset_processes", asset.clone_node_key(), process.clone_node_key());
+    graph.add_edge("created_connections", process.clone_node_key(), outbound.clone_node_key());
+    graph.add_edge("connected_over", outbound.clone_node_key(), src_port.clone_node_key());
+    graph.add_edge("connected_to", outbound.clone_node_key(), dst_port.clone_node_key());
+    graph.add_edge("outbound_connection_to", src_port.clone_node_key(), network_connection.clone_node_key());
+    graph.add_edge("inbound_connection_to", network_connection.clone_node_key(), dst_port.clone_node_key());
+
+    graph.add_node(asset);
+    graph.add_node(process);
+    graph.add_node(outbound);
+    graph.add_node(src_ip);
+    graph.add_node(dst_ip);
+    graph.add_node(src_port);
+    graph.add_node(dst_port);
+    graph.add_node(network_connection);
+
+    graph
+}
+
+fn handle_accept(hostname: &str, timestamp: u64, pid: u64, conn: Connection) -> Graph {
+    let mut graph = Graph::new(timestamp);
+
+    let asset = asset(hostname);
+    let process = existing_process(hostname, pid, timestamp);
+
+    let inbound = ProcessInboundConnectionBuilder::default()
+        .hostname(hostname.to_owned())
+        .ip_address(conn.local_ip.clone())
+        .protocol(conn.protocol.clone())
+        .state(ProcessInboundConnectionState::Existing)
+        .port(conn.local_port)
+        .last_seen_timestamp(timestamp)
+        .build()
+        .unwrap();
+
+    let src_ip = ip_address(&conn.remote_ip, timestamp);
+    let dst_ip = ip_address(&conn.local_ip, timestamp);
+    let src_port = ip_port(&conn.remote_ip, conn.remote_port, &conn.protocol);
+    let dst_port = ip_port(&conn.local_ip, conn.local_port, &conn.protocol);
+
+    // The remote side initiated the connection, so it is the src
+    let network_connection = NetworkConnectionBuilder::default()
+        .state(NetworkConnectionState::Created)
+        .src_ip_address(conn.remote_ip)
+        .src_port(conn.remote_port)
+        .dst_ip_address(conn.local_ip)
+        .dst_port(conn.local_port)
+        .protocol(conn.protocol)
+        .created_timestamp(timestamp)
+        .build()
+        .unwrap();
+
+    graph.add_edge("asset_ip", asset.clone_node_key(), dst_ip.clone_node_key());
+    graph.add_edge("asset_processes", asset.clone_node_key(), process.clone_node_key());
+    graph.add_edge("received_connection", process.clone_node_key(), inbound.clone_node_key());
+    graph.add_edge("bound_port", inbound.clone_node_key(), dst_port.clone_node_key());
+    graph.add_edge("outbound_connection_to", src_port.clone_node_key(), network_connection.clone_node_key());
+    graph.add_edge("inbound_connection_to", network_connection.clone_node_key(), dst_port.clone_node_key());
+
+    graph.add_node(asset);
+    graph.add_node(process);
+    graph.add_node(inbound);
+    graph.add_node(src_ip);
+    graph.add_node(dst_ip);
+    graph.add_node(src_port);
+    graph.add_node(dst_port);
+    graph.add_node(network_connection);
+
+    graph
+}
+
+fn handle_bind(
+    hostname: &str,
+    timestamp: u64,
+    pid: u64,
+    local_ip: Option<String>,
+    local_port: u32,
+    protocol: String,
+) -> Graph {
+    let mut graph = Graph::new(timestamp);
+
+    // An unspecified bind address listens on every interface
+    let local_ip = local_ip.unwrap_or_else(|| "0.0.0.0".to_owned());
+
+    let asset = asset(hostname);
+    let process = existing_process(hostname, pid, timestamp);
+
+    let inbound = ProcessInboundConnectionBuilder::default()
+        .hostname(hostname.to_owned())
+        .ip_address(local_ip.clone())
+        .protocol(protocol.clone())
+        .state(ProcessInboundConnectionState::Bound)
+        .port(local_port)
+        .created_timestamp(timestamp)
+        .build()
+        .unwrap();
+
+    let port = ip_port(&local_ip, local_port, &protocol);
+
+    graph.add_edge("asset_processes", asset.clone_node_key(), process.clone_node_key());
+    graph.add_edge("inbound_connections", process.clone_node_key(), inbound.clone_node_key());
+    graph.add_edge("bound_port", inbound.clone_node_key(), port.clone_node_key());
+
+    graph.add_node(asset);
+    graph.add_node(process);
+    graph.add_node(inbound);
+    graph.add_node(port);
+
+    graph
+}
+
+// File integrity monitoring events do not record the process responsible
+fn handle_file_event(hostname: &str, timestamp: u64, row: &Row) -> Result<Option<Graph>, Error> {
+    let path = match row.string("target_path") {
+        Some(path) => path,
+        None => bail!("Row is missing column: target_path"),
+    };
+
+    let mut builder = FileBuilder::default();
+
+    match row.str("action") {
+        Some("CREATED") | Some("MOVED_TO") => {
+            builder.state(FileState::Created).created_timestamp(timestamp);
+        }
+        Some("DELETED") | Some("MOVED_FROM") => {
+            builder.state(FileState::Deleted).deleted_timestamp(timestamp);
+        }
+        _ => {
+            builder.state(FileState::Existing).last_seen_timestamp(timestamp);
+        }
+    };
+
+    let file = builder
+        .hostname(hostname.to_owned())
+        .file_path(path)
+        .file_inode(row.u64("inode").unwrap_or_default())
+        .file_size(row.u64("size").unwrap_or_default())
+        .md5_hash(row.string("md5").unwrap_or_default())
+        .sha1_hash(row.string("sha1").unwrap_or_default())
+        .sha256_hash(row.string("sha256").unwrap_or_default())
+        .build()
+        .unwrap();
+
+    let mut graph = Graph::new(timestamp);
+    graph.add_node(file);
+
+    Ok(Some(graph))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(result: &str) -> QueryResult {
+        serde_json::from_str(result).expect("QueryResult")
+    }
+
+    #[test]
+    fn snapshot_processes_are_existing() {
+        let result = parse(r#"{
+            "name": "pack_grapl_processes",
+            "hostIdentifier": "web-1",
+            "unixTime": 1565555555,
+            "action": "snapshot",
+            "snapshot": [
+                {"pid": "100", "parent": "1", "name": "nginx", "path": "/usr/sbin/nginx", "cmdline": "nginx -g daemon off;"}
+            ]
+        }"#);
+
+        let graph = generate_subgraph(result)
+            .expect("generate_subgraph")
+            .expect("processes produce a graph");
+
+        // asset, parent, process and bin_file
+        assert_eq!(graph.nodes.len(), 4);
+        assert!(
+            graph.nodes.values()
+                .filter_map(|node| node.as_process())
+                .all(|process| process.state == u32::from(ProcessState::Existing))
+        );
+    }
+
+    #[test]
+    fn process_events_are_created_or_terminated() {
+        let result = parse(r#"{
+            "name": "pack_grapl_process_events",
+            "hostIdentifier": "web-1",
+            "unixTime": 1565555555,
+            "diffResults": {
+                "added": [
+                    {"pid": "200", "parent": "100", "path": "/bin/sh", "cmdline": "sh -c id", "syscall": "execve", "time": "1565555550"},
+                    {"pid": "300", "parent": "100", "syscall": "exit", "time": "1565555551"}
+                ]
+            }
+        }"#);
+
+        let graph = generate_subgraph(result)
+            .expect("generate_subgraph")
+            .expect("process_events produce a graph");
+
+        let processes: Vec<_> = graph.nodes.values()
+            .filter_map(|node| node.as_process())
+            .collect();
+
+        let child = processes.iter().find(|p| p.process_id == 200).expect("child");
+        assert_eq!(child.state, u32::from(ProcessState::Created));
+        assert_eq!(child.created_timestamp, 1565555550000);
+
+        let exited = processes.iter().find(|p| p.process_id == 300).expect("exited");
+        assert_eq!(exited.state, u32::from(ProcessState::Terminated));
+    }
+
+    #[test]
+    fn processes_run_in_their_container() {
+        let result = parse(r#"{
+            "name": "pack_grapl_processes",
+            "hostIdentifier": "web-1",
+            "unixTime": 1565555555,
+            "columns": {
+                "pid": "100", "name": "nginx", "path": "/usr/sbin/nginx",
+                "container_id": "containerd://8d1f9b1c4a7e", "container_image": "nginx:1.17"
+            },
+            "action": "added"
+        }"#);
+
+        let graph = generate_subgraph(result)
+            .expect("generate_subgraph")
+            .expect("processes produce a graph");
+
+        let container = graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .find(|node| node.node_type == "Container")
+            .expect("container");
+
+        assert_eq!(
+            container.properties.get("container_id").map(|id| id.to_string()),
+            Some("8d1f9b1c4a7e".to_owned())
+        );
+        assert_eq!(
+            container.properties.get("runtime").map(|runtime| runtime.to_string()),
+            Some("containerd".to_owned())
+        );
+        assert!(
+            graph.edges.values()
+                .flat_map(|edges| edges.edges.iter())
+                .any(|edge| edge.edge_name == "runs_in")
+        );
+    }
+
+    #[test]
+    fn unknown_queries_are_skipped() {
+        let result = parse(r#"{
+            "name": "pack_grapl_users",
+            "hostIdentifier": "web-1",
+            "unixTime": 1565555555,
+            "columns": {"uid": "0"},
+            "action": "added"
+        }"#);
+
+        assert!(generate_subgraph(result).expect("generate_subgraph").is_none());
+    }
+}
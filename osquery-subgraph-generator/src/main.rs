@@ -0,0 +1,69 @@
+extern crate aws_lambda_events;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::generator::generate_subgraph;
+use crate::results::QueryResult;
+
+mod generator;
+mod results;
+
+#[derive(Clone)]
+struct OsquerySubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for OsquerySubgraphGenerator {
+    type Event = String;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<String>, failure::Error> {
+        // Each line of the payload is one line of the osquery results log
+        let events = payload.split(|i| *i == b'\n')
+            .map(String::from_utf8_lossy)
+            .filter(|event| !event.trim().is_empty())
+            .map(|event| event.into_owned())
+            .collect();
+
+        Ok(events)
+    }
+
+    fn identity(&self, event: &String) -> String {
+        event.clone()
+    }
+
+    async fn generate_subgraph(&mut self, event: String) -> Result<Option<Graph>, failure::Error> {
+        let result: QueryResult = serde_json::from_str(&event)
+            .map_err(|e| format_err!("Failed to deserialize event: {}, {}", e, event))?;
+
+        generate_subgraph(result)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "OSQUERY", OsquerySubgraphGenerator, ZstdDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
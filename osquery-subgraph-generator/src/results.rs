@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// A single row of query results. osquery logs columns as strings unless
+/// `--logger_numerics` is set, so values are coerced when they are read.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Row {
+    columns: HashMap<String, Value>,
+}
+
+impl Row {
+    pub fn str(&self, name: &str) -> Option<&str> {
+        match self.columns.get(name) {
+            Some(Value::String(value)) if !value.is_empty() => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<String> {
+        match self.columns.get(name) {
+            Some(Value::Number(value)) => Some(value.to_string()),
+            _ => self.str(name).map(String::from),
+        }
+    }
+
+    pub fn u64(&self, name: &str) -> Option<u64> {
+        match self.columns.get(name) {
+            Some(Value::Number(value)) => value.as_u64(),
+            Some(Value::String(value)) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    // Columns such as parent are -1 when unknown
+    pub fn i64(&self, name: &str) -> Option<i64> {
+        match self.columns.get(name) {
+            Some(Value::Number(value)) => value.as_i64(),
+            Some(Value::String(value)) => value.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RowAction {
+    /// The row is part of a point in time snapshot of the table
+    Snapshot,
+    /// The row was added since the last time the query ran
+    Added,
+    /// The row was removed since the last time the query ran
+    Removed,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct DiffResults {
+    #[serde(default)]
+    pub added: Vec<Row>,
+    #[serde(default)]
+    pub removed: Vec<Row>,
+}
+
+/// One line of the osquery results log, in either the event, batch or snapshot format
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryResult {
+    pub name: String,
+    pub host_identifier: String,
+    #[serde(deserialize_with = "u64_or_string")]
+    pub unix_time: u64,
+    #[serde(default)]
+    pub decorations: HashMap<String, String>,
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub columns: Option<Row>,
+    #[serde(default)]
+    pub snapshot: Option<Vec<Row>>,
+    #[serde(default)]
+    pub diff_results: Option<DiffResults>,
+}
+
+impl QueryResult {
+    /// The hostname decoration is preferred, as the host identifier may be a uuid
+    pub fn hostname(&self) -> &str {
+        self.decorations.get("hostname")
+            .map(String::as_str)
+            .unwrap_or(&self.host_identifier)
+    }
+
+    pub fn into_rows(self) -> Vec<(RowAction, Row)> {
+        let mut rows = Vec::new();
+
+        if let Some(columns) = self.columns {
+            let action = match self.action.as_deref() {
+                Some("removed") => RowAction::Removed,
+                _ => RowAction::Added,
+            };
+            rows.push((action, columns));
+        }
+
+        if let Some(snapshot) = self.snapshot {
+            rows.extend(snapshot.into_iter().map(|row| (RowAction::Snapshot, row)));
+        }
+
+        if let Some(diff_results) = self.diff_results {
+            rows.extend(diff_results.added.into_iter().map(|row| (RowAction::Added, row)));
+            rows.extend(diff_results.removed.into_iter().map(|row| (RowAction::Removed, row)));
+        }
+
+        rows
+    }
+}
+
+// Older versions of osquery log unixTime as a string
+fn u64_or_string<'de, D>(deserializer: D) -> Result<u64, D::Error>
+    where D: Deserializer<'de>
+{
+    match Value::deserialize(deserializer)? {
+        Value::Number(n) => n.as_u64()
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid unixTime: {}", n))),
+        Value::String(s) => s.parse()
+            .map_err(|e| serde::de::Error::custom(format!("invalid unixTime {}: {}", s, e))),
+        other => Err(serde::de::Error::custom(format!("invalid unixTime: {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_and_batch_rows_are_expanded() {
+        let snapshot: QueryResult = serde_json::from_str(r#"{
+            "name": "pack_grapl_processes",
+            "hostIdentifier": "web-1",
+            "unixTime": "1565555555",
+            "action": "snapshot",
+            "snapshot": [{"pid": "1"}, {"pid": "2"}]
+        }"#).expect("snapshot");
+
+        assert_eq!(snapshot.unix_time, 1565555555);
+        let rows = snapshot.into_rows();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|(action, _)| *action == RowAction::Snapshot));
+
+        let batch: QueryResult = serde_json::from_str(r#"{
+            "name": "pack_grapl_processes",
+            "hostIdentifier": "web-1",
+            "unixTime": 1565555555,
+            "diffResults": {"added": [{"pid": 3}], "removed": [{"pid": "4"}]}
+        }"#).expect("batch");
+
+        let rows = batch.into_rows();
+        assert_eq!(rows[0].0, RowAction::Added);
+        assert_eq!(rows[0].1.u64("pid"), Some(3));
+        assert_eq!(rows[1].0, RowAction::Removed);
+        assert_eq!(rows[1].1.u64("pid"), Some(4));
+    }
+}
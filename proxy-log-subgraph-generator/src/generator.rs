@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::node::NodeT;
+use graph_descriptions::process::ProcessState;
+use graph_descriptions::url::{domain_name, host_domain, url};
+
+use crate::proxy_log::ProxyRequest;
+
+// What the proxy saw of the request is stored on the edge, as a url is requested many times
+fn request_properties(request: &ProxyRequest) -> HashMap<String, NodeProperty> {
+    let mut properties = HashMap::with_capacity(4);
+
+    properties.insert("method".to_owned(), request.method.to_uppercase().into());
+
+    if let Some(status) = request.status {
+        properties.insert("status".to_owned(), status.into());
+    }
+    if let Some(bytes) = request.bytes {
+        properties.insert("bytes".to_owned(), bytes.into());
+    }
+    if let Some(ref user_agent) = request.user_agent {
+        properties.insert("user_agent".to_owned(), user_agent.clone().into());
+    }
+
+    properties
+}
+
+// Requests are attributed to the process that made them when the proxy knows it,
+// otherwise to the client address
+fn add_requester(graph: &mut Graph, request: &ProxyRequest) -> Result<String, Error> {
+    if let (Some(hostname), Some(pid)) = (request.hostname.as_ref(), request.pid) {
+        let asset = AssetBuilder::default()
+            .asset_id(hostname.clone())
+            .hostname(hostname.clone())
+            .build()
+            .unwrap();
+
+        let process = ProcessBuilder::default()
+            .hostname(hostname.clone())
+            .state(ProcessState::Existing)
+            .process_id(pid)
+            .last_seen_timestamp(request.timestamp)
+            .build()
+            .unwrap();
+
+        let process_key = process.clone_node_key();
+
+        graph.add_edge("asset_processes", asset.clone_node_key(), process_key.clone());
+        graph.add_node(process);
+        graph.add_node(asset);
+
+        return Ok(process_key);
+    }
+
+    let client_ip = IpAddressBuilder::default()
+        .ip_address(request.client_ip.clone())
+        .last_seen_timestamp(request.timestamp)
+        .build()
+        .map_err(|e| format_err!("client_ip: {}", e))?;
+
+    let client_key = client_ip.clone_node_key();
+    graph.add_node(client_ip);
+
+    Ok(client_key)
+}
+
+pub fn generate_subgraph(request: &ProxyRequest) -> Result<Graph, Error> {
+    let mut graph = Graph::new(request.timestamp);
+
+    let requester_key = add_requester(&mut graph, request)?;
+    let properties = request_properties(request);
+    let domain = host_domain(request.authority())
+        .map(|domain| domain_name(domain, request.timestamp));
+
+    // Tunnels only tell us the host they were made to
+    if request.is_tunnel() {
+        if let Some(domain) = domain {
+            graph.add_edge_with_properties(
+                "requested_domain",
+                requester_key,
+                domain.clone_node_key(),
+                properties,
+            );
+            graph.add_node(domain);
+        }
+
+        return Ok(graph);
+    }
+
+    let mut requested_url = url(request.url.clone(), request.timestamp);
+    requested_url.set_property("host", request.authority().to_owned());
+
+    graph.add_edge_with_properties(
+        "requested_url",
+        requester_key,
+        requested_url.clone_node_key(),
+        properties,
+    );
+
+    if let Some(domain) = domain {
+        graph.add_edge("url_domain", requested_url.clone_node_key(), domain.clone_node_key());
+        graph.add_node(domain);
+    }
+
+    graph.add_node(requested_url);
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy_log::parse_proxy_log;
+
+    fn generate(line: &str) -> Graph {
+        let requests = parse_proxy_log(line).expect("parse_proxy_log");
+        generate_subgraph(&requests[0]).expect("generate_subgraph")
+    }
+
+    fn edge<'a>(graph: &'a Graph, edge_name: &str) -> &'a Edge {
+        graph.edges.values()
+            .flat_map(|edges| edges.edges.iter())
+            .find(|edge| edge.edge_name == edge_name)
+            .expect(edge_name)
+    }
+
+    #[test]
+    fn squid_request_is_made_by_client_ip() {
+        let graph = generate(
+            "1286536309.450 99 192.168.0.68 TCP_MISS/200 507 GET http://www.example.com/index.html - HIER_DIRECT/93.184.216.34 text/html"
+        );
+
+        // client ip, url and domain
+        assert_eq!(graph.nodes.len(), 3);
+
+        let requested_url = edge(&graph, "requested_url");
+        assert!(graph.nodes[&requested_url.from].as_ip_address().is_some());
+        assert_eq!(
+            requested_url.properties.get("status").map(|status| status.to_string()),
+            Some("200".to_owned())
+        );
+        assert_eq!(
+            requested_url.properties.get("method").map(|method| method.to_string()),
+            Some("GET".to_owned())
+        );
+    }
+
+    #[test]
+    fn request_with_pid_is_made_by_process() {
+        let graph = generate(
+            r#"{"timestamp": 1286536309450, "client_ip": "10.0.0.5", "hostname": "web-1", "pid": 4242, "method": "GET", "url": "http://10.0.0.9/health", "user_agent": "curl/7.58.0"}"#
+        );
+
+        // asset, process and url, but no domain for an address
+        assert_eq!(graph.nodes.len(), 3);
+
+        let requested_url = edge(&graph, "requested_url");
+        assert!(graph.nodes[&requested_url.from].as_process().is_some());
+        assert_eq!(
+            requested_url.properties.get("user_agent").map(|user_agent| user_agent.to_string()),
+            Some("curl/7.58.0".to_owned())
+        );
+    }
+
+    #[test]
+    fn tunnel_requests_domain() {
+        let graph = generate(
+            "192.168.0.68 - - [08/Oct/2010:10:11:49 +0000] \"CONNECT mail.example.com:443 HTTP/1.1\" 200 3528"
+        );
+
+        assert_eq!(graph.nodes.len(), 2);
+        edge(&graph, "requested_domain");
+    }
+}
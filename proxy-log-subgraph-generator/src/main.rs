@@ -0,0 +1,64 @@
+extern crate aws_lambda_events;
+extern crate chrono;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+#[macro_use]
+extern crate lazy_static;
+extern crate log;
+extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::generator::generate_subgraph;
+use crate::proxy_log::{parse_proxy_log, ProxyRequest};
+
+mod generator;
+mod proxy_log;
+
+#[derive(Clone)]
+struct ProxyLogSubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for ProxyLogSubgraphGenerator {
+    type Event = ProxyRequest;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<ProxyRequest>, failure::Error> {
+        // The payload is a Squid access.log, or json proxy log lines
+        parse_proxy_log(&String::from_utf8_lossy(payload))
+    }
+
+    fn identity(&self, event: &ProxyRequest) -> String {
+        event.line.clone()
+    }
+
+    async fn generate_subgraph(&mut self, event: ProxyRequest) -> Result<Option<Graph>, failure::Error> {
+        generate_subgraph(&event).map(Some)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "PROXY_LOG", ProxyLogSubgraphGenerator, ZstdDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
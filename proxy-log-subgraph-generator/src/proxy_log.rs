@@ -0,0 +1,201 @@
+use chrono::prelude::*;
+use failure::Error;
+use regex::{Captures, Regex};
+
+lazy_static! {
+    // Squid's native format, which may be extended with a quoted user agent:
+    // <time> <elapsed> <client> <result>/<status> <bytes> <method> <url> <user> <hierarchy>/<peer> <type>
+    static ref SQUID_NATIVE: Regex = Regex::new(
+        r#"^(?P<time>\d+(?:\.\d+)?)\s+-?\d+\s+(?P<client>\S+)\s+\S+/(?P<status>\d{3})\s+(?P<bytes>\d+)\s+(?P<method>\S+)\s+(?P<url>\S+)\s+\S+\s+\S+\s+\S+(?:\s+"(?P<user_agent>[^"]*)")?"#
+    ).expect("SQUID_NATIVE");
+
+    // Squid's combined format, as shared with web servers
+    static ref SQUID_COMBINED: Regex = Regex::new(
+        r#"^(?P<client>\S+) \S+ \S+ \[(?P<time>[^\]]+)\] "(?P<method>\S+) (?P<url>\S+)(?: [^"]*)?" (?P<status>\d{3}) (?P<bytes>\d+|-)(?: "[^"]*" "(?P<user_agent>[^"]*)")?"#
+    ).expect("SQUID_COMBINED");
+}
+
+/// A request made through a web proxy
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProxyRequest {
+    pub timestamp: u64,
+    pub client_ip: String,
+    /// The host, and pid, of the process that made the request, when the proxy knows them
+    pub hostname: Option<String>,
+    pub pid: Option<u64>,
+    pub method: String,
+    pub url: String,
+    pub status: Option<u64>,
+    pub bytes: Option<u64>,
+    pub user_agent: Option<String>,
+    /// The raw line, used to identify it
+    pub line: String,
+}
+
+impl ProxyRequest {
+    /// CONNECT requests are for a tunnel to the host:port in place of the url
+    pub fn is_tunnel(&self) -> bool {
+        self.method.eq_ignore_ascii_case("CONNECT")
+    }
+
+    /// The host:port, or host, the request was sent to
+    pub fn authority(&self) -> &str {
+        if self.is_tunnel() {
+            return &self.url;
+        }
+
+        let url = match self.url.find("://") {
+            Some(index) => &self.url[index + 3..],
+            None => &self.url,
+        };
+
+        let authority = url.split(['/', '?', '#'])
+            .next()
+            .unwrap_or_default();
+
+        authority.rsplit('@').next().unwrap_or_default()
+    }
+}
+
+/// The generic proxy log format, one json object per line
+#[derive(Clone, Debug, Deserialize)]
+struct JsonProxyRequest {
+    /// Milliseconds since the epoch
+    timestamp: u64,
+    client_ip: String,
+    #[serde(default)]
+    hostname: Option<String>,
+    #[serde(default)]
+    pid: Option<u64>,
+    method: String,
+    url: String,
+    #[serde(default)]
+    status: Option<u64>,
+    #[serde(default)]
+    bytes: Option<u64>,
+    #[serde(default)]
+    user_agent: Option<String>,
+}
+
+/// Parses Squid access logs, in its native or combined format, and json proxy logs.
+/// Lines in any other format are skipped.
+pub fn parse_proxy_log(payload: &str) -> Result<Vec<ProxyRequest>, Error> {
+    let mut requests = Vec::new();
+
+    for line in payload.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let request = if line.starts_with('{') {
+            Some(from_json(line)?)
+        } else if let Some(captures) = SQUID_NATIVE.captures(line) {
+            Some(from_squid(line, &captures, native_timestamp(&captures["time"])?)?)
+        } else if let Some(captures) = SQUID_COMBINED.captures(line) {
+            Some(from_squid(line, &captures, combined_timestamp(&captures["time"])?)?)
+        } else {
+            None
+        };
+
+        if let Some(request) = request {
+            requests.push(request);
+        }
+    }
+
+    Ok(requests)
+}
+
+fn from_json(line: &str) -> Result<ProxyRequest, Error> {
+    let request: JsonProxyRequest = serde_json::from_str(line)
+        .map_err(|e| format_err!("Invalid proxy log {}: {}", line, e))?;
+
+    Ok(ProxyRequest {
+        timestamp: request.timestamp,
+        client_ip: request.client_ip,
+        hostname: request.hostname.filter(|hostname| !hostname.is_empty()),
+        pid: request.pid,
+        method: request.method,
+        url: request.url,
+        status: request.status,
+        bytes: request.bytes,
+        user_agent: request.user_agent.filter(|user_agent| !user_agent.is_empty()),
+        line: line.to_owned(),
+    })
+}
+
+fn from_squid(line: &str, captures: &Captures, timestamp: u64) -> Result<ProxyRequest, Error> {
+    // Squid logs a status of 000 for requests that were never answered
+    let status = captures["status"].parse::<u64>()?;
+
+    Ok(ProxyRequest {
+        timestamp,
+        client_ip: captures["client"].to_owned(),
+        hostname: None,
+        pid: None,
+        method: captures["method"].to_owned(),
+        url: captures["url"].to_owned(),
+        status: Some(status).filter(|status| *status != 0),
+        bytes: captures["bytes"].parse().ok(),
+        user_agent: captures.name("user_agent")
+            .map(|user_agent| user_agent.as_str())
+            .filter(|user_agent| !user_agent.is_empty() && *user_agent != "-")
+            .map(String::from),
+        line: line.to_owned(),
+    })
+}
+
+// Seconds since the epoch, with milliseconds, ie: 1286536309.450
+fn native_timestamp(time: &str) -> Result<u64, Error> {
+    let seconds: f64 = time.parse()?;
+    if seconds < 0.0 {
+        bail!("Timestamp is negative")
+    }
+
+    Ok((seconds * 1000.0).round() as u64)
+}
+
+// ie: 08/Oct/2010:10:11:49 +0000
+fn combined_timestamp(time: &str) -> Result<u64, Error> {
+    let ts = DateTime::parse_from_str(time, "%d/%b/%Y:%H:%M:%S %z")
+        .map_err(|e| format_err!("Invalid timestamp {}: {}", time, e))?
+        .timestamp_millis();
+
+    if ts < 0 {
+        bail!("Timestamp is negative")
+    }
+
+    Ok(ts as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn squid_and_json_lines_are_parsed() {
+        let payload = concat!(
+            "1286536309.450 99 192.168.0.68 TCP_MISS/200 507 GET http://www.example.com/index.html - HIER_DIRECT/93.184.216.34 text/html\n",
+            "192.168.0.68 - alice [08/Oct/2010:10:11:49 +0000] \"CONNECT mail.example.com:443 HTTP/1.1\" 200 3528 \"-\" \"Mozilla/5.0\" TCP_TUNNEL:HIER_DIRECT\n",
+            "2010/10/08 10:11:49| Starting Squid Cache\n",
+            r#"{"timestamp": 1286536309450, "client_ip": "10.0.0.5", "hostname": "web-1", "pid": 4242, "method": "POST", "url": "https://user@api.example.com:8443/v1?q=1", "status": 201}"#,
+        );
+
+        let requests = parse_proxy_log(payload).expect("parse_proxy_log");
+
+        assert_eq!(requests.len(), 3);
+
+        assert_eq!(requests[0].timestamp, 1286536309450);
+        assert_eq!(requests[0].status, Some(200));
+        assert_eq!(requests[0].bytes, Some(507));
+        assert_eq!(requests[0].authority(), "www.example.com");
+
+        assert_eq!(requests[1].timestamp, 1286532709000);
+        assert!(requests[1].is_tunnel());
+        assert_eq!(requests[1].authority(), "mail.example.com:443");
+        assert_eq!(requests[1].user_agent, Some("Mozilla/5.0".to_owned()));
+
+        assert_eq!(requests[2].pid, Some(4242));
+        assert_eq!(requests[2].authority(), "api.example.com:8443");
+    }
+}
@@ -0,0 +1,485 @@
+//! The SQS and S3 plumbing shared by the subgraph generators.
+//!
+//! A generator only describes how to split a payload into events and how to turn
+//! each event into a subgraph, by implementing `SubgraphGenerator`. `run` drives it
+//! from the lambda's SQS event: payloads are retrieved from S3 and decoded, events
+//! are deduplicated against the redis cache, and the merged subgraphs are
+//! serialized to the unid-subgraphs-generated bucket.
+
+extern crate aws_lambda_events;
+extern crate failure;
+extern crate flate2;
+extern crate futures;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate rusoto_core;
+extern crate rusoto_s3;
+extern crate rusoto_sqs;
+extern crate sqs_lambda;
+extern crate uuid;
+
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use flate2::read::GzDecoder;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use log::*;
+use rusoto_core::Region;
+use rusoto_s3::S3Client;
+use rusoto_sqs::SqsClient;
+
+use sqs_lambda::cache::{Cache, CacheResponse};
+use sqs_lambda::completion_event_serializer::CompletionEventSerializer;
+use sqs_lambda::event_decoder::PayloadDecoder;
+use sqs_lambda::event_emitter::S3EventEmitter;
+use sqs_lambda::event_handler::{Completion, EventHandler, OutputEvent};
+use sqs_lambda::event_processor::{EventProcessor, EventProcessorActor};
+use sqs_lambda::event_retriever::S3PayloadRetriever;
+use sqs_lambda::redis_cache::RedisCache;
+use sqs_lambda::sqs_completion_handler::{CompletionPolicy, SqsCompletionHandler, SqsCompletionHandlerActor};
+use sqs_lambda::sqs_consumer::{ConsumePolicy, SqsConsumer, SqsConsumerActor};
+
+/// The log source specific half of a subgraph generator
+#[async_trait]
+pub trait SubgraphGenerator: Clone + Send + Sync + 'static {
+    type Event: Send;
+
+    /// Splits a decoded payload into its events. An error fails the whole payload.
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<Self::Event>, failure::Error>;
+
+    /// The key an event is deduplicated on in the cache
+    fn identity(&self, event: &Self::Event) -> String;
+
+    /// Generates the subgraph for a single event. Events that describe nothing worth
+    /// adding to the graph produce `None`, and are not retried.
+    async fn generate_subgraph(&mut self, event: Self::Event) -> Result<Option<Graph>, failure::Error>;
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SubgraphSerializer {
+    proto: Vec<u8>,
+}
+
+impl CompletionEventSerializer for SubgraphSerializer {
+    type CompletedEvent = Graph;
+    type Output = Vec<u8>;
+    type Error = failure::Error;
+
+    fn serialize_completed_events(
+        &mut self,
+        completed_events: &[Self::CompletedEvent],
+    ) -> Result<Vec<Self::Output>, Self::Error> {
+        let mut subgraph = Graph::new(
+            0
+        );
+
+        let mut pre_nodes = 0;
+        let mut pre_edges = 0;
+
+        for sg in completed_events.iter() {
+            pre_nodes += sg.nodes.len();
+            pre_edges += sg.edges.len();
+            subgraph.merge(sg);
+        }
+
+        if subgraph.is_empty() {
+            warn!(
+                concat!(
+                    "Output subgraph is empty. Serializing to empty vector.",
+                    "pre_nodes: {} pre_edges: {}"
+                ),
+                pre_nodes,
+                pre_edges,
+            );
+            return Ok(vec![]);
+        }
+
+        info!(
+            "Serializing {} nodes {} edges. Down from {} nodes {} edges.",
+            subgraph.nodes.len(),
+            subgraph.edges.len(),
+            pre_nodes,
+            pre_edges,
+        );
+
+        let subgraphs = GeneratedSubgraphs { subgraphs: vec![subgraph] };
+
+        self.proto.clear();
+
+        prost::Message::encode(&subgraphs, &mut self.proto)?;
+
+
+        let mut compressed = Vec::with_capacity(self.proto.len());
+        let mut proto = Cursor::new(&self.proto);
+        zstd::stream::copy_encode(&mut proto, &mut compressed, 4)?;
+
+        Ok(vec![compressed])
+    }
+}
+
+/// Decodes payloads forwarded through our own pipeline, which are zstd compressed
+#[derive(Debug, Clone, Default)]
+pub struct ZstdDecoder;
+
+impl PayloadDecoder<Vec<u8>> for ZstdDecoder
+{
+    fn decode(&mut self, body: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>>
+    {
+        let mut decompressed = Vec::new();
+
+        let mut body = Cursor::new(&body);
+
+        zstd::stream::copy_decode(&mut body, &mut decompressed)?;
+
+        Ok(decompressed)
+    }
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decodes payloads that are uploaded as the log source writes them, which may be
+/// gzipped or not compressed at all, as well as anything forwarded through our own
+/// pipeline, which is zstd compressed
+#[derive(Debug, Clone, Default)]
+pub struct SniffingDecoder;
+
+impl PayloadDecoder<Vec<u8>> for SniffingDecoder
+{
+    fn decode(&mut self, body: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>>
+    {
+        let mut decompressed = Vec::new();
+
+        if body.starts_with(GZIP_MAGIC) {
+            GzDecoder::new(&body[..]).read_to_end(&mut decompressed)?;
+        } else if body.starts_with(ZSTD_MAGIC) {
+            let mut body = Cursor::new(&body);
+            zstd::stream::copy_decode(&mut body, &mut decompressed)?;
+        } else {
+            decompressed = body;
+        }
+
+        Ok(decompressed)
+    }
+}
+
+#[derive(Clone)]
+struct GeneratorEventHandler<G>
+    where G: SubgraphGenerator
+{
+    cache: RedisCache,
+    generator: G,
+}
+
+#[async_trait]
+impl<G> EventHandler for GeneratorEventHandler<G>
+    where G: SubgraphGenerator
+{
+    type InputEvent = Vec<u8>;
+    type OutputEvent = Graph;
+    type Error = Arc<failure::Error>;
+
+    async fn handle_event(&mut self, events: Vec<u8>) -> OutputEvent<Self::OutputEvent, Self::Error> {
+        info!("Handling raw event");
+
+        let mut failed: Option<failure::Error> = None;
+
+        let events = match self.generator.parse_events(&events) {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Failed to parse events: {}", e);
+                failed = Some(e);
+                vec![]
+            }
+        };
+
+        info!("Handling {} events", events.len());
+
+        let mut identities = Vec::with_capacity(events.len());
+
+        let mut final_subgraph = Graph::new(0);
+
+        for event in events {
+            let identity = self.generator.identity(&event);
+
+            match self.cache.get(identity.clone()).await {
+                Ok(CacheResponse::Hit) =>  {
+                    info!("Got cached response");
+                    continue
+                },
+                Err(e) => warn!("Cache failed with: {:?}", e),
+                _ => ()
+            };
+
+            match self.generator.generate_subgraph(event).await {
+                Ok(Some(graph)) => final_subgraph.merge(&graph),
+                Ok(None) => (),
+                Err(e) => {
+                    warn!("Failed to generate subgraph: {}", e);
+                    failed = Some(e);
+                    continue;
+                }
+            };
+
+            identities.push(identity);
+        }
+
+        info!("Completed generating {} subgraphs", identities.len());
+
+        let mut completed = if let Some(e) = failed {
+            OutputEvent::new(
+                Completion::Partial(
+                    (
+                        final_subgraph,
+                        Arc::new(e)
+                    )
+                )
+            )
+        } else {
+            OutputEvent::new(Completion::Total(final_subgraph))
+        };
+
+        identities.into_iter().for_each(|identity| completed.add_identity(identity));
+
+        completed
+    }
+}
+
+fn time_based_key_fn(_event: &[u8]) -> String {
+    info!("event length {}", _event.len());
+    let cur_ms = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(n) => n.as_millis(),
+        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
+    };
+
+    let cur_day = cur_ms - (cur_ms % 86400);
+
+    format!(
+        "{}/{}-{}",
+        cur_day, cur_ms, uuid::Uuid::new_v4()
+    )
+}
+
+fn map_sqs_message(event: aws_lambda_events::event::sqs::SqsMessage) -> rusoto_sqs::Message {
+    rusoto_sqs::Message {
+        attributes: Some(event.attributes),
+        body: event.body,
+        md5_of_body: event.md5_of_body,
+        md5_of_message_attributes: event.md5_of_message_attributes,
+        message_attributes: None,
+        message_id: event.message_id,
+        receipt_handle: event.receipt_handle,
+    }
+}
+
+/// Handles the lambda's SQS event with `generator`, then keeps consuming the queue
+/// until the lambda is close to its deadline.
+///
+/// The generator's redis cache is read from `{cache_name}_EVENT_CACHE_ADDR` and
+/// `{cache_name}_EVENT_CACHE_PORT`.
+pub fn run<G, D>(
+    event: SqsEvent,
+    ctx: Context,
+    cache_name: &str,
+    generator: G,
+    decoder: D,
+) -> Result<(), HandlerError>
+    where
+        G: SubgraphGenerator,
+        D: PayloadDecoder<Vec<u8>> + Clone + Send + Sync + 'static,
+{
+    info!("Handling event");
+
+    let mut initial_events: HashSet<String> = event.records
+        .iter()
+        .map(|event| event.message_id.clone().unwrap())
+        .collect();
+
+    info!("Initial Events {:?}", initial_events);
+
+    let (tx, rx) = std::sync::mpsc::sync_channel(10);
+
+    let cache_addr_var = format!("{}_EVENT_CACHE_ADDR", cache_name);
+    let cache_port_var = format!("{}_EVENT_CACHE_PORT", cache_name);
+
+    std::thread::spawn(move || {
+        tokio_compat::run_std(
+            async move {
+                let queue_url = std::env::var("QUEUE_URL").expect("QUEUE_URL");
+                info!("Queue Url: {}", queue_url);
+                let bucket_prefix = std::env::var("BUCKET_PREFIX").expect("BUCKET_PREFIX");
+                let cache_address = {
+                    let event_cache_addr = std::env::var(&cache_addr_var).expect(&cache_addr_var);
+                    let event_cache_port = std::env::var(&cache_port_var).expect(&cache_port_var);
+
+                    format!(
+                        "{}:{}",
+                        event_cache_addr,
+                        event_cache_port,
+                    )
+                };
+
+                info!("Redis cache: {}", &cache_address);
+
+                let bucket = bucket_prefix + "-unid-subgraphs-generated-bucket";
+                info!("Output events to: {}", bucket);
+                let region = {
+                    let region_str = std::env::var("AWS_REGION").expect("AWS_REGION");
+                    Region::from_str(&region_str).expect("Region error")
+                };
+
+                let cache = RedisCache::new(cache_address.to_owned()).await.expect("Could not create redis client");
+
+                let event_handler = GeneratorEventHandler {
+                    cache: cache.clone(),
+                    generator,
+                };
+
+                info!("SqsCompletionHandler");
+
+                let finished_tx = tx.clone();
+                let sqs_completion_handler = SqsCompletionHandlerActor::new(
+                    SqsCompletionHandler::new(
+                        SqsClient::new(region.clone()),
+                        queue_url.to_string(),
+                        SubgraphSerializer { proto: Vec::with_capacity(1024) },
+                        S3EventEmitter::new(
+                            S3Client::new(region.clone()),
+                            bucket.to_owned(),
+                            time_based_key_fn,
+                        ),
+                        CompletionPolicy::new(
+                            1000, // Buffer up to 1000 messages
+                            Duration::from_secs(30), // Buffer for up to 30 seconds
+                        ),
+                        move |_self_actor, result: Result<String, String>| {
+                            match result {
+                                Ok(worked) => {
+                                    info!("Handled an event, which was successfully deleted: {}", &worked);
+                                    tx.send(worked).unwrap();
+                                }
+                                Err(worked) => {
+                                    info!("Handled an initial_event, though we failed to delete it: {}", &worked);
+                                    tx.send(worked).unwrap();
+                                }
+                            }
+                        },
+                        cache.clone()
+                    )
+                );
+
+
+                info!("Defining consume policy");
+                let consume_policy = ConsumePolicy::new(
+                    ctx, // Use the Context.deadline from the lambda_runtime
+                    Duration::from_secs(10), // Stop consuming when there's 2 seconds left in the runtime
+                    3, // If we get 3 empty receives in a row, stop consuming
+                );
+
+                info!("Defining consume policy");
+                let (shutdown_tx, shutdown_notify) = tokio::sync::oneshot::channel();
+
+                info!("SqsConsumer");
+                let sqs_consumer = SqsConsumerActor::new(
+                    SqsConsumer::new(
+                        SqsClient::new(region.clone()),
+                        queue_url.clone(),
+                        consume_policy,
+                        sqs_completion_handler.clone(),
+                        shutdown_tx,
+                    )
+                );
+
+                info!("EventProcessors");
+                let event_processors: Vec<_> = (0..10)
+                    .map(|_| {
+                        EventProcessorActor::new(EventProcessor::new(
+                            sqs_consumer.clone(),
+                            sqs_completion_handler.clone(),
+                            event_handler.clone(),
+                            S3PayloadRetriever::new(S3Client::new(region.clone()), decoder.clone()),
+                        ))
+                    })
+                    .collect();
+
+                info!("Start Processing");
+
+                futures::future::join_all(event_processors.iter().map(|ep| ep.start_processing())).await;
+
+                let mut proc_iter = event_processors.iter().cycle();
+                for event in event.records {
+                    let next_proc = proc_iter.next().unwrap();
+                    next_proc.process_event(
+                        map_sqs_message(event)
+                    ).await;
+                }
+
+                info!("Waiting for shutdown notification");
+
+                // Wait for the consumers to shutdown
+                let _ = shutdown_notify.await;
+                info!("Consumer shutdown");
+                finished_tx.send("Completed".to_owned()).unwrap();
+            });
+    });
+
+    info!("Checking acks");
+    for r in &rx {
+        info!("Acking event: {}", &r);
+        initial_events.remove(&r);
+        if r == "Completed" {
+            let r = rx.recv_timeout(Duration::from_millis(100));
+            if let Ok(r) = r {
+                initial_events.remove(&r);
+            }
+            // If we're done go ahead and try to clear out any remaining
+            while let Ok(r) = rx.try_recv() {
+                initial_events.remove(&r);
+            }
+            break;
+        }
+    }
+
+    info!("Completed execution");
+
+    if initial_events.is_empty() {
+        info!("Successfully acked all initial events");
+        Ok(())
+    } else {
+        Err(lambda::error::HandlerError::from("Failed to ack all initial events"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    use super::*;
+
+    const LOG: &[u8] = b"a line of a log\n";
+
+    #[test]
+    fn sniffing_decoder_decodes_gzip_zstd_and_plain_payloads() {
+        let mut gzipped = GzEncoder::new(Vec::new(), Compression::default());
+        gzipped.write_all(LOG).expect("gzip");
+        let gzipped = gzipped.finish().expect("gzip");
+
+        let zstd = zstd::stream::encode_all(LOG, 4).expect("zstd");
+
+        for payload in &[gzipped, zstd, LOG.to_vec()] {
+            let decoded = SniffingDecoder.decode(payload.clone()).expect("decode");
+            assert_eq!(decoded, LOG);
+        }
+    }
+}
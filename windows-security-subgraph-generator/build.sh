@@ -0,0 +1,8 @@
+#!/usr/bin/env bash
+docker run --rm -it -v "$(pwd)":/home/rust/src -t 096f585a5019 cargo build --release &&
+cp "./target/x86_64-unknown-linux-musl/release/windows-security-subgraph-generator" "./bootstrap" &&
+zip -r "./windows-security-subgraph-generator.zip" "./bootstrap" &&
+cp "./windows-security-subgraph-generator.zip" "../grapl-cdk/"
+rm "./windows-security-subgraph-generator.zip"
+rm "./bootstrap"
+date
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use failure::Error;
+
+/// A Windows event, as rendered to XML by the event log, with its EventData flattened by name
+#[derive(Clone, Debug)]
+pub struct SecurityEvent {
+    pub event_id: u32,
+    pub record_id: u64,
+    pub computer: String,
+    pub timestamp: u64,
+    data: HashMap<String, String>,
+}
+
+impl SecurityEvent {
+    pub fn from_xml(xml: &str) -> Result<SecurityEvent, Error> {
+        let document = roxmltree::Document::parse(xml)
+            .map_err(|e| format_err!("Invalid event xml: {}", e))?;
+
+        let root = document.root_element();
+        let system = child(root, "System")
+            .ok_or_else(|| format_err!("Event is missing System"))?;
+
+        let event_id = child(system, "EventID")
+            .and_then(|event_id| event_id.text())
+            .and_then(|event_id| event_id.trim().parse().ok())
+            .ok_or_else(|| format_err!("Event is missing EventID"))?;
+
+        let record_id = child(system, "EventRecordID")
+            .and_then(|record_id| record_id.text())
+            .and_then(|record_id| record_id.trim().parse().ok())
+            .unwrap_or_default();
+
+        let computer = child(system, "Computer")
+            .and_then(|computer| computer.text())
+            .map(|computer| computer.trim().to_owned())
+            .ok_or_else(|| format_err!("Event is missing Computer"))?;
+
+        let system_time = child(system, "TimeCreated")
+            .and_then(|time_created| time_created.attribute("SystemTime"))
+            .ok_or_else(|| format_err!("Event is missing TimeCreated"))?;
+
+        let timestamp = DateTime::parse_from_rfc3339(system_time)
+            .map_err(|e| format_err!("Invalid SystemTime {}: {}", system_time, e))?
+            .timestamp_millis();
+
+        if timestamp < 0 {
+            bail!("Timestamp is negative")
+        }
+
+        let mut data = HashMap::new();
+
+        if let Some(event_data) = child(root, "EventData") {
+            for node in event_data.children().filter(|node| node.has_tag_name("Data")) {
+                if let (Some(name), Some(value)) = (node.attribute("Name"), node.text()) {
+                    data.insert(name.to_owned(), value.to_owned());
+                }
+            }
+        }
+
+        Ok(SecurityEvent {
+            event_id,
+            record_id,
+            computer,
+            timestamp: timestamp as u64,
+            data,
+        })
+    }
+
+    /// The named EventData value. Windows writes '-' for values that do not apply
+    pub fn data(&self, name: &str) -> Option<&str> {
+        match self.data.get(name).map(|value| value.trim()) {
+            Some("") | Some("-") | None => None,
+            Some(value) => Some(value),
+        }
+    }
+
+    pub fn require(&self, name: &str) -> Result<&str, Error> {
+        self.data(name)
+            .ok_or_else(|| format_err!("Event {} is missing {}", self.event_id, name))
+    }
+
+    /// Logon and process ids are written as hex, ie: 0x3e7
+    pub fn hex(&self, name: &str) -> Option<u64> {
+        let value = self.data(name)?;
+        let value = value.trim_start_matches("0x").trim_start_matches("0X");
+
+        u64::from_str_radix(value, 16).ok()
+    }
+
+    pub fn u64(&self, name: &str) -> Option<u64> {
+        self.data(name)?.parse().ok()
+    }
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logon_event_is_parsed() {
+        let xml = concat!(
+            "<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System>",
+            "<Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-A5BA-3E3B0328C30D}'/>",
+            "<EventID>4624</EventID><TimeCreated SystemTime='2019-07-24T17:51:10.855748600Z'/>",
+            "<EventRecordID>8231</EventRecordID><Channel>Security</Channel><Computer>DESKTOP-FVSHABR</Computer>",
+            "</System><EventData>",
+            "<Data Name='TargetLogonId'>0x1d3a2f</Data><Data Name='LogonType'>10</Data>",
+            "<Data Name='IpAddress'>-</Data><Data Name='WorkstationName'></Data>",
+            "</EventData></Event>",
+        );
+
+        let event = SecurityEvent::from_xml(xml).expect("from_xml");
+
+        assert_eq!(event.event_id, 4624);
+        assert_eq!(event.record_id, 8231);
+        assert_eq!(event.computer, "DESKTOP-FVSHABR");
+        assert_eq!(event.timestamp, 1563990670855);
+        assert_eq!(event.hex("TargetLogonId"), Some(0x1d3a2f));
+        assert_eq!(event.u64("LogonType"), Some(10));
+        assert_eq!(event.data("IpAddress"), None);
+        assert_eq!(event.data("WorkstationName"), None);
+    }
+}
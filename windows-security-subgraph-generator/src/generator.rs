@@ -0,0 +1,515 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::file::FileState;
+use graph_descriptions::node::NodeT;
+use graph_descriptions::process::ProcessState;
+
+use crate::events::SecurityEvent;
+
+const LOGON: u32 = 4624;
+const FAILED_LOGON: u32 = 4625;
+const LOGOFF: u32 = 4634;
+const SPECIAL_PRIVILEGES: u32 = 4672;
+const PROCESS_CREATE: u32 = 4688;
+
+// Written in place of the user for events that were not attributed to one
+const NULL_SID: &str = "S-1-0-0";
+
+// The value of ElevatedToken for tokens that are elevated
+const ELEVATED: &str = "%%1842";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SessionState {
+    Created,
+    Existing,
+    Terminated,
+}
+
+/// Generates the subgraph for a Security event, or None if we do not model it
+pub fn generate_subgraph(event: &SecurityEvent) -> Result<Option<Graph>, Error> {
+    let graph = match event.event_id {
+        LOGON => handle_logon(event)?,
+        FAILED_LOGON => handle_failed_logon(event)?,
+        LOGOFF => handle_logoff(event)?,
+        SPECIAL_PRIVILEGES => handle_special_privileges(event)?,
+        PROCESS_CREATE => handle_process_create(event)?,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(graph))
+}
+
+fn get_image_name(image_path: &str) -> Option<String> {
+    image_path.split('\\').next_back().map(String::from)
+}
+
+fn asset(event: &SecurityEvent) -> Result<Asset, Error> {
+    AssetBuilder::default()
+        .asset_id(event.computer.clone())
+        .hostname(event.computer.clone())
+        .build()
+        .map_err(|e| format_err!("asset: {}", e))
+}
+
+// Windows users are identified by their SID. Domain SIDs are unique across hosts, but
+// well-known SIDs such as SYSTEM or the builtin groups are the same on every host, and
+// local accounts are only unique to the machine that issued them, so those are keyed
+// on the asset as well
+fn user(event: &SecurityEvent, prefix: &str) -> Option<DynamicNode> {
+    let sid = event.data(&format!("{}UserSid", prefix))
+        .filter(|sid| *sid != NULL_SID)?;
+    let domain_name = event.data(&format!("{}DomainName", prefix));
+
+    let host_local = is_host_local_sid(sid, domain_name, &event.computer);
+    let computer = if host_local { Some(event.computer.clone()) } else { None };
+
+    let mut user = DynamicNode {
+        properties: HashMap::with_capacity(3),
+        node_key: uuid::Uuid::new_v4().to_string(),
+        node_type: "User".to_owned(),
+        seen_at: event.timestamp,
+        asset_id: computer.clone(),
+        hostname: computer,
+        host_ip: None,
+        host_mac: None,
+        id_strategy: vec![
+            Static {
+                primary_key_properties: vec!["user_sid".to_owned()],
+                primary_key_requires_asset_id: host_local,
+            }.into()
+        ],
+    };
+
+    user.set_property("user_sid", sid.to_owned());
+
+    if let Some(user_name) = event.data(&format!("{}UserName", prefix)) {
+        user.set_property("user_name", user_name.to_owned());
+    }
+    if let Some(domain_name) = domain_name {
+        user.set_property("domain_name", domain_name.to_owned());
+    }
+
+    Some(user)
+}
+
+// SYSTEM, LOCAL SERVICE, NETWORK SERVICE and the builtin groups (S-1-5-32-*) are
+// the same on every host. Local accounts are reported with the computer's
+// NetBIOS name as their domain
+fn is_host_local_sid(sid: &str, domain_name: Option<&str>, computer: &str) -> bool {
+    match sid {
+        "S-1-5-18" | "S-1-5-19" | "S-1-5-20" => return true,
+        sid if sid.starts_with("S-1-5-32-") => return true,
+        _ => (),
+    }
+
+    let netbios_name = computer.split('.').next().unwrap_or(computer);
+    domain_name.is_some_and(|domain_name| domain_name.eq_ignore_ascii_case(netbios_name))
+}
+
+// Logon ids are only unique on the asset, and are reused across boots,
+// so sessions are identified by the node-identifier's session timelines
+fn logon_session(event: &SecurityEvent, logon_id: u64, state: SessionState) -> DynamicNode {
+    let (created_time, last_seen_time, terminated_time) = match state {
+        SessionState::Created => (event.timestamp, 0, 0),
+        SessionState::Existing => (0, event.timestamp, 0),
+        SessionState::Terminated => (0, 0, event.timestamp),
+    };
+
+    let mut session = DynamicNode {
+        properties: HashMap::with_capacity(8),
+        node_key: uuid::Uuid::new_v4().to_string(),
+        node_type: "LogonSession".to_owned(),
+        seen_at: event.timestamp,
+        asset_id: Some(event.computer.clone()),
+        hostname: Some(event.computer.clone()),
+        host_ip: None,
+        host_mac: None,
+        id_strategy: vec![
+            Session {
+                primary_key_properties: vec!["logon_id".to_owned()],
+                primary_key_requires_asset_id: true,
+                created_time,
+                last_seen_time,
+                terminated_time,
+            }.into()
+        ],
+    };
+
+    session.set_property("logon_id", format!("{:#x}", logon_id));
+
+    session
+}
+
+// The address a logon came from, if it came over the network
+fn source_ip(event: &SecurityEvent) -> Result<Option<IpAddress>, Error> {
+    let ip = match event.data("IpAddress").map(|ip| ip.parse::<IpAddr>()) {
+        Some(Ok(ip)) if !ip.is_loopback() && !ip.is_unspecified() => ip,
+        _ => return Ok(None),
+    };
+
+    let ip_address = IpAddressBuilder::default()
+        .ip_address(ip.to_string())
+        .last_seen_timestamp(event.timestamp)
+        .build()
+        .map_err(|e| format_err!("source_ip: {}", e))?;
+
+    Ok(Some(ip_address))
+}
+
+fn set_logon_properties(node: &mut DynamicNode, event: &SecurityEvent) {
+    if let Some(logon_type) = event.u64("LogonType") {
+        node.set_property("logon_type", logon_type);
+    }
+    if let Some(logon_process) = event.data("LogonProcessName") {
+        node.set_property("logon_process", logon_process.to_owned());
+    }
+    if let Some(authentication_package) = event.data("AuthenticationPackageName") {
+        node.set_property("authentication_package", authentication_package.to_owned());
+    }
+    if let Some(workstation_name) = event.data("WorkstationName") {
+        node.set_property("workstation_name", workstation_name.to_owned());
+    }
+    if let Some(ip_address) = event.data("IpAddress") {
+        node.set_property("ip_address", ip_address.to_owned());
+    }
+}
+
+// Links a session to the asset it is on and, if we know them, to its user
+fn add_session(graph: &mut Graph, asset: &Asset, session: DynamicNode, user: Option<DynamicNode>) {
+    graph.add_edge("logged_on_to", session.clone_node_key(), asset.clone_node_key());
+
+    if let Some(user) = user {
+        graph.add_edge("logon_sessions", user.clone_node_key(), session.clone_node_key());
+        graph.add_node(user);
+    }
+
+    graph.add_node(session);
+}
+
+fn handle_logon(event: &SecurityEvent) -> Result<Graph, Error> {
+    let mut graph = Graph::new(event.timestamp);
+
+    let logon_id = event.hex("TargetLogonId")
+        .ok_or_else(|| format_err!("Logon is missing TargetLogonId"))?;
+
+    let asset = asset(event)?;
+
+    let mut session = logon_session(event, logon_id, SessionState::Created);
+    set_logon_properties(&mut session, event);
+
+    if let Some(elevated_token) = event.data("ElevatedToken") {
+        session.set_property("elevated", (elevated_token == ELEVATED) as u64);
+    }
+
+    if let Some(source_ip) = source_ip(event)? {
+        graph.add_edge("logon_from", session.clone_node_key(), source_ip.clone_node_key());
+        graph.add_node(source_ip);
+    }
+
+    add_session(&mut graph, &asset, session, user(event, "Target"));
+    graph.add_node(asset);
+
+    Ok(graph)
+}
+
+// A failed logon has no session, so each attempt is its own node
+fn handle_failed_logon(event: &SecurityEvent) -> Result<Graph, Error> {
+    let mut graph = Graph::new(event.timestamp);
+
+    let asset = asset(event)?;
+
+    let mut attempt = DynamicNode {
+        properties: HashMap::with_capacity(10),
+        node_key: uuid::Uuid::new_v4().to_string(),
+        node_type: "FailedLogon".to_owned(),
+        seen_at: event.timestamp,
+        asset_id: Some(event.computer.clone()),
+        hostname: Some(event.computer.clone()),
+        host_ip: None,
+        host_mac: None,
+        id_strategy: vec![
+            Static {
+                primary_key_properties: vec!["record_id".to_owned()],
+                primary_key_requires_asset_id: true,
+            }.into()
+        ],
+    };
+
+    attempt.set_property("record_id", event.record_id);
+    set_logon_properties(&mut attempt, event);
+
+    // The attempted user name is recorded even when it did not resolve to an account
+    if let Some(user_name) = event.data("TargetUserName") {
+        attempt.set_property("target_user_name", user_name.to_owned());
+    }
+    if let Some(domain_name) = event.data("TargetDomainName") {
+        attempt.set_property("target_domain_name", domain_name.to_owned());
+    }
+    if let Some(status) = event.hex("Status") {
+        attempt.set_property("status", format!("{:#x}", status));
+    }
+    if let Some(sub_status) = event.hex("SubStatus") {
+        attempt.set_property("sub_status", format!("{:#x}", sub_status));
+    }
+
+    graph.add_edge("attempted_logon_to", attempt.clone_node_key(), asset.clone_node_key());
+
+    if let Some(user) = user(event, "Target") {
+        graph.add_edge("failed_logons", user.clone_node_key(), attempt.clone_node_key());
+        graph.add_node(user);
+    }
+
+    if let Some(source_ip) = source_ip(event)? {
+        graph.add_edge("logon_from", attempt.clone_node_key(), source_ip.clone_node_key());
+        graph.add_node(source_ip);
+    }
+
+    graph.add_node(attempt);
+    graph.add_node(asset);
+
+    Ok(graph)
+}
+
+fn handle_logoff(event: &SecurityEvent) -> Result<Graph, Error> {
+    let mut graph = Graph::new(event.timestamp);
+
+    let logon_id = event.hex("TargetLogonId")
+        .ok_or_else(|| format_err!("Logoff is missing TargetLogonId"))?;
+
+    let asset = asset(event)?;
+    let session = logon_session(event, logon_id, SessionState::Terminated);
+
+    add_session(&mut graph, &asset, session, user(event, "Target"));
+    graph.add_node(asset);
+
+    Ok(graph)
+}
+
+// 4672 follows the 4624 of a logon that was assigned admin equivalent privileges
+fn handle_special_privileges(event: &SecurityEvent) -> Result<Graph, Error> {
+    let mut graph = Graph::new(event.timestamp);
+
+    let logon_id = event.hex("SubjectLogonId")
+        .ok_or_else(|| format_err!("Special privileges is missing SubjectLogonId"))?;
+
+    let asset = asset(event)?;
+    let mut session = logon_session(event, logon_id, SessionState::Existing);
+
+    if let Some(privileges) = event.data("PrivilegeList") {
+        let privileges: Vec<_> = privileges.split_whitespace().collect();
+        session.set_property("privileges", privileges.join(","));
+    }
+
+    add_session(&mut graph, &asset, session, user(event, "Subject"));
+    graph.add_node(asset);
+
+    Ok(graph)
+}
+
+fn handle_process_create(event: &SecurityEvent) -> Result<Graph, Error> {
+    let mut graph = Graph::new(event.timestamp);
+
+    let asset = asset(event)?;
+
+    let image = event.require("NewProcessName")?;
+    let process_id = event.hex("NewProcessId")
+        .ok_or_else(|| format_err!("Process create is missing NewProcessId"))?;
+    let parent_process_id = event.hex("ProcessId")
+        .ok_or_else(|| format_err!("Process create is missing ProcessId"))?;
+
+    let mut child = ProcessBuilder::default();
+    child
+        .asset_id(event.computer.clone())
+        .state(ProcessState::Created)
+        .process_id(process_id)
+        .created_timestamp(event.timestamp);
+
+    if let Some(process_name) = get_image_name(image) {
+        child.process_name(process_name);
+    }
+    // Only logged when process command line auditing is enabled
+    if let Some(command_line) = event.data("CommandLine") {
+        child.process_command_line(command_line);
+    }
+
+    let child = child.build()
+        .map_err(|e| format_err!("process_create.child: {}", e))?;
+
+    let mut parent = ProcessBuilder::default();
+    parent
+        .asset_id(event.computer.clone())
+        .state(ProcessState::Existing)
+        .process_id(parent_process_id)
+        .last_seen_timestamp(event.timestamp);
+
+    // ParentProcessName was added in Windows 10 and Server 2016
+    if let Some(parent_process_name) = event.data("ParentProcessName").and_then(get_image_name) {
+        parent.process_name(parent_process_name);
+    }
+
+    let parent = parent.build()
+        .map_err(|e| format_err!("process_create.parent: {}", e))?;
+
+    let child_exe = FileBuilder::default()
+        .asset_id(event.computer.clone())
+        .state(FileState::Existing)
+        .last_seen_timestamp(event.timestamp)
+        .file_path(image)
+        .build()
+        .map_err(|e| format_err!("process_create.child_exe: {}", e))?;
+
+    // The Target fields, also added in Windows 10, describe the new process's logon.
+    // Before then, or when they are unset, the process runs in its creator's logon
+    let target_logon_id = event.hex("TargetLogonId").filter(|logon_id| *logon_id != 0);
+    let (logon_id, user) = match target_logon_id {
+        Some(logon_id) => (Some(logon_id), user(event, "Target")),
+        None => (event.hex("SubjectLogonId"), user(event, "Subject")),
+    };
+
+    if let Some(logon_id) = logon_id {
+        let session = logon_session(event, logon_id, SessionState::Existing);
+        graph.add_edge("session_processes", session.clone_node_key(), child.clone_node_key());
+        graph.add_edge("logged_on_to", session.clone_node_key(), asset.clone_node_key());
+        graph.add_node(session);
+    }
+
+    if let Some(user) = user {
+        graph.add_edge("process_user", child.clone_node_key(), user.clone_node_key());
+        graph.add_node(user);
+    }
+
+    graph.add_edge("asset_processes", asset.clone_node_key(), child.clone_node_key());
+    graph.add_edge("bin_file", child.clone_node_key(), child_exe.clone_node_key());
+    graph.add_edge("children", parent.clone_node_key(), child.clone_node_key());
+
+    graph.add_node(asset);
+    graph.add_node(parent);
+    graph.add_node(child);
+    graph.add_node(child_exe);
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use graph_descriptions::graph_description::id_strategy::Strategy;
+
+    use super::*;
+
+    fn recorded_event(event_id: u32) -> SecurityEvent {
+        include_str!("../test_data/security.xml")
+            .lines()
+            .filter(|line| line.starts_with("<Event"))
+            .map(|line| SecurityEvent::from_xml(line).expect("recorded event"))
+            .find(|event| event.event_id == event_id)
+            .expect("event_id")
+    }
+
+    fn generate(event_id: u32) -> Graph {
+        generate_subgraph(&recorded_event(event_id))
+            .expect("generate_subgraph")
+            .expect("modelled event")
+    }
+
+    fn dynamic_nodes<'a>(graph: &'a Graph, node_type: &'a str) -> impl Iterator<Item=&'a DynamicNode> {
+        graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .filter(move |node| node.node_type == node_type)
+    }
+
+    fn session_strategy(graph: &Graph) -> Session {
+        let session = dynamic_nodes(graph, "LogonSession").next().expect("LogonSession");
+        match session.id_strategy[0].strategy {
+            Some(Strategy::Session(ref strategy)) => strategy.clone(),
+            _ => panic!("LogonSession is not identified by session"),
+        }
+    }
+
+    fn edge_names(graph: &Graph) -> Vec<String> {
+        let mut edge_names: Vec<_> = graph.edges.values()
+            .flat_map(|edges| edges.edges.iter())
+            .map(|edge| edge.edge_name.clone())
+            .collect();
+        edge_names.sort();
+        edge_names
+    }
+
+    fn user_requires_asset_id(user: &DynamicNode) -> bool {
+        match user.id_strategy[0].strategy {
+            Some(Strategy::Static(ref strategy)) => strategy.primary_key_requires_asset_id,
+            _ => panic!("User is not identified statically"),
+        }
+    }
+
+    #[test]
+    fn host_local_users_are_keyed_on_the_asset() {
+        let event = recorded_event(LOGON);
+
+        let system = user(&event, "Subject").expect("SYSTEM");
+        assert!(user_requires_asset_id(&system));
+        assert_eq!(system.asset_id, Some("DESKTOP-FVSHABR".to_owned()));
+
+        let local_account = user(&event, "Target").expect("local account");
+        assert!(user_requires_asset_id(&local_account));
+        assert_eq!(local_account.asset_id, Some("DESKTOP-FVSHABR".to_owned()));
+    }
+
+    #[test]
+    fn domain_users_are_not_keyed_on_the_asset() {
+        let xml = include_str!("../test_data/security.xml")
+            .lines()
+            .filter(|line| line.starts_with("<Event"))
+            .find(|line| line.contains("<EventID>4624</EventID>"))
+            .expect("logon event")
+            .replace("<Data Name='TargetDomainName'>DESKTOP-FVSHABR", "<Data Name='TargetDomainName'>CORP");
+        let event = SecurityEvent::from_xml(&xml).expect("logon event");
+
+        let domain_account = user(&event, "Target").expect("domain account");
+        assert!(!user_requires_asset_id(&domain_account));
+        assert_eq!(domain_account.asset_id, None);
+    }
+
+    #[test]
+    fn logon_creates_session() {
+        let graph = generate(LOGON);
+
+        let strategy = session_strategy(&graph);
+        assert_ne!(strategy.created_time, 0);
+        assert_eq!(strategy.primary_key_properties, vec!["logon_id".to_owned()]);
+        assert_eq!(edge_names(&graph), vec!["logged_on_to", "logon_from", "logon_sessions"]);
+    }
+
+    #[test]
+    fn logoff_terminates_session() {
+        let graph = generate(LOGOFF);
+
+        assert_ne!(session_strategy(&graph).terminated_time, 0);
+    }
+
+    #[test]
+    fn failed_logon_without_account_has_no_user() {
+        let graph = generate(FAILED_LOGON);
+
+        assert_eq!(dynamic_nodes(&graph, "User").count(), 0);
+        assert_eq!(edge_names(&graph), vec!["attempted_logon_to", "logon_from"]);
+    }
+
+    #[test]
+    fn process_runs_in_its_session() {
+        let graph = generate(PROCESS_CREATE);
+
+        assert_eq!(
+            edge_names(&graph),
+            vec!["asset_processes", "bin_file", "children", "logged_on_to", "process_user", "session_processes"]
+        );
+
+        let session = dynamic_nodes(&graph, "LogonSession").next().expect("LogonSession");
+        assert_eq!(
+            session.properties.get("logon_id").map(|logon_id| logon_id.to_string()),
+            Some("0x1d3a2f".to_owned())
+        );
+    }
+}
@@ -0,0 +1,68 @@
+extern crate aws_lambda_events;
+extern crate chrono;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate roxmltree;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+extern crate uuid;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::events::SecurityEvent;
+use crate::generator::generate_subgraph;
+
+mod events;
+mod generator;
+
+#[derive(Clone)]
+struct WindowsSecuritySubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for WindowsSecuritySubgraphGenerator {
+    type Event = String;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<String>, failure::Error> {
+        // The payload is newline separated events, each rendered to XML
+        let events = String::from_utf8_lossy(payload)
+            .lines()
+            .map(str::trim)
+            .filter(|event| event.starts_with("<Event"))
+            .map(String::from)
+            .collect();
+
+        Ok(events)
+    }
+
+    fn identity(&self, event: &String) -> String {
+        event.clone()
+    }
+
+    async fn generate_subgraph(&mut self, event: String) -> Result<Option<Graph>, failure::Error> {
+        let security_event = SecurityEvent::from_xml(&event)?;
+
+        generate_subgraph(&security_event)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "WINDOWS_SECURITY", WindowsSecuritySubgraphGenerator, ZstdDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}
@@ -0,0 +1,8 @@
+#!/usr/bin/env bash
+docker run --rm -it -v "$(pwd)":/home/rust/src -t 096f585a5019 cargo build --release &&
+cp "./target/x86_64-unknown-linux-musl/release/zeek-subgraph-generator" "./bootstrap" &&
+zip -r "./zeek-subgraph-generator.zip" "./bootstrap" &&
+cp "./zeek-subgraph-generator.zip" "../grapl-cdk/"
+rm "./zeek-subgraph-generator.zip"
+rm "./bootstrap"
+date
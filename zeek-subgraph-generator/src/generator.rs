@@ -0,0 +1,445 @@
+use std::net::IpAddr;
+
+use failure::Error;
+use graph_descriptions::graph_description::*;
+use graph_descriptions::network_connection::NetworkConnectionState;
+use graph_descriptions::node::NodeT;
+use graph_descriptions::process_inbound_connection::ProcessInboundConnectionState;
+use graph_descriptions::process_outbound_connection::ProcessOutboundConnectionState;
+use graph_descriptions::url::{domain_name, host_domain, url};
+
+use crate::logs::{LogPath, ZeekRecord};
+
+pub fn generate_subgraph(record: &ZeekRecord) -> Result<Graph, Error> {
+    let timestamp = record.timestamp()?;
+
+    match record.path {
+        LogPath::Conn => handle_conn(record, timestamp),
+        LogPath::Dns => handle_dns(record, timestamp),
+        LogPath::Ssl => handle_ssl(record, timestamp),
+        LogPath::Http => handle_http(record, timestamp),
+    }
+}
+
+// The nodes describing one side of a connection
+struct Endpoint {
+    ip: IpAddress,
+    port: IpPort,
+}
+
+// The nodes shared by every log that is about a connection
+struct Connection {
+    src: Endpoint,
+    dst: Endpoint,
+    network_connection: NetworkConnection,
+    // The connection as seen by each host in our network. Zeek logs carry no hostnames,
+    // so these are attributed to the asset that held the address at the time
+    outbound: Option<ProcessOutboundConnection>,
+    inbound: Option<ProcessInboundConnection>,
+}
+
+impl Connection {
+    /// Builds the connection from the id.* fields of a record. Every log that has a uid
+    /// also has the 5 tuple, so they all identify the same NetworkConnection, and the
+    /// uid lets analyzers join it back to the individual logs.
+    fn from_record(
+        record: &ZeekRecord,
+        timestamp: u64,
+        state: NetworkConnectionState,
+        default_protocol: &str,
+    ) -> Result<Self, Error> {
+        let src_ip = required_str(record, "id.orig_h")?;
+        let src_port = required_u64(record, "id.orig_p")? as u32;
+        let dst_ip = required_str(record, "id.resp_h")?;
+        let dst_port = required_u64(record, "id.resp_p")? as u32;
+
+        let protocol = record.string("proto").unwrap_or_else(|| default_protocol.to_owned());
+
+        let mut builder = NetworkConnectionBuilder::default();
+        builder
+            .src_ip_address(src_ip.clone())
+            .src_port(src_port)
+            .dst_ip_address(dst_ip.clone())
+            .dst_port(dst_port)
+            .protocol(protocol.clone())
+            .connection_uid(record.string("uid").unwrap_or_default());
+
+        match state {
+            NetworkConnectionState::Created => builder.created_timestamp(timestamp),
+            NetworkConnectionState::Terminated => builder.terminated_timestamp(timestamp),
+            NetworkConnectionState::Existing => builder.last_seen_timestamp(timestamp),
+        };
+
+        let outbound = if is_private(&src_ip) {
+            let mut builder = ProcessOutboundConnectionBuilder::default();
+            builder
+                .host_ip(src_ip.clone())
+                .ip_address(src_ip.clone())
+                .port(src_port)
+                .protocol(protocol.clone());
+
+            match state {
+                NetworkConnectionState::Created => builder
+                    .state(ProcessOutboundConnectionState::Connected)
+                    .created_timestamp(timestamp),
+                NetworkConnectionState::Terminated => builder
+                    .state(ProcessOutboundConnectionState::Closed)
+                    .terminated_timestamp(timestamp),
+                NetworkConnectionState::Existing => builder
+                    .state(ProcessOutboundConnectionState::Existing)
+                    .last_seen_timestamp(timestamp),
+            };
+
+            Some(builder.build().map_err(|e| format_err!("outbound: {}", e))?)
+        } else {
+            None
+        };
+
+        let inbound = if is_private(&dst_ip) {
+            let mut builder = ProcessInboundConnectionBuilder::default();
+            builder
+                .host_ip(dst_ip.clone())
+                .ip_address(dst_ip.clone())
+                .port(dst_port)
+                .protocol(protocol.clone());
+
+            // The port was bound before the connection was made, so a new connection is
+            // only an observation of the bound port
+            match state {
+                NetworkConnectionState::Terminated => builder
+                    .state(ProcessInboundConnectionState::Closed)
+                    .terminated_timestamp(timestamp),
+                _ => builder
+                    .state(ProcessInboundConnectionState::Existing)
+                    .last_seen_timestamp(timestamp),
+            };
+
+            Some(builder.build().map_err(|e| format_err!("inbound: {}", e))?)
+        } else {
+            None
+        };
+
+        let network_connection = builder
+            .state(state)
+            .build()
+            .map_err(|e| format_err!("network_connection: {}", e))?;
+
+        Ok(Self {
+            src: Endpoint::new(src_ip, src_port, &protocol, timestamp)?,
+            dst: Endpoint::new(dst_ip, dst_port, &protocol, timestamp)?,
+            network_connection,
+            outbound,
+            inbound,
+        })
+    }
+
+    fn add_to(self, graph: &mut Graph) {
+        // There is a network connection between the src and dst ports
+        graph.add_edge(
+            "outbound_connection_to",
+            self.src.port.clone_node_key(),
+            self.network_connection.clone_node_key(),
+        );
+
+        graph.add_edge(
+            "inbound_connection_to",
+            self.network_connection.clone_node_key(),
+            self.dst.port.clone_node_key(),
+        );
+
+        if let Some(outbound) = self.outbound {
+            graph.add_edge("connected_over", outbound.clone_node_key(), self.src.port.clone_node_key());
+            graph.add_edge("connected_to", outbound.clone_node_key(), self.dst.port.clone_node_key());
+            graph.add_node(outbound);
+        }
+
+        if let Some(inbound) = self.inbound {
+            graph.add_edge("bound_port", inbound.clone_node_key(), self.dst.port.clone_node_key());
+            graph.add_node(inbound);
+        }
+
+        graph.add_node(self.network_connection);
+        self.src.add_to(graph);
+        self.dst.add_to(graph);
+    }
+}
+
+impl Endpoint {
+    fn new(ip: String, port: u32, protocol: &str, timestamp: u64) -> Result<Self, Error> {
+        let ip_address = IpAddressBuilder::default()
+            .ip_address(ip.clone())
+            .last_seen_timestamp(timestamp)
+            .build()
+            .map_err(|e| format_err!("ip_address: {}", e))?;
+
+        let ip_port = IpPortBuilder::default()
+            .ip_address(ip)
+            .port(port)
+            .protocol(protocol.to_owned())
+            .build()
+            .map_err(|e| format_err!("ip_port: {}", e))?;
+
+        Ok(Self { ip: ip_address, port: ip_port })
+    }
+
+    fn add_to(self, graph: &mut Graph) {
+        graph.add_node(self.ip);
+        graph.add_node(self.port);
+    }
+}
+
+// Only addresses in our own network can be mapped to one of our assets
+fn is_private(ip: &str) -> bool {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_private(),
+        // Unique local addresses, fc00::/7
+        Ok(IpAddr::V6(ip)) => ip.segments()[0] & 0xfe00 == 0xfc00,
+        Err(_) => false,
+    }
+}
+
+fn required_str(record: &ZeekRecord, name: &str) -> Result<String, Error> {
+    match record.string(name) {
+        Some(value) => Ok(value),
+        None => bail!("Record is missing field: {}", name),
+    }
+}
+
+fn required_u64(record: &ZeekRecord, name: &str) -> Result<u64, Error> {
+    match record.u64(name) {
+        Some(value) => Ok(value),
+        None => bail!("Record is missing field: {}", name),
+    }
+}
+
+fn handle_conn(record: &ZeekRecord, timestamp: u64) -> Result<Graph, Error> {
+    let mut graph = Graph::new(timestamp);
+
+    // conn.log is written when the connection ends, but ts is when it started
+    let mut connection = Connection::from_record(
+        record,
+        timestamp,
+        NetworkConnectionState::Created,
+        "tcp",
+    )?;
+
+    let duration = record.string("duration").and_then(|duration| duration.parse::<f64>().ok());
+    if let Some(duration) = duration {
+        connection.network_connection.terminated_timestamp = timestamp + (duration * 1000.0) as u64;
+    }
+
+    let ip_connection = IpConnectionBuilder::default()
+        .state(NetworkConnectionState::Created)
+        .src_ip_address(connection.network_connection.src_ip_address.clone())
+        .dst_ip_address(connection.network_connection.dst_ip_address.clone())
+        .protocol(connection.network_connection.protocol.clone())
+        .created_timestamp(timestamp)
+        .build()
+        .map_err(|e| format_err!("conn.ip_connection: {}", e))?;
+
+    // There is also a connection between the two IP addresses
+    graph.add_edge(
+        "ip_connection_to",
+        connection.src.ip.clone_node_key(),
+        ip_connection.clone_node_key(),
+    );
+
+    graph.add_edge(
+        "ip_connection_to",
+        ip_connection.clone_node_key(),
+        connection.dst.ip.clone_node_key(),
+    );
+
+    graph.add_node(ip_connection);
+    connection.add_to(&mut graph);
+
+    Ok(graph)
+}
+
+fn handle_dns(record: &ZeekRecord, timestamp: u64) -> Result<Graph, Error> {
+    let mut graph = Graph::new(timestamp);
+
+    let connection = Connection::from_record(
+        record,
+        timestamp,
+        NetworkConnectionState::Existing,
+        "udp",
+    )?;
+
+    if let Some(query) = record.str("query") {
+        let domain = domain_name(query, timestamp);
+
+        graph.add_edge(
+            "dns_query",
+            connection.network_connection.clone_node_key(),
+            domain.clone_node_key(),
+        );
+
+        for answer in record.list("answers") {
+            // Answers are either addresses or the names of CNAME, PTR and similar records
+            if answer.parse::<IpAddr>().is_ok() {
+                let resolved = IpAddressBuilder::default()
+                    .ip_address(answer)
+                    .last_seen_timestamp(timestamp)
+                    .build()
+                    .map_err(|e| format_err!("dns.resolved: {}", e))?;
+
+                graph.add_edge("resolves_to", domain.clone_node_key(), resolved.clone_node_key());
+                graph.add_node(resolved);
+            } else {
+                let alias = domain_name(&answer, timestamp);
+
+                graph.add_edge("resolves_to_domain", domain.clone_node_key(), alias.clone_node_key());
+                graph.add_node(alias);
+            }
+        }
+
+        graph.add_node(domain);
+    }
+
+    connection.add_to(&mut graph);
+
+    Ok(graph)
+}
+
+fn handle_ssl(record: &ZeekRecord, timestamp: u64) -> Result<Graph, Error> {
+    let mut graph = Graph::new(timestamp);
+
+    let connection = Connection::from_record(
+        record,
+        timestamp,
+        NetworkConnectionState::Existing,
+        "tcp",
+    )?;
+
+    if let Some(server_name) = record.str("server_name") {
+        let domain = domain_name(server_name, timestamp);
+
+        graph.add_edge(
+            "server_name",
+            connection.network_connection.clone_node_key(),
+            domain.clone_node_key(),
+        );
+        graph.add_node(domain);
+    }
+
+    connection.add_to(&mut graph);
+
+    Ok(graph)
+}
+
+fn handle_http(record: &ZeekRecord, timestamp: u64) -> Result<Graph, Error> {
+    let mut graph = Graph::new(timestamp);
+
+    let connection = Connection::from_record(
+        record,
+        timestamp,
+        NetworkConnectionState::Existing,
+        "tcp",
+    )?;
+
+    // Requests without a Host header are addressed to the server ip
+    let host = record.string("host")
+        .unwrap_or_else(|| connection.network_connection.dst_ip_address.clone());
+
+    if let Some(uri) = record.str("uri") {
+        let mut requested_url = url(format!("http://{}{}", host, uri), timestamp);
+        requested_url.set_property("host", host.clone());
+
+        graph.add_edge(
+            "requested_url",
+            connection.network_connection.clone_node_key(),
+            requested_url.clone_node_key(),
+        );
+
+        if let Some(hostname) = host_domain(&host) {
+            let domain = domain_name(hostname, timestamp);
+
+            graph.add_edge("url_domain", requested_url.clone_node_key(), domain.clone_node_key());
+            graph.add_node(domain);
+        }
+
+        graph.add_node(requested_url);
+    }
+
+    connection.add_to(&mut graph);
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::parse_logs;
+
+    fn dynamic_nodes<'a>(graph: &'a Graph, node_type: &'a str) -> impl Iterator<Item=&'a DynamicNode> + 'a {
+        graph.nodes.values()
+            .filter_map(|node| node.as_dynamic_node())
+            .filter(move |node| node.node_type == node_type)
+    }
+
+    #[test]
+    fn conn_log_generates_connections() {
+        let records = parse_logs(
+            r#"{"ts":1258531221.486539,"uid":"C1","id.orig_h":"10.0.0.2","id.orig_p":53211,"id.resp_h":"10.0.0.1","id.resp_p":443,"proto":"tcp","duration":1.5,"conn_state":"SF"}"#
+        ).expect("parse_logs");
+
+        let graph = generate_subgraph(&records[0]).expect("generate_subgraph");
+
+        // src/dst ip, src/dst port, network and ip connection, and both hosts' view of it
+        assert_eq!(graph.nodes.len(), 8);
+
+        let connection = graph.nodes.values()
+            .filter_map(|node| node.as_network_connection())
+            .next()
+            .expect("network_connection");
+        assert_eq!(connection.connection_uid, "C1");
+        assert_eq!(connection.terminated_timestamp, 1258531222986);
+    }
+
+    #[test]
+    fn private_hosts_are_attributed_by_ip() {
+        let records = parse_logs(
+            r#"{"ts":1258531221.486539,"uid":"C4","id.orig_h":"10.0.0.2","id.orig_p":53211,"id.resp_h":"93.184.216.34","id.resp_p":443,"server_name":"example.com"}"#
+        ).expect("parse_logs");
+
+        let graph = generate_subgraph(&records[0]).expect("generate_subgraph");
+
+        let outbound = graph.nodes.values()
+            .find_map(|node| node.as_process_outbound_connection())
+            .expect("outbound");
+        assert_eq!(outbound.host_ip, Some("10.0.0.2".to_owned()));
+        assert_eq!(outbound.asset_id, None);
+
+        // The public responder is not one of our assets
+        assert!(graph.nodes.values().all(|node| node.as_process_inbound_connection().is_none()));
+    }
+
+    #[test]
+    fn dns_log_resolves_domains() {
+        let records = parse_logs(
+            r#"{"ts":1258531221.486539,"uid":"C2","id.orig_h":"10.0.0.2","id.orig_p":53211,"id.resp_h":"10.0.0.1","id.resp_p":53,"proto":"udp","query":"www.Example.com","answers":["example.com","93.184.216.34"]}"#
+        ).expect("parse_logs");
+
+        let graph = generate_subgraph(&records[0]).expect("generate_subgraph");
+
+        assert_eq!(dynamic_nodes(&graph, "DomainName").count(), 2);
+        assert_eq!(graph.nodes.values().filter_map(|node| node.as_ip_address()).count(), 3);
+    }
+
+    #[test]
+    fn http_log_generates_url() {
+        let records = parse_logs(
+            r#"{"ts":1258531221.486539,"uid":"C3","id.orig_h":"10.0.0.2","id.orig_p":53211,"id.resp_h":"93.184.216.34","id.resp_p":80,"method":"GET","host":"example.com","uri":"/index.html"}"#
+        ).expect("parse_logs");
+
+        let graph = generate_subgraph(&records[0]).expect("generate_subgraph");
+
+        let url = dynamic_nodes(&graph, "Url").next().expect("url");
+        assert_eq!(
+            url.properties.get("url").map(|url| url.to_string()),
+            Some("http://example.com/index.html".to_owned())
+        );
+        assert_eq!(dynamic_nodes(&graph, "DomainName").count(), 1);
+    }
+}
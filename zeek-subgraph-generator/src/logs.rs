@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use failure::Error;
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogPath {
+    Conn,
+    Dns,
+    Ssl,
+    Http,
+}
+
+impl LogPath {
+    fn from_path(path: &str) -> Option<LogPath> {
+        let path = match path {
+            "conn" => LogPath::Conn,
+            "dns" => LogPath::Dns,
+            "ssl" => LogPath::Ssl,
+            "http" => LogPath::Http,
+            _ => return None,
+        };
+
+        Some(path)
+    }
+
+    // JSON logs only name their path when written with the json-streaming-logs package,
+    // otherwise we go by the fields that are unique to each log
+    fn infer(fields: &HashMap<String, Value>) -> Option<LogPath> {
+        if let Some(Value::String(path)) = fields.get("_path") {
+            return LogPath::from_path(path);
+        }
+
+        if fields.contains_key("conn_state") {
+            Some(LogPath::Conn)
+        } else if fields.contains_key("qtype_name") || fields.contains_key("query") {
+            Some(LogPath::Dns)
+        } else if fields.contains_key("server_name") || fields.contains_key("cipher") {
+            Some(LogPath::Ssl)
+        } else if fields.contains_key("method") && fields.contains_key("uri") {
+            Some(LogPath::Http)
+        } else {
+            None
+        }
+    }
+}
+
+/// A single entry of a Zeek log, from either the TSV or JSON writer
+#[derive(Clone, Debug)]
+pub struct ZeekRecord {
+    pub path: LogPath,
+    fields: HashMap<String, Value>,
+    /// The raw line, used to identify the record
+    pub line: String,
+}
+
+impl ZeekRecord {
+    pub fn str(&self, name: &str) -> Option<&str> {
+        match self.fields.get(name) {
+            Some(Value::String(value)) if !value.is_empty() => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<String> {
+        match self.fields.get(name) {
+            Some(Value::Number(value)) => Some(value.to_string()),
+            _ => self.str(name).map(String::from),
+        }
+    }
+
+    pub fn u64(&self, name: &str) -> Option<u64> {
+        match self.fields.get(name) {
+            Some(Value::Number(value)) => value.as_u64(),
+            Some(Value::String(value)) => value.parse().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn list(&self, name: &str) -> Vec<String> {
+        match self.fields.get(name) {
+            Some(Value::Array(values)) => values.iter()
+                .filter_map(|value| match value {
+                    Value::String(value) => Some(value.clone()),
+                    Value::Number(value) => Some(value.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            Some(Value::String(value)) if !value.is_empty() => vec![value.clone()],
+            _ => vec![],
+        }
+    }
+
+    /// The record timestamp in milliseconds. Zeek writes epoch seconds by default,
+    /// or ISO 8601 when JSON::use_json_iso8601 is set
+    pub fn timestamp(&self) -> Result<u64, Error> {
+        let ts = match self.fields.get("ts") {
+            Some(Value::Number(ts)) => ts.as_f64().map(seconds_to_millis),
+            Some(Value::String(ts)) => match ts.parse::<f64>() {
+                Ok(ts) => Some(seconds_to_millis(ts)),
+                Err(_) => {
+                    let dt = DateTime::parse_from_rfc3339(ts)
+                        .map_err(|e| format_err!("Invalid ts {}: {}", ts, e))?;
+                    Some(dt.timestamp_millis())
+                }
+            },
+            _ => None,
+        };
+
+        match ts {
+            Some(ts) if ts >= 0 => Ok(ts as u64),
+            Some(ts) => bail!("Timestamp is negative: {}", ts),
+            None => bail!("Record is missing ts"),
+        }
+    }
+}
+
+fn seconds_to_millis(seconds: f64) -> i64 {
+    (seconds * 1000.0) as i64
+}
+
+// The header of a TSV log, which describes how the rows that follow are written
+#[derive(Clone, Debug)]
+struct TsvHeader {
+    separator: String,
+    set_separator: String,
+    empty_field: String,
+    unset_field: String,
+    path: Option<LogPath>,
+    fields: Vec<String>,
+    types: Vec<String>,
+}
+
+impl Default for TsvHeader {
+    fn default() -> Self {
+        Self {
+            separator: "\t".to_owned(),
+            set_separator: ",".to_owned(),
+            empty_field: "(empty)".to_owned(),
+            unset_field: "-".to_owned(),
+            path: None,
+            fields: vec![],
+            types: vec![],
+        }
+    }
+}
+
+impl TsvHeader {
+    fn parse_directive(&mut self, line: &str) {
+        // '#separator \x09' is written with a space, every other directive uses the separator
+        if let Some(separator) = line.strip_prefix("#separator ") {
+            self.separator = unescape(separator);
+            return;
+        }
+
+        let mut parts = line.split(self.separator.as_str());
+        let directive = parts.next().unwrap_or_default();
+        let values: Vec<String> = parts.map(String::from).collect();
+
+        match directive {
+            "#set_separator" => self.set_separator = values.concat(),
+            "#empty_field" => self.empty_field = values.concat(),
+            "#unset_field" => self.unset_field = values.concat(),
+            "#path" => self.path = values.first().and_then(|path| LogPath::from_path(path)),
+            "#fields" => self.fields = values,
+            "#types" => self.types = values,
+            _ => (),
+        }
+    }
+
+    fn parse_row(&self, line: &str) -> HashMap<String, Value> {
+        let mut fields = HashMap::with_capacity(self.fields.len());
+
+        for (i, value) in line.split(self.separator.as_str()).enumerate() {
+            let name = match self.fields.get(i) {
+                Some(name) => name,
+                None => break,
+            };
+
+            if value == self.unset_field {
+                continue;
+            }
+
+            let is_container = self.types.get(i)
+                .map(|ty| ty.starts_with("set[") || ty.starts_with("vector["))
+                .unwrap_or_default();
+
+            let value = if value == self.empty_field {
+                if is_container { Value::Array(vec![]) } else { Value::String(String::new()) }
+            } else if is_container {
+                Value::Array(
+                    value.split(self.set_separator.as_str())
+                        .map(|v| Value::String(v.to_owned()))
+                        .collect()
+                )
+            } else {
+                Value::String(value.to_owned())
+            };
+
+            fields.insert(name.clone(), value);
+        }
+
+        fields
+    }
+}
+
+// Decodes the \xNN escapes Zeek uses in its header
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = value;
+
+    while let Some(index) = rest.find("\\x") {
+        unescaped.push_str(&rest[..index]);
+
+        let escaped = rest.get(index + 2..index + 4)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                unescaped.push_str("\\x");
+                rest = &rest[index + 2..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Parses a payload of Zeek logs, in either the TSV or JSON format.
+/// Records from logs we do not model are skipped.
+pub fn parse_logs(payload: &str) -> Result<Vec<ZeekRecord>, Error> {
+    let mut records = Vec::new();
+    let mut header = TsvHeader::default();
+
+    for line in payload.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if line.starts_with('#') {
+            // A new log starts with its own header
+            if line.starts_with("#separator ") {
+                header = TsvHeader::default();
+            }
+            header.parse_directive(line);
+            continue;
+        }
+
+        let (path, fields) = if line.starts_with('{') {
+            let fields: HashMap<String, Value> = serde_json::from_str(line)?;
+            (LogPath::infer(&fields), fields)
+        } else {
+            if header.fields.is_empty() {
+                bail!("TSV row without a #fields header: {}", line);
+            }
+            (header.path, header.parse_row(line))
+        };
+
+        if let Some(path) = path {
+            records.push(ZeekRecord { path, fields, line: line.to_owned() });
+        }
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONN_TSV: &str = concat!(
+        "#separator \\x09\n",
+        "#set_separator\t,\n",
+        "#empty_field\t(empty)\n",
+        "#unset_field\t-\n",
+        "#path\tconn\n",
+        "#fields\tts\tuid\tid.orig_h\tid.orig_p\tid.resp_h\tid.resp_p\tproto\tservice\ttunnel_parents\n",
+        "#types\ttime\tstring\taddr\tport\taddr\tport\tenum\tstring\tset[string]\n",
+        "1258531221.486539\tCHhAvVGS1DHFjwGM9\t192.168.1.102\t68\t192.168.1.1\t67\tudp\t-\t(empty)\n",
+        "#close\t2019-01-01-00-00-00\n",
+    );
+
+    #[test]
+    fn tsv_conn_log_is_parsed() {
+        let records = parse_logs(CONN_TSV).expect("parse_logs");
+
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.path, LogPath::Conn);
+        assert_eq!(record.str("uid"), Some("CHhAvVGS1DHFjwGM9"));
+        assert_eq!(record.u64("id.orig_p"), Some(68));
+        assert_eq!(record.str("service"), None);
+        assert!(record.list("tunnel_parents").is_empty());
+        assert_eq!(record.timestamp().expect("timestamp"), 1258531221486);
+    }
+
+    #[test]
+    fn json_log_path_is_inferred() {
+        let payload = concat!(
+            r#"{"ts":1258531221.486539,"uid":"C1","id.orig_h":"10.0.0.2","id.orig_p":53211,"id.resp_h":"10.0.0.1","id.resp_p":53,"proto":"udp","query":"example.com","answers":["93.184.216.34"]}"#,
+            "\n",
+            r#"{"ts":"2009-11-18T08:00:21.486539Z","uid":"C2","id.orig_h":"10.0.0.2","id.orig_p":53212,"id.resp_h":"93.184.216.34","id.resp_p":443,"server_name":"example.com"}"#,
+            "\n",
+        );
+
+        let records = parse_logs(payload).expect("parse_logs");
+
+        assert_eq!(records[0].path, LogPath::Dns);
+        assert_eq!(records[0].list("answers"), vec!["93.184.216.34".to_owned()]);
+        assert_eq!(records[1].path, LogPath::Ssl);
+        assert_eq!(records[1].timestamp().expect("timestamp"), 1258531221486);
+    }
+}
@@ -0,0 +1,58 @@
+extern crate aws_lambda_events;
+extern crate chrono;
+#[macro_use]
+extern crate failure;
+extern crate graph_descriptions;
+extern crate lambda_runtime as lambda;
+extern crate log;
+extern crate serde_json;
+extern crate simple_logger;
+extern crate subgraph_generator_runtime;
+
+use async_trait::async_trait;
+use aws_lambda_events::event::sqs::SqsEvent;
+use graph_descriptions::graph_description::*;
+use lambda::Context;
+use lambda::error::HandlerError;
+use lambda::lambda;
+use log::*;
+use subgraph_generator_runtime::{SubgraphGenerator, ZstdDecoder};
+
+use crate::generator::generate_subgraph;
+use crate::logs::{parse_logs, ZeekRecord};
+
+mod generator;
+mod logs;
+
+#[derive(Clone)]
+struct ZeekSubgraphGenerator;
+
+#[async_trait]
+impl SubgraphGenerator for ZeekSubgraphGenerator {
+    type Event = ZeekRecord;
+
+    fn parse_events(&self, payload: &[u8]) -> Result<Vec<ZeekRecord>, failure::Error> {
+        // The payload is a Zeek log file, with either TSV headers or one json record per line
+        parse_logs(&String::from_utf8_lossy(payload))
+    }
+
+    fn identity(&self, event: &ZeekRecord) -> String {
+        event.line.clone()
+    }
+
+    async fn generate_subgraph(&mut self, event: ZeekRecord) -> Result<Option<Graph>, failure::Error> {
+        generate_subgraph(&event).map(Some)
+    }
+}
+
+fn handler(event: SqsEvent, ctx: Context) -> Result<(), HandlerError> {
+    subgraph_generator_runtime::run(event, ctx, "ZEEK", ZeekSubgraphGenerator, ZstdDecoder)
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    simple_logger::init_with_level(log::Level::Info).unwrap();
+
+    info!("Starting lambda");
+    lambda!(handler);
+    Ok(())
+}